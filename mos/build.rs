@@ -0,0 +1,61 @@
+//! 生成嵌入内核的符号表
+//!
+//! 环境变量 `MOS_SYMBOLS` 指向 `nm -n -C` 的输出时，从中取出代码段的符号，
+//! 按地址排序写成 `地址 名称` 的文本，放入内核的 `.symbols` 段；否则符号表为空。
+//! 嵌入符号表不会改变代码段的布局，因此 `Makefile` 先链接一次生成符号，再重新编译
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=MOS_SYMBOLS");
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let table = Path::new(&out_dir).join("symbols");
+
+    let mut symbols = Vec::new();
+    if let Ok(path) = env::var("MOS_SYMBOLS") {
+        println!("cargo:rerun-if-changed={}", path);
+        let content = fs::read_to_string(&path).expect("failed to read MOS_SYMBOLS");
+        for line in content.lines() {
+            let mut parts = line.splitn(3, ' ');
+            let (address, kind, name) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(address), Some(kind), Some(name)) => (address, kind, name),
+                _ => continue,
+            };
+            if !matches!(kind, "t" | "T" | "w" | "W") {
+                continue;
+            }
+            if let Ok(address) = u64::from_str_radix(address, 16) {
+                symbols.push((address, strip_hash(name)));
+            }
+        }
+    }
+    symbols.sort();
+    let mut text = String::new();
+    for (address, name) in symbols {
+        text += &format!("{:016x} {}\n", address, name);
+    }
+    fs::write(&table, &text).unwrap();
+
+    fs::write(
+        Path::new(&out_dir).join("symbols.rs"),
+        format!(
+            "#[used]\n#[link_section = \".symbols\"]\nstatic SYMBOLS: [u8; {}] = *include_bytes!({:?});\n",
+            text.len(),
+            table
+        ),
+    )
+    .unwrap();
+}
+
+/// 去掉 Rust 符号末尾的哈希，例如 `::h0123456789abcdef`
+fn strip_hash(name: &str) -> &str {
+    match name.rfind("::h") {
+        Some(index)
+            if name.len() - index == 19
+                && name[index + 3..].chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            &name[..index]
+        }
+        _ => name,
+    }
+}
@@ -0,0 +1,46 @@
+#!/bin/sh
+# `cargo run` 和 `cargo test` 的 runner：把内核转换为二进制文件并在 QEMU 中运行
+#
+# 测试内核通过 sifive_test 设备退出，QEMU 的退出码即为测试结果。
+# 设置 MEMORY 时指定 QEMU 的内存大小，设置 TEST_IMG 时将其作为 virtio 块设备挂载，设置 TEST_TIMEOUT 时超时后结束 QEMU。
+# 设置 NETDEV 时添加 virtio 网卡，其值为 QEMU 的网络后端，例如 `user`、`user,hostfwd=tcp::5555-:7` 或 `socket,listen=:1234`。
+# 总是添加 virtio 熵源。设置 VIRTIO_LOG 时添加 virtio 控制台，名为 log 的端口写入该文件，内核日志不再输出到终端；
+# 设置 VIRTIO_DATA 时添加名为 data 的端口，其值为 QEMU 的 chardev 后端，例如 `file,path=vport.out` 或 `socket,path=vport.sock,server,nowait`。
+# 设置 GDB_PORT 时添加名为 gdb 的端口，监听该 TCP 端口，GDB 通过 `target remote :$GDB_PORT` 连接内核中的调试端口。
+# virt 机器只有一个 ns16550a 串口，因此调试端口同样使用 virtio 控制台
+set -e
+
+KERNEL=$1
+BIN=$KERNEL.bin
+rust-objcopy --binary-architecture=riscv64 "$KERNEL" --strip-all -O binary "$BIN"
+
+set -- -machine virt -nographic -bios default \
+    -device loader,file="$BIN",addr=0x80200000
+if [ -n "$MEMORY" ]; then
+    set -- "$@" -m "$MEMORY"
+fi
+if [ -n "$TEST_IMG" ]; then
+    set -- "$@" -drive file="$TEST_IMG",format=raw,id=sfs \
+        -device virtio-blk-device,drive=sfs
+fi
+if [ -n "$NETDEV" ]; then
+    set -- "$@" -netdev "$NETDEV",id=net0 -device virtio-net-device,netdev=net0
+fi
+set -- "$@" -object rng-random,id=rng0,filename=/dev/urandom -device virtio-rng-device,rng=rng0
+if [ -n "$VIRTIO_LOG" ] || [ -n "$VIRTIO_DATA" ] || [ -n "$GDB_PORT" ]; then
+    set -- "$@" -device virtio-serial-device
+fi
+if [ -n "$VIRTIO_LOG" ]; then
+    set -- "$@" -chardev file,id=vlog,path="$VIRTIO_LOG" -device virtconsole,chardev=vlog,name=log
+fi
+if [ -n "$VIRTIO_DATA" ]; then
+    set -- "$@" -chardev "$VIRTIO_DATA",id=vdata -device virtserialport,chardev=vdata,name=data
+fi
+if [ -n "$GDB_PORT" ]; then
+    set -- "$@" -chardev socket,id=vgdb,host=localhost,port="$GDB_PORT",server,nowait \
+        -device virtserialport,chardev=vgdb,name=gdb
+fi
+if [ -n "$TEST_TIMEOUT" ]; then
+    exec timeout "$TEST_TIMEOUT" qemu-system-riscv64 "$@"
+fi
+exec qemu-system-riscv64 "$@"
@@ -1,7 +1,7 @@
 //! 先入先出队列的调度器 [`FifoScheduler`]
 
 use super::Scheduler;
-use alloc::collections::LinkedList;
+use alloc::{collections::LinkedList, vec::Vec};
 
 /// 采用 FIFO 算法的线程调度器
 pub struct FifoScheduler<ThreadType: Clone + Eq> {
@@ -38,4 +38,7 @@ impl<ThreadType: Clone + Eq> Scheduler<ThreadType> for FifoScheduler<ThreadType>
         assert!(removed.next().is_some() && removed.next().is_none());
     }
     fn set_priority(&mut self, _thread: ThreadType, _priority: ()) {}
+    fn threads(&self) -> Vec<ThreadType> {
+        self.pool.iter().cloned().collect()
+    }
 }
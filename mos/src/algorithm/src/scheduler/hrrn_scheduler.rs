@@ -1,7 +1,7 @@
 //! 最高响应比优先算法的调度器 [`HrrnScheduler`]
 
 use super::Scheduler;
-use alloc::collections::LinkedList;
+use alloc::{collections::LinkedList, vec::Vec};
 
 /// 将线程和调度信息打包
 struct HrrnThread<ThreadType: Clone + Eq> {
@@ -63,4 +63,7 @@ impl<ThreadType: Clone + Eq> Scheduler<ThreadType> for HrrnScheduler<ThreadType>
         assert!(removed.next().is_some() && removed.next().is_none());
     }
     fn set_priority(&mut self, _thread: ThreadType, _priority: ()) {}
+    fn threads(&self) -> Vec<ThreadType> {
+        self.pool.iter().map(|t| t.thread.clone()).collect()
+    }
 }
@@ -3,6 +3,8 @@
 mod fifo_scheduler;
 mod hrrn_scheduler;
 
+use alloc::vec::Vec;
+
 /// 线程调度器
 ///
 /// `ThreadType` 应为 `Arc<Thread>`
@@ -23,6 +25,8 @@ pub trait Scheduler<ThreadType: Clone + Eq>: Default {
     fn remove_thread(&mut self, thread: &ThreadType);
     /// 设置线程的优先级
     fn set_priority(&mut self, thread: ThreadType, priority: Self::Priority);
+    /// 按照队列中的顺序列出所有线程
+    fn threads(&self) -> Vec<ThreadType>;
 }
 
 pub use fifo_scheduler::FifoScheduler;
@@ -0,0 +1,132 @@
+//! 基于帧指针的栈回溯
+//!
+//! 内核编译时开启 `force-frame-pointers`，每个栈帧中 `fp - 8` 处保存返回地址，`fp - 16` 处保存调用者的 `fp`。
+//! 预编译的 `core` 等库不保存帧指针，但 `s0` 是被调用者保存的寄存器，回溯时这些函数会被跳过。
+//!
+//! 地址通过嵌入 `.symbols` 段的符号表解析为函数名，符号表的生成见 `build.rs`。
+//! 这里的输出直接写入控制台而不经过日志，因为 panic 时日志的锁可能已被持有
+
+use crate::interrupt::Context;
+use crate::memory::{mapping::Mapping, VirtualAddress, KERNEL_MAP_OFFSET};
+use core::{slice, str};
+use riscv::register::sstatus::SPP;
+
+include!(concat!(env!("OUT_DIR"), "/symbols.rs"));
+
+/// 最多回溯的栈帧数
+const MAX_DEPTH: usize = 32;
+
+/// 通用寄存器的 ABI 名称
+const REGISTER_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+extern "C" {
+    /// 由 `linker.ld` 指定的符号表位置
+    fn symbols_start();
+    fn symbols_end();
+}
+
+/// 嵌入的符号表，每行为按地址排序的 `地址 名称`
+fn symbol_table() -> &'static str {
+    let start = symbols_start as usize;
+    let len = symbols_end as usize - start;
+    unsafe { str::from_utf8_unchecked(slice::from_raw_parts(start as *const u8, len)) }
+}
+
+/// 查找地址所在的函数，返回函数名和地址相对于函数入口的偏移
+pub fn symbol(address: usize) -> Option<(&'static str, usize)> {
+    let mut found = None;
+    for line in symbol_table().lines() {
+        let mut parts = line.splitn(2, ' ');
+        let start = match parts.next().map(|start| usize::from_str_radix(start, 16)) {
+            Some(Ok(start)) => start,
+            _ => continue,
+        };
+        if start > address {
+            break;
+        }
+        found = parts.next().map(|name| (name, address - start));
+    }
+    found
+}
+
+/// 打印一个地址及其所在的函数
+fn print_frame(index: usize, address: usize) {
+    match symbol(address) {
+        Some((name, offset)) => {
+            println!("  #{:<2} {:#018x} {}+{:#x}", index, address, name, offset)
+        }
+        None => println!("  #{:<2} {:#018x} ??", index, address),
+    }
+}
+
+/// 栈帧是否可以读取：地址对齐、位于对应的地址空间且已经映射
+fn readable(fp: usize, user: bool) -> bool {
+    fp >= 16
+        && fp % 8 == 0
+        && (fp < KERNEL_MAP_OFFSET) == user
+        && Mapping::lookup(VirtualAddress(fp - 16)).is_some()
+        && Mapping::lookup(VirtualAddress(fp - 8)).is_some()
+}
+
+/// 沿着帧指针向上回溯，依次打印返回地址，`index` 为第一个栈帧的序号
+fn walk(mut fp: usize, user: bool, mut index: usize) {
+    while index < MAX_DEPTH && readable(fp, user) {
+        let (caller_fp, ra) =
+            unsafe { (*((fp - 16) as *const usize), *((fp - 8) as *const usize)) };
+        if ra == 0 {
+            break;
+        }
+        print_frame(index, ra);
+        index += 1;
+        // 调用者的栈帧总是在更高的地址，否则栈已经损坏
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+    }
+}
+
+/// 打印当前的内核调用链
+pub fn print_backtrace() {
+    let fp: usize;
+    unsafe { llvm_asm!("mv $0, s0" : "=r"(fp) ::: "volatile") };
+    println!("backtrace:");
+    walk(fp, false, 0);
+}
+
+/// 打印 `Context` 中的全部寄存器
+pub fn print_context(context: &Context) {
+    for (row, registers) in context.x.chunks(4).enumerate() {
+        for (column, value) in registers.iter().enumerate() {
+            print!("{:>4}: {:#018x}  ", REGISTER_NAMES[row * 4 + column], value);
+        }
+        println!("");
+    }
+    let mode = match context.sstatus.spp() {
+        SPP::User => "user",
+        SPP::Supervisor => "supervisor",
+    };
+    println!(
+        "sepc: {:#018x}  mode: {}  spie: {}",
+        context.sepc,
+        mode,
+        context.sstatus.spie()
+    );
+}
+
+/// 打印发生异常时的调用链，从 `sepc` 开始沿着 `Context` 中的帧指针回溯
+///
+/// 用户程序的符号不在内核的符号表中，只打印地址。叶函数可能没有保存 `ra`，因此单独打印一次
+pub fn print_trap_backtrace(context: &Context) {
+    let user = context.sstatus.spp() == SPP::User;
+    println!("backtrace:");
+    print_frame(0, context.sepc);
+    if user {
+        println!("  ra  {:#018x}", context.ra());
+    }
+    walk(context.x[8], user, 1);
+}
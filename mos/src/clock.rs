@@ -0,0 +1,60 @@
+//! 墙上时间
+//!
+//! 启动时从实时时钟读取一次当前时间，记下它与 `time` 计数器之间的差，之后的墙上时间都由计数器推算，
+//! 不再访问实时时钟。没有实时时钟时墙上时间从 1970-01-01 UTC 开始
+
+use crate::drivers::rtc::rtc_device;
+use crate::fs::Timespec;
+use crate::interrupt::CLOCK_FREQ;
+use core::sync::atomic::{AtomicU64, Ordering};
+use riscv::register::time;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+/// `time` 计数器每个周期的纳秒数
+const NANOS_PER_CYCLE: u64 = NANOS_PER_SEC / CLOCK_FREQ as u64;
+
+/// `time` 计数器为 0 时的墙上时间，自 1970-01-01 UTC 起的纳秒数
+static BOOT_TIME: AtomicU64 = AtomicU64::new(0);
+
+/// 纳秒数转换为 [`Timespec`]
+fn timespec(nanos: u64) -> Timespec {
+    Timespec {
+        sec: (nanos / NANOS_PER_SEC) as i64,
+        nsec: (nanos % NANOS_PER_SEC) as i32,
+    }
+}
+
+/// 启动以来的纳秒数，不受墙上时间调整的影响
+pub fn monotonic_nanos() -> u64 {
+    time::read() as u64 * NANOS_PER_CYCLE
+}
+
+/// 自 1970-01-01 UTC 起的纳秒数
+pub fn realtime_nanos() -> u64 {
+    BOOT_TIME.load(Ordering::Relaxed) + monotonic_nanos()
+}
+
+/// 启动以来的时间
+pub fn monotonic() -> Timespec {
+    timespec(monotonic_nanos())
+}
+
+/// 当前的墙上时间
+pub fn realtime() -> Timespec {
+    timespec(realtime_nanos())
+}
+
+/// 从实时时钟初始化墙上时间
+pub fn init() {
+    match rtc_device() {
+        Some(rtc) => {
+            let now = rtc.read_time();
+            BOOT_TIME.store(now.saturating_sub(monotonic_nanos()), Ordering::Relaxed);
+            info!(
+                "mod clock initialized, {} seconds since the epoch",
+                now / NANOS_PER_SEC
+            );
+        }
+        None => warn!("mod clock initialized without a real-time clock"),
+    }
+}
@@ -8,11 +8,41 @@
 //!
 //! 我们声明一个类型，为其实现 [`write_str`] 方法后，就可以使用 [`write_fmt`] 来进行格式化输出
 //!
+//! # 输出设备
+//!
+//! 驱动初始化之前通过 SBI 逐个字符输入输出，之后改用串口驱动，见 [`use_serial`]
+//!
 //! [`write_str`]: core::fmt::Write::write_str
 //! [`write_fmt`]: core::fmt::Write::write_fmt
 
+use crate::drivers::{driver::Driver, plic};
 use crate::sbi::*;
+use alloc::sync::Arc;
 use core::fmt::{self, Write};
+use lazy_static::lazy_static;
+use spin::RwLock;
+
+lazy_static! {
+    /// 驱动初始化之后用作控制台的串口，在此之前通过 SBI 输入输出
+    static ref SERIAL: RwLock<Option<Arc<dyn Driver>>> = RwLock::new(None);
+}
+
+/// 改用串口作为控制台
+pub fn use_serial(driver: Arc<dyn Driver>) {
+    *SERIAL.write() = Some(driver);
+}
+
+/// 控制台输入是否由中断驱动，否则需要轮询
+pub fn interrupt_driven() -> bool {
+    SERIAL.read().is_some() && plic::enabled()
+}
+
+/// 等待串口发送缓冲区中的数据全部发出，在关机之前调用
+pub fn flush() {
+    if let Some(serial) = SERIAL.read().as_ref() {
+        serial.flush();
+    }
+}
 
 /// 一个 [Zero-Sized Type]，实现 [`core::fmt::Write`] trait 来进行格式化输出
 ///
@@ -24,17 +54,37 @@ impl Write for Stdout {
     ///
     /// [`console_putchar`] sbi 调用每次接受一个 `usize`，但实际上会把它作为 `u8` 来打印字符。
     /// 因此，如果字符串中存在非 ASCII 字符，需要在 utf-8 编码下，对于每一个 `u8` 调用一次 [`console_putchar`]
+    ///
+    /// 改用串口之后，整个字符串一次交给驱动
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        let mut buffer = [0u8; 4];
-        for c in s.chars() {
-            for code_point in c.encode_utf8(&mut buffer).as_bytes().iter() {
-                console_putchar(*code_point as usize);
-            }
-        }
+        write_bytes(s.as_bytes());
         Ok(())
     }
 }
 
+/// 输出一段字节，终端设备的输出经过这里
+pub fn write_bytes(bytes: &[u8]) {
+    if let Some(serial) = SERIAL.read().as_ref() {
+        serial.write_chars(bytes);
+        return;
+    }
+    for byte in bytes.iter() {
+        console_putchar(*byte as usize);
+    }
+}
+
+/// 读取一个输入的字符，没有输入时返回 `None`
+pub fn getchar() -> Option<u8> {
+    if let Some(serial) = SERIAL.read().as_ref() {
+        return serial.read_char();
+    }
+    // 没有读取到字符时 SBI 返回 -1
+    match console_getchar() {
+        c if c == usize::max_value() => None,
+        c => Some(c as u8),
+    }
+}
+
 /// 打印由 [`core::format_args!`] 格式化后的数据
 ///
 /// [`print!`] 和 [`println!`] 宏都将展开成此函数
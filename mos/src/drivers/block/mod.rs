@@ -1,15 +1,158 @@
 //! 块设备抽象
 //!
-//! 目前仅仅实现了 virtio 协议的块设备，另外还有类似 AHCI 等协议
+//! 目前仅仅实现了 virtio 协议的块设备，另外还有类似 AHCI 等协议。
+//! 磁盘上有 MBR 或 GPT 分区表时，每个分区也作为一个独立的 [`BlockDevice`]
 
-use super::driver::Driver;
-use alloc::sync::Arc;
+use super::driver::{DeviceType, Driver, DRIVERS};
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use filesystem::read_partitions;
+use lazy_static::lazy_static;
 use rcore_fs::dev;
+use spin::RwLock;
 
 pub mod virtio_blk;
 
-/// 块设备抽象（驱动的引用）
-pub struct BlockDevice(pub Arc<dyn Driver>);
+/// 块设备的读写统计
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockStatistics {
+    /// 成功读取的块数
+    pub reads: usize,
+    /// 成功写入的块数
+    pub writes: usize,
+    /// 读写失败的次数
+    pub errors: usize,
+}
+
+/// virtio 块设备的主设备号
+pub const VIRTIO_BLK_MAJOR: usize = 254;
+/// 每个磁盘最多的分区数，每个磁盘占用 16 个次设备号
+pub const MAX_PARTITIONS: usize = 15;
+
+/// 块设备上的一段连续的块：整个磁盘或者其中的一个分区
+#[derive(Clone)]
+pub struct BlockDevice {
+    /// 磁盘的驱动
+    pub driver: Arc<dyn Driver>,
+    /// 在磁盘上的起始块
+    pub offset: usize,
+    /// 块数
+    pub blocks: usize,
+}
+
+impl BlockDevice {
+    /// 整个磁盘
+    pub fn new(driver: Arc<dyn Driver>) -> Self {
+        let blocks = driver.num_blocks();
+        Self {
+            driver,
+            offset: 0,
+            blocks,
+        }
+    }
+}
+
+/// 一个有名字的块设备
+#[derive(Clone)]
+pub struct BlockDeviceInfo {
+    /// 设备名，例如 vda、vda1
+    pub name: String,
+    pub device: BlockDevice,
+    /// 所在磁盘在所有磁盘中的序号
+    pub disk: usize,
+    /// 分区号，0 表示整个磁盘
+    pub partition: usize,
+    /// 分区的 `PARTUUID`
+    pub uuid: Option<String>,
+    /// GPT 中的分区名
+    pub label: Option<String>,
+}
+
+impl BlockDeviceInfo {
+    /// 次设备号，和 Linux 一样为磁盘序号乘以 16 再加上分区号
+    pub fn minor(&self) -> usize {
+        self.disk * (MAX_PARTITIONS + 1) + self.partition
+    }
+
+    /// 是否和 `root=` 等参数描述的设备一致
+    ///
+    /// 支持 `vda2`、`/dev/vda2`、`PARTUUID=<uuid>` 和 `PARTLABEL=<label>` 几种写法
+    pub fn matches(&self, spec: &str) -> bool {
+        if let Some(uuid) = spec.strip_prefix("PARTUUID=") {
+            self.uuid
+                .as_ref()
+                .map_or(false, |own| own.eq_ignore_ascii_case(uuid))
+        } else if let Some(label) = spec.strip_prefix("PARTLABEL=") {
+            self.label.as_deref() == Some(label)
+        } else {
+            spec.trim_start_matches("/dev/") == self.name
+        }
+    }
+}
+
+lazy_static! {
+    /// 所有磁盘和分区，由 [`init`] 生成
+    static ref BLOCK_DEVICES: RwLock<Vec<BlockDeviceInfo>> = RwLock::new(Vec::new());
+}
+
+/// 列出所有块设备：每个磁盘之后紧跟着它的分区
+pub fn block_devices() -> Vec<BlockDeviceInfo> {
+    BLOCK_DEVICES.read().clone()
+}
+
+/// 按照 [`static@DRIVERS`] 中的顺序把磁盘依次命名为 vda、vdb……，
+/// 并读取每个磁盘的分区表，分区命名为 vda1、vda2……
+pub fn init() {
+    let disks: Vec<Arc<dyn Driver>> = DRIVERS
+        .read()
+        .iter()
+        .filter(|driver| driver.device_type() == DeviceType::Block)
+        .cloned()
+        .collect();
+    let mut devices = Vec::new();
+    for (disk, driver) in disks.into_iter().enumerate() {
+        let whole = BlockDevice::new(driver);
+        let name = format!("vd{}", (b'a' + disk as u8) as char);
+        let partitions = read_partitions(&whole, whole.blocks).unwrap_or_else(|error| {
+            warn!("failed to read partition table of {}: {:?}", name, error);
+            Vec::new()
+        });
+        devices.push(BlockDeviceInfo {
+            name: name.clone(),
+            device: whole.clone(),
+            disk,
+            partition: 0,
+            uuid: None,
+            label: None,
+        });
+        for partition in partitions {
+            if partition.number > MAX_PARTITIONS {
+                warn!("ignored partition {} of {}", partition.number, name);
+                continue;
+            }
+            let info = BlockDeviceInfo {
+                name: format!("{}{}", name, partition.number),
+                device: BlockDevice {
+                    driver: whole.driver.clone(),
+                    offset: partition.start,
+                    blocks: partition.sectors,
+                },
+                disk,
+                partition: partition.number,
+                uuid: Some(partition.uuid),
+                label: partition.label,
+            };
+            info!(
+                "found partition {}: {} blocks at {}, PARTUUID={}",
+                info.name,
+                info.device.blocks,
+                info.device.offset,
+                info.uuid.as_deref().unwrap_or("")
+            );
+            devices.push(info);
+        }
+    }
+    *BLOCK_DEVICES.write() = devices;
+}
 
 /// 为 [`BlockDevice`] 实现 [`rcore-fs`] 中 [`BlockDevice`] trait
 ///
@@ -20,9 +163,12 @@ impl dev::BlockDevice for BlockDevice {
     /// 这里取 512B 是因为 virtio 驱动对设备的操作粒度为 512B
     const BLOCK_SIZE_LOG2: u8 = 9;
 
-    /// 读取某个块到 buf 中
+    /// 读取某个块到 buf 中，块号相对于分区的起始位置
     fn read_at(&self, block_id: usize, buf: &mut [u8]) -> dev::Result<()> {
-        match self.0.read_block(block_id, buf) {
+        if block_id >= self.blocks {
+            return Err(dev::DevError);
+        }
+        match self.driver.read_block(self.offset + block_id, buf) {
             true => Ok(()),
             false => Err(dev::DevError),
         }
@@ -30,7 +176,10 @@ impl dev::BlockDevice for BlockDevice {
 
     /// 将 buf 中的数据写入块中
     fn write_at(&self, block_id: usize, buf: &[u8]) -> dev::Result<()> {
-        match self.0.write_block(block_id, buf) {
+        if block_id >= self.blocks {
+            return Err(dev::DevError);
+        }
+        match self.driver.write_block(self.offset + block_id, buf) {
             true => Ok(()),
             false => Err(dev::DevError),
         }
@@ -1,10 +1,21 @@
 use super::super::driver::{DeviceType, Driver, DRIVERS};
+use super::BlockStatistics;
 use alloc::sync::Arc;
 use spin::Mutex;
 use virtio_drivers::{VirtIOBlk, VirtIOHeader};
 
+/// virtio MMIO 设备配置空间相对于 Header 的偏移
+const VIRTIO_CONFIG_SPACE_OFFSET: usize = 0x100;
+
 /// virtio 协议的块设备驱动
-struct VirtIOBlkDriver(Mutex<VirtIOBlk<'static>>);
+struct VirtIOBlkDriver {
+    /// [`virtio_drivers`] 中的设备
+    device: Mutex<VirtIOBlk<'static>>,
+    /// 设备容量，单位为 512B 的块
+    capacity: usize,
+    /// 读写统计
+    statistics: Mutex<BlockStatistics>,
+}
 
 /// 为 [`VirtIOBlkDriver`] 实现 [`Driver`] trait
 ///
@@ -17,18 +28,52 @@ impl Driver for VirtIOBlkDriver {
 
     /// 读取某个块到 buf 中
     fn read_block(&self, block_id: usize, buf: &mut [u8]) -> bool {
-        self.0.lock().read_block(block_id, buf).is_ok()
+        let success = self.device.lock().read_block(block_id, buf).is_ok();
+        let mut statistics = self.statistics.lock();
+        if success {
+            statistics.reads += 1;
+        } else {
+            statistics.errors += 1;
+        }
+        success
     }
 
     /// 将 buf 中的数据写入块中
     fn write_block(&self, block_id: usize, buf: &[u8]) -> bool {
-        self.0.lock().write_block(block_id, buf).is_ok()
+        let success = self.device.lock().write_block(block_id, buf).is_ok();
+        let mut statistics = self.statistics.lock();
+        if success {
+            statistics.writes += 1;
+        } else {
+            statistics.errors += 1;
+        }
+        success
+    }
+
+    /// 设备中块的个数
+    fn num_blocks(&self) -> usize {
+        self.capacity
+    }
+
+    /// 读写统计
+    fn statistics(&self) -> BlockStatistics {
+        *self.statistics.lock()
     }
 }
 
 /// 将从设备树中读取出的设备信息放到 [`static@DRIVERS`] 中
 pub fn add_driver(header: &'static mut VirtIOHeader) {
+    // 配置空间的第一项即为设备容量，[`VirtIOBlk`] 读取后不会对外提供，这里自己读一次
+    let capacity = unsafe {
+        core::ptr::read_volatile(
+            (header as *const _ as usize + VIRTIO_CONFIG_SPACE_OFFSET) as *const u64,
+        )
+    } as usize;
     let virtio_blk = VirtIOBlk::new(header).expect("failed to init blk driver");
-    let driver = Arc::new(VirtIOBlkDriver(Mutex::new(virtio_blk)));
+    let driver = Arc::new(VirtIOBlkDriver {
+        device: Mutex::new(virtio_blk),
+        capacity,
+        statistics: Mutex::new(BlockStatistics::default()),
+    });
     DRIVERS.write().push(driver);
 }
@@ -4,3 +4,4 @@
 //! MMIO 指通过读写特定内存段来实现设备交互
 
 pub mod virtio_mmio;
+pub mod virtqueue;
@@ -1,15 +1,20 @@
 //! virtio MMIO 总线协议驱动
 //!
-//! 目前仅仅实现了 virtio Block Device 协议，另外还有类似 virtio Network 等协议
+//! 目前实现了 virtio Block Device、Network、Console 和 Entropy Source 协议，
+//! 后两者不在 [`virtio_drivers`] 中，使用 [`virtqueue`](super::virtqueue) 自己操作队列
 
 use super::super::block::virtio_blk;
+use super::super::net::virtio_net;
+use super::super::rng::virtio_rng;
+use super::super::serial::virtio_console;
+use super::super::device_tree::map_reg;
 use crate::memory::{
     frame::{FrameTracker, FRAME_ALLOCATOR},
     mapping::Mapping,
     PhysicalAddress, VirtualAddress, PAGE_SIZE,
 };
 use alloc::collections::btree_map::BTreeMap;
-use device_tree::{util::SliceRead, Node};
+use device_tree::Node;
 use lazy_static::lazy_static;
 use spin::RwLock;
 use virtio_drivers::{DeviceType, VirtIOHeader};
@@ -17,12 +22,10 @@ use virtio_drivers::{DeviceType, VirtIOHeader};
 /// 从设备树的某个节点探测 virtio 协议具体类型
 pub fn virtio_probe(node: &Node) {
     // reg 属性中包含了描述设备的 Header 的位置
-    let reg = match node.prop_raw("reg") {
-        Some(reg) => reg,
+    let va = match map_reg(node) {
+        Some(va) => va,
         _ => return,
     };
-    let pa = PhysicalAddress(reg.as_slice().read_be_u64(0).unwrap() as usize);
-    let va = VirtualAddress::from(pa);
     let header = unsafe { &mut *(va.0 as *mut VirtIOHeader) };
     // 目前只支持某个特定版本的 virtio 协议
     if !header.verify() {
@@ -31,7 +34,12 @@ pub fn virtio_probe(node: &Node) {
     // 判断设备类型
     match header.device_type() {
         DeviceType::Block => virtio_blk::add_driver(header),
-        device => println!("unrecognized virtio device: {:?}", device),
+        DeviceType::Network => virtio_net::add_driver(header, node.prop_u32("interrupts").ok()),
+        DeviceType::Console => {
+            virtio_console::add_driver(header, node.prop_u32("interrupts").ok())
+        }
+        DeviceType::EntropySource => virtio_rng::add_driver(header),
+        device => warn!("unrecognized virtio device: {:?}", device),
     }
 }
 
@@ -41,13 +49,12 @@ lazy_static! {
         RwLock::new(BTreeMap::new());
 }
 
-/// 为 DMA 操作申请连续 pages 个物理页（为 [`virtio_drivers`] 库提供）
+/// 为 DMA 操作申请连续 pages 个物理页
 ///
 /// 为什么要求连续的物理内存？设备的 DMA 操作只涉及到内存和对应设备
 /// 这个过程不会涉及到 CPU 的 MMU 机制，我们只能给设备传递物理地址
 /// 而陷于我们之前每次只能分配一个物理页的设计，这里我们假设我们连续分配的地址是连续的
-#[no_mangle]
-extern "C" fn virtio_dma_alloc(pages: usize) -> PhysicalAddress {
+pub fn dma_alloc(pages: usize) -> PhysicalAddress {
     let mut pa: PhysicalAddress = Default::default();
     let mut last: PhysicalAddress = Default::default();
     for i in 0..pages {
@@ -63,12 +70,23 @@ extern "C" fn virtio_dma_alloc(pages: usize) -> PhysicalAddress {
     pa
 }
 
-/// 为 DMA 操作释放对应的之前申请的连续的物理页（为 [`virtio_drivers`] 库提供）
-#[no_mangle]
-extern "C" fn virtio_dma_dealloc(pa: PhysicalAddress, pages: usize) -> i32 {
+/// 释放 [`dma_alloc`] 申请的连续物理页
+pub fn dma_dealloc(pa: PhysicalAddress, pages: usize) {
     for i in 0..pages {
         TRACKERS.write().remove(&(pa + i * PAGE_SIZE));
     }
+}
+
+/// 为 DMA 操作申请连续 pages 个物理页（为 [`virtio_drivers`] 库提供）
+#[no_mangle]
+extern "C" fn virtio_dma_alloc(pages: usize) -> PhysicalAddress {
+    dma_alloc(pages)
+}
+
+/// 为 DMA 操作释放对应的之前申请的连续的物理页（为 [`virtio_drivers`] 库提供）
+#[no_mangle]
+extern "C" fn virtio_dma_dealloc(pa: PhysicalAddress, pages: usize) -> i32 {
+    dma_dealloc(pa, pages);
     0
 }
 
@@ -0,0 +1,292 @@
+//! 旧版（version 1）virtio MMIO 传输层和 virtqueue
+//!
+//! [`virtio_drivers`] 只实现了块设备、网卡等几种设备，控制台的多端口和熵源设备需要自己操作队列。
+//! 这里只实现驱动需要的部分：协商特性、建立队列、提交缓冲区和取回设备用完的缓冲区
+
+use super::virtio_mmio::{dma_alloc, dma_dealloc};
+use crate::memory::{mapping::Mapping, PhysicalAddress, VirtualAddress, PAGE_SIZE};
+use alloc::vec::Vec;
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{fence, spin_loop_hint, Ordering};
+use virtio_drivers::VirtIOHeader;
+
+// 寄存器的偏移
+const HOST_FEATURES: usize = 0x010;
+const HOST_FEATURES_SEL: usize = 0x014;
+const GUEST_FEATURES: usize = 0x020;
+const GUEST_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c;
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const CONFIG: usize = 0x100;
+
+// 设备状态
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+
+// 描述符的标志
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+
+/// 设备的寄存器
+pub struct Transport {
+    base: usize,
+}
+
+impl Transport {
+    pub fn new(header: &'static mut VirtIOHeader) -> Self {
+        Self {
+            base: header as *mut _ as usize,
+        }
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, value) }
+    }
+
+    /// 重置设备并协商特性，`negotiate` 由设备提供的特性得到驱动使用的特性
+    pub fn begin_init(&self, negotiate: impl FnOnce(u32) -> u32) {
+        self.write(STATUS, 0);
+        self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+        self.write(HOST_FEATURES_SEL, 0);
+        let features = negotiate(self.read(HOST_FEATURES));
+        self.write(GUEST_FEATURES_SEL, 0);
+        self.write(GUEST_FEATURES, features);
+        self.write(GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+    }
+
+    /// 队列建立完成，设备开始工作
+    pub fn finish_init(&self) {
+        self.write(STATUS, self.read(STATUS) | STATUS_DRIVER_OK);
+    }
+
+    /// 建立第 `index` 个队列，长度不超过 `size`，设备没有这个队列时返回 `None`
+    pub fn setup_queue(&self, index: u32, size: u16) -> Option<VirtQueue> {
+        self.write(QUEUE_SEL, index);
+        let max = self.read(QUEUE_NUM_MAX);
+        if max == 0 || self.read(QUEUE_PFN) != 0 {
+            return None;
+        }
+        // 队列长度取不超过设备上限的 2 的幂
+        let mut size = size.min(max.min(u16::max_value() as u32) as u16);
+        while !size.is_power_of_two() {
+            size &= size - 1;
+        }
+        let queue = VirtQueue::new(index, size);
+        self.write(QUEUE_NUM, size as u32);
+        self.write(QUEUE_ALIGN, PAGE_SIZE as u32);
+        self.write(QUEUE_PFN, (queue.address.0 / PAGE_SIZE) as u32);
+        Some(queue)
+    }
+
+    /// 通知设备处理队列中新提交的缓冲区
+    pub fn notify(&self, queue: &VirtQueue) {
+        fence(Ordering::SeqCst);
+        self.write(QUEUE_NOTIFY, queue.index);
+    }
+
+    /// 应答中断，返回中断状态
+    pub fn ack_interrupt(&self) -> u32 {
+        let status = self.read(INTERRUPT_STATUS);
+        if status != 0 {
+            self.write(INTERRUPT_ACK, status);
+        }
+        status
+    }
+
+    /// 读取配置空间中偏移为 `offset` 的字段
+    pub fn config<T: Copy>(&self, offset: usize) -> T {
+        unsafe { read_volatile((self.base + CONFIG + offset) as *const T) }
+    }
+}
+
+/// 描述符表中的一项
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Descriptor {
+    address: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+/// 一个 virtqueue，由描述符表、可用环和已用环组成
+///
+/// 旧版协议要求三者位于连续的物理内存中，已用环从新的一页开始
+pub struct VirtQueue {
+    index: u32,
+    size: u16,
+    /// 队列所在的物理内存
+    address: PhysicalAddress,
+    pages: usize,
+    /// 空闲描述符组成的链表
+    free_head: u16,
+    free: u16,
+    /// 下一个要取回的已用环位置
+    last_used: u16,
+}
+
+/// 队列只通过持有它的驱动访问
+unsafe impl Send for VirtQueue {}
+
+impl VirtQueue {
+    fn new(index: u32, size: u16) -> Self {
+        let n = size as usize;
+        let avail_end = 16 * n + 6 + 2 * n;
+        let used_start = (avail_end + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+        let pages = (used_start + 6 + 8 * n + PAGE_SIZE - 1) / PAGE_SIZE;
+        let address = dma_alloc(pages);
+        let queue = Self {
+            index,
+            size,
+            address,
+            pages,
+            free_head: 0,
+            free: size,
+            last_used: 0,
+        };
+        unsafe { core::ptr::write_bytes(queue.base() as *mut u8, 0, pages * PAGE_SIZE) };
+        for i in 0..size {
+            unsafe { (*queue.descriptor(i)).next = i + 1 };
+        }
+        queue
+    }
+
+    fn base(&self) -> usize {
+        VirtualAddress::from(self.address).0
+    }
+
+    fn descriptor(&self, index: u16) -> *mut Descriptor {
+        (self.base() + 16 * index as usize) as *mut Descriptor
+    }
+
+    /// 可用环中偏移为 `offset` 字节处的 u16
+    fn avail(&self, offset: usize) -> *mut u16 {
+        (self.base() + 16 * self.size as usize + offset) as *mut u16
+    }
+
+    /// 已用环的起始地址
+    fn used(&self) -> usize {
+        let avail_end = 16 * self.size as usize + 6 + 2 * self.size as usize;
+        self.base() + (avail_end + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE
+    }
+
+    /// 提交一组缓冲区，`inputs` 由设备读取，`outputs` 由设备写入，返回头部描述符的编号
+    ///
+    /// 缓冲区必须在设备用完之前保持有效，描述符不够时返回 `None`
+    pub fn add(&mut self, inputs: &[&[u8]], outputs: &[&mut [u8]]) -> Option<u16> {
+        let count = inputs.len() + outputs.len();
+        if count == 0 || count > self.free as usize {
+            return None;
+        }
+        // 设备只能看到物理地址
+        let mut buffers = Vec::with_capacity(count);
+        for buffer in inputs {
+            buffers.push((buffer.as_ptr(), buffer.len(), 0));
+        }
+        for buffer in outputs {
+            buffers.push((buffer.as_ptr(), buffer.len(), DESC_F_WRITE));
+        }
+        let mut physical = Vec::with_capacity(count);
+        for (pointer, _, _) in buffers.iter() {
+            physical.push(Mapping::lookup(VirtualAddress(*pointer as usize))?);
+        }
+
+        let head = self.free_head;
+        for (index, ((_, len, flags), address)) in buffers.into_iter().zip(physical).enumerate() {
+            let descriptor = self.descriptor(self.free_head);
+            unsafe {
+                (*descriptor).address = address.0 as u64;
+                (*descriptor).len = len as u32;
+                (*descriptor).flags = if index + 1 < count {
+                    flags | DESC_F_NEXT
+                } else {
+                    flags
+                };
+                self.free_head = (*descriptor).next;
+            }
+        }
+        self.free -= count as u16;
+
+        // 放入可用环，更新下标之前保证设备能看到描述符
+        unsafe {
+            let avail_index = read_volatile(self.avail(2));
+            write_volatile(self.avail(4 + 2 * (avail_index % self.size) as usize), head);
+            fence(Ordering::SeqCst);
+            write_volatile(self.avail(2), avail_index.wrapping_add(1));
+        }
+        Some(head)
+    }
+
+    /// 是否有设备用完的缓冲区
+    pub fn can_pop(&self) -> bool {
+        fence(Ordering::SeqCst);
+        let used_index = unsafe { read_volatile((self.used() + 2) as *const u16) };
+        used_index != self.last_used
+    }
+
+    /// 取回一组设备用完的缓冲区，返回头部描述符的编号和设备写入的长度
+    pub fn pop_used(&mut self) -> Option<(u16, u32)> {
+        if !self.can_pop() {
+            return None;
+        }
+        let element = self.used() + 4 + 8 * (self.last_used % self.size) as usize;
+        let (head, len) = unsafe {
+            (
+                read_volatile(element as *const u32) as u16,
+                read_volatile((element + 4) as *const u32),
+            )
+        };
+        self.last_used = self.last_used.wrapping_add(1);
+        // 把整条描述符链放回空闲链表
+        let mut index = head;
+        loop {
+            let descriptor = unsafe { &mut *self.descriptor(index) };
+            let has_next = descriptor.flags & DESC_F_NEXT != 0;
+            descriptor.flags = 0;
+            self.free += 1;
+            if !has_next {
+                descriptor.next = self.free_head;
+                break;
+            }
+            index = descriptor.next;
+        }
+        self.free_head = head;
+        Some((head, len))
+    }
+
+    /// 提交一组缓冲区并等待设备用完，返回设备写入的长度
+    ///
+    /// 队列中不能有其他尚未取回的缓冲区
+    pub fn add_and_wait(
+        &mut self,
+        transport: &Transport,
+        inputs: &[&[u8]],
+        outputs: &[&mut [u8]],
+    ) -> Option<u32> {
+        self.add(inputs, outputs)?;
+        transport.notify(self);
+        while !self.can_pop() {
+            spin_loop_hint();
+        }
+        self.pop_used().map(|(_, len)| len)
+    }
+}
+
+impl Drop for VirtQueue {
+    fn drop(&mut self) {
+        dma_dealloc(self.address, self.pages);
+    }
+}
@@ -1,18 +1,101 @@
 use crate::drivers::bus::virtio_mmio::virtio_probe;
-use super::VirtualAddress;
+use crate::drivers::{plic, power, rtc::goldfish, serial::ns16550a};
+use super::{PhysicalAddress, VirtualAddress};
+use crate::memory::{layout::MemoryLayout, map_mmio, range::Range};
 
+use alloc::{string::String, vec::Vec};
 use core::slice;
-use device_tree::{DeviceTree, Node};
+use device_tree::{util::SliceRead, DeviceTree, Node};
+use lazy_static::lazy_static;
+use spin::RwLock;
 
 /// 验证某内存段为设备树格式的 Magic Number（固定）
 const DEVICE_TREE_MAGIC: u32 = 0xd00d_feed;
 
+lazy_static! {
+    /// bootloader 通过 `/chosen` 节点传入的 initrd 的物理地址范围 `[start, end)`
+    pub static ref INITRD: RwLock<Option<(PhysicalAddress, PhysicalAddress)>> = RwLock::new(None);
+    /// bootloader 通过 `/chosen` 节点传入的命令行
+    static ref BOOTARGS: RwLock<String> = RwLock::new(String::new());
+}
+
+/// 命令行中 `name=value` 形式的参数的值
+pub fn bootarg(name: &str) -> Option<String> {
+    BOOTARGS.read().split_whitespace().find_map(|arg| {
+        let mut parts = arg.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) if key == name => Some(String::from(value)),
+            _ => None,
+        }
+    })
+}
+
+/// 读取 32 位或 64 位的整数属性
+fn prop_usize(node: &Node, name: &str) -> Option<usize> {
+    let raw = node.prop_raw(name)?;
+    match raw.len() {
+        4 => raw.as_slice().read_be_u32(0).ok().map(|value| value as usize),
+        8 => raw.as_slice().read_be_u64(0).ok().map(|value| value as usize),
+        _ => None,
+    }
+}
+
+/// `reg` 属性中的全部区域，QEMU virt 中 `#address-cells` 和 `#size-cells` 均为 2
+pub fn reg(node: &Node) -> Vec<Range<PhysicalAddress>> {
+    let raw = match node.prop_raw("reg") {
+        Some(raw) => raw,
+        None => return Vec::new(),
+    };
+    (0..raw.len() / 16)
+        .filter_map(|index| {
+            let address = raw.as_slice().read_be_u64(index * 16).ok()? as usize;
+            let size = raw.as_slice().read_be_u64(index * 16 + 8).ok()? as usize;
+            Some(Range::from(
+                PhysicalAddress(address)..PhysicalAddress(address + size),
+            ))
+        })
+        .collect()
+}
+
+/// 登记设备 `reg` 中第一个区域的 MMIO 映射，返回其虚拟地址
+pub fn map_reg(node: &Node) -> Option<VirtualAddress> {
+    reg(node)
+        .first()
+        .map(|range| map_mmio(range.start, range.len()))
+}
+
+/// 解析 `/chosen` 节点
+fn parse_chosen(node: &Node) {
+    if let Ok(bootargs) = node.prop_str("bootargs") {
+        crate::logging::configure(bootargs);
+        *BOOTARGS.write() = String::from(bootargs);
+    }
+    if let (Some(start), Some(end)) = (
+        prop_usize(node, "linux,initrd-start"),
+        prop_usize(node, "linux,initrd-end"),
+    ) {
+        *INITRD.write() = Some((PhysicalAddress(start), PhysicalAddress(end)));
+    }
+}
+
 /// 递归遍历设备树
 fn walk(node: &Node) {
     // 检查设备的协议支持并初始化
+    // compatible 可能包含多个以 '\0' 分隔的字符串，使用第一个支持的
     if let Ok(compatible) = node.prop_str("compatible") {
-        if compatible == "virtio,mmio" {
-            virtio_probe(node);
+        for compatible in compatible.split('\0') {
+            let probe: fn(&Node) = match compatible {
+                "virtio,mmio" => virtio_probe,
+                "ns16550a" => ns16550a::add_driver,
+                "riscv,plic0" | "sifive,plic-1.0.0" => plic::probe,
+                "google,goldfish-rtc" => goldfish::add_driver,
+                "sifive,test1" | "sifive,test0" | "syscon" => power::probe_syscon,
+                "syscon-poweroff" => power::probe_poweroff,
+                "syscon-reboot" => power::probe_reboot,
+                _ => continue,
+            };
+            probe(node);
+            break;
         }
     }
     // 遍历子树
@@ -22,20 +105,81 @@ fn walk(node: &Node) {
 }
 
 /// 整个设备树的 Headers（用于验证和读取）
+#[repr(C)]
+#[allow(dead_code)]
 struct DtbHeader {
     magic: u32,
     size: u32,
+    off_dt_struct: u32,
+    off_dt_strings: u32,
+    /// 内存保留块的偏移，其中每项为 64 位的地址和大小，以全 0 结束
+    off_mem_rsvmap: u32,
 }
 
-/// 遍历设备树并初始化设备
-pub fn init(dtb_va: VirtualAddress) {
+/// 验证并取得设备树的全部数据
+fn load(dtb_va: VirtualAddress) -> Option<(&'static DtbHeader, &'static [u8])> {
     let header = unsafe { &*(dtb_va.0 as *const DtbHeader) };
     // from_be 是大小端序的转换（from big endian）
-    let magic = u32::from_be(header.magic);
-    if magic == DEVICE_TREE_MAGIC {
-        let size = u32::from_be(header.size);
-        // 拷贝数据，加载并遍历
-        let data = unsafe { slice::from_raw_parts(dtb_va.0 as *const u8, size as usize) };
+    if u32::from_be(header.magic) != DEVICE_TREE_MAGIC {
+        return None;
+    }
+    let size = u32::from_be(header.size) as usize;
+    let data = unsafe { slice::from_raw_parts(dtb_va.0 as *const u8, size) };
+    Some((header, data))
+}
+
+/// 读取内存布局，在驱动初始化之前由 [`crate::memory::init`] 调用
+///
+/// 同时解析 `/chosen` 节点，设备树自身和 initrd 都会作为保留区域
+pub fn memory_layout(dtb_pa: PhysicalAddress) -> MemoryLayout {
+    let mut layout = MemoryLayout::default();
+    let (header, data) = match load(VirtualAddress::from(dtb_pa)) {
+        Some(dtb) => dtb,
+        None => return layout,
+    };
+    layout
+        .reserved
+        .push(Range::from(dtb_pa..dtb_pa + data.len()));
+    let mut offset = u32::from_be(header.off_mem_rsvmap) as usize;
+    while let (Ok(address), Ok(size)) = (data.read_be_u64(offset), data.read_be_u64(offset + 8)) {
+        if size == 0 {
+            break;
+        }
+        let address = PhysicalAddress(address as usize);
+        layout
+            .reserved
+            .push(Range::from(address..address + size as usize));
+        offset += 16;
+    }
+
+    let dt = match DeviceTree::load(data) {
+        Ok(dt) => dt,
+        Err(_) => return layout,
+    };
+    for node in dt.root.children.iter() {
+        if node.name == "chosen" {
+            parse_chosen(node);
+        } else if node.name == "reserved-memory" {
+            for child in node.children.iter() {
+                if child.prop_raw("no-map").is_some() {
+                    layout.no_map.extend(reg(child));
+                } else {
+                    layout.reserved.extend(reg(child));
+                }
+            }
+        } else if matches!(node.prop_str("device_type"), Ok("memory")) {
+            layout.memory.extend(reg(node));
+        }
+    }
+    if let Some((start, end)) = *INITRD.read() {
+        layout.reserved.push(Range::from(start..end));
+    }
+    layout
+}
+
+/// 遍历设备树并初始化设备
+pub fn init(dtb_va: VirtualAddress) {
+    if let Some((_, data)) = load(dtb_va) {
         if let Ok(dt) = DeviceTree::load(data) {
             walk(&dt.root);
         }
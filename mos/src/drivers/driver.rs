@@ -1,17 +1,22 @@
 //! 驱动接口的定义
 //!
-//! 目前接口中只支持块设备类型
+//! 目前接口中支持块设备、字符设备、网络设备、熵源和实时时钟类型
 
+use super::block::BlockStatistics;
 use alloc::{sync::Arc, vec::Vec};
 use lazy_static::lazy_static;
 use spin::RwLock;
 
 /// 驱动类型
 ///
-/// 目前只有块设备，可能还有网络、GPU 设备等
+/// 目前有块设备、字符设备（串口）、网络设备、熵源和实时时钟，可能还有 GPU 设备等
 #[derive(Debug, Eq, PartialEq)]
 pub enum DeviceType {
     Block,
+    Char,
+    Net,
+    Rng,
+    Rtc,
 }
 
 /// 驱动的接口
@@ -28,6 +33,64 @@ pub trait Driver: Send + Sync {
     fn write_block(&self, _block_id: usize, _buf: &[u8]) -> bool {
         unimplemented!("not a block driver")
     }
+
+    /// 设备中块的个数（块设备接口）
+    fn num_blocks(&self) -> usize {
+        unimplemented!("not a block driver")
+    }
+
+    /// 读写统计（块设备接口）
+    fn statistics(&self) -> BlockStatistics {
+        unimplemented!("not a block driver")
+    }
+
+    /// 读取一个收到的字符，没有时返回 `None`（字符设备接口）
+    fn read_char(&self) -> Option<u8> {
+        unimplemented!("not a char driver")
+    }
+
+    /// 发送数据，可能只是放入发送缓冲区（字符设备接口）
+    fn write_chars(&self, _buf: &[u8]) {
+        unimplemented!("not a char driver")
+    }
+
+    /// 等待发送缓冲区中的数据全部发出（字符设备接口）
+    fn flush(&self) {
+        unimplemented!("not a char driver")
+    }
+
+    /// 网卡的 MAC 地址（网络设备接口）
+    fn mac_address(&self) -> [u8; 6] {
+        unimplemented!("not a net driver")
+    }
+
+    /// 链路是否连通（网络设备接口）
+    fn link_up(&self) -> bool {
+        unimplemented!("not a net driver")
+    }
+
+    /// 取出一个收到的以太网帧，没有时返回 `None`（网络设备接口）
+    fn receive_frame(&self) -> Option<Vec<u8>> {
+        unimplemented!("not a net driver")
+    }
+
+    /// 发送一个以太网帧，返回是否成功（网络设备接口）
+    fn send_frame(&self, _frame: &[u8]) -> bool {
+        unimplemented!("not a net driver")
+    }
+
+    /// 读取随机数填满 buf，返回实际读取的字节数（熵源接口）
+    fn read_random(&self, _buf: &mut [u8]) -> usize {
+        unimplemented!("not a rng driver")
+    }
+
+    /// 当前时间，自 1970-01-01 UTC 起的纳秒数（实时时钟接口）
+    fn read_time(&self) -> u64 {
+        unimplemented!("not a rtc driver")
+    }
+
+    /// 处理设备的中断，由 [`plic`](super::plic) 调用
+    fn handle_irq(&self) {}
 }
 
 lazy_static! {
@@ -8,11 +8,27 @@ pub mod device_tree;
 pub mod bus;
 pub mod block;
 pub mod driver;
+pub mod net;
+pub mod plic;
+pub mod power;
+pub mod rng;
+pub mod rtc;
+pub mod serial;
 
 
 /// 从设备树的物理地址来获取全部设备信息并初始化
 pub fn init(dtb_pa: PhysicalAddress) {
     let dtb_va = VirtualAddress::from(dtb_pa);
     device_tree::init(dtb_va);
-    println!("mod driver initialized")
+    block::init();
+    plic::init();
+    // 此后控制台输出改用串口
+    if let Some(serial) = serial::console_serial() {
+        crate::console::use_serial(serial);
+    }
+    // 有名为 log 的 virtio 控制台端口时，日志改为写入这个端口
+    if let Some(port) = serial::virtio_console::log_port() {
+        crate::logging::use_channel(port);
+    }
+    info!("mod driver initialized")
 }
\ No newline at end of file
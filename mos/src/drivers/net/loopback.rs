@@ -0,0 +1,47 @@
+//! 回环网卡
+//!
+//! 完全在内存中实现的网络设备：发送的帧放入队列，随后原样收回。
+//! 不在 [`static@DRIVERS`](super::super::driver::DRIVERS) 中，由协议栈直接创建为 `lo` 接口
+
+use super::super::driver::{DeviceType, Driver};
+use alloc::{collections::VecDeque, vec::Vec};
+use spin::Mutex;
+
+/// 本地管理的单播 MAC 地址，只在回环接口上使用
+const LOOPBACK_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+/// 队列中最多缓存的帧数，队列满时发送失败
+const QUEUE_CAPACITY: usize = 256;
+
+/// 回环网卡
+#[derive(Default)]
+pub struct LoopbackDriver {
+    /// 已经发送、尚未收回的帧
+    queue: Mutex<VecDeque<Vec<u8>>>,
+}
+
+impl Driver for LoopbackDriver {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Net
+    }
+
+    fn mac_address(&self) -> [u8; 6] {
+        LOOPBACK_MAC
+    }
+
+    fn link_up(&self) -> bool {
+        true
+    }
+
+    fn receive_frame(&self) -> Option<Vec<u8>> {
+        self.queue.lock().pop_front()
+    }
+
+    fn send_frame(&self, frame: &[u8]) -> bool {
+        let mut queue = self.queue.lock();
+        if queue.len() >= QUEUE_CAPACITY {
+            return false;
+        }
+        queue.push_back(frame.to_vec());
+        true
+    }
+}
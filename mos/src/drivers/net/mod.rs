@@ -0,0 +1,20 @@
+//! 网络设备抽象
+//!
+//! 目前实现了 virtio 协议的网卡，以及不依赖硬件的回环网卡
+
+use super::driver::{DeviceType, Driver, DRIVERS};
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+
+pub mod loopback;
+pub mod virtio_net;
+
+/// 按照 [`static@DRIVERS`] 中的顺序列出所有网络设备，并依次命名为 eth0、eth1……
+pub fn net_devices() -> Vec<(String, Arc<dyn Driver>)> {
+    DRIVERS
+        .read()
+        .iter()
+        .filter(|driver| driver.device_type() == DeviceType::Net)
+        .enumerate()
+        .map(|(index, driver)| (format!("eth{}", index), driver.clone()))
+        .collect()
+}
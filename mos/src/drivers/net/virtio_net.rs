@@ -0,0 +1,118 @@
+//! virtio 协议的网卡驱动
+//!
+//! 开启外部中断之后，收到的帧由中断读入接收队列；在此之前或者没有中断号时由调用者轮询设备。
+//! 发送总是同步的，[`virtio_drivers`] 会等待设备取走数据
+
+use super::super::driver::{DeviceType, Driver, DRIVERS};
+use super::super::plic;
+use crate::process::lock::Lock;
+use alloc::{collections::VecDeque, sync::Arc, vec, vec::Vec};
+use core::ptr::read_volatile;
+use virtio_drivers::{VirtIOHeader, VirtIONet};
+
+/// virtio MMIO 设备配置空间相对于 Header 的偏移
+const VIRTIO_CONFIG_SPACE_OFFSET: usize = 0x100;
+/// 配置空间中 `status` 字段的偏移，位于 6 字节的 MAC 地址之后
+const CONFIG_STATUS_OFFSET: usize = 6;
+/// `status` 中表示链路连通的位
+const STATUS_LINK_UP: u16 = 1;
+/// 接收缓冲区的大小，可以放下一个最大的以太网帧
+const FRAME_SIZE: usize = 1536;
+/// 接收队列中最多缓存的帧数，队列满时丢弃新到达的帧
+const RX_CAPACITY: usize = 64;
+
+/// virtio 协议的网卡驱动
+///
+/// 设备和接收队列也会在中断处理中访问，因此使用关闭中断的锁
+struct VirtIONetDriver {
+    /// [`virtio_drivers`] 中的设备
+    device: Lock<VirtIONet<'static>>,
+    /// 设备 Header 的虚拟地址，用于读取配置空间
+    header: usize,
+    mac: [u8; 6],
+    /// 已经收到但尚未取走的帧
+    rx: Lock<VecDeque<Vec<u8>>>,
+}
+
+impl VirtIONetDriver {
+    /// 从设备中读出一个帧
+    fn receive_from_device(device: &mut VirtIONet<'static>) -> Option<Vec<u8>> {
+        if !device.can_recv() {
+            return None;
+        }
+        let mut frame = vec![0u8; FRAME_SIZE];
+        let len = device.recv(&mut frame).ok()?;
+        frame.truncate(len);
+        Some(frame)
+    }
+}
+
+impl Driver for VirtIONetDriver {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Net
+    }
+
+    fn mac_address(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    fn link_up(&self) -> bool {
+        let status = unsafe {
+            read_volatile(
+                (self.header + VIRTIO_CONFIG_SPACE_OFFSET + CONFIG_STATUS_OFFSET) as *const u16,
+            )
+        };
+        status & STATUS_LINK_UP != 0
+    }
+
+    fn receive_frame(&self) -> Option<Vec<u8>> {
+        if let Some(frame) = self.rx.lock().pop_front() {
+            return Some(frame);
+        }
+        // 中断可能还没有开启，或者帧在上一次中断之后才到达
+        Self::receive_from_device(&mut self.device.lock())
+    }
+
+    fn send_frame(&self, frame: &[u8]) -> bool {
+        self.device.lock().send(frame).is_ok()
+    }
+
+    /// 读入所有收到的帧
+    fn handle_irq(&self) {
+        let mut device = self.device.lock();
+        device.ack_interrupt();
+        let mut rx = self.rx.lock();
+        while let Some(frame) = Self::receive_from_device(&mut device) {
+            if rx.len() < RX_CAPACITY {
+                rx.push_back(frame);
+            }
+        }
+    }
+}
+
+/// 将从设备树中读取出的设备信息放到 [`static@DRIVERS`] 中，并注册中断
+pub fn add_driver(header: &'static mut VirtIOHeader, irq: Option<u32>) {
+    let header_address = header as *const _ as usize;
+    let virtio_net = VirtIONet::new(header).expect("failed to init net driver");
+    let driver = Arc::new(VirtIONetDriver {
+        mac: virtio_net.mac(),
+        device: Lock::new(virtio_net),
+        header: header_address,
+        rx: Lock::new(VecDeque::new()),
+    });
+    let mac = driver.mac;
+    info!(
+        "virtio-net: mac {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}, link {}",
+        mac[0],
+        mac[1],
+        mac[2],
+        mac[3],
+        mac[4],
+        mac[5],
+        if driver.link_up() { "up" } else { "down" }
+    );
+    DRIVERS.write().push(driver.clone());
+    if let Some(irq) = irq {
+        plic::register(irq, driver);
+    }
+}
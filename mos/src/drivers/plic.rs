@@ -0,0 +1,108 @@
+//! 平台级中断控制器（PLIC）
+//!
+//! 把设备的中断分发给注册的驱动。只使用 0 号 hart 的 S 态上下文
+
+use super::driver::Driver;
+use super::device_tree::map_reg;
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, Ordering};
+use device_tree::Node;
+use lazy_static::lazy_static;
+use riscv::register::sie;
+use spin::RwLock;
+
+/// 每个中断源的优先级
+const PRIORITY: usize = 0x0;
+/// 每个上下文的中断使能位
+const ENABLE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+/// 每个上下文的优先级阈值，其后是领取和完成寄存器
+const THRESHOLD: usize = 0x20_0000;
+const CLAIM: usize = 0x20_0004;
+const CONTEXT_STRIDE: usize = 0x1000;
+/// 0 号 hart 的 S 态对应的上下文
+const CONTEXT: usize = 1;
+
+lazy_static! {
+    /// PLIC 寄存器的虚拟地址，设备树中没有 PLIC 时为 `None`
+    static ref BASE: RwLock<Option<usize>> = RwLock::new(None);
+    /// 中断号对应的驱动
+    static ref HANDLERS: RwLock<BTreeMap<u32, Arc<dyn Driver>>> = RwLock::new(BTreeMap::new());
+}
+
+/// 是否已经开启外部中断
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+fn read(base: usize, offset: usize) -> u32 {
+    unsafe { read_volatile((base + offset) as *const u32) }
+}
+
+fn write(base: usize, offset: usize, value: u32) {
+    unsafe { write_volatile((base + offset) as *mut u32, value) }
+}
+
+/// 在设备树中找到 PLIC，记录它的位置
+pub fn probe(node: &Node) {
+    if let Some(va) = map_reg(node) {
+        *BASE.write() = Some(va.0);
+    }
+}
+
+/// 注册设备的中断号，在 [`init`] 时开启
+pub fn register(irq: u32, driver: Arc<dyn Driver>) {
+    HANDLERS.write().insert(irq, driver);
+}
+
+/// 外部中断是否已经开启，在此之前驱动需要轮询设备
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 开启所有注册的中断，在遍历设备树之后调用
+///
+/// 设备树中的节点没有固定顺序，设备可能先于 PLIC 被找到，因此在这里统一开启
+pub fn init() {
+    let base = match *BASE.read() {
+        Some(base) => base,
+        None => return,
+    };
+    let drivers: Vec<_> = {
+        let handlers = HANDLERS.read();
+        for &irq in handlers.keys() {
+            let irq = irq as usize;
+            write(base, PRIORITY + irq * 4, 1);
+            let enable = ENABLE + CONTEXT * ENABLE_STRIDE + irq / 32 * 4;
+            write(base, enable, read(base, enable) | 1 << (irq % 32));
+        }
+        handlers.values().cloned().collect()
+    };
+    write(base, THRESHOLD + CONTEXT * CONTEXT_STRIDE, 0);
+    ENABLED.store(true, Ordering::Relaxed);
+    unsafe { sie::set_sext() };
+    // 处理开启之前到达的数据，驱动也借此开启设备自己的中断
+    for driver in drivers {
+        driver.handle_irq();
+    }
+    info!("plic enabled for {} devices", HANDLERS.read().len());
+}
+
+/// 处理外部中断：领取中断号，交给驱动处理后通知完成
+pub fn handle_interrupt() {
+    let base = match *BASE.read() {
+        Some(base) => base,
+        None => return,
+    };
+    let claim = CLAIM + CONTEXT * CONTEXT_STRIDE;
+    loop {
+        let irq = read(base, claim);
+        if irq == 0 {
+            break;
+        }
+        let driver = HANDLERS.read().get(&irq).cloned();
+        if let Some(driver) = driver {
+            driver.handle_irq();
+        }
+        write(base, claim, irq);
+    }
+}
@@ -0,0 +1,146 @@
+//! 关机和重启
+//!
+//! 设备树中的 `syscon-poweroff` 和 `syscon-reboot` 节点通过 `regmap` 引用一个 syscon 设备，
+//! 向它偏移为 `offset` 的寄存器写入 `value` 即可关机或重启。QEMU virt 上的 syscon 是 sifive_test，
+//! 它还可以带着退出码结束 QEMU，用来报告 panic 和测试结果。设备树中没有这些设备时退回到 SBI
+
+use super::device_tree::map_reg;
+use crate::memory::VirtualAddress;
+use crate::sbi::{self, ResetReason, ResetType};
+use alloc::collections::BTreeMap;
+use core::ptr::{read_volatile, write_volatile};
+use device_tree::Node;
+use lazy_static::lazy_static;
+use spin::RwLock;
+
+/// 写入 sifive_test 使 QEMU 以高 16 位为退出码退出
+const FINISHER_FAIL: u32 = 0x3333;
+/// 写入 sifive_test 使 QEMU 以退出码 0 退出
+const FINISHER_PASS: u32 = 0x5555;
+/// 写入 sifive_test 使 QEMU 重启
+const FINISHER_RESET: u32 = 0x7777;
+
+/// `syscon-poweroff` 或 `syscon-reboot` 节点描述的寄存器写入
+#[derive(Clone, Copy)]
+struct SysconWrite {
+    /// syscon 设备的 phandle
+    regmap: u32,
+    offset: usize,
+    value: u32,
+    mask: u32,
+}
+
+#[derive(Default)]
+struct Power {
+    /// 按照 phandle 登记的 syscon 设备
+    syscons: BTreeMap<u32, VirtualAddress>,
+    /// sifive_test 设备
+    finisher: Option<VirtualAddress>,
+    poweroff: Option<SysconWrite>,
+    reboot: Option<SysconWrite>,
+}
+
+impl Power {
+    /// 执行寄存器写入，引用的 syscon 不存在时什么也不做
+    fn write(&self, action: Option<SysconWrite>) {
+        let action = match action {
+            Some(action) => action,
+            None => return,
+        };
+        if let Some(base) = self.syscons.get(&action.regmap) {
+            let register = (base.0 + action.offset) as *mut u32;
+            unsafe {
+                let value = if action.mask == u32::max_value() {
+                    action.value
+                } else {
+                    read_volatile(register) & !action.mask | action.value & action.mask
+                };
+                write_volatile(register, value);
+            }
+        }
+    }
+
+    /// 向 sifive_test 写入命令
+    fn finish(&self, command: u32) {
+        if let Some(finisher) = self.finisher {
+            unsafe { write_volatile(finisher.0 as *mut u32, command) };
+        }
+    }
+}
+
+lazy_static! {
+    static ref POWER: RwLock<Power> = RwLock::new(Power::default());
+}
+
+/// 登记 syscon 设备，sifive_test 同时用来带着退出码关机
+///
+/// `syscon-poweroff` 等节点在设备树中可能出现在它引用的 syscon 之前，所以只登记地址，关机时再查找
+pub fn probe_syscon(node: &Node) {
+    let va = match map_reg(node) {
+        Some(va) => va,
+        None => return,
+    };
+    let mut power = POWER.write();
+    if let Ok(phandle) = node.prop_u32("phandle") {
+        power.syscons.insert(phandle, va);
+    }
+    let compatible = node.prop_str("compatible").unwrap_or("");
+    if compatible
+        .split('\0')
+        .any(|compatible| compatible.starts_with("sifive,test"))
+    {
+        power.finisher = Some(va);
+    }
+}
+
+/// 读取 `syscon-poweroff` 或 `syscon-reboot` 节点
+fn syscon_write(node: &Node) -> Option<SysconWrite> {
+    let mask = node.prop_u32("mask").unwrap_or(u32::max_value());
+    Some(SysconWrite {
+        regmap: node.prop_u32("regmap").ok()?,
+        offset: node.prop_u32("offset").unwrap_or(0) as usize,
+        // 只有 mask 时写入的值就是 mask
+        value: node.prop_u32("value").unwrap_or(mask),
+        mask,
+    })
+}
+
+/// 登记 `syscon-poweroff` 节点
+pub fn probe_poweroff(node: &Node) {
+    POWER.write().poweroff = syscon_write(node);
+}
+
+/// 登记 `syscon-reboot` 节点
+pub fn probe_reboot(node: &Node) {
+    POWER.write().reboot = syscon_write(node);
+}
+
+/// 关机，`code` 不为 0 表示因为错误而关机
+///
+/// 有 sifive_test 时 QEMU 以 `code` 为退出码退出，否则只能通过 SBI 报告是否失败
+pub fn poweroff(code: u16) -> ! {
+    // panic 时可能有人正持有写锁
+    if let Some(power) = POWER.try_read() {
+        if code == 0 {
+            power.write(power.poweroff);
+            power.finish(FINISHER_PASS);
+        } else {
+            power.finish((code as u32) << 16 | FINISHER_FAIL);
+        }
+    }
+    if code == 0 {
+        sbi::shutdown()
+    } else {
+        sbi::shutdown_on_failure()
+    }
+}
+
+/// 重启，无法重启时关机
+pub fn reboot() -> ! {
+    if let Some(power) = POWER.try_read() {
+        power.write(power.reboot);
+        power.finish(FINISHER_RESET);
+    }
+    sbi::system_reset(ResetType::ColdReboot, ResetReason::NoReason);
+    sbi::shutdown()
+}
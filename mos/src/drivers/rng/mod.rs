@@ -0,0 +1,18 @@
+//! 熵源设备
+//!
+//! 目前仅仅实现了 virtio 协议的熵源，读出的随机数用于内核的熵池
+
+use super::driver::{DeviceType, Driver, DRIVERS};
+use alloc::{sync::Arc, vec::Vec};
+
+pub mod virtio_rng;
+
+/// 按照 [`static@DRIVERS`] 中的顺序列出所有熵源
+pub fn rng_devices() -> Vec<Arc<dyn Driver>> {
+    DRIVERS
+        .read()
+        .iter()
+        .filter(|driver| driver.device_type() == DeviceType::Rng)
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,57 @@
+//! virtio 协议的熵源驱动
+//!
+//! 设备只有一个请求队列，驱动提交可写的缓冲区，设备填入随机数后归还。
+//! 读取是同步的，提交之后等待设备完成
+
+use super::super::bus::virtqueue::{Transport, VirtQueue};
+use super::super::driver::{DeviceType, Driver, DRIVERS};
+use alloc::sync::Arc;
+use spin::Mutex;
+use virtio_drivers::VirtIOHeader;
+
+/// 请求队列的长度，同一时间只有一个请求
+const QUEUE_SIZE: u16 = 2;
+
+/// virtio 协议的熵源驱动
+struct VirtIORngDriver {
+    transport: Transport,
+    queue: Mutex<VirtQueue>,
+}
+
+impl Driver for VirtIORngDriver {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Rng
+    }
+
+    fn read_random(&self, buf: &mut [u8]) -> usize {
+        let mut queue = self.queue.lock();
+        let mut read = 0;
+        // 设备每次可能只填入一部分
+        while read < buf.len() {
+            match queue.add_and_wait(&self.transport, &[], &[&mut buf[read..]]) {
+                Some(len) if len > 0 => read += len as usize,
+                _ => break,
+            }
+        }
+        read
+    }
+}
+
+/// 将从设备树中读取出的设备信息放到 [`static@DRIVERS`] 中
+pub fn add_driver(header: &'static mut VirtIOHeader) {
+    let transport = Transport::new(header);
+    transport.begin_init(|_| 0);
+    let queue = match transport.setup_queue(0, QUEUE_SIZE) {
+        Some(queue) => queue,
+        None => {
+            warn!("virtio-rng: no request queue");
+            return;
+        }
+    };
+    transport.finish_init();
+    info!("virtio-rng: entropy source ready");
+    DRIVERS.write().push(Arc::new(VirtIORngDriver {
+        transport,
+        queue: Mutex::new(queue),
+    }));
+}
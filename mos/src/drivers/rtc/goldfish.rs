@@ -0,0 +1,44 @@
+//! goldfish RTC 驱动
+//!
+//! 设备树中为 `google,goldfish-rtc`。时间是自 1970-01-01 UTC 起的纳秒数，
+//! 读取低 32 位时设备同时锁存高 32 位，因此必须先读低位
+
+use super::super::driver::{DeviceType, Driver, DRIVERS};
+use crate::drivers::device_tree::map_reg;
+use alloc::sync::Arc;
+use core::ptr::read_volatile;
+use device_tree::Node;
+
+// 寄存器的偏移
+const TIME_LOW: usize = 0x00;
+const TIME_HIGH: usize = 0x04;
+
+/// goldfish RTC
+struct GoldfishRtc {
+    base: usize,
+}
+
+impl GoldfishRtc {
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+}
+
+impl Driver for GoldfishRtc {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Rtc
+    }
+
+    fn read_time(&self) -> u64 {
+        let low = self.read(TIME_LOW);
+        let high = self.read(TIME_HIGH);
+        (high as u64) << 32 | low as u64
+    }
+}
+
+/// 将从设备树中读取出的设备信息放到 [`static@DRIVERS`] 中
+pub fn add_driver(node: &Node) {
+    if let Some(va) = map_reg(node) {
+        DRIVERS.write().push(Arc::new(GoldfishRtc { base: va.0 }));
+    }
+}
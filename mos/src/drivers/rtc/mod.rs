@@ -0,0 +1,17 @@
+//! 实时时钟
+//!
+//! 目前仅仅实现了 QEMU virt 机器上的 goldfish RTC，只在启动时读取一次用来初始化墙上时间
+
+use super::driver::{DeviceType, Driver, DRIVERS};
+use alloc::sync::Arc;
+
+pub mod goldfish;
+
+/// [`static@DRIVERS`] 中的第一个实时时钟
+pub fn rtc_device() -> Option<Arc<dyn Driver>> {
+    DRIVERS
+        .read()
+        .iter()
+        .find(|driver| driver.device_type() == DeviceType::Rtc)
+        .cloned()
+}
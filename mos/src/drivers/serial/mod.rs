@@ -0,0 +1,19 @@
+//! 串口抽象
+//!
+//! 目前实现了 ns16550a，第一个串口在驱动初始化之后作为控制台。
+//! virtio 控制台的端口另外管理，见 [`virtio_console`]
+
+use super::driver::{DeviceType, Driver, DRIVERS};
+use alloc::sync::Arc;
+
+pub mod ns16550a;
+pub mod virtio_console;
+
+/// 第一个字符设备，用作控制台
+pub fn console_serial() -> Option<Arc<dyn Driver>> {
+    DRIVERS
+        .read()
+        .iter()
+        .find(|driver| driver.device_type() == DeviceType::Char)
+        .cloned()
+}
@@ -0,0 +1,174 @@
+//! ns16550a 串口驱动
+//!
+//! 开启外部中断之后，发送的数据先放入缓冲区，在发送 FIFO 空闲时由中断继续写入，
+//! 收到的数据也由中断读入接收缓冲区；在此之前发送时等待设备空闲，接收由调用者轮询
+
+use super::super::driver::{DeviceType, Driver, DRIVERS};
+use super::super::plic;
+use super::super::device_tree::map_reg;
+use crate::process::lock::Lock;
+use alloc::{collections::VecDeque, sync::Arc};
+use core::ptr::{read_volatile, write_volatile};
+use device_tree::Node;
+
+/// 接收缓冲寄存器（读）和发送保持寄存器（写）
+const RBR: usize = 0;
+const THR: usize = 0;
+/// 中断使能寄存器
+const IER: usize = 1;
+/// FIFO 控制寄存器（写）
+const FCR: usize = 2;
+/// 线路控制寄存器
+const LCR: usize = 3;
+/// 调制解调器控制寄存器
+const MCR: usize = 4;
+/// 线路状态寄存器
+const LSR: usize = 5;
+
+const IER_RX_AVAILABLE: u8 = 0x01;
+const IER_TX_EMPTY: u8 = 0x02;
+const LSR_DATA_READY: u8 = 0x01;
+const LSR_TX_EMPTY: u8 = 0x20;
+
+/// 发送 FIFO 的深度
+const FIFO_SIZE: usize = 16;
+/// 发送和接收缓冲区的容量，发送缓冲区满时等待设备发送，接收缓冲区满时丢弃数据
+const BUFFER_CAPACITY: usize = 0x1000;
+
+/// ns16550a 串口
+pub struct Ns16550a {
+    /// 寄存器的虚拟地址
+    base: usize,
+    /// 寄存器间隔的对数，来自设备树的 `reg-shift`
+    reg_shift: usize,
+    /// 中断号，设备树中没有时只能轮询
+    irq: Option<u32>,
+    /// 缓冲区也会在中断处理中访问，因此使用关闭中断的锁
+    tx: Lock<VecDeque<u8>>,
+    rx: Lock<VecDeque<u8>>,
+}
+
+impl Ns16550a {
+    fn read(&self, reg: usize) -> u8 {
+        unsafe { read_volatile((self.base + (reg << self.reg_shift)) as *const u8) }
+    }
+
+    fn write(&self, reg: usize, value: u8) {
+        unsafe { write_volatile((self.base + (reg << self.reg_shift)) as *mut u8, value) }
+    }
+
+    /// 初始化设备，暂不开启中断
+    fn init(&self) {
+        self.write(IER, 0);
+        // 8 位数据，无校验，1 位停止位
+        self.write(LCR, 0x03);
+        // 开启并清空 FIFO
+        self.write(FCR, 0x07);
+        // DTR、RTS，以及一些硬件产生中断所需的 OUT2
+        self.write(MCR, 0x0b);
+    }
+
+    /// 是否通过中断收发
+    fn interrupt_driven(&self) -> bool {
+        self.irq.is_some() && plic::enabled()
+    }
+
+    /// 等待设备空闲，写入一个字节
+    fn write_sync(&self, byte: u8) {
+        while self.read(LSR) & LSR_TX_EMPTY == 0 {}
+        self.write(THR, byte);
+    }
+
+    /// 发送 FIFO 空闲时从缓冲区中写入，还有剩余数据时开启发送中断
+    fn flush_tx(&self, tx: &mut VecDeque<u8>) {
+        if self.read(LSR) & LSR_TX_EMPTY != 0 {
+            for _ in 0..FIFO_SIZE {
+                match tx.pop_front() {
+                    Some(byte) => self.write(THR, byte),
+                    None => break,
+                }
+            }
+        }
+        if tx.is_empty() {
+            self.write(IER, IER_RX_AVAILABLE);
+        } else {
+            self.write(IER, IER_RX_AVAILABLE | IER_TX_EMPTY);
+        }
+    }
+}
+
+impl Driver for Ns16550a {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Char
+    }
+
+    fn read_char(&self) -> Option<u8> {
+        if let Some(byte) = self.rx.lock().pop_front() {
+            return Some(byte);
+        }
+        if self.read(LSR) & LSR_DATA_READY != 0 {
+            Some(self.read(RBR))
+        } else {
+            None
+        }
+    }
+
+    fn write_chars(&self, buf: &[u8]) {
+        if !self.interrupt_driven() {
+            for &byte in buf {
+                self.write_sync(byte);
+            }
+            return;
+        }
+        let mut tx = self.tx.lock();
+        for &byte in buf {
+            if tx.len() >= BUFFER_CAPACITY {
+                self.write_sync(tx.pop_front().unwrap());
+            }
+            tx.push_back(byte);
+        }
+        self.flush_tx(&mut tx);
+    }
+
+    fn flush(&self) {
+        let mut tx = self.tx.lock();
+        while let Some(byte) = tx.pop_front() {
+            self.write_sync(byte);
+        }
+    }
+
+    /// 读入所有收到的数据，并继续发送
+    fn handle_irq(&self) {
+        {
+            let mut rx = self.rx.lock();
+            while self.read(LSR) & LSR_DATA_READY != 0 {
+                let byte = self.read(RBR);
+                if rx.len() < BUFFER_CAPACITY {
+                    rx.push_back(byte);
+                }
+            }
+        }
+        self.flush_tx(&mut self.tx.lock());
+    }
+}
+
+/// 从设备树的节点创建串口驱动
+pub fn add_driver(node: &Node) {
+    let va = match map_reg(node) {
+        Some(va) => va,
+        None => return,
+    };
+    let irq = node.prop_u32("interrupts").ok();
+    let driver = Arc::new(Ns16550a {
+        base: va.0,
+        reg_shift: node.prop_u32("reg-shift").unwrap_or(0) as usize,
+        irq,
+        tx: Lock::new(VecDeque::new()),
+        rx: Lock::new(VecDeque::new()),
+    });
+    driver.init();
+    DRIVERS.write().push(driver.clone());
+    if let Some(irq) = irq {
+        plic::register(irq, driver);
+    }
+}
@@ -0,0 +1,467 @@
+//! virtio 协议的控制台驱动，支持多端口
+//!
+//! 设备支持多端口时，端口由设备通过控制队列逐个添加，每个端口有自己的收发队列，
+//! 可以对应 QEMU 中的多个 `virtconsole` 或 `virtserialport`。
+//! 端口不放入 [`static@DRIVERS`]，以免改变串口的顺序；名为 `log` 的端口用作内核日志的输出，
+//! 名为 `gdb` 的端口用作 GDB 的调试端口，其余端口作为设备文件 `/dev/vport{n}p{id}` 提供给用户程序。
+//!
+//! 收到的数据由中断读入端口的缓冲区，在此之前由读取者轮询；发送是同步的
+
+use super::super::bus::virtqueue::{Transport, VirtQueue};
+use super::super::driver::{DeviceType, Driver};
+use super::super::plic;
+use crate::process::condvar::Condvar;
+use crate::process::lock::Lock;
+use alloc::{boxed::Box, collections::VecDeque, format, string::String, sync::Arc, vec, vec::Vec};
+use lazy_static::lazy_static;
+use spin::RwLock;
+use virtio_drivers::VirtIOHeader;
+
+/// 设备支持多端口和控制队列
+const F_MULTIPORT: u32 = 1 << 1;
+/// 配置空间中 `max_nr_ports` 的偏移
+const CONFIG_MAX_NR_PORTS: usize = 4;
+
+// 控制消息的事件
+const DEVICE_READY: u16 = 0;
+const DEVICE_ADD: u16 = 1;
+const DEVICE_REMOVE: u16 = 2;
+const PORT_READY: u16 = 3;
+const CONSOLE_PORT: u16 = 4;
+const PORT_OPEN: u16 = 6;
+const PORT_NAME: u16 = 7;
+
+/// 用作内核日志通道的端口名
+pub const LOG_PORT: &str = "log";
+/// 用作 GDB 调试端口的端口名
+pub const GDB_PORT: &str = "gdb";
+/// 最多使用的端口数，设备添加的其他端口会被拒绝
+const MAX_PORTS: u32 = 4;
+/// 每个队列的长度
+const QUEUE_SIZE: u16 = 8;
+/// 接收缓冲区的大小
+const RX_BUFFER_SIZE: usize = 512;
+/// 每次发送的最大长度
+const TX_CHUNK_SIZE: usize = 2048;
+/// 每个端口最多缓存的字节数，满时丢弃新到达的数据
+const RX_CAPACITY: usize = 0x1000;
+
+/// 提交了接收缓冲区的队列
+struct ReceiveQueue {
+    queue: VirtQueue,
+    /// 按照头部描述符的编号保存提交给设备的缓冲区
+    buffers: Vec<Option<Box<[u8]>>>,
+}
+
+impl ReceiveQueue {
+    /// 用缓冲区填满队列
+    fn new(mut queue: VirtQueue) -> Self {
+        let mut buffers = Vec::new();
+        loop {
+            let mut buffer = vec![0u8; RX_BUFFER_SIZE].into_boxed_slice();
+            let head = match queue.add(&[], &[&mut buffer[..]]) {
+                Some(head) => head as usize,
+                None => break,
+            };
+            if buffers.len() <= head {
+                buffers.resize_with(head + 1, || None);
+            }
+            buffers[head] = Some(buffer);
+        }
+        Self { queue, buffers }
+    }
+
+    /// 取出一段收到的数据，缓冲区随即重新提交
+    fn pop(&mut self) -> Option<Vec<u8>> {
+        let (head, len) = self.queue.pop_used()?;
+        let mut buffer = self.buffers[head as usize].take()?;
+        let data = buffer[..(len as usize).min(buffer.len())].to_vec();
+        if let Some(head) = self.queue.add(&[], &[&mut buffer[..]]) {
+            self.buffers[head as usize] = Some(buffer);
+        }
+        Some(data)
+    }
+}
+
+/// 端口的状态
+#[derive(Default)]
+struct PortState {
+    /// 已经被设备添加
+    added: bool,
+    /// 是否为控制台端口
+    console: bool,
+    /// 主机一端是否已经连接
+    host_connected: bool,
+    name: Option<String>,
+    /// 收到但尚未读取的数据
+    rx: VecDeque<u8>,
+}
+
+/// 可变的状态，和设备的交互都在锁内进行
+struct Inner {
+    receive: Vec<ReceiveQueue>,
+    transmit: Vec<VirtQueue>,
+    /// 控制队列，设备不支持多端口时为 `None`
+    control: Option<(ReceiveQueue, VirtQueue)>,
+    ports: Vec<PortState>,
+}
+
+/// virtio 协议的控制台
+pub struct VirtIOConsole {
+    /// 在所有控制台中的序号
+    index: usize,
+    transport: Transport,
+    /// 中断处理中也会访问，因此使用关闭中断的锁
+    inner: Lock<Inner>,
+    /// 每个端口收到数据或者状态变化时唤醒
+    condvars: Vec<Condvar>,
+}
+
+/// 控制台的一个端口
+pub struct ConsolePort {
+    console: Arc<VirtIOConsole>,
+    id: u32,
+}
+
+lazy_static! {
+    /// 所有 virtio 控制台
+    static ref CONSOLES: RwLock<Vec<Arc<VirtIOConsole>>> = RwLock::new(Vec::new());
+}
+
+/// 控制消息，名字等附加数据紧随其后
+fn control_message(id: u32, event: u16, value: u16) -> Vec<u8> {
+    let mut message = Vec::with_capacity(8);
+    message.extend_from_slice(&id.to_le_bytes());
+    message.extend_from_slice(&event.to_le_bytes());
+    message.extend_from_slice(&value.to_le_bytes());
+    message
+}
+
+/// 端口 `id` 的接收队列和发送队列的编号
+fn queue_indices(id: u32) -> (u32, u32) {
+    let rx = if id == 0 { 0 } else { 2 + 2 * id };
+    (rx, rx + 1)
+}
+
+impl VirtIOConsole {
+    /// 通过控制队列发送消息
+    fn send_control(&self, inner: &mut Inner, id: u32, event: u16, value: u16) {
+        if let Some((_, tx)) = inner.control.as_mut() {
+            let message = control_message(id, event, value);
+            tx.add_and_wait(&self.transport, &[&message], &[]);
+        }
+    }
+
+    /// 处理设备发来的控制消息
+    fn handle_control(&self, inner: &mut Inner, message: &[u8]) {
+        if message.len() < 8 {
+            return;
+        }
+        let id = u32::from_le_bytes([message[0], message[1], message[2], message[3]]);
+        let event = u16::from_le_bytes([message[4], message[5]]);
+        let value = u16::from_le_bytes([message[6], message[7]]);
+        let index = id as usize;
+        if index >= inner.ports.len() {
+            // 超出数量的端口不使用
+            if event == DEVICE_ADD {
+                self.send_control(inner, id, PORT_READY, 0);
+            }
+            return;
+        }
+        match event {
+            DEVICE_ADD => {
+                inner.ports[index].added = true;
+                self.send_control(inner, id, PORT_READY, 1);
+                // 内核随时准备接收数据，直接打开端口
+                self.send_control(inner, id, PORT_OPEN, 1);
+            }
+            DEVICE_REMOVE => inner.ports[index].added = false,
+            CONSOLE_PORT => inner.ports[index].console = true,
+            PORT_OPEN => inner.ports[index].host_connected = value != 0,
+            PORT_NAME => {
+                let name = &message[8..];
+                let len = name
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(name.len());
+                inner.ports[index].name = Some(String::from_utf8_lossy(&name[..len]).into_owned());
+            }
+            _ => {}
+        }
+    }
+
+    /// 读入所有收到的数据和控制消息，返回是否有变化
+    fn poll(&self) -> bool {
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        let mut changed = false;
+        for id in 0..inner.receive.len() {
+            let mut received = false;
+            while let Some(data) = inner.receive[id].pop() {
+                let rx = &mut inner.ports[id].rx;
+                let len = data.len().min(RX_CAPACITY - rx.len());
+                rx.extend(&data[..len]);
+                received = true;
+            }
+            if received {
+                self.transport.notify(&inner.receive[id].queue);
+                changed = true;
+            }
+        }
+        loop {
+            let message = match inner.control.as_mut() {
+                Some((rx, _)) => rx.pop(),
+                None => None,
+            };
+            match message {
+                Some(message) => {
+                    self.handle_control(inner, &message);
+                    changed = true;
+                }
+                None => break,
+            }
+        }
+        if changed {
+            if let Some((rx, _)) = inner.control.as_ref() {
+                self.transport.notify(&rx.queue);
+            }
+        }
+        changed
+    }
+
+    /// 读入数据之后唤醒等待端口的线程
+    fn poll_and_notify(&self) {
+        if self.poll() {
+            for condvar in self.condvars.iter() {
+                condvar.notify_all();
+            }
+        }
+    }
+}
+
+impl Driver for VirtIOConsole {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Char
+    }
+
+    fn handle_irq(&self) {
+        self.transport.ack_interrupt();
+        self.poll_and_notify();
+    }
+}
+
+impl ConsolePort {
+    /// 端口的名字，来自 QEMU 中的 `name=`
+    pub fn name(&self) -> Option<String> {
+        self.console.inner.lock().ports[self.id as usize]
+            .name
+            .clone()
+    }
+
+    /// 设备文件的名字
+    pub fn device_name(&self) -> String {
+        format!("vport{}p{}", self.console.index, self.id)
+    }
+
+    /// 读取收到的数据，没有数据时返回 0
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        // 中断可能还没有开启，或者数据在上一次中断之后才到达
+        if !self.readable() {
+            self.console.poll();
+        }
+        let mut inner = self.console.inner.lock();
+        let rx = &mut inner.ports[self.id as usize].rx;
+        let len = buf.len().min(rx.len());
+        for (byte, data) in buf.iter_mut().zip(rx.drain(..len)) {
+            *byte = data;
+        }
+        len
+    }
+
+    /// 主机一端是否已经连接
+    pub fn host_connected(&self) -> bool {
+        self.console.inner.lock().ports[self.id as usize].host_connected
+    }
+
+    /// 是否有可读的数据
+    pub fn readable(&self) -> bool {
+        !self.console.inner.lock().ports[self.id as usize]
+            .rx
+            .is_empty()
+    }
+
+    /// 发送数据，端口尚未被设备添加时丢弃
+    pub fn write(&self, buf: &[u8]) {
+        let mut inner = self.console.inner.lock();
+        if !inner.ports[self.id as usize].added {
+            return;
+        }
+        let tx = &mut inner.transmit[self.id as usize];
+        // 复制到内核堆上，保证每一段在物理内存中连续
+        for chunk in buf.chunks(TX_CHUNK_SIZE) {
+            let data = chunk.to_vec();
+            tx.add_and_wait(&self.console.transport, &[&data], &[]);
+        }
+    }
+
+    /// 收到数据时唤醒的条件变量
+    pub fn condvar(&self) -> &Condvar {
+        &self.console.condvars[self.id as usize]
+    }
+}
+
+impl Driver for ConsolePort {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Char
+    }
+
+    fn read_char(&self) -> Option<u8> {
+        let mut byte = [0u8];
+        match self.read(&mut byte) {
+            0 => None,
+            _ => Some(byte[0]),
+        }
+    }
+
+    fn write_chars(&self, buf: &[u8]) {
+        self.write(buf);
+    }
+
+    /// 发送是同步的，不需要等待
+    fn flush(&self) {}
+}
+
+/// 所有控制台中已经被设备添加的端口
+pub fn console_ports() -> Vec<Arc<ConsolePort>> {
+    let mut ports = Vec::new();
+    for console in CONSOLES.read().iter() {
+        let inner = console.inner.lock();
+        for (id, port) in inner.ports.iter().enumerate() {
+            if port.added {
+                ports.push(Arc::new(ConsolePort {
+                    console: console.clone(),
+                    id: id as u32,
+                }));
+            }
+        }
+    }
+    ports
+}
+
+/// 名为 `name` 的端口
+fn named_port(name: &str) -> Option<Arc<ConsolePort>> {
+    console_ports()
+        .into_iter()
+        .find(|port| port.name().as_deref() == Some(name))
+}
+
+/// 名为 [`LOG_PORT`] 的端口
+pub fn log_port() -> Option<Arc<ConsolePort>> {
+    named_port(LOG_PORT)
+}
+
+/// 名为 [`GDB_PORT`] 的端口
+pub fn gdb_port() -> Option<Arc<ConsolePort>> {
+    named_port(GDB_PORT)
+}
+
+/// 初始化设备，等待设备添加端口，并注册中断
+pub fn add_driver(header: &'static mut VirtIOHeader, irq: Option<u32>) {
+    let transport = Transport::new(header);
+    let mut multiport = false;
+    transport.begin_init(|features| {
+        multiport = features & F_MULTIPORT != 0;
+        features & F_MULTIPORT
+    });
+    let count = if multiport {
+        transport.config::<u32>(CONFIG_MAX_NR_PORTS).min(MAX_PORTS)
+    } else {
+        1
+    };
+
+    let mut receive = Vec::new();
+    let mut transmit = Vec::new();
+    for id in 0..count {
+        let (rx, tx) = queue_indices(id);
+        match (
+            transport.setup_queue(rx, QUEUE_SIZE),
+            transport.setup_queue(tx, QUEUE_SIZE),
+        ) {
+            (Some(rx), Some(tx)) => {
+                receive.push(ReceiveQueue::new(rx));
+                transmit.push(tx);
+            }
+            _ => break,
+        }
+    }
+    let control = if multiport {
+        match (
+            transport.setup_queue(2, QUEUE_SIZE),
+            transport.setup_queue(3, QUEUE_SIZE),
+        ) {
+            (Some(rx), Some(tx)) => Some((ReceiveQueue::new(rx), tx)),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    transport.finish_init();
+    for queue in receive.iter() {
+        transport.notify(&queue.queue);
+    }
+    if let Some((rx, _)) = control.as_ref() {
+        transport.notify(&rx.queue);
+    }
+
+    let mut ports: Vec<PortState> = receive.iter().map(|_| PortState::default()).collect();
+    if control.is_none() {
+        // 没有多端口时只有一个端口，总是存在
+        if let Some(port) = ports.first_mut() {
+            port.added = true;
+        }
+    }
+    let mut consoles = CONSOLES.write();
+    let console = Arc::new(VirtIOConsole {
+        index: consoles.len(),
+        transport,
+        condvars: ports.iter().map(|_| Condvar::default()).collect(),
+        inner: Lock::new(Inner {
+            receive,
+            transmit,
+            control,
+            ports,
+        }),
+    });
+    consoles.push(console.clone());
+    drop(consoles);
+
+    // 设备收到 DEVICE_READY 后添加端口，之后依次发来端口的名字等信息
+    {
+        let mut inner = console.inner.lock();
+        console.send_control(&mut inner, 0, DEVICE_READY, 1);
+    }
+    while console.poll() {}
+
+    // 日志可能输出到端口上，不能在持有锁时打印
+    let ports: Vec<_> = console
+        .inner
+        .lock()
+        .ports
+        .iter()
+        .enumerate()
+        .filter(|(_, port)| port.added)
+        .map(|(id, port)| (id, port.name.clone(), port.console))
+        .collect();
+    for (id, name, is_console) in ports {
+        info!(
+            "virtio-console: vport{}p{} {}{}",
+            console.index,
+            id,
+            name.as_deref().unwrap_or("(unnamed)"),
+            if is_console { " (console)" } else { "" }
+        );
+    }
+    if let Some(irq) = irq {
+        plic::register(irq, console);
+    }
+}
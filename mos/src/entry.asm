@@ -53,9 +53,9 @@ boot_page_table:
     # 第 2 项：0x8000_0000 -> 0x8000_0000，0xcf 表示 VRWXAD 均为 1
     .8byte (0x80000 << 10) | 0xcf
     .zero 505 * 8
-    # 第 508 项（外设用）：0xffff_ffff_0000_0000 -> 0x0000_0000，0xcf 表示 VRWXAD 均为 1
+    # 第 508 ~ 511 项：0xffff_ffff_0000_0000 -> 0x0000_0000，线性映射低 4G 的外设和内存
+    # 0xcf 表示 VRWXAD 均为 1
     .8byte (0x00000 << 10) | 0xcf
-    .8byte 0
-    # 第 510 项：0xffff_ffff_8000_0000 -> 0x8000_0000，0xcf 表示 VRWXAD 均为 1
+    .8byte (0x40000 << 10) | 0xcf
     .8byte (0x80000 << 10) | 0xcf
-    .8byte 0
+    .8byte (0xc0000 << 10) | 0xcf
@@ -0,0 +1,31 @@
+//! 磁盘上的数据结构都是小端序，这里提供读写其中整数的辅助函数
+
+/// 从小端序字节中读取 `u16`
+pub fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// 从小端序字节中读取 `u32`
+pub fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// 从小端序字节中读取 `u64`
+pub fn read_u64(data: &[u8], offset: usize) -> u64 {
+    read_u32(data, offset) as u64 | (read_u32(data, offset + 4) as u64) << 32
+}
+
+/// 以小端序写入 `u16`
+pub fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+/// 以小端序写入 `u32`
+pub fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,154 @@
+//! cpio "newc" 格式的归档，用于 initramfs
+//!
+//! 每个文件由 110 字节的 ASCII 头、文件名和内容组成，头和文件名、内容分别按 4 字节对齐，
+//! 以名为 `TRAILER!!!` 的文件结束
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::str;
+use rcore_fs::vfs::*;
+
+/// newc 格式的魔数，`070702` 表示带有校验和
+const MAGIC: &[&[u8]] = &[b"070701", b"070702"];
+/// 头的长度
+const HEADER_SIZE: usize = 110;
+/// 结束标记
+const TRAILER: &str = "TRAILER!!!";
+
+/// 文件类型所在的位
+const MODE_TYPE_MASK: u32 = 0o170_000;
+const MODE_DIR: u32 = 0o040_000;
+const MODE_FILE: u32 = 0o100_000;
+const MODE_SYMLINK: u32 = 0o120_000;
+
+/// 头中用到的字段
+struct Header {
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u32,
+    mtime: u32,
+    file_size: usize,
+    name_size: usize,
+}
+
+impl Header {
+    /// 解析头，其中每个字段都是 8 位十六进制数
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < HEADER_SIZE || !MAGIC.contains(&&data[..6]) {
+            return Err(FsError::InvalidParam);
+        }
+        let field = |index: usize| -> Result<u32> {
+            let start = 6 + index * 8;
+            str::from_utf8(&data[start..start + 8])
+                .ok()
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or(FsError::InvalidParam)
+        };
+        Ok(Self {
+            ino: field(0)?,
+            mode: field(1)?,
+            uid: field(2)?,
+            gid: field(3)?,
+            nlink: field(4)?,
+            mtime: field(5)?,
+            file_size: field(6)? as usize,
+            name_size: field(11)? as usize,
+        })
+    }
+}
+
+/// 向上对齐到 4 字节
+fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// 找到或创建 `path` 对应的目录
+fn make_dirs(root: &Arc<dyn INode>, path: &str) -> Result<Arc<dyn INode>> {
+    let mut dir = root.clone();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        dir = match dir.find(name) {
+            Err(FsError::EntryNotFound) => dir.create(name, FileType::Dir, 0o755)?,
+            result => result?,
+        };
+    }
+    Ok(dir)
+}
+
+/// 将归档解压到 `root` 目录中
+///
+/// 支持目录、普通文件（包括硬链接）和符号链接，其他类型的文件会被跳过。
+/// 目录和文件已存在时会覆盖其内容和元数据
+pub fn unpack(archive: &[u8], root: &Arc<dyn INode>) -> Result<()> {
+    // 有多个链接的普通文件，以 inode 编号为键。newc 格式中只有最后一个链接带有内容
+    let mut links: BTreeMap<u32, Arc<dyn INode>> = BTreeMap::new();
+    let mut offset = 0;
+    loop {
+        let header = Header::parse(archive.get(offset..).ok_or(FsError::InvalidParam)?)?;
+        let name_start = offset + HEADER_SIZE;
+        let data_start = align(name_start + header.name_size);
+        let data_end = data_start + header.file_size;
+        if header.name_size == 0 || data_end > archive.len() {
+            return Err(FsError::InvalidParam);
+        }
+        // 文件名以 0 结尾
+        let name = str::from_utf8(&archive[name_start..name_start + header.name_size - 1])
+            .map_err(|_| FsError::InvalidParam)?;
+        if name == TRAILER {
+            return Ok(());
+        }
+        let data = &archive[data_start..data_end];
+        offset = align(data_end);
+
+        let path = name.trim_start_matches("./").trim_start_matches('/');
+        if path.is_empty() || path == "." {
+            continue;
+        }
+        let (parent, name) = match path.rfind('/') {
+            Some(index) => (&path[..index], &path[index + 1..]),
+            None => ("", path),
+        };
+        let type_ = match header.mode & MODE_TYPE_MASK {
+            MODE_DIR => FileType::Dir,
+            MODE_FILE => FileType::File,
+            MODE_SYMLINK => FileType::SymLink,
+            _ => continue,
+        };
+        let parent = make_dirs(root, parent)?;
+        let mode = header.mode & 0o7777;
+        let inode = match links.get(&header.ino) {
+            Some(inode) if type_ == FileType::File => {
+                parent.link(name, inode)?;
+                inode.clone()
+            }
+            _ => match parent.find(name) {
+                Ok(inode) => inode,
+                Err(FsError::EntryNotFound) => parent.create(name, type_, mode)?,
+                Err(error) => return Err(error),
+            },
+        };
+        if type_ == FileType::File && header.nlink > 1 {
+            links.insert(header.ino, inode.clone());
+        }
+        match type_ {
+            FileType::File => {
+                inode.resize(data.len())?;
+                inode.write_at(0, data)?;
+            }
+            FileType::SymLink => {
+                inode.write_at(0, data)?;
+            }
+            _ => {}
+        }
+        let mut metadata = inode.metadata()?;
+        metadata.mode = mode as u16;
+        metadata.uid = header.uid as usize;
+        metadata.gid = header.gid as usize;
+        metadata.mtime = Timespec {
+            sec: header.mtime as i64,
+            nsec: 0,
+        };
+        metadata.atime = metadata.mtime;
+        inode.set_metadata(&metadata)?;
+    }
+}
@@ -0,0 +1,218 @@
+//! ext2 中的 INode
+
+use super::layout::*;
+use super::Ext2FS;
+use crate::bytes::{read_u16, read_u32};
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::any::Any;
+use core::cmp::min;
+use rcore_fs::vfs::*;
+
+/// ext2 中的文件、目录、符号链接或设备文件
+pub struct Ext2INode {
+    /// inode 编号
+    id: u32,
+    /// 磁盘上的 inode，文件系统只读，因此不会改变
+    disk: DiskINode,
+    /// 所属的文件系统
+    fs: Arc<Ext2FS>,
+}
+
+/// 目录中的一项
+struct DirEntry {
+    inode: u32,
+    name: String,
+}
+
+impl Ext2INode {
+    pub(super) fn new(id: u32, disk: DiskINode, fs: Arc<Ext2FS>) -> Self {
+        Self { id, disk, fs }
+    }
+
+    /// 每个间接块中的块号个数
+    fn pointers_per_block(&self) -> usize {
+        self.fs.superblock.block_size / 4
+    }
+
+    /// 读取间接块 `block` 中的第 `index` 个块号
+    fn indirect(&self, block: u32, index: usize) -> Result<u32> {
+        let mut data = [0u8; 4];
+        self.fs.read_block(block, index * 4, &mut data)?;
+        Ok(read_u32(&data, 0))
+    }
+
+    /// 文件中第 `index` 个块对应的块号，0 表示空洞
+    fn block_id(&self, index: usize) -> Result<u32> {
+        let n = self.pointers_per_block();
+        if index < DIRECT_BLOCKS {
+            return Ok(self.disk.block[index]);
+        }
+        let index = index - DIRECT_BLOCKS;
+        if index < n {
+            return self.indirect(self.disk.block[12], index);
+        }
+        let index = index - n;
+        if index < n * n {
+            let block = self.indirect(self.disk.block[13], index / n)?;
+            return self.indirect(block, index % n);
+        }
+        let index = index - n * n;
+        if index < n * n * n {
+            let block = self.indirect(self.disk.block[14], index / (n * n))?;
+            let block = self.indirect(block, index / n % n)?;
+            return self.indirect(block, index % n);
+        }
+        Err(FsError::InvalidParam)
+    }
+
+    /// 读取内容，不检查文件类型
+    fn read_content(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let size = self.disk.size as usize;
+        let start = min(offset, size);
+        let end = min(offset + buf.len(), size);
+        if self.disk.is_fast_symlink(self.fs.superblock.block_size) {
+            let mut target = [0u8; BLOCK_POINTERS_SIZE];
+            for (i, block) in self.disk.block.iter().enumerate() {
+                target[i * 4..i * 4 + 4].copy_from_slice(&block.to_le_bytes());
+            }
+            buf[..end - start].copy_from_slice(&target[start..end]);
+            return Ok(end - start);
+        }
+        let block_size = self.fs.superblock.block_size;
+        let mut position = start;
+        while position < end {
+            let len = min(end - position, block_size - position % block_size);
+            self.fs.read_block(
+                self.block_id(position / block_size)?,
+                position % block_size,
+                &mut buf[position - start..position - start + len],
+            )?;
+            position += len;
+        }
+        Ok(end - start)
+    }
+
+    /// 读出目录中的所有目录项，包括 `.` 和 `..`
+    fn dir_entries(&self) -> Result<Vec<DirEntry>> {
+        if self.disk.type_() != FileType::Dir {
+            return Err(FsError::NotDir);
+        }
+        let mut data = vec![0u8; self.disk.size as usize];
+        self.read_content(0, &mut data)?;
+        let block_size = self.fs.superblock.block_size;
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset + 8 <= data.len() {
+            let inode = read_u32(&data, offset);
+            let rec_len = read_u16(&data, offset + 4) as usize;
+            // 没有 filetype 特性时，第 7 个字节是名称长度的高 8 位
+            let name_len = if self.fs.superblock.filetype {
+                data[offset + 6] as usize
+            } else {
+                read_u16(&data, offset + 6) as usize
+            };
+            // 目录项不能跨越块的边界
+            if rec_len < 8 || offset % block_size + rec_len > block_size || 8 + name_len > rec_len {
+                return Err(FsError::DeviceError);
+            }
+            if inode != 0 {
+                entries.push(DirEntry {
+                    inode,
+                    name: String::from_utf8_lossy(&data[offset + 8..offset + 8 + name_len])
+                        .to_string(),
+                });
+            }
+            offset += rec_len;
+        }
+        Ok(entries)
+    }
+}
+
+impl INode for Ext2INode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        match self.disk.type_() {
+            FileType::File | FileType::SymLink => self.read_content(offset, buf),
+            FileType::Dir => Err(FsError::IsDir),
+            _ => Err(FsError::NotFile),
+        }
+    }
+
+    /// 只读文件系统
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        Err(FsError::NotSupported)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: false,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        let type_ = self.disk.type_();
+        Ok(Metadata {
+            dev: 0,
+            inode: self.id as usize,
+            size: self.disk.size as usize,
+            blk_size: self.fs.superblock.block_size,
+            blocks: self.disk.blocks as usize,
+            atime: timespec(self.disk.atime),
+            mtime: timespec(self.disk.mtime),
+            ctime: timespec(self.disk.ctime),
+            type_,
+            mode: self.disk.mode & 0o7777,
+            nlinks: self.disk.links_count as usize,
+            uid: self.disk.uid as usize,
+            gid: self.disk.gid as usize,
+            rdev: match type_ {
+                FileType::CharDevice | FileType::BlockDevice => self.disk.rdev(),
+                _ => 0,
+            },
+        })
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 默认实现会和 `create2` 互相调用，需要显式拒绝
+    fn create(&self, _name: &str, _type: FileType, _mode: u32) -> Result<Arc<dyn INode>> {
+        Err(FsError::NotSupported)
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        let entry = self
+            .dir_entries()?
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .ok_or(FsError::EntryNotFound)?;
+        Ok(self.fs.get_inode(entry.inode)?)
+    }
+
+    fn get_entry(&self, id: usize) -> Result<String> {
+        self.dir_entries()?
+            .into_iter()
+            .nth(id)
+            .map(|entry| entry.name)
+            .ok_or(FsError::EntryNotFound)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.clone()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,214 @@
+//! ext2 在磁盘上的数据结构：超级块、块组描述符和 inode
+
+use crate::bytes::{read_u16, read_u32};
+use rcore_fs::vfs::{FileType, FsError, Result, Timespec};
+
+/// 超级块在设备上的偏移
+pub const SUPERBLOCK_OFFSET: usize = 1024;
+/// 超级块的大小
+pub const SUPERBLOCK_SIZE: usize = 1024;
+/// ext2 的魔数
+const MAGIC: u16 = 0xef53;
+/// 根目录的 inode 编号
+pub const ROOT_INODE: u32 = 2;
+
+/// 目录项中记录了文件类型
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+/// 块组的位图和 inode 表可以放在其他块组中，只影响布局，不影响读取
+const INCOMPAT_FLEX_BG: u32 = 0x0200;
+/// 能够处理的 incompat 特性，其他特性（例如 extent）都不支持
+const SUPPORTED_INCOMPAT: u32 = INCOMPAT_FILETYPE | INCOMPAT_FLEX_BG;
+
+/// 块组描述符的大小
+pub const GROUP_DESC_SIZE: usize = 32;
+/// `s_log_block_size` 的最大值，对应 64K 的块
+const MAX_LOG_BLOCK_SIZE: u32 = 6;
+
+/// 超级块中用到的字段
+#[derive(Debug, Clone)]
+pub struct SuperBlock {
+    pub inodes_count: u32,
+    pub blocks_count: u32,
+    pub free_blocks_count: u32,
+    pub free_inodes_count: u32,
+    pub first_data_block: u32,
+    /// 块大小，为 `1024 << s_log_block_size`
+    pub block_size: usize,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    /// inode 的大小，版本 0 中固定为 128
+    pub inode_size: usize,
+    /// 目录项中是否记录了文件类型
+    pub filetype: bool,
+}
+
+impl SuperBlock {
+    /// 解析超级块，不是 ext2 或者包含不支持的特性时返回错误
+    ///
+    /// 之后计算偏移和下标都依赖这些字段，这里检查它们的范围，损坏的镜像返回 [`FsError::WrongFs`]
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if read_u16(data, 56) != MAGIC {
+            return Err(FsError::WrongFs);
+        }
+        let revision = read_u32(data, 76);
+        let (inode_size, incompat) = if revision == 0 {
+            (128, 0)
+        } else {
+            (read_u16(data, 88) as usize, read_u32(data, 96))
+        };
+        if incompat & !SUPPORTED_INCOMPAT != 0 {
+            return Err(FsError::NotSupported);
+        }
+        let log_block_size = read_u32(data, 24);
+        if log_block_size > MAX_LOG_BLOCK_SIZE {
+            return Err(FsError::WrongFs);
+        }
+        let superblock = Self {
+            inodes_count: read_u32(data, 0),
+            blocks_count: read_u32(data, 4),
+            free_blocks_count: read_u32(data, 12),
+            free_inodes_count: read_u32(data, 16),
+            first_data_block: read_u32(data, 20),
+            block_size: 1024 << log_block_size,
+            blocks_per_group: read_u32(data, 32),
+            inodes_per_group: read_u32(data, 40),
+            inode_size,
+            filetype: incompat & INCOMPAT_FILETYPE != 0,
+        };
+        // 每个块组的块和 inode 各由一个块大小的位图管理
+        let bitmap_bits = superblock.block_size * 8;
+        if superblock.blocks_per_group == 0
+            || superblock.blocks_per_group as usize > bitmap_bits
+            || superblock.inodes_per_group == 0
+            || superblock.inodes_per_group as usize > bitmap_bits
+            || superblock.inode_size < 128
+            || superblock.inode_size > superblock.block_size
+            || !superblock.inode_size.is_power_of_two()
+            || superblock.first_data_block >= superblock.blocks_count
+            || superblock.inodes_count as usize
+                > superblock.group_count() * superblock.inodes_per_group as usize
+        {
+            return Err(FsError::WrongFs);
+        }
+        Ok(superblock)
+    }
+
+    /// 块组的个数
+    pub fn group_count(&self) -> usize {
+        let blocks = (self.blocks_count - self.first_data_block) as usize;
+        let per_group = self.blocks_per_group as usize;
+        (blocks + per_group - 1) / per_group
+    }
+}
+
+/// 块组描述符中用到的字段
+#[derive(Debug, Clone)]
+pub struct GroupDesc {
+    /// inode 表的起始块号
+    pub inode_table: u32,
+}
+
+impl GroupDesc {
+    pub fn parse(data: &[u8]) -> Self {
+        Self {
+            inode_table: read_u32(data, 8),
+        }
+    }
+}
+
+/// 直接块的个数
+pub const DIRECT_BLOCKS: usize = 12;
+/// `i_block` 的字节数，快速符号链接的目标直接保存在这里
+pub const BLOCK_POINTERS_SIZE: usize = 60;
+
+/// 磁盘上的 inode
+#[derive(Debug, Clone)]
+pub struct DiskINode {
+    pub mode: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub atime: u32,
+    pub ctime: u32,
+    pub mtime: u32,
+    pub links_count: u16,
+    /// 占用的 512 字节扇区数
+    pub blocks: u32,
+    /// 12 个直接块、一级、二级和三级间接块
+    pub block: [u32; 15],
+    /// 扩展属性块
+    pub file_acl: u32,
+}
+
+impl DiskINode {
+    pub fn parse(data: &[u8]) -> Self {
+        let mut block = [0u32; 15];
+        for (i, block) in block.iter_mut().enumerate() {
+            *block = read_u32(data, 40 + i * 4);
+        }
+        let mode = read_u16(data, 0);
+        let mut size = read_u32(data, 4) as u64;
+        // 普通文件的 i_dir_acl 字段保存了大小的高 32 位（large_file 特性）
+        if mode & 0xf000 == 0x8000 {
+            size |= (read_u32(data, 108) as u64) << 32;
+        }
+        Self {
+            mode,
+            uid: read_u16(data, 2) as u32 | (read_u16(data, 120) as u32) << 16,
+            gid: read_u16(data, 24) as u32 | (read_u16(data, 122) as u32) << 16,
+            size,
+            atime: read_u32(data, 8),
+            ctime: read_u32(data, 12),
+            mtime: read_u32(data, 16),
+            links_count: read_u16(data, 26),
+            blocks: read_u32(data, 28),
+            block,
+            file_acl: read_u32(data, 104),
+        }
+    }
+
+    /// 文件类型
+    pub fn type_(&self) -> FileType {
+        match self.mode & 0xf000 {
+            0x4000 => FileType::Dir,
+            0xa000 => FileType::SymLink,
+            0x2000 => FileType::CharDevice,
+            0x6000 => FileType::BlockDevice,
+            0x1000 => FileType::NamedPipe,
+            0xc000 => FileType::Socket,
+            _ => FileType::File,
+        }
+    }
+
+    /// 是否是目标直接保存在 `i_block` 中的快速符号链接
+    pub fn is_fast_symlink(&self, block_size: usize) -> bool {
+        let acl_blocks = if self.file_acl != 0 {
+            block_size as u32 / 512
+        } else {
+            0
+        };
+        self.type_() == FileType::SymLink
+            && self.size < BLOCK_POINTERS_SIZE as u64
+            && self.blocks == acl_blocks
+    }
+
+    /// 字符设备和块设备的设备号，新格式保存在 `i_block[1]` 中
+    pub fn rdev(&self) -> usize {
+        if self.block[0] != 0 {
+            self.block[0] as usize
+        } else {
+            let encoded = self.block[1] as usize;
+            let major = (encoded >> 8) & 0xfff;
+            let minor = (encoded & 0xff) | ((encoded >> 12) & 0xfff00);
+            (major << 8) | minor
+        }
+    }
+}
+
+/// 将秒数转换为 [`Timespec`]
+pub fn timespec(sec: u32) -> Timespec {
+    Timespec {
+        sec: sec as i64,
+        nsec: 0,
+    }
+}
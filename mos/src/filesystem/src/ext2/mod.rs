@@ -0,0 +1,124 @@
+//! 只读的 ext2 文件系统 [`Ext2FS`]
+//!
+//! 支持 `mke2fs` 默认生成的镜像：直接块和各级间接块、目录项、符号链接和元数据。
+//! 所有修改操作都返回 [`FsError::NotSupported`]
+
+mod inode;
+mod layout;
+
+pub use inode::Ext2INode;
+
+use alloc::{
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use layout::*;
+use rcore_fs::dev::Device;
+use rcore_fs::vfs::*;
+
+/// ext2 文件系统
+pub struct Ext2FS {
+    device: Arc<dyn Device>,
+    superblock: SuperBlock,
+    /// 块组描述符表
+    groups: Vec<GroupDesc>,
+    /// 指向自身的弱引用，INode 通过它来持有文件系统
+    self_ref: Weak<Ext2FS>,
+}
+
+impl Ext2FS {
+    /// 检查设备上是否有 ext2 的超级块
+    pub fn probe(device: &dyn Device) -> bool {
+        let mut data = [0u8; SUPERBLOCK_SIZE];
+        device.read_at(SUPERBLOCK_OFFSET, &mut data).is_ok() && SuperBlock::parse(&data).is_ok()
+    }
+
+    /// 打开设备上的 ext2 文件系统
+    pub fn open(device: Arc<dyn Device>) -> Result<Arc<Self>> {
+        let mut data = [0u8; SUPERBLOCK_SIZE];
+        device.read_at(SUPERBLOCK_OFFSET, &mut data)?;
+        let superblock = SuperBlock::parse(&data)?;
+        // 块组描述符表紧跟在超级块所在的块之后
+        let mut table = vec![0u8; superblock.group_count() * GROUP_DESC_SIZE];
+        device.read_at(
+            (superblock.first_data_block as usize + 1) * superblock.block_size,
+            &mut table,
+        )?;
+        let groups = table
+            .chunks(GROUP_DESC_SIZE)
+            .map(GroupDesc::parse)
+            .collect();
+        let fs = Arc::new(Self {
+            device,
+            superblock,
+            groups,
+            self_ref: Weak::new(),
+        });
+        let weak = Arc::downgrade(&fs);
+        let ptr = Arc::into_raw(fs) as *mut Self;
+        unsafe {
+            (*ptr).self_ref = weak;
+            Ok(Arc::from_raw(ptr))
+        }
+    }
+
+    /// 读取块中 `offset` 开始的内容，块号为 0 表示空洞，读出全 0
+    fn read_block(&self, block: u32, offset: usize, buf: &mut [u8]) -> Result<()> {
+        if block == 0 {
+            for byte in buf.iter_mut() {
+                *byte = 0;
+            }
+        } else {
+            self.device
+                .read_at(block as usize * self.superblock.block_size + offset, buf)?;
+        }
+        Ok(())
+    }
+
+    /// 读取编号为 `id` 的 inode
+    fn get_inode(&self, id: u32) -> Result<Arc<Ext2INode>> {
+        if id == 0 || id > self.superblock.inodes_count {
+            return Err(FsError::EntryNotFound);
+        }
+        let index = (id - 1) as usize;
+        let group = self
+            .groups
+            .get(index / self.superblock.inodes_per_group as usize)
+            .ok_or(FsError::DeviceError)?;
+        let offset = group.inode_table as usize * self.superblock.block_size
+            + index % self.superblock.inodes_per_group as usize * self.superblock.inode_size;
+        let mut data = [0u8; 128];
+        self.device.read_at(offset, &mut data)?;
+        Ok(Arc::new(Ext2INode::new(
+            id,
+            DiskINode::parse(&data),
+            self.self_ref.upgrade().unwrap(),
+        )))
+    }
+}
+
+impl FileSystem for Ext2FS {
+    /// 只读文件系统不需要写回
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        self.get_inode(ROOT_INODE)
+            .expect("failed to read ext2 root inode")
+    }
+
+    fn info(&self) -> FsInfo {
+        FsInfo {
+            bsize: self.superblock.block_size,
+            frsize: self.superblock.block_size,
+            blocks: self.superblock.blocks_count as usize,
+            bfree: self.superblock.free_blocks_count as usize,
+            bavail: self.superblock.free_blocks_count as usize,
+            files: self.superblock.inodes_count as usize,
+            ffree: self.superblock.free_inodes_count as usize,
+            namemax: 255,
+        }
+    }
+}
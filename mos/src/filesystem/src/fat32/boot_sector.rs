@@ -0,0 +1,156 @@
+//! 引导扇区中的 BIOS Parameter Block 以及 FSInfo 扇区
+
+use crate::bytes::{read_u16, read_u32, write_u32};
+use alloc::vec;
+use rcore_fs::dev::Device;
+use rcore_fs::vfs::{FsError, Result};
+
+/// 引导扇区末尾的签名
+const BOOT_SIGNATURE: u16 = 0xaa55;
+/// FSInfo 扇区开头的签名
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+/// FSInfo 扇区中间的签名
+const FS_INFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+
+/// FAT32 引导扇区中需要用到的参数
+#[derive(Debug, Clone)]
+pub struct BootSector {
+    /// 每个扇区的字节数
+    pub bytes_per_sector: usize,
+    /// 每个簇的扇区数
+    pub sectors_per_cluster: usize,
+    /// FAT 之前的保留扇区数
+    pub reserved_sectors: usize,
+    /// FAT 的份数
+    pub num_fats: usize,
+    /// 总扇区数
+    pub total_sectors: usize,
+    /// 每份 FAT 占用的扇区数
+    pub fat_size: usize,
+    /// 根目录的起始簇号
+    pub root_cluster: u32,
+    /// FSInfo 所在的扇区
+    pub fs_info_sector: usize,
+}
+
+impl BootSector {
+    /// 解析引导扇区，不是 FAT32 时返回 `None`
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 512 || read_u16(data, 510) != BOOT_SIGNATURE {
+            return None;
+        }
+        let bytes_per_sector = read_u16(data, 11) as usize;
+        let sectors_per_cluster = data[13] as usize;
+        let boot_sector = Self {
+            bytes_per_sector,
+            sectors_per_cluster,
+            reserved_sectors: read_u16(data, 14) as usize,
+            num_fats: data[16] as usize,
+            total_sectors: match read_u16(data, 19) {
+                0 => read_u32(data, 32) as usize,
+                count => count as usize,
+            },
+            fat_size: read_u32(data, 36) as usize,
+            root_cluster: read_u32(data, 44),
+            fs_info_sector: read_u16(data, 48) as usize,
+        };
+        // FAT32 中根目录项个数和 16 位的 FAT 大小都必须为 0
+        let valid = [512, 1024, 2048, 4096].contains(&bytes_per_sector)
+            && sectors_per_cluster.is_power_of_two()
+            && boot_sector.reserved_sectors > 0
+            && boot_sector.num_fats > 0
+            && read_u16(data, 17) == 0
+            && read_u16(data, 22) == 0
+            && boot_sector.fat_size > 0
+            && boot_sector.root_cluster >= 2
+            && boot_sector.data_sector() < boot_sector.total_sectors;
+        if valid {
+            Some(boot_sector)
+        } else {
+            None
+        }
+    }
+
+    /// 从设备中读取并解析引导扇区
+    pub fn read(device: &dyn Device) -> Result<Self> {
+        let mut data = [0u8; 512];
+        device.read_at(0, &mut data)?;
+        Self::parse(&data).ok_or(FsError::WrongFs)
+    }
+
+    /// 簇的字节数
+    pub fn cluster_size(&self) -> usize {
+        self.bytes_per_sector * self.sectors_per_cluster
+    }
+
+    /// 第 `index` 份 FAT 的字节偏移
+    pub fn fat_offset(&self, index: usize) -> usize {
+        (self.reserved_sectors + index * self.fat_size) * self.bytes_per_sector
+    }
+
+    /// 数据区的起始扇区
+    fn data_sector(&self) -> usize {
+        self.reserved_sectors + self.num_fats * self.fat_size
+    }
+
+    /// 簇的字节偏移（簇号从 2 开始）
+    pub fn cluster_offset(&self, cluster: u32) -> usize {
+        self.data_sector() * self.bytes_per_sector + (cluster as usize - 2) * self.cluster_size()
+    }
+
+    /// 数据区中的簇数，合法的簇号为 `2..cluster_count + 2`
+    pub fn cluster_count(&self) -> u32 {
+        let clusters = (self.total_sectors - self.data_sector()) / self.sectors_per_cluster;
+        // 同时受 FAT 大小的限制
+        let fat_entries = self.fat_size * self.bytes_per_sector / 4 - 2;
+        clusters.min(fat_entries) as u32
+    }
+}
+
+/// FSInfo 扇区中记录的空闲簇信息
+#[derive(Debug, Clone, Copy)]
+pub struct FsInfoSector {
+    /// 空闲簇数，未知时为 `u32::max_value()`
+    pub free_count: u32,
+    /// 下一个可能空闲的簇号，未知时为 `u32::max_value()`
+    pub next_free: u32,
+}
+
+impl FsInfoSector {
+    /// 读取 FSInfo 扇区，签名不正确时认为信息未知
+    pub fn read(device: &dyn Device, boot_sector: &BootSector) -> Result<Self> {
+        let mut data = vec![0u8; boot_sector.bytes_per_sector];
+        device.read_at(
+            boot_sector.fs_info_sector * boot_sector.bytes_per_sector,
+            &mut data,
+        )?;
+        if read_u32(&data, 0) == FS_INFO_LEAD_SIGNATURE
+            && read_u32(&data, 484) == FS_INFO_STRUCT_SIGNATURE
+        {
+            Ok(Self {
+                free_count: read_u32(&data, 488),
+                next_free: read_u32(&data, 492),
+            })
+        } else {
+            Ok(Self {
+                free_count: u32::max_value(),
+                next_free: u32::max_value(),
+            })
+        }
+    }
+
+    /// 写回 FSInfo 扇区中的空闲簇信息
+    pub fn write(&self, device: &dyn Device, boot_sector: &BootSector) -> Result<()> {
+        let offset = boot_sector.fs_info_sector * boot_sector.bytes_per_sector;
+        let mut data = vec![0u8; boot_sector.bytes_per_sector];
+        device.read_at(offset, &mut data)?;
+        if read_u32(&data, 0) != FS_INFO_LEAD_SIGNATURE {
+            // 没有合法的 FSInfo 扇区，不写入
+            return Ok(());
+        }
+        write_u32(&mut data, 488, self.free_count);
+        write_u32(&mut data, 492, self.next_free);
+        device.write_at(offset, &data)?;
+        Ok(())
+    }
+}
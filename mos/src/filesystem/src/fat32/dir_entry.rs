@@ -0,0 +1,333 @@
+//! 32 字节的目录项，包括短文件名目录项和长文件名（LFN）目录项
+
+use crate::bytes::{read_u16, read_u32, write_u16, write_u32};
+use alloc::{format, string::String, vec::Vec};
+use rcore_fs::vfs::Timespec;
+
+/// 目录项的大小
+pub const ENTRY_SIZE: usize = 32;
+
+/// 只读
+pub const ATTR_READ_ONLY: u8 = 0x01;
+/// 卷标
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+/// 目录
+pub const ATTR_DIRECTORY: u8 = 0x10;
+/// 归档，新建文件时设置
+pub const ATTR_ARCHIVE: u8 = 0x20;
+/// 长文件名目录项的属性
+pub const ATTR_LONG_NAME: u8 = 0x0f;
+
+/// 首字节为该值表示目录项已删除
+pub const DELETED: u8 = 0xe5;
+/// 首字节为 0 表示目录在此结束
+pub const END: u8 = 0x00;
+
+/// 短文件名中主文件名为小写（Windows NT 的扩展）
+const NT_LOWER_BASE: u8 = 0x08;
+/// 短文件名中扩展名为小写
+const NT_LOWER_EXT: u8 = 0x10;
+
+/// 每个长文件名目录项保存的 UTF-16 字符数
+const LFN_CHARS: usize = 13;
+/// 长文件名目录项中各个字符的偏移
+const LFN_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+/// 序号中标记最后一个（即磁盘上第一个）长文件名目录项的位
+const LFN_LAST: u8 = 0x40;
+
+/// 短文件名目录项
+#[derive(Debug, Clone)]
+pub struct ShortEntry {
+    /// 8.3 格式的文件名，不足的部分用空格填充
+    pub name: [u8; 11],
+    pub attr: u8,
+    /// Windows NT 保留字段，用来记录短文件名的大小写
+    pub nt_res: u8,
+    pub create_time: u16,
+    pub create_date: u16,
+    pub access_date: u16,
+    pub first_cluster: u32,
+    pub write_time: u16,
+    pub write_date: u16,
+    pub size: u32,
+}
+
+impl ShortEntry {
+    /// 新建一个目录项，时间都设为 FAT 的起始时间 1980-01-01
+    pub fn new(name: [u8; 11], attr: u8) -> Self {
+        let (date, time) = timespec_to_fat(Timespec { sec: 0, nsec: 0 });
+        Self {
+            name,
+            attr,
+            nt_res: 0,
+            create_time: time,
+            create_date: date,
+            access_date: date,
+            first_cluster: 0,
+            write_time: time,
+            write_date: date,
+            size: 0,
+        }
+    }
+
+    /// 从 32 字节中解析
+    pub fn parse(data: &[u8]) -> Self {
+        let mut name = [0u8; 11];
+        name.copy_from_slice(&data[0..11]);
+        Self {
+            name,
+            attr: data[11],
+            nt_res: data[12],
+            create_time: read_u16(data, 14),
+            create_date: read_u16(data, 16),
+            access_date: read_u16(data, 18),
+            first_cluster: (read_u16(data, 20) as u32) << 16 | read_u16(data, 26) as u32,
+            write_time: read_u16(data, 22),
+            write_date: read_u16(data, 24),
+            size: read_u32(data, 28),
+        }
+    }
+
+    /// 转换为 32 字节
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut data = [0u8; ENTRY_SIZE];
+        data[0..11].copy_from_slice(&self.name);
+        data[11] = self.attr;
+        data[12] = self.nt_res;
+        write_u16(&mut data, 14, self.create_time);
+        write_u16(&mut data, 16, self.create_date);
+        write_u16(&mut data, 18, self.access_date);
+        write_u16(&mut data, 20, (self.first_cluster >> 16) as u16);
+        write_u16(&mut data, 22, self.write_time);
+        write_u16(&mut data, 24, self.write_date);
+        write_u16(&mut data, 26, self.first_cluster as u16);
+        write_u32(&mut data, 28, self.size);
+        data
+    }
+
+    /// 是否是目录
+    pub fn is_dir(&self) -> bool {
+        self.attr & ATTR_DIRECTORY != 0
+    }
+
+    /// 由短文件名得到的显示名称，例如 `README.TXT`
+    pub fn display_name(&self) -> String {
+        let mut name = self.name;
+        // 0x05 用来表示实际的首字符 0xe5
+        if name[0] == 0x05 {
+            name[0] = DELETED;
+        }
+        let convert = |part: &[u8], lower: bool| -> String {
+            part.iter()
+                .rev()
+                .skip_while(|&&c| c == b' ')
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .map(|&c| {
+                    if lower {
+                        c.to_ascii_lowercase() as char
+                    } else {
+                        c as char
+                    }
+                })
+                .collect()
+        };
+        let mut display = convert(&name[0..8], self.nt_res & NT_LOWER_BASE != 0);
+        let ext = convert(&name[8..11], self.nt_res & NT_LOWER_EXT != 0);
+        if !ext.is_empty() {
+            display.push('.');
+            display.push_str(&ext);
+        }
+        display
+    }
+
+    /// 长文件名目录项中使用的校验和
+    pub fn checksum(&self) -> u8 {
+        self.name.iter().fold(0u8, |sum, &c| {
+            ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(c)
+        })
+    }
+
+    /// 修改时间
+    pub fn mtime(&self) -> Timespec {
+        fat_to_timespec(self.write_date, self.write_time)
+    }
+
+    /// 访问时间，FAT 只记录日期
+    pub fn atime(&self) -> Timespec {
+        fat_to_timespec(self.access_date, 0)
+    }
+
+    /// 创建时间
+    pub fn ctime(&self) -> Timespec {
+        fat_to_timespec(self.create_date, self.create_time)
+    }
+}
+
+/// 是否是长文件名目录项
+pub fn is_long_entry(data: &[u8]) -> bool {
+    data[11] & 0x3f == ATTR_LONG_NAME
+}
+
+/// 长文件名目录项的信息：序号、是否是最后一项、校验和以及包含的 UTF-16 字符
+pub fn parse_long_entry(data: &[u8]) -> (u8, bool, u8, [u16; LFN_CHARS]) {
+    let mut chars = [0u16; LFN_CHARS];
+    for (c, &offset) in chars.iter_mut().zip(LFN_OFFSETS.iter()) {
+        *c = read_u16(data, offset);
+    }
+    (data[0] & 0x1f, data[0] & LFN_LAST != 0, data[13], chars)
+}
+
+/// 将长文件名目录项按序号拼接后的 UTF-16 字符转换为字符串
+pub fn decode_long_name(chars: &[u16]) -> Option<String> {
+    let len = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+    core::char::decode_utf16(chars[..len].iter().cloned())
+        .collect::<core::result::Result<String, _>>()
+        .ok()
+}
+
+/// 生成 `name` 的长文件名目录项，按磁盘上的顺序（序号从大到小）排列
+pub fn long_entries(name: &str, checksum: u8) -> Vec<[u8; ENTRY_SIZE]> {
+    let chars: Vec<u16> = name.encode_utf16().collect();
+    let count = (chars.len() + LFN_CHARS - 1) / LFN_CHARS;
+    (0..count)
+        .rev()
+        .map(|index| {
+            let mut data = [0u8; ENTRY_SIZE];
+            data[0] = (index + 1) as u8;
+            if index == count - 1 {
+                data[0] |= LFN_LAST;
+            }
+            data[11] = ATTR_LONG_NAME;
+            data[13] = checksum;
+            for (i, &offset) in LFN_OFFSETS.iter().enumerate() {
+                // 名称之后是一个 0，再之后用 0xffff 填充
+                let c = match (index * LFN_CHARS + i).cmp(&chars.len()) {
+                    core::cmp::Ordering::Less => chars[index * LFN_CHARS + i],
+                    core::cmp::Ordering::Equal => 0,
+                    core::cmp::Ordering::Greater => 0xffff,
+                };
+                write_u16(&mut data, offset, c);
+            }
+            data
+        })
+        .collect()
+}
+
+/// 长文件名的最大长度（UTF-16 字符数）
+pub const MAX_NAME_LEN: usize = 255;
+
+/// 短文件名中允许的字符（除字母和数字外）
+fn is_short_name_char(c: u8) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || b"$%'-_@~`!(){}^#&".contains(&c)
+}
+
+/// 名称是否可以原样保存为短文件名，此时不需要长文件名目录项
+pub fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = match name.rfind('.') {
+        Some(index) => (&name[..index], &name[index + 1..]),
+        None => (name, ""),
+    };
+    let valid = |part: &str, max: usize| part.len() <= max && part.bytes().all(is_short_name_char);
+    if base.is_empty() || !valid(base, 8) || !valid(ext, 3) || name.ends_with('.') {
+        return None;
+    }
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(base.as_bytes());
+    short[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(short)
+}
+
+/// 为长文件名生成一个 `BASE~N.EXT` 形式的短文件名，`exists` 用来检查是否重名
+pub fn generate_short_name(name: &str, exists: impl Fn(&[u8; 11]) -> bool) -> [u8; 11] {
+    let convert = |part: &str| -> Vec<u8> {
+        part.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if c.is_ascii() && is_short_name_char(c as u8) {
+                    c as u8
+                } else {
+                    b'_'
+                }
+            })
+            .collect()
+    };
+    let trimmed = name.trim_start_matches('.');
+    let (base, ext) = match trimmed.rfind('.') {
+        Some(index) => (convert(&trimmed[..index]), convert(&trimmed[index + 1..])),
+        None => (convert(trimmed), Vec::new()),
+    };
+    let mut short = [b' '; 11];
+    let ext_len = ext.len().min(3);
+    short[8..8 + ext_len].copy_from_slice(&ext[..ext_len]);
+    for n in 1.. {
+        let tail = format!("~{}", n);
+        let tail = tail.as_bytes();
+        let base_len = base.len().min(8 - tail.len());
+        let mut candidate = short;
+        candidate[..base_len].copy_from_slice(&base[..base_len]);
+        candidate[base_len..base_len + tail.len()].copy_from_slice(tail);
+        for c in candidate[base_len + tail.len()..8].iter_mut() {
+            *c = b' ';
+        }
+        if !exists(&candidate) {
+            return candidate;
+        }
+    }
+    unreachable!()
+}
+
+/// 1970-01-01 到 1980-01-01 的秒数
+const FAT_EPOCH: i64 = 315_532_800;
+
+/// 从 1970-01-01 起算的天数转换为年月日
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// 年月日转换为从 1970-01-01 起算的天数
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// FAT 格式的日期和时间转换为 [`Timespec`]
+pub fn fat_to_timespec(date: u16, time: u16) -> Timespec {
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xf).max(1) as u32;
+    let day = (date & 0x1f).max(1) as u32;
+    let seconds =
+        (time >> 11) as i64 * 3600 + ((time >> 5) & 0x3f) as i64 * 60 + (time & 0x1f) as i64 * 2;
+    Timespec {
+        sec: days_from_civil(year, month, day) * 86400 + seconds,
+        nsec: 0,
+    }
+}
+
+/// [`Timespec`] 转换为 FAT 格式的日期和时间，早于 1980 年的时间按 1980-01-01 处理
+pub fn timespec_to_fat(time: Timespec) -> (u16, u16) {
+    let sec = time.sec.max(FAT_EPOCH);
+    let (year, month, day) = civil_from_days(sec.div_euclid(86400));
+    let seconds = sec.rem_euclid(86400);
+    let date = (((year - 1980).min(127) as u16) << 9) | (month as u16) << 5 | day as u16;
+    let time = ((seconds / 3600) as u16) << 11
+        | ((seconds / 60 % 60) as u16) << 5
+        | (seconds % 60 / 2) as u16;
+    (date, time)
+}
@@ -0,0 +1,231 @@
+//! 带写回缓存的文件分配表
+
+use super::boot_sector::{BootSector, FsInfoSector};
+use crate::bytes::{read_u32, write_u32};
+use alloc::{
+    collections::btree_map::{BTreeMap, Entry},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use rcore_fs::dev::Device;
+use rcore_fs::vfs::{FsError, Result};
+
+/// 表项中只有低 28 位有效
+const ENTRY_MASK: u32 = 0x0fff_ffff;
+/// 坏簇标记
+const BAD_CLUSTER: u32 = 0x0fff_fff7;
+/// 写入的簇链结束标记，读取时大于等于 [`BAD_CLUSTER`] 的值都视为结束
+pub const END_OF_CHAIN: u32 = 0x0fff_ffff;
+
+/// 缓存中的一个 FAT 扇区
+struct CachedSector {
+    data: Vec<u8>,
+    /// 是否被修改过，写回时会写到每一份 FAT
+    dirty: bool,
+    /// 最近一次访问的时间戳，用于替换最久未使用的扇区
+    last_used: usize,
+}
+
+/// 文件分配表
+///
+/// 按扇区缓存第一份 FAT，修改只发生在缓存中，
+/// 在 [`Fat::flush`] 或扇区被替换出缓存时才写回到所有的 FAT 副本
+pub struct Fat {
+    device: Arc<dyn Device>,
+    boot_sector: BootSector,
+    /// 以 FAT 内的扇区序号为键
+    sectors: BTreeMap<usize, CachedSector>,
+    /// 最多缓存的扇区数
+    capacity: usize,
+    /// 访问计数，作为 [`CachedSector::last_used`]
+    clock: usize,
+    /// 空闲簇数
+    free_count: u32,
+    /// 下一次分配时开始查找的簇号
+    next_free: u32,
+}
+
+impl Fat {
+    /// 根据引导扇区和 FSInfo 创建，FSInfo 中的信息无效时会扫描整个 FAT
+    pub fn new(device: Arc<dyn Device>, boot_sector: BootSector, capacity: usize) -> Result<Self> {
+        let fs_info = FsInfoSector::read(&*device, &boot_sector)?;
+        let mut fat = Self {
+            device,
+            boot_sector,
+            sectors: BTreeMap::new(),
+            capacity,
+            clock: 0,
+            free_count: fs_info.free_count,
+            next_free: fs_info.next_free,
+        };
+        if fat.free_count > fat.boot_sector.cluster_count() {
+            fat.free_count = 0;
+            for cluster in 2..fat.boot_sector.cluster_count() + 2 {
+                if fat.get(cluster)? == 0 {
+                    fat.free_count += 1;
+                }
+            }
+        }
+        if !fat.is_valid(fat.next_free) {
+            fat.next_free = 2;
+        }
+        Ok(fat)
+    }
+
+    /// 空闲簇数
+    pub fn free_count(&self) -> u32 {
+        self.free_count
+    }
+
+    /// 是否是数据区中合法的簇号
+    pub fn is_valid(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster < self.boot_sector.cluster_count() + 2
+    }
+
+    /// 取得 `cluster` 对应表项所在的缓存扇区，以及表项在扇区中的偏移
+    fn sector(&mut self, cluster: u32) -> Result<(&mut CachedSector, usize)> {
+        let bytes_per_sector = self.boot_sector.bytes_per_sector;
+        let index = cluster as usize * 4 / bytes_per_sector;
+        let offset = cluster as usize * 4 % bytes_per_sector;
+        if self.sectors.len() >= self.capacity && !self.sectors.contains_key(&index) {
+            self.evict()?;
+        }
+        self.clock += 1;
+        let sector = match self.sectors.entry(index) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut data = vec![0u8; bytes_per_sector];
+                self.device.read_at(
+                    self.boot_sector.fat_offset(0) + index * bytes_per_sector,
+                    &mut data,
+                )?;
+                entry.insert(CachedSector {
+                    data,
+                    dirty: false,
+                    last_used: 0,
+                })
+            }
+        };
+        sector.last_used = self.clock;
+        Ok((sector, offset))
+    }
+
+    /// 替换掉最久未使用的扇区
+    fn evict(&mut self) -> Result<()> {
+        let index = *self
+            .sectors
+            .iter()
+            .min_by_key(|(_, sector)| sector.last_used)
+            .unwrap()
+            .0;
+        let sector = self.sectors.remove(&index).unwrap();
+        if sector.dirty {
+            self.write_sector(index, &sector.data)?;
+        }
+        Ok(())
+    }
+
+    /// 将一个扇区写到所有的 FAT 副本
+    fn write_sector(&self, index: usize, data: &[u8]) -> Result<()> {
+        for fat_index in 0..self.boot_sector.num_fats {
+            self.device.write_at(
+                self.boot_sector.fat_offset(fat_index) + index * self.boot_sector.bytes_per_sector,
+                data,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// 读取表项
+    pub fn get(&mut self, cluster: u32) -> Result<u32> {
+        let (sector, offset) = self.sector(cluster)?;
+        Ok(read_u32(&sector.data, offset) & ENTRY_MASK)
+    }
+
+    /// 修改表项，保留高 4 位
+    fn set(&mut self, cluster: u32, value: u32) -> Result<()> {
+        let (sector, offset) = self.sector(cluster)?;
+        let old = read_u32(&sector.data, offset);
+        write_u32(&mut sector.data, offset, (old & !ENTRY_MASK) | value);
+        sector.dirty = true;
+        Ok(())
+    }
+
+    /// 从 `start` 开始的簇链，`start` 为 0 表示空链
+    pub fn chain(&mut self, start: u32) -> Result<Vec<u32>> {
+        let mut clusters = Vec::new();
+        let mut cluster = start;
+        while cluster != 0 && cluster < BAD_CLUSTER {
+            // 防止损坏的 FAT 形成环
+            if !self.is_valid(cluster) || clusters.len() as u32 >= self.boot_sector.cluster_count()
+            {
+                return Err(FsError::DeviceError);
+            }
+            clusters.push(cluster);
+            cluster = self.get(cluster)?;
+        }
+        Ok(clusters)
+    }
+
+    /// 分配一个空闲簇，并接在 `prev` 之后（如果有）
+    pub fn alloc(&mut self, prev: Option<u32>) -> Result<u32> {
+        if self.free_count == 0 {
+            return Err(FsError::NoDeviceSpace);
+        }
+        let count = self.boot_sector.cluster_count();
+        for i in 0..count {
+            let cluster = (self.next_free - 2 + i) % count + 2;
+            if self.get(cluster)? == 0 {
+                self.set(cluster, END_OF_CHAIN)?;
+                if let Some(prev) = prev {
+                    self.set(prev, cluster)?;
+                }
+                self.free_count -= 1;
+                // 下一次从这个簇之后开始查找
+                self.next_free = (cluster - 1) % count + 2;
+                return Ok(cluster);
+            }
+        }
+        Err(FsError::NoDeviceSpace)
+    }
+
+    /// 释放从 `start` 开始的整条簇链
+    pub fn free_chain(&mut self, start: u32) -> Result<()> {
+        for cluster in self.chain(start)? {
+            self.set(cluster, 0)?;
+            self.free_count += 1;
+        }
+        Ok(())
+    }
+
+    /// 将簇链在 `last` 处截断，释放之后的所有簇
+    pub fn truncate_after(&mut self, last: u32) -> Result<()> {
+        let next = self.get(last)?;
+        self.set(last, END_OF_CHAIN)?;
+        if next < BAD_CLUSTER {
+            self.free_chain(next)?;
+        }
+        Ok(())
+    }
+
+    /// 将所有修改过的扇区以及 FSInfo 写回设备
+    pub fn flush(&mut self) -> Result<()> {
+        let dirty: Vec<usize> = self
+            .sectors
+            .iter()
+            .filter(|(_, sector)| sector.dirty)
+            .map(|(&index, _)| index)
+            .collect();
+        for index in dirty {
+            let data = self.sectors[&index].data.clone();
+            self.write_sector(index, &data)?;
+            self.sectors.get_mut(&index).unwrap().dirty = false;
+        }
+        FsInfoSector {
+            free_count: self.free_count,
+            next_free: self.next_free,
+        }
+        .write(&*self.device, &self.boot_sector)
+    }
+}
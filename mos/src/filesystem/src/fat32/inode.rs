@@ -0,0 +1,683 @@
+//! FAT32 中的文件和目录
+
+use super::dir_entry::*;
+use super::{Fat32FS, ROOT_POSITION};
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::any::Any;
+use core::cmp::min;
+use rcore_fs::vfs::*;
+use spin::RwLock;
+
+/// [`Metadata::blocks`] 的单位，和 `st_blocks` 一样为 512 字节
+const BLOCK_SIZE: usize = 512;
+
+/// FAT32 中的一个文件或目录
+pub struct FatINode {
+    /// 所属的文件系统
+    fs: Arc<Fat32FS>,
+    inner: RwLock<FatINodeInner>,
+}
+
+/// [`FatINode`] 中需要可变的部分
+struct FatINodeInner {
+    /// 指向自身的弱引用
+    this: Weak<FatINode>,
+    /// 短文件名目录项，修改后需要调用 [`FatINodeInner::write_entry`] 写回
+    entry: ShortEntry,
+    /// 目录项在设备上的字节偏移，根目录没有目录项，为 [`ROOT_POSITION`]
+    position: usize,
+    /// 父目录，根目录为 `None`
+    parent: Option<Arc<FatINode>>,
+    /// 缓存的簇链
+    clusters: Vec<u32>,
+    /// 是否已被删除
+    removed: bool,
+}
+
+/// 从目录中读出的一个目录项
+struct DirEntry {
+    /// 文件名，有长文件名时使用长文件名
+    name: String,
+    entry: ShortEntry,
+    /// 第一个长文件名目录项在目录中的偏移，没有长文件名时等于 `offset`
+    first: usize,
+    /// 短文件名目录项在目录中的偏移
+    offset: usize,
+}
+
+impl DirEntry {
+    /// 名称是否匹配，和其他 FAT 实现一样不区分大小写，也可以使用短文件名
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.entry.display_name().eq_ignore_ascii_case(name)
+    }
+}
+
+impl FatINode {
+    /// 创建 INode，只由 [`Fat32FS::get_inode`] 调用
+    pub(super) fn new(
+        fs: Arc<Fat32FS>,
+        position: usize,
+        entry: ShortEntry,
+        parent: Option<Arc<FatINode>>,
+        clusters: Vec<u32>,
+    ) -> Arc<Self> {
+        let inode = Arc::new(Self {
+            fs,
+            inner: RwLock::new(FatINodeInner {
+                this: Weak::new(),
+                entry,
+                position,
+                parent,
+                clusters,
+                removed: false,
+            }),
+        });
+        inode.inner.write().this = Arc::downgrade(&inode);
+        inode
+    }
+}
+
+impl FatINodeInner {
+    /// 当前 INode 的强引用
+    fn this(&self) -> Arc<FatINode> {
+        self.this.upgrade().unwrap()
+    }
+
+    /// 内容的长度，目录没有记录大小，按簇链的长度计算
+    fn size(&self, fs: &Fat32FS) -> usize {
+        if self.entry.is_dir() {
+            self.clusters.len() * fs.boot_sector.cluster_size()
+        } else {
+            self.entry.size as usize
+        }
+    }
+
+    /// 将目录项写回父目录
+    fn write_entry(&self, fs: &Fat32FS) -> Result<()> {
+        if self.position != ROOT_POSITION && !self.removed {
+            fs.device.write_at(self.position, &self.entry.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// 内容中 `offset` 处在设备上的字节偏移
+    fn device_offset(&self, fs: &Fat32FS, offset: usize) -> usize {
+        let cluster_size = fs.boot_sector.cluster_size();
+        fs.boot_sector
+            .cluster_offset(self.clusters[offset / cluster_size])
+            + offset % cluster_size
+    }
+
+    /// 读取已分配的簇中的内容，不检查文件大小
+    fn read_data(&self, fs: &Fat32FS, offset: usize, buf: &mut [u8]) -> Result<()> {
+        let cluster_size = fs.boot_sector.cluster_size();
+        let mut done = 0;
+        while done < buf.len() {
+            let position = offset + done;
+            let len = min(buf.len() - done, cluster_size - position % cluster_size);
+            fs.device
+                .read_at(self.device_offset(fs, position), &mut buf[done..done + len])?;
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// 写入已分配的簇中，不修改文件大小
+    fn write_data(&self, fs: &Fat32FS, offset: usize, buf: &[u8]) -> Result<()> {
+        let cluster_size = fs.boot_sector.cluster_size();
+        let mut done = 0;
+        while done < buf.len() {
+            let position = offset + done;
+            let len = min(buf.len() - done, cluster_size - position % cluster_size);
+            fs.device
+                .write_at(self.device_offset(fs, position), &buf[done..done + len])?;
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// 将簇链调整为 `count` 个簇，新分配的簇会被清零
+    fn resize_clusters(&mut self, fs: &Fat32FS, count: usize) -> Result<()> {
+        let cluster_size = fs.boot_sector.cluster_size();
+        let mut fat = fs.fat.lock();
+        if count < self.clusters.len() {
+            if count == 0 {
+                fat.free_chain(self.clusters[0])?;
+                self.entry.first_cluster = 0;
+            } else {
+                fat.truncate_after(self.clusters[count - 1])?;
+            }
+            self.clusters.truncate(count);
+        }
+        let zeros = vec![0u8; cluster_size];
+        while self.clusters.len() < count {
+            let cluster = fat.alloc(self.clusters.last().cloned())?;
+            fs.device
+                .write_at(fs.boot_sector.cluster_offset(cluster), &zeros)?;
+            if self.clusters.is_empty() {
+                self.entry.first_cluster = cluster;
+            }
+            self.clusters.push(cluster);
+        }
+        Ok(())
+    }
+
+    /// 将文件大小调整为 `len`，新增的部分填 0
+    fn resize(&mut self, fs: &Fat32FS, len: usize) -> Result<()> {
+        if len > u32::max_value() as usize {
+            return Err(FsError::InvalidParam);
+        }
+        let cluster_size = fs.boot_sector.cluster_size();
+        let old_len = self.entry.size as usize;
+        let result = self.resize_clusters(fs, (len + cluster_size - 1) / cluster_size);
+        // 空间不足时保留已经分配到的簇，文件大小不超过它们的容量
+        let len = min(len, self.clusters.len() * cluster_size);
+        if len > old_len {
+            // 新分配的簇已经清零，只需要清理原来最后一个簇中文件末尾之后的部分
+            let end = min(
+                len,
+                (old_len + cluster_size - 1) / cluster_size * cluster_size,
+            );
+            if end > old_len {
+                self.write_data(fs, old_len, &vec![0u8; end - old_len])?;
+            }
+        }
+        self.entry.size = len as u32;
+        self.write_entry(fs)?;
+        result
+    }
+
+    /// 检查当前 INode 是仍然存在的目录
+    fn check_dir(&self) -> Result<()> {
+        if !self.entry.is_dir() {
+            return Err(FsError::NotDir);
+        }
+        if self.removed {
+            return Err(FsError::DirRemoved);
+        }
+        Ok(())
+    }
+
+    /// 读出目录中的所有目录项，跳过 `.`、`..` 和卷标
+    fn dir_entries(&self, fs: &Fat32FS) -> Result<Vec<DirEntry>> {
+        let mut data = vec![0u8; self.size(fs)];
+        self.read_data(fs, 0, &mut data)?;
+        let mut entries = Vec::new();
+        // 正在拼接的长文件名：(校验和, 下一个期望的序号, 第一项的偏移, 字符)
+        let mut long_name: Option<(u8, u8, usize, Vec<u16>)> = None;
+        for (index, raw) in data.chunks(ENTRY_SIZE).enumerate() {
+            let offset = index * ENTRY_SIZE;
+            match raw[0] {
+                END => break,
+                DELETED => {
+                    long_name = None;
+                    continue;
+                }
+                _ => {}
+            }
+            if is_long_entry(raw) {
+                let (order, last, checksum, chars) = parse_long_entry(raw);
+                long_name = if order == 0 {
+                    None
+                } else if last {
+                    let mut name = vec![0xffffu16; order as usize * chars.len()];
+                    name[(order as usize - 1) * chars.len()..].copy_from_slice(&chars);
+                    Some((checksum, order - 1, offset, name))
+                } else {
+                    match long_name.take() {
+                        Some((sum, expected, first, mut name))
+                            if sum == checksum && expected == order =>
+                        {
+                            let start = (order as usize - 1) * chars.len();
+                            name[start..start + chars.len()].copy_from_slice(&chars);
+                            Some((sum, order - 1, first, name))
+                        }
+                        _ => None,
+                    }
+                };
+                continue;
+            }
+            let entry = ShortEntry::parse(raw);
+            let long_name = long_name.take();
+            if entry.attr & ATTR_VOLUME_ID != 0 || entry.name[0] == b'.' {
+                continue;
+            }
+            // 长文件名完整且校验和一致时才使用
+            let (name, first) = match long_name {
+                Some((checksum, 0, first, chars)) if checksum == entry.checksum() => {
+                    match decode_long_name(&chars) {
+                        Some(name) => (name, first),
+                        None => (entry.display_name(), offset),
+                    }
+                }
+                _ => (entry.display_name(), offset),
+            };
+            entries.push(DirEntry {
+                name,
+                entry,
+                first,
+                offset,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// 按名称查找目录项
+    fn find_entry(&self, fs: &Fat32FS, name: &str) -> Result<DirEntry> {
+        self.dir_entries(fs)?
+            .into_iter()
+            .find(|entry| entry.matches(name))
+            .ok_or(FsError::EntryNotFound)
+    }
+
+    /// 在目录中加入名为 `name` 的目录项，返回短文件名目录项在设备上的位置
+    fn add_entry(&mut self, fs: &Fat32FS, name: &str, mut entry: ShortEntry) -> Result<usize> {
+        let entries = self.dir_entries(fs)?;
+        if entries.iter().any(|entry| entry.matches(name)) {
+            return Err(FsError::EntryExist);
+        }
+        let mut slots = match exact_short_name(name) {
+            Some(short) => {
+                entry.name = short;
+                Vec::new()
+            }
+            None => {
+                entry.name = generate_short_name(name, |short| {
+                    entries.iter().any(|e| &e.entry.name == short)
+                });
+                long_entries(name, entry.checksum())
+            }
+        };
+        slots.push(entry.to_bytes());
+
+        // 找到足够长的连续空闲目录项，目录结束标记之后的目录项都是空闲的
+        let mut data = vec![0u8; self.size(fs)];
+        self.read_data(fs, 0, &mut data)?;
+        let mut start = 0;
+        let mut free = 0;
+        let mut ended = false;
+        for (index, raw) in data.chunks(ENTRY_SIZE).enumerate() {
+            if free == slots.len() {
+                break;
+            }
+            ended |= raw[0] == END;
+            if ended || raw[0] == DELETED {
+                if free == 0 {
+                    start = index * ENTRY_SIZE;
+                }
+                free += 1;
+            } else {
+                free = 0;
+            }
+        }
+        if free < slots.len() {
+            // 空间不足，扩展目录
+            if free == 0 {
+                start = data.len();
+            }
+            let needed = start + slots.len() * ENTRY_SIZE;
+            let cluster_size = fs.boot_sector.cluster_size();
+            self.resize_clusters(fs, (needed + cluster_size - 1) / cluster_size)?;
+        }
+        let bytes: Vec<u8> = slots.iter().flat_map(|slot| slot.iter().cloned()).collect();
+        self.write_data(fs, start, &bytes)?;
+        Ok(self.device_offset(fs, start + bytes.len() - ENTRY_SIZE))
+    }
+
+    /// 将目录项（包括其长文件名目录项）标记为已删除
+    fn remove_entry(&self, fs: &Fat32FS, entry: &DirEntry) -> Result<()> {
+        for offset in (entry.first..=entry.offset).step_by(ENTRY_SIZE) {
+            fs.device
+                .write_at(self.device_offset(fs, offset), &[DELETED])?;
+        }
+        Ok(())
+    }
+
+    /// 目录项对应的 INode
+    fn child(&self, fs: &Fat32FS, entry: &DirEntry) -> Result<Arc<FatINode>> {
+        fs.get_inode(
+            self.device_offset(fs, entry.offset),
+            entry.entry.clone(),
+            Some(self.this()),
+        )
+    }
+
+    /// 删除目录项，并释放对应文件的所有簇
+    fn remove_child(&self, fs: &Fat32FS, entry: &DirEntry) -> Result<()> {
+        let child = self.child(fs, entry)?;
+        let mut child_inner = child.inner.write();
+        if child_inner.entry.is_dir() && !child_inner.dir_entries(fs)?.is_empty() {
+            return Err(FsError::DirNotEmpty);
+        }
+        self.remove_entry(fs, entry)?;
+        // 已经打开的文件不能再访问被释放的簇
+        child_inner.resize_clusters(fs, 0)?;
+        child_inner.entry.size = 0;
+        child_inner.removed = true;
+        fs.inodes.lock().remove(&child_inner.position);
+        Ok(())
+    }
+
+    /// 目录的第一个簇，根目录在 `..` 目录项中记为 0
+    fn dir_cluster(&self) -> u32 {
+        if self.position == ROOT_POSITION {
+            0
+        } else {
+            self.entry.first_cluster
+        }
+    }
+}
+
+/// 检查目录项名称是否合法
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(FsError::InvalidParam);
+    }
+    if name.encode_utf16().count() > MAX_NAME_LEN
+        || name
+            .chars()
+            .any(|c| (c as u32) < 0x20 || "\"*/:<>?\\|".contains(c))
+    {
+        return Err(FsError::InvalidParam);
+    }
+    Ok(())
+}
+
+/// 从 `dyn INode` 中取得 [`FatINode`]
+fn downcast(other: &Arc<dyn INode>) -> Result<Arc<FatINode>> {
+    other
+        .downcast_ref::<FatINode>()
+        .map(|inode| inode.inner.read().this())
+        .ok_or(FsError::NotSameFs)
+}
+
+impl INode for FatINode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let inner = self.inner.read();
+        if inner.entry.is_dir() {
+            return Err(FsError::IsDir);
+        }
+        let size = inner.entry.size as usize;
+        let start = min(offset, size);
+        let end = min(offset + buf.len(), size);
+        inner.read_data(&self.fs, start, &mut buf[..end - start])?;
+        Ok(end - start)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        let mut inner = self.inner.write();
+        if inner.entry.is_dir() {
+            return Err(FsError::IsDir);
+        }
+        let end = offset + buf.len();
+        if end > inner.entry.size as usize {
+            inner.resize(&self.fs, end)?;
+        }
+        inner.write_data(&self.fs, offset, buf)?;
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        let inner = self.inner.read();
+        let cluster_size = self.fs.boot_sector.cluster_size();
+        let mode = if inner.entry.attr & ATTR_READ_ONLY != 0 {
+            0o555
+        } else {
+            0o777
+        };
+        Ok(Metadata {
+            dev: 0,
+            inode: if inner.position == ROOT_POSITION {
+                1
+            } else {
+                inner.position / ENTRY_SIZE
+            },
+            size: inner.size(&self.fs),
+            blk_size: cluster_size,
+            blocks: inner.clusters.len() * cluster_size / BLOCK_SIZE,
+            atime: inner.entry.atime(),
+            mtime: inner.entry.mtime(),
+            ctime: inner.entry.ctime(),
+            type_: if inner.entry.is_dir() {
+                FileType::Dir
+            } else {
+                FileType::File
+            },
+            mode,
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+        })
+    }
+
+    /// 只保存时间和只读属性，FAT 没有所有者
+    fn set_metadata(&self, metadata: &Metadata) -> Result<()> {
+        let mut inner = self.inner.write();
+        let (date, time) = timespec_to_fat(metadata.mtime);
+        inner.entry.write_date = date;
+        inner.entry.write_time = time;
+        inner.entry.access_date = timespec_to_fat(metadata.atime).0;
+        if metadata.mode & 0o222 == 0 {
+            inner.entry.attr |= ATTR_READ_ONLY;
+        } else {
+            inner.entry.attr &= !ATTR_READ_ONLY;
+        }
+        inner.write_entry(&self.fs)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.inner.read().write_entry(&self.fs)?;
+        self.fs.sync()
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.sync_all()
+    }
+
+    fn resize(&self, len: usize) -> Result<()> {
+        let mut inner = self.inner.write();
+        if inner.entry.is_dir() {
+            return Err(FsError::IsDir);
+        }
+        inner.resize(&self.fs, len)
+    }
+
+    fn create(&self, name: &str, type_: FileType, _mode: u32) -> Result<Arc<dyn INode>> {
+        let mut inner = self.inner.write();
+        inner.check_dir()?;
+        check_name(name)?;
+        let fs = &*self.fs;
+        let attr = match type_ {
+            FileType::File => ATTR_ARCHIVE,
+            FileType::Dir => ATTR_DIRECTORY,
+            _ => return Err(FsError::NotSupported),
+        };
+        let mut entry = ShortEntry::new([b' '; 11], attr);
+        if type_ == FileType::Dir {
+            // 目录至少占用一个簇，其中包含 `.` 和 `..` 两个目录项
+            let cluster = fs.fat.lock().alloc(None)?;
+            let mut data = vec![0u8; fs.boot_sector.cluster_size()];
+            let mut dot = ShortEntry::new(*b".          ", ATTR_DIRECTORY);
+            dot.first_cluster = cluster;
+            data[..ENTRY_SIZE].copy_from_slice(&dot.to_bytes());
+            dot.name = *b"..         ";
+            dot.first_cluster = inner.dir_cluster();
+            data[ENTRY_SIZE..2 * ENTRY_SIZE].copy_from_slice(&dot.to_bytes());
+            fs.device
+                .write_at(fs.boot_sector.cluster_offset(cluster), &data)?;
+            entry.first_cluster = cluster;
+        }
+        let first_cluster = entry.first_cluster;
+        let position = match inner.add_entry(fs, name, entry) {
+            Ok(position) => position,
+            Err(error) => {
+                if first_cluster != 0 {
+                    fs.fat.lock().free_chain(first_cluster)?;
+                }
+                return Err(error);
+            }
+        };
+        let entry = ShortEntry::parse(&{
+            let mut data = [0u8; ENTRY_SIZE];
+            fs.device.read_at(position, &mut data)?;
+            data
+        });
+        Ok(fs.get_inode(position, entry, Some(inner.this()))?)
+    }
+
+    /// FAT 不支持硬链接
+    fn link(&self, _name: &str, _other: &Arc<dyn INode>) -> Result<()> {
+        Err(FsError::NotSupported)
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        let inner = self.inner.write();
+        inner.check_dir()?;
+        if name == "." || name == ".." {
+            return Err(FsError::IsDir);
+        }
+        let entry = inner.find_entry(&self.fs, name)?;
+        inner.remove_child(&self.fs, &entry)
+    }
+
+    fn move_(&self, old_name: &str, target: &Arc<dyn INode>, new_name: &str) -> Result<()> {
+        let target = downcast(target)?;
+        check_name(old_name)?;
+        check_name(new_name)?;
+        if !Arc::ptr_eq(&self.fs, &target.fs) {
+            return Err(FsError::NotSameFs);
+        }
+        let fs = &*self.fs;
+        let child = downcast(&self.find(old_name)?)?;
+        let is_dir = child.inner.read().entry.is_dir();
+        if is_dir {
+            // 不能将目录移动到它自己的子树中
+            let mut ancestor = Some(target.clone());
+            while let Some(dir) = ancestor {
+                if Arc::ptr_eq(&dir, &child) {
+                    return Err(FsError::InvalidParam);
+                }
+                ancestor = dir.inner.read().parent.clone();
+            }
+        }
+        // 目标位置已有其他文件时，按照 rename 的语义替换它
+        match target.find(new_name) {
+            Ok(existing) => {
+                let existing = downcast(&existing)?;
+                if !Arc::ptr_eq(&existing, &child) {
+                    match (is_dir, existing.inner.read().entry.is_dir()) {
+                        (true, false) => return Err(FsError::NotDir),
+                        (false, true) => return Err(FsError::IsDir),
+                        _ => {}
+                    }
+                    target.unlink(new_name)?;
+                }
+            }
+            Err(FsError::EntryNotFound) => {}
+            Err(error) => return Err(error),
+        }
+
+        let this = self.inner.read().this();
+        let same_dir = Arc::ptr_eq(&this, &target);
+        let mut inner = self.inner.write();
+        let old = inner.find_entry(fs, old_name)?;
+        inner.remove_entry(fs, &old)?;
+        let mut child_inner = child.inner.write();
+        let position = if same_dir {
+            inner.add_entry(fs, new_name, child_inner.entry.clone())?
+        } else {
+            drop(inner);
+            target
+                .inner
+                .write()
+                .add_entry(fs, new_name, child_inner.entry.clone())?
+        };
+        {
+            let mut inodes = fs.inodes.lock();
+            inodes.remove(&child_inner.position);
+            inodes.insert(position, Arc::downgrade(&child));
+        }
+        child_inner.position = position;
+        let mut data = [0u8; ENTRY_SIZE];
+        fs.device.read_at(position, &mut data)?;
+        child_inner.entry = ShortEntry::parse(&data);
+        if !same_dir {
+            if is_dir {
+                // 修改 `..` 目录项，指向新的父目录
+                let mut dotdot = [0u8; ENTRY_SIZE];
+                child_inner.read_data(fs, ENTRY_SIZE, &mut dotdot)?;
+                let mut dotdot = ShortEntry::parse(&dotdot);
+                dotdot.first_cluster = target.inner.read().dir_cluster();
+                child_inner.write_data(fs, ENTRY_SIZE, &dotdot.to_bytes())?;
+            }
+            let old_parent = child_inner.parent.replace(target);
+            // 父目录可能在这里被释放，它的析构需要锁住 `fs.inodes`
+            drop(child_inner);
+            drop(old_parent);
+        }
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        let inner = self.inner.read();
+        inner.check_dir()?;
+        match name {
+            "." => Ok(inner.this()),
+            ".." => Ok(inner.parent.clone().unwrap_or_else(|| inner.this())),
+            name => {
+                let entry = inner.find_entry(&self.fs, name)?;
+                Ok(inner.child(&self.fs, &entry)?)
+            }
+        }
+    }
+
+    fn get_entry(&self, id: usize) -> Result<String> {
+        let inner = self.inner.read();
+        inner.check_dir()?;
+        match id {
+            0 => Ok(String::from(".")),
+            1 => Ok(String::from("..")),
+            id => inner
+                .dir_entries(&self.fs)?
+                .into_iter()
+                .nth(id - 2)
+                .map(|entry| entry.name)
+                .ok_or(FsError::EntryNotFound),
+        }
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.clone()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// INode 被释放时从 [`Fat32FS::inodes`] 中移除自己
+impl Drop for FatINode {
+    fn drop(&mut self) {
+        let position = self.inner.read().position;
+        let mut inodes = self.fs.inodes.lock();
+        if let Some(weak) = inodes.get(&position) {
+            if weak.as_ptr() == self as *const _ {
+                inodes.remove(&position);
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! FAT32 文件系统 [`Fat32FS`]
+//!
+//! 支持长文件名、目录的创建和删除、文件的增长和截断。
+//! 文件分配表按扇区缓存在内存中，在 [`FileSystem::sync`] 时写回设备。
+//! FAT 没有硬链接和符号链接，对应的操作返回 [`FsError::NotSupported`]
+
+mod boot_sector;
+mod dir_entry;
+mod fat;
+mod inode;
+
+pub use inode::FatINode;
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+use boot_sector::BootSector;
+use dir_entry::{ShortEntry, ATTR_DIRECTORY};
+use fat::Fat;
+use rcore_fs::dev::Device;
+use rcore_fs::vfs::*;
+use spin::Mutex;
+
+/// FAT 缓存的扇区数
+const FAT_CACHE_CAPACITY: usize = 64;
+
+/// 根目录在 [`Fat32FS::inodes`] 中使用的键，不会和任何目录项的位置重复
+const ROOT_POSITION: usize = 0;
+
+/// FAT32 文件系统
+pub struct Fat32FS {
+    device: Arc<dyn Device>,
+    boot_sector: BootSector,
+    /// 文件分配表
+    fat: Mutex<Fat>,
+    /// 正在使用的 INode，以短文件名目录项在设备上的字节偏移为键
+    ///
+    /// 保证同一个文件只对应一个 [`FatINode`]，它们共享文件大小和簇链
+    inodes: Mutex<BTreeMap<usize, Weak<FatINode>>>,
+    /// 指向自身的弱引用，INode 通过它来持有文件系统
+    self_ref: Weak<Fat32FS>,
+}
+
+impl Fat32FS {
+    /// 检查设备上的引导扇区是否是 FAT32
+    pub fn probe(device: &dyn Device) -> bool {
+        BootSector::read(device).is_ok()
+    }
+
+    /// 打开设备上的 FAT32 文件系统
+    pub fn open(device: Arc<dyn Device>) -> Result<Arc<Self>> {
+        let boot_sector = BootSector::read(&*device)?;
+        let fat = Fat::new(device.clone(), boot_sector.clone(), FAT_CACHE_CAPACITY)?;
+        let fs = Arc::new(Self {
+            device,
+            boot_sector,
+            fat: Mutex::new(fat),
+            inodes: Mutex::new(BTreeMap::new()),
+            self_ref: Weak::new(),
+        });
+        let weak = Arc::downgrade(&fs);
+        let ptr = Arc::into_raw(fs) as *mut Self;
+        unsafe {
+            (*ptr).self_ref = weak;
+            Ok(Arc::from_raw(ptr))
+        }
+    }
+
+    /// 取得目录项对应的 INode，已经打开时返回同一个
+    fn get_inode(
+        &self,
+        position: usize,
+        entry: ShortEntry,
+        parent: Option<Arc<FatINode>>,
+    ) -> Result<Arc<FatINode>> {
+        let mut inodes = self.inodes.lock();
+        if let Some(inode) = inodes.get(&position).and_then(Weak::upgrade) {
+            return Ok(inode);
+        }
+        let clusters = self.fat.lock().chain(entry.first_cluster)?;
+        let inode = FatINode::new(
+            self.self_ref.upgrade().unwrap(),
+            position,
+            entry,
+            parent,
+            clusters,
+        );
+        inodes.insert(position, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+
+    /// 根目录
+    fn root(&self) -> Arc<FatINode> {
+        let mut entry = ShortEntry::new([b' '; 11], ATTR_DIRECTORY);
+        entry.first_cluster = self.boot_sector.root_cluster;
+        self.get_inode(ROOT_POSITION, entry, None)
+            .expect("failed to read root directory")
+    }
+}
+
+impl FileSystem for Fat32FS {
+    /// 写回文件分配表和 FSInfo
+    fn sync(&self) -> Result<()> {
+        self.fat.lock().flush()?;
+        self.device.sync()?;
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        self.root()
+    }
+
+    fn info(&self) -> FsInfo {
+        let free = self.fat.lock().free_count() as usize;
+        FsInfo {
+            bsize: self.boot_sector.cluster_size(),
+            frsize: self.boot_sector.cluster_size(),
+            blocks: self.boot_sector.cluster_count() as usize,
+            bfree: free,
+            bavail: free,
+            files: 0,
+            ffree: 0,
+            namemax: dir_entry::MAX_NAME_LEN,
+        }
+    }
+}
+
+/// 释放文件系统前写回缓存
+impl Drop for Fat32FS {
+    /// 卸载时写回，失败时只能记录下来
+    fn drop(&mut self) {
+        if let Err(error) = self.sync() {
+            log::warn!("failed to sync FAT32 on drop: {:?}", error);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! 不依赖内核的文件系统实现
+//!
+//! 这里的文件系统都只依赖 [`rcore_fs`] 中的接口，因此可以直接在 host 上测试。
+//! 磁盘的分区表也在这里解析，见 [`partition`]
+#![no_std]
+
+extern crate alloc;
+
+mod bytes;
+pub mod cpio;
+pub mod ext2;
+pub mod fat32;
+pub mod partition;
+pub mod tmpfs;
+
+pub use ext2::{Ext2FS, Ext2INode};
+pub use fat32::{Fat32FS, FatINode};
+pub use partition::{read_partitions, Partition};
+pub use tmpfs::{TmpFS, TmpINode};
@@ -0,0 +1,227 @@
+//! MBR 和 GPT 分区表
+//!
+//! 只读取分区表，不修改。MBR 中类型为 0xee 的保护分区表示磁盘使用 GPT，
+//! 此时读取主 GPT 头，校验失败时改用磁盘末尾的备份。不支持 MBR 扩展分区中的逻辑分区。
+//! 分区的位置和大小都以 512 字节的扇区为单位
+
+use crate::bytes::{read_u16, read_u32, read_u64};
+use crate::fat32::Fat32FS;
+use alloc::{format, string::String, vec, vec::Vec};
+use rcore_fs::dev::Device;
+use rcore_fs::vfs::Result;
+
+/// 扇区大小
+pub const SECTOR_SIZE: usize = 512;
+
+/// MBR 末尾的签名
+const MBR_SIGNATURE: u16 = 0xaa55;
+/// 分区项在 MBR 中的偏移
+const MBR_ENTRIES: usize = 446;
+/// 磁盘签名在 MBR 中的偏移
+const MBR_DISK_SIGNATURE: usize = 440;
+/// GPT 的保护分区
+const TYPE_GPT_PROTECTIVE: u8 = 0xee;
+/// 扩展分区，其中的逻辑分区不会被读取
+const TYPE_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+
+/// GPT 头的签名
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+/// GPT 头的最小长度
+const GPT_HEADER_SIZE: usize = 92;
+/// 分区项的最小长度
+const GPT_ENTRY_SIZE: usize = 128;
+/// 分区项的最大长度，分区项数组最大为 4 MiB
+const GPT_MAX_ENTRY_SIZE: usize = 4096;
+/// 最多读取的分区项数
+const GPT_MAX_ENTRIES: usize = 1024;
+
+/// 磁盘上的一个分区
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    /// 分区号，从 1 开始；GPT 中为分区项的序号加一
+    pub number: usize,
+    /// 起始扇区
+    pub start: usize,
+    /// 扇区数
+    pub sectors: usize,
+    /// 和 Linux 的 `PARTUUID` 相同：GPT 中为分区的 GUID，MBR 中为磁盘签名加上分区号
+    pub uuid: String,
+    /// GPT 中的分区名，MBR 中没有
+    pub label: Option<String>,
+}
+
+/// 读取共 `sectors` 个扇区的设备上的分区表，没有分区表时返回空表
+pub fn read_partitions(device: &dyn Device, sectors: usize) -> Result<Vec<Partition>> {
+    let mut mbr = [0u8; SECTOR_SIZE];
+    device.read_at(0, &mut mbr)?;
+    if !is_mbr(&mbr) || Fat32FS::probe(device) {
+        return Ok(Vec::new());
+    }
+    let entries =
+        (0..4).map(|index| &mbr[MBR_ENTRIES + 16 * index..MBR_ENTRIES + 16 * (index + 1)]);
+    if entries.clone().any(|entry| entry[4] == TYPE_GPT_PROTECTIVE) {
+        return read_gpt(device, sectors);
+    }
+    let signature = read_u32(&mbr, MBR_DISK_SIGNATURE);
+    let mut partitions = Vec::new();
+    for (index, entry) in entries.enumerate() {
+        let type_ = entry[4];
+        let start = read_u32(entry, 8) as usize;
+        let count = read_u32(entry, 12) as usize;
+        if type_ == 0 || count == 0 || TYPE_EXTENDED.contains(&type_) || start + count > sectors {
+            continue;
+        }
+        partitions.push(Partition {
+            number: index + 1,
+            start,
+            sectors: count,
+            uuid: format!("{:08x}-{:02x}", signature, index + 1),
+            label: None,
+        });
+    }
+    Ok(partitions)
+}
+
+/// 第一个扇区是否为 MBR
+///
+/// FAT 的引导扇区也以 0xaa55 结尾，此时分区项的位置是引导代码，活动标志通常既不是 0 也不是 0x80
+fn is_mbr(data: &[u8]) -> bool {
+    read_u16(data, 510) == MBR_SIGNATURE
+        && (0..4).all(|index| matches!(data[MBR_ENTRIES + 16 * index], 0x00 | 0x80))
+}
+
+/// GPT 头中用到的字段
+struct GptHeader {
+    /// 分区项数组的起始扇区
+    entries_lba: usize,
+    entries: usize,
+    entry_size: usize,
+    entries_crc: u32,
+}
+
+/// 读取 GPT，主 GPT 损坏时使用备份
+fn read_gpt(device: &dyn Device, sectors: usize) -> Result<Vec<Partition>> {
+    for &lba in [1, sectors.saturating_sub(1)].iter() {
+        let header = match read_gpt_header(device, lba, sectors)? {
+            Some(header) => header,
+            None => continue,
+        };
+        let mut data = vec![0u8; header.entries * header.entry_size];
+        device.read_at(header.entries_lba * SECTOR_SIZE, &mut data)?;
+        if crc32(&data) != header.entries_crc {
+            continue;
+        }
+        let partitions = data
+            .chunks(header.entry_size)
+            .enumerate()
+            .filter_map(|(index, entry)| gpt_entry(index + 1, entry, sectors))
+            .collect();
+        return Ok(partitions);
+    }
+    Ok(Vec::new())
+}
+
+/// 读取并校验第 `lba` 个扇区上的 GPT 头，分区项数组必须完整地位于磁盘内
+fn read_gpt_header(device: &dyn Device, lba: usize, sectors: usize) -> Result<Option<GptHeader>> {
+    let mut data = [0u8; SECTOR_SIZE];
+    device.read_at(lba * SECTOR_SIZE, &mut data)?;
+    let size = read_u32(&data, 12) as usize;
+    if &data[..8] != GPT_SIGNATURE
+        || size < GPT_HEADER_SIZE
+        || size > SECTOR_SIZE
+        || read_u64(&data, 24) as usize != lba
+    {
+        return Ok(None);
+    }
+    // 计算校验和时，校验和字段本身视为 0
+    let crc = read_u32(&data, 16);
+    data[16..20].copy_from_slice(&[0; 4]);
+    if crc32(&data[..size]) != crc {
+        return Ok(None);
+    }
+    let header = GptHeader {
+        entries_lba: read_u64(&data, 72) as usize,
+        entries: read_u32(&data, 80) as usize,
+        entry_size: read_u32(&data, 84) as usize,
+        entries_crc: read_u32(&data, 88),
+    };
+    if header.entries > GPT_MAX_ENTRIES
+        || header.entry_size < GPT_ENTRY_SIZE
+        || header.entry_size > GPT_MAX_ENTRY_SIZE
+        || header.entry_size % 8 != 0
+    {
+        return Ok(None);
+    }
+    // 分区项数组在 GPT 头之后，以字节为单位检查结尾是否超出磁盘
+    let end = header
+        .entries_lba
+        .checked_mul(SECTOR_SIZE)
+        .and_then(|offset| offset.checked_add(header.entries * header.entry_size));
+    match end {
+        Some(end) if header.entries_lba >= 2 && end <= sectors.saturating_mul(SECTOR_SIZE) => {
+            Ok(Some(header))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 解析一个分区项，未使用或超出磁盘时返回 `None`
+fn gpt_entry(number: usize, entry: &[u8], sectors: usize) -> Option<Partition> {
+    if entry[..16].iter().all(|byte| *byte == 0) {
+        return None;
+    }
+    let first = read_u64(entry, 32) as usize;
+    let last = read_u64(entry, 40) as usize;
+    if last < first || last >= sectors {
+        return None;
+    }
+    // 分区名是以 0 结尾的 UTF-16LE，最多 36 个字符
+    let name: Vec<u16> = (0..36)
+        .map(|index| read_u16(entry, 56 + 2 * index))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    let label = core::char::decode_utf16(name.iter().cloned())
+        .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+        .collect::<String>();
+    Some(Partition {
+        number,
+        start: first,
+        sectors: last - first + 1,
+        uuid: guid(&entry[16..32]),
+        label: if label.is_empty() { None } else { Some(label) },
+    })
+}
+
+/// GUID 的字符串形式，前三段在磁盘上是小端序
+fn guid(data: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        read_u32(data, 0),
+        read_u16(data, 4),
+        read_u16(data, 6),
+        data[8],
+        data[9],
+        data[10],
+        data[11],
+        data[12],
+        data[13],
+        data[14],
+        data[15]
+    )
+}
+
+/// GPT 使用的 CRC32（IEEE 802.3）
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,454 @@
+//! 内存文件系统 [`TmpFS`]
+//!
+//! 所有数据都放在内核堆上，不会写回任何块设备。
+//! 既可以作为临时存储，也可以在没有块设备时作为根文件系统。
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::any::Any;
+use core::cmp::min;
+use rcore_fs::vfs::*;
+use spin::{Mutex, RwLock};
+
+/// 块大小，只用于填写 [`Metadata`] 和 [`FsInfo`]
+const BLOCK_SIZE: usize = 512;
+
+/// 文件名的最大长度
+const MAX_NAME_LEN: usize = 255;
+
+/// 内存文件系统
+pub struct TmpFS {
+    /// 根目录
+    root: Arc<TmpINode>,
+    /// 容量上限（字节），所有文件内容的总长度不能超过它
+    capacity: usize,
+    /// 已经使用的字节数
+    used: Mutex<usize>,
+    /// 下一个分配的 inode 编号
+    next_inode_id: Mutex<usize>,
+}
+
+/// [`TmpFS`] 中的一个文件、目录或符号链接
+pub struct TmpINode(RwLock<TmpINodeInner>);
+
+/// [`TmpINode`] 中需要可变的部分
+struct TmpINodeInner {
+    /// 指向自身的弱引用，用于 `find(".")` 以及在目录间移动
+    this: Weak<TmpINode>,
+    /// 父目录，根目录的父目录是它自己
+    parent: Weak<TmpINode>,
+    /// 目录项（只有目录会使用）
+    children: BTreeMap<String, Arc<TmpINode>>,
+    /// 文件内容，符号链接则保存目标路径
+    content: Vec<u8>,
+    /// 元数据，其中 `size` 和 `blocks` 在读取时根据 `content` 计算
+    metadata: Metadata,
+    /// 所属的文件系统
+    ///
+    /// 和 SFS 一样由 INode 持有文件系统，只要还有 INode 在使用，文件系统就不会被释放。
+    /// 根目录和文件系统互相持有，因此挂载后的 [`TmpFS`] 会一直存在
+    fs: Option<Arc<TmpFS>>,
+}
+
+impl TmpFS {
+    /// 创建一个容量为 `capacity` 字节的空文件系统
+    pub fn new(capacity: usize) -> Arc<Self> {
+        let root = TmpINode::new(0, FileType::Dir, 0o777, None);
+        let fs = Arc::new(Self {
+            root: root.clone(),
+            capacity,
+            used: Mutex::new(0),
+            next_inode_id: Mutex::new(1),
+        });
+        {
+            let mut inner = root.0.write();
+            inner.parent = inner.this.clone();
+            inner.fs = Some(fs.clone());
+        }
+        fs
+    }
+
+    /// 分配一个新的 inode 编号
+    fn alloc_inode_id(&self) -> usize {
+        let mut next = self.next_inode_id.lock();
+        *next += 1;
+        *next - 1
+    }
+
+    /// 申请 `size` 字节的空间，超出容量则返回 [`FsError::NoDeviceSpace`]
+    fn alloc_space(&self, size: usize) -> Result<()> {
+        let mut used = self.used.lock();
+        match used.checked_add(size) {
+            Some(total) if total <= self.capacity => {
+                *used = total;
+                Ok(())
+            }
+            _ => Err(FsError::NoDeviceSpace),
+        }
+    }
+
+    /// 释放 `size` 字节的空间
+    fn dealloc_space(&self, size: usize) {
+        *self.used.lock() -= size;
+    }
+}
+
+impl FileSystem for TmpFS {
+    /// 数据都在内存中，不需要同步
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        self.root.clone()
+    }
+
+    fn info(&self) -> FsInfo {
+        let free = (self.capacity - *self.used.lock()) / BLOCK_SIZE;
+        FsInfo {
+            bsize: BLOCK_SIZE,
+            frsize: BLOCK_SIZE,
+            blocks: self.capacity / BLOCK_SIZE,
+            bfree: free,
+            bavail: free,
+            files: *self.next_inode_id.lock(),
+            ffree: usize::max_value(),
+            namemax: MAX_NAME_LEN,
+        }
+    }
+}
+
+impl TmpINode {
+    /// 创建一个 INode，`this` 会在创建后立即填写
+    fn new(id: usize, type_: FileType, mode: u32, fs: Option<Arc<TmpFS>>) -> Arc<Self> {
+        let zero = Timespec { sec: 0, nsec: 0 };
+        let inode = Arc::new(Self(RwLock::new(TmpINodeInner {
+            this: Weak::new(),
+            parent: Weak::new(),
+            children: BTreeMap::new(),
+            content: Vec::new(),
+            metadata: Metadata {
+                dev: 0,
+                inode: id,
+                size: 0,
+                blk_size: BLOCK_SIZE,
+                blocks: 0,
+                atime: zero,
+                mtime: zero,
+                ctime: zero,
+                type_,
+                mode: mode as u16,
+                // 目录会被自身的 "." 和父目录中的目录项引用
+                nlinks: if type_ == FileType::Dir { 2 } else { 1 },
+                uid: 0,
+                gid: 0,
+                rdev: 0,
+            },
+            fs,
+        })));
+        inode.0.write().this = Arc::downgrade(&inode);
+        inode
+    }
+}
+
+impl TmpINodeInner {
+    /// 所属的文件系统
+    fn fs(&self) -> Arc<TmpFS> {
+        self.fs.clone().unwrap()
+    }
+
+    /// 将文件内容调整为 `len` 字节，新增的部分填 0
+    fn resize(&mut self, len: usize) -> Result<()> {
+        let old_len = self.content.len();
+        if len > old_len {
+            self.fs().alloc_space(len - old_len)?;
+        } else {
+            self.fs().dealloc_space(old_len - len);
+        }
+        self.content.resize(len, 0);
+        Ok(())
+    }
+
+    /// 检查当前 INode 是仍然存在的目录
+    fn check_dir(&self) -> Result<()> {
+        if self.metadata.type_ != FileType::Dir {
+            return Err(FsError::NotDir);
+        }
+        if self.metadata.nlinks == 0 {
+            return Err(FsError::DirRemoved);
+        }
+        Ok(())
+    }
+}
+
+/// 检查目录项名称是否合法
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(FsError::InvalidParam);
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(FsError::InvalidParam);
+    }
+    Ok(())
+}
+
+/// 从 `dyn INode` 中取得同一个 [`TmpFS`] 中的 [`TmpINode`]
+fn downcast(other: &Arc<dyn INode>) -> Result<Arc<TmpINode>> {
+    other
+        .downcast_ref::<TmpINode>()
+        .and_then(|inode| inode.0.read().this.upgrade())
+        .ok_or(FsError::NotSameFs)
+}
+
+impl INode for TmpINode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let inner = self.0.read();
+        match inner.metadata.type_ {
+            FileType::File | FileType::SymLink => {}
+            FileType::Dir => return Err(FsError::IsDir),
+            _ => return Err(FsError::NotFile),
+        }
+        let end = offset.checked_add(buf.len()).ok_or(FsError::InvalidParam)?;
+        let start = min(offset, inner.content.len());
+        let end = min(end, inner.content.len());
+        buf[..end - start].copy_from_slice(&inner.content[start..end]);
+        Ok(end - start)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        let mut inner = self.0.write();
+        match inner.metadata.type_ {
+            FileType::File | FileType::SymLink => {}
+            FileType::Dir => return Err(FsError::IsDir),
+            _ => return Err(FsError::NotFile),
+        }
+        let end = offset.checked_add(buf.len()).ok_or(FsError::InvalidParam)?;
+        if end > inner.content.len() {
+            inner.resize(end)?;
+        }
+        inner.content[offset..end].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// 内存中的文件随时可读可写
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        let inner = self.0.read();
+        let mut metadata = inner.metadata.clone();
+        metadata.size = inner.content.len();
+        metadata.blocks = (metadata.size + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        Ok(metadata)
+    }
+
+    /// 只允许修改时间、权限和所有者
+    fn set_metadata(&self, metadata: &Metadata) -> Result<()> {
+        let mut inner = self.0.write();
+        inner.metadata.atime = metadata.atime;
+        inner.metadata.mtime = metadata.mtime;
+        inner.metadata.ctime = metadata.ctime;
+        inner.metadata.mode = metadata.mode;
+        inner.metadata.uid = metadata.uid;
+        inner.metadata.gid = metadata.gid;
+        Ok(())
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn resize(&self, len: usize) -> Result<()> {
+        let mut inner = self.0.write();
+        match inner.metadata.type_ {
+            FileType::File => inner.resize(len),
+            FileType::Dir => Err(FsError::IsDir),
+            _ => Err(FsError::NotFile),
+        }
+    }
+
+    fn create(&self, name: &str, type_: FileType, mode: u32) -> Result<Arc<dyn INode>> {
+        let mut inner = self.0.write();
+        inner.check_dir()?;
+        check_name(name)?;
+        match type_ {
+            FileType::File | FileType::Dir | FileType::SymLink => {}
+            _ => return Err(FsError::NotSupported),
+        }
+        if inner.children.contains_key(name) {
+            return Err(FsError::EntryExist);
+        }
+        let fs = inner.fs();
+        let inode = TmpINode::new(fs.alloc_inode_id(), type_, mode, Some(fs));
+        inode.0.write().parent = inner.this.clone();
+        if type_ == FileType::Dir {
+            inner.metadata.nlinks += 1;
+        }
+        inner.children.insert(name.to_string(), inode.clone());
+        Ok(inode)
+    }
+
+    fn link(&self, name: &str, other: &Arc<dyn INode>) -> Result<()> {
+        let other = downcast(other)?;
+        // 先检查 other，它是目录时可能就是 self 本身
+        if other.0.read().metadata.type_ == FileType::Dir {
+            return Err(FsError::IsDir);
+        }
+        check_name(name)?;
+        let mut inner = self.0.write();
+        inner.check_dir()?;
+        if !Arc::ptr_eq(&inner.fs(), &other.0.read().fs()) {
+            return Err(FsError::NotSameFs);
+        }
+        if inner.children.contains_key(name) {
+            return Err(FsError::EntryExist);
+        }
+        other.0.write().metadata.nlinks += 1;
+        inner.children.insert(name.to_string(), other);
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        let mut inner = self.0.write();
+        inner.check_dir()?;
+        if name == "." || name == ".." {
+            return Err(FsError::IsDir);
+        }
+        let child = inner
+            .children
+            .get(name)
+            .cloned()
+            .ok_or(FsError::EntryNotFound)?;
+        let mut child_inner = child.0.write();
+        if child_inner.metadata.type_ == FileType::Dir {
+            if !child_inner.children.is_empty() {
+                return Err(FsError::DirNotEmpty);
+            }
+            // 目录被删除后不能再在其中创建文件
+            child_inner.metadata.nlinks = 0;
+            inner.metadata.nlinks -= 1;
+        } else {
+            child_inner.metadata.nlinks -= 1;
+        }
+        drop(child_inner);
+        inner.children.remove(name);
+        Ok(())
+    }
+
+    fn move_(&self, old_name: &str, target: &Arc<dyn INode>, new_name: &str) -> Result<()> {
+        let target = downcast(target)?;
+        check_name(old_name)?;
+        check_name(new_name)?;
+        let this = self.0.read().this.upgrade().unwrap();
+        if !Arc::ptr_eq(&self.0.read().fs(), &target.0.read().fs()) {
+            return Err(FsError::NotSameFs);
+        }
+        target.0.read().check_dir()?;
+        let child = self.find(old_name)?;
+        let child = downcast(&child)?;
+        let is_dir = child.0.read().metadata.type_ == FileType::Dir;
+        if is_dir {
+            // 不能将目录移动到它自己的子树中
+            let mut ancestor = target.clone();
+            loop {
+                if Arc::ptr_eq(&ancestor, &child) {
+                    return Err(FsError::InvalidParam);
+                }
+                let parent = ancestor.0.read().parent.upgrade().unwrap();
+                if Arc::ptr_eq(&parent, &ancestor) {
+                    break;
+                }
+                ancestor = parent;
+            }
+        }
+        // 目标位置已有目录项时，按照 rename 的语义替换它
+        if let Ok(existing) = target.find(new_name) {
+            let existing = downcast(&existing)?;
+            if Arc::ptr_eq(&existing, &child) {
+                return Ok(());
+            }
+            match (is_dir, existing.0.read().metadata.type_ == FileType::Dir) {
+                (true, false) => return Err(FsError::NotDir),
+                (false, true) => return Err(FsError::IsDir),
+                _ => {}
+            }
+            target.unlink(new_name)?;
+        }
+        self.0.write().children.remove(old_name);
+        if Arc::ptr_eq(&this, &target) {
+            self.0.write().children.insert(new_name.to_string(), child);
+        } else {
+            if is_dir {
+                self.0.write().metadata.nlinks -= 1;
+                target.0.write().metadata.nlinks += 1;
+            }
+            child.0.write().parent = Arc::downgrade(&target);
+            target
+                .0
+                .write()
+                .children
+                .insert(new_name.to_string(), child);
+        }
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        let inner = self.0.read();
+        inner.check_dir()?;
+        match name {
+            "." => Ok(inner.this.upgrade().unwrap()),
+            ".." => Ok(inner.parent.upgrade().unwrap()),
+            name => inner
+                .children
+                .get(name)
+                .map(|inode| inode.clone() as Arc<dyn INode>)
+                .ok_or(FsError::EntryNotFound),
+        }
+    }
+
+    fn get_entry(&self, id: usize) -> Result<String> {
+        let inner = self.0.read();
+        inner.check_dir()?;
+        match id {
+            0 => Ok(String::from(".")),
+            1 => Ok(String::from("..")),
+            id => inner
+                .children
+                .keys()
+                .nth(id - 2)
+                .cloned()
+                .ok_or(FsError::EntryNotFound),
+        }
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.0.read().fs()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// INode 被释放时归还其内容占用的空间
+impl Drop for TmpINode {
+    fn drop(&mut self) {
+        let inner = self.0.read();
+        if let Some(fs) = &inner.fs {
+            fs.dealloc_space(inner.content.len());
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! 各个测试共用的内存设备和辅助函数
+
+// 每个测试只用到其中的一部分
+#![allow(dead_code)]
+
+use rcore_fs::dev::{self, Device};
+use rcore_fs::vfs::INode;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// 内存中的设备
+pub struct MemDevice(Mutex<Vec<u8>>);
+
+impl MemDevice {
+    pub fn new(image: Vec<u8>) -> Self {
+        Self(Mutex::new(image))
+    }
+
+    /// 设备的全部内容
+    pub fn data(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap()
+    }
+}
+
+impl Device for MemDevice {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> dev::Result<usize> {
+        let data = self.data();
+        buf.copy_from_slice(&data[offset..offset + buf.len()]);
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> dev::Result<usize> {
+        let mut data = self.data();
+        data[offset..offset + buf.len()].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn sync(&self) -> dev::Result<()> {
+        Ok(())
+    }
+}
+
+/// 读取整个文件，和内核中 `INodeExt::readall` 的做法相同
+pub fn readall(inode: &Arc<dyn INode>) -> Vec<u8> {
+    let size = inode.metadata().unwrap().size;
+    let mut buffer = vec![0u8; size];
+    assert_eq!(inode.read_at(0, &mut buffer).unwrap(), size);
+    buffer
+}
@@ -0,0 +1,130 @@
+//! 构造 cpio newc 归档并解压到 [`TmpFS`] 中
+
+mod common;
+
+use common::readall;
+use filesystem::{cpio, TmpFS};
+use rcore_fs::vfs::*;
+use std::sync::Arc;
+
+/// 向归档中加入一个文件
+fn push(archive: &mut Vec<u8>, ino: u32, mode: u32, nlink: u32, name: &str, data: &[u8]) {
+    let fields = [
+        ino,
+        mode,
+        1000,
+        100,
+        nlink,
+        1_600_000_000,
+        data.len() as u32,
+        0,
+        0,
+        0,
+        0,
+        name.len() as u32 + 1,
+        0,
+    ];
+    archive.extend_from_slice(b"070701");
+    for field in fields.iter() {
+        archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+    }
+    archive.extend_from_slice(name.as_bytes());
+    archive.push(0);
+    while archive.len() % 4 != 0 {
+        archive.push(0);
+    }
+    archive.extend_from_slice(data);
+    while archive.len() % 4 != 0 {
+        archive.push(0);
+    }
+}
+
+/// 一个包含目录、文件、符号链接和硬链接的归档
+fn archive() -> Vec<u8> {
+    let mut archive = Vec::new();
+    push(&mut archive, 1, 0o040_755, 2, ".", b"");
+    push(&mut archive, 2, 0o040_700, 2, "bin", b"");
+    push(&mut archive, 3, 0o100_755, 1, "bin/init", b"\x7fELF init");
+    // 父目录没有单独出现在归档中
+    push(
+        &mut archive,
+        4,
+        0o100_644,
+        1,
+        "etc/conf/app.conf",
+        b"key=value\n",
+    );
+    push(&mut archive, 5, 0o120_777, 1, "sbin", b"bin");
+    // 硬链接，只有最后一个带有内容
+    push(&mut archive, 6, 0o100_644, 2, "a", b"");
+    push(&mut archive, 6, 0o100_644, 2, "b", b"shared");
+    // 设备文件会被跳过
+    push(&mut archive, 7, 0o020_666, 1, "console", b"");
+    push(&mut archive, 0, 0, 1, "TRAILER!!!", b"");
+    archive
+}
+
+#[test]
+fn unpack_tree() {
+    let root = TmpFS::new(0x10000).root_inode();
+    cpio::unpack(&archive(), &root).unwrap();
+    assert_eq!(
+        root.list().unwrap(),
+        vec![".", "..", "a", "b", "bin", "etc", "sbin"]
+    );
+    let init = root.lookup("bin/init").unwrap();
+    assert_eq!(readall(&init), b"\x7fELF init");
+    let metadata = init.metadata().unwrap();
+    assert_eq!(metadata.mode, 0o755);
+    assert_eq!(metadata.uid, 1000);
+    assert_eq!(metadata.mtime.sec, 1_600_000_000);
+    assert_eq!(root.lookup("bin").unwrap().metadata().unwrap().mode, 0o700);
+    assert_eq!(
+        readall(&root.lookup("etc/conf/app.conf").unwrap()),
+        b"key=value\n"
+    );
+    assert_eq!(
+        root.lookup("sbin").unwrap().metadata().unwrap().type_,
+        FileType::SymLink
+    );
+    assert_eq!(
+        readall(&root.lookup_follow("sbin/init", 1).unwrap()),
+        b"\x7fELF init"
+    );
+}
+
+#[test]
+fn hard_links() {
+    let root = TmpFS::new(0x10000).root_inode();
+    cpio::unpack(&archive(), &root).unwrap();
+    let a = root.find("a").unwrap();
+    assert_eq!(readall(&a), b"shared");
+    assert_eq!(a.metadata().unwrap().nlinks, 2);
+    assert_eq!(
+        a.metadata().unwrap().inode,
+        root.find("b").unwrap().metadata().unwrap().inode
+    );
+}
+
+#[test]
+fn invalid_archive() {
+    let root = TmpFS::new(0x10000).root_inode();
+    assert_eq!(
+        cpio::unpack(b"not an archive", &root).err(),
+        Some(FsError::InvalidParam)
+    );
+    // 截断的归档，没有结束标记
+    let archive = archive();
+    assert_eq!(
+        cpio::unpack(&archive[..200], &root).err(),
+        Some(FsError::InvalidParam)
+    );
+    // 内容超出归档的范围
+    let mut archive = Vec::new();
+    push(&mut archive, 1, 0o100_644, 1, "file", b"data");
+    archive.truncate(archive.len() - 4);
+    assert_eq!(
+        cpio::unpack(&archive, &root).err(),
+        Some(FsError::InvalidParam)
+    );
+}
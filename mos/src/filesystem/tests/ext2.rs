@@ -0,0 +1,206 @@
+//! 用 `mke2fs -d` 生成 ext2 镜像，通过 [`INode`] trait 测试 [`Ext2FS`]
+//!
+//! 需要 host 上装有 e2fsprogs，没有 `mke2fs` 时跳过测试
+
+mod common;
+
+use common::{readall, MemDevice};
+use filesystem::Ext2FS;
+use rcore_fs::vfs::*;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::{env, fs};
+
+/// 大文件的内容，1K 的块大小下会用到二级间接块
+fn big_content() -> Vec<u8> {
+    (0..300 * 1024).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+/// 长度超过 60 字节、需要单独数据块的符号链接目标
+fn long_target() -> String {
+    format!("{}dir/nested/deep.txt", "./".repeat(40))
+}
+
+/// 生成镜像并返回根目录，没有 `mke2fs` 时返回 `None`
+fn root(test: &str) -> Option<Arc<dyn INode>> {
+    let base = env::temp_dir().join(format!("ext2-{}-{}", std::process::id(), test));
+    let tree = base.join("tree");
+    fs::create_dir_all(tree.join("dir/nested")).unwrap();
+    fs::create_dir_all(tree.join("many")).unwrap();
+    fs::write(tree.join("hello.txt"), "hello ext2\n").unwrap();
+    fs::write(tree.join("dir/nested/deep.txt"), "deep").unwrap();
+    fs::write(tree.join("big.bin"), big_content()).unwrap();
+    fs::write(tree.join("private"), "secret").unwrap();
+    fs::set_permissions(tree.join("private"), fs::Permissions::from_mode(0o600)).unwrap();
+    for i in 0..200 {
+        fs::write(tree.join(format!("many/file-{:03}", i)), i.to_string()).unwrap();
+    }
+    symlink("hello.txt", tree.join("short-link")).unwrap();
+    symlink(long_target(), tree.join("long-link")).unwrap();
+
+    let image: PathBuf = base.join("ext2.img");
+    let status = Command::new("mke2fs")
+        .args(&["-q", "-F", "-t", "ext2", "-b", "1024", "-d"])
+        .arg(&tree)
+        .arg(&image)
+        .arg("2048")
+        .stdout(Stdio::null())
+        .status();
+    let result = match status {
+        Ok(status) if status.success() => {
+            let device = Arc::new(MemDevice::new(fs::read(&image).unwrap()));
+            Some(Ext2FS::open(device).unwrap().root_inode())
+        }
+        _ => {
+            eprintln!("mke2fs not available, skipping");
+            None
+        }
+    };
+    fs::remove_dir_all(&base).unwrap();
+    result
+}
+
+#[test]
+fn probe() {
+    let blank = MemDevice::new(vec![0u8; 4096]);
+    assert!(!Ext2FS::probe(&blank));
+    assert_eq!(Ext2FS::open(Arc::new(blank)).err(), Some(FsError::WrongFs));
+}
+
+/// 只有超级块的镜像，`corrupt` 修改超级块中的一个字段
+fn superblock_image(corrupt: impl FnOnce(&mut [u8])) -> MemDevice {
+    let mut image = vec![0u8; 4096];
+    {
+        let superblock = &mut image[1024..2048];
+        superblock[0..4].copy_from_slice(&256u32.to_le_bytes());
+        superblock[4..8].copy_from_slice(&2048u32.to_le_bytes());
+        superblock[20..24].copy_from_slice(&1u32.to_le_bytes());
+        superblock[32..36].copy_from_slice(&8192u32.to_le_bytes());
+        superblock[40..44].copy_from_slice(&256u32.to_le_bytes());
+        superblock[56..58].copy_from_slice(&0xef53u16.to_le_bytes());
+        superblock[76..80].copy_from_slice(&1u32.to_le_bytes());
+        superblock[88..90].copy_from_slice(&128u16.to_le_bytes());
+        corrupt(superblock);
+    }
+    MemDevice::new(image)
+}
+
+#[test]
+fn corrupt_superblock() {
+    assert!(Ext2FS::probe(&superblock_image(|_| {})));
+    let corruptions: [fn(&mut [u8]); 6] = [
+        // 块大小过大
+        |data| data[24..28].copy_from_slice(&32u32.to_le_bytes()),
+        |data| data[32..36].copy_from_slice(&0u32.to_le_bytes()),
+        |data| data[40..44].copy_from_slice(&0u32.to_le_bytes()),
+        |data| data[88..90].copy_from_slice(&0u16.to_le_bytes()),
+        // 超出块组能容纳的 inode 数
+        |data| data[0..4].copy_from_slice(&u32::max_value().to_le_bytes()),
+        |data| data[20..24].copy_from_slice(&4096u32.to_le_bytes()),
+    ];
+    for corrupt in corruptions.iter() {
+        let device = superblock_image(corrupt);
+        assert!(!Ext2FS::probe(&device));
+        assert_eq!(Ext2FS::open(Arc::new(device)).err(), Some(FsError::WrongFs));
+    }
+}
+
+#[test]
+fn read_files() {
+    let root = match root("read") {
+        Some(root) => root,
+        None => return,
+    };
+    assert_eq!(readall(&root.lookup("hello.txt").unwrap()), b"hello ext2\n");
+    assert_eq!(readall(&root.lookup("big.bin").unwrap()), big_content());
+    // 从间接块中间开始读
+    let big = root.lookup("big.bin").unwrap();
+    let mut buffer = [0u8; 3000];
+    assert_eq!(big.read_at(270 * 1024 + 100, &mut buffer).unwrap(), 3000);
+    assert_eq!(
+        &buffer[..],
+        &big_content()[270 * 1024 + 100..270 * 1024 + 3100]
+    );
+    assert_eq!(big.read_at(300 * 1024, &mut buffer).unwrap(), 0);
+    assert_eq!(root.read_at(0, &mut buffer).err(), Some(FsError::IsDir));
+}
+
+#[test]
+fn directories() {
+    let root = match root("dirs") {
+        Some(root) => root,
+        None => return,
+    };
+    let names = root.list().unwrap();
+    for name in [".", "..", "lost+found", "hello.txt", "dir", "many"].iter() {
+        assert!(names.contains(&name.to_string()), "missing {}", name);
+    }
+    // 目录项占用多个块
+    let many = root.lookup("many").unwrap();
+    assert_eq!(many.list().unwrap().len(), 202);
+    assert_eq!(readall(&many.find("file-199").unwrap()), b"199");
+    assert_eq!(
+        readall(&root.lookup("dir/nested/deep.txt").unwrap()),
+        b"deep"
+    );
+    assert_eq!(
+        root.lookup("dir/nested/..")
+            .unwrap()
+            .metadata()
+            .unwrap()
+            .inode,
+        root.lookup("dir").unwrap().metadata().unwrap().inode
+    );
+    assert_eq!(root.metadata().unwrap().inode, 2);
+    assert_eq!(root.find("missing").err(), Some(FsError::EntryNotFound));
+}
+
+#[test]
+fn symlinks() {
+    let root = match root("links") {
+        Some(root) => root,
+        None => return,
+    };
+    let short = root.lookup("short-link").unwrap();
+    assert_eq!(short.metadata().unwrap().type_, FileType::SymLink);
+    assert_eq!(readall(&short), b"hello.txt");
+    assert_eq!(
+        readall(&root.lookup("long-link").unwrap()),
+        long_target().as_bytes()
+    );
+    assert_eq!(
+        readall(&root.lookup_follow("short-link", 1).unwrap()),
+        b"hello ext2\n"
+    );
+    assert_eq!(
+        readall(&root.lookup_follow("long-link", 1).unwrap()),
+        b"deep"
+    );
+}
+
+#[test]
+fn metadata_and_read_only() {
+    let root = match root("meta") {
+        Some(root) => root,
+        None => return,
+    };
+    let private = root.lookup("private").unwrap();
+    let metadata = private.metadata().unwrap();
+    assert_eq!(metadata.type_, FileType::File);
+    assert_eq!(metadata.mode, 0o600);
+    assert_eq!(metadata.size, 6);
+    assert_eq!(metadata.nlinks, 1);
+    let dir = root.lookup("dir").unwrap().metadata().unwrap();
+    assert_eq!(dir.type_, FileType::Dir);
+    assert_eq!(dir.nlinks, 3);
+
+    assert_eq!(private.write_at(0, b"x").err(), Some(FsError::NotSupported));
+    assert_eq!(private.resize(0).err(), Some(FsError::NotSupported));
+    assert_eq!(
+        root.create("new", FileType::File, 0o644).err(),
+        Some(FsError::NotSupported)
+    );
+    assert_eq!(root.unlink("private").err(), Some(FsError::NotSupported));
+}
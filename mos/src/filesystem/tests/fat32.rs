@@ -0,0 +1,286 @@
+//! 在内存中格式化 FAT32 镜像，通过 [`INode`] trait 测试 [`Fat32FS`]
+
+mod common;
+
+use common::{readall, MemDevice};
+use filesystem::Fat32FS;
+use rcore_fs::vfs::*;
+use std::sync::Arc;
+
+/// 扇区大小
+const SECTOR: usize = 512;
+/// 保留扇区数
+const RESERVED: usize = 32;
+
+/// 格式化一个共 `sectors` 个扇区、每簇一个扇区的 FAT32 镜像，返回设备和每份 FAT 的扇区数
+fn format(sectors: usize) -> (Arc<MemDevice>, usize) {
+    let clusters = sectors - RESERVED;
+    let fat_size = ((clusters + 2) * 4 + SECTOR - 1) / SECTOR;
+    let mut image = vec![0u8; sectors * SECTOR];
+    {
+        let boot = &mut image[..SECTOR];
+        boot[0..3].copy_from_slice(&[0xeb, 0x58, 0x90]);
+        boot[3..11].copy_from_slice(b"MSWIN4.1");
+        boot[11..13].copy_from_slice(&(SECTOR as u16).to_le_bytes());
+        boot[13] = 1;
+        boot[14..16].copy_from_slice(&(RESERVED as u16).to_le_bytes());
+        boot[16] = 2;
+        boot[21] = 0xf8;
+        boot[32..36].copy_from_slice(&(sectors as u32).to_le_bytes());
+        boot[36..40].copy_from_slice(&(fat_size as u32).to_le_bytes());
+        boot[44..48].copy_from_slice(&2u32.to_le_bytes());
+        boot[48..50].copy_from_slice(&1u16.to_le_bytes());
+        boot[82..90].copy_from_slice(b"FAT32   ");
+        boot[510..512].copy_from_slice(&[0x55, 0xaa]);
+    }
+    {
+        let info = &mut image[SECTOR..2 * SECTOR];
+        info[0..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+        info[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+        // 故意写入未知的空闲簇数，让文件系统扫描 FAT
+        info[488..492].copy_from_slice(&u32::max_value().to_le_bytes());
+        info[492..496].copy_from_slice(&3u32.to_le_bytes());
+        info[508..512].copy_from_slice(&0xaa55_0000u32.to_le_bytes());
+    }
+    for fat in 0..2 {
+        let offset = (RESERVED + fat * fat_size) * SECTOR;
+        for (index, value) in [0x0fff_fff8u32, 0x0fff_ffff, 0x0fff_ffff]
+            .iter()
+            .enumerate()
+        {
+            image[offset + index * 4..offset + index * 4 + 4].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+    (Arc::new(MemDevice::new(image)), fat_size)
+}
+
+/// 格式化并打开一个 4 MiB 的文件系统
+fn open() -> (Arc<MemDevice>, Arc<Fat32FS>) {
+    let (device, _) = format(8192);
+    let fs = Fat32FS::open(device.clone()).unwrap();
+    (device, fs)
+}
+
+#[test]
+fn probe() {
+    let blank = MemDevice::new(vec![0u8; 8192 * SECTOR]);
+    assert!(!Fat32FS::probe(&blank));
+    assert_eq!(Fat32FS::open(Arc::new(blank)).err(), Some(FsError::WrongFs));
+    let (device, _) = format(8192);
+    assert!(Fat32FS::probe(&*device));
+}
+
+#[test]
+fn file_read_write() {
+    let (_, fs) = open();
+    let root = fs.root_inode();
+    let file = root.create("data.bin", FileType::File, 0o644).unwrap();
+    // 跨越多个簇
+    let data: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+    assert_eq!(file.write_at(0, &data).unwrap(), 3000);
+    assert_eq!(readall(&file), data);
+    // 在文件末尾之后写入，中间的空洞填 0
+    file.write_at(4000, b"!").unwrap();
+    let content = readall(&file);
+    assert_eq!(content.len(), 4001);
+    assert!(content[3000..4000].iter().all(|&b| b == 0));
+    let mut buffer = [0u8; 4];
+    assert_eq!(file.read_at(3999, &mut buffer).unwrap(), 2);
+    assert_eq!(file.read_at(5000, &mut buffer).unwrap(), 0);
+    assert_eq!(file.metadata().unwrap().blocks, 8);
+}
+
+#[test]
+fn long_file_names() {
+    let (device, fs) = open();
+    let root = fs.root_inode();
+    let names = [
+        "README.TXT",
+        "lower.txt",
+        "A much longer file name with spaces.tar.gz",
+        "中文文件名",
+    ];
+    for name in names.iter() {
+        let file = root.create(name, FileType::File, 0o644).unwrap();
+        file.write_at(0, name.as_bytes()).unwrap();
+    }
+    assert_eq!(
+        root.create("readme.txt", FileType::File, 0o644).err(),
+        Some(FsError::EntryExist)
+    );
+    let mut expected = vec![".", ".."];
+    expected.extend(names.iter());
+    assert_eq!(root.list().unwrap(), expected);
+    // 查找不区分大小写，也可以使用生成的短文件名
+    assert_eq!(readall(&root.find("readme.txt").unwrap()), b"README.TXT");
+    assert_eq!(readall(&root.find("LOWER.TXT").unwrap()), b"lower.txt");
+    assert!(root.find("AMUCHL~1.GZ").is_ok());
+
+    // 重新打开后文件名和内容都还在
+    fs.sync().unwrap();
+    let root = Fat32FS::open(device).unwrap().root_inode();
+    assert_eq!(root.list().unwrap(), expected);
+    for name in names.iter() {
+        assert_eq!(readall(&root.find(name).unwrap()), name.as_bytes());
+    }
+}
+
+#[test]
+fn directories() {
+    let (device, fs) = open();
+    let free = fs.info().bfree;
+    let root = fs.root_inode();
+    let dir = root.create("dir", FileType::Dir, 0o755).unwrap();
+    let sub = dir.create("Sub Directory", FileType::Dir, 0o755).unwrap();
+    sub.create("file", FileType::File, 0o644).unwrap();
+    assert_eq!(dir.list().unwrap(), vec![".", "..", "Sub Directory"]);
+    assert_eq!(
+        root.lookup("dir/Sub Directory/..")
+            .unwrap()
+            .metadata()
+            .unwrap()
+            .inode,
+        dir.metadata().unwrap().inode
+    );
+    assert_eq!(
+        root.lookup("dir/..").unwrap().metadata().unwrap().inode,
+        root.metadata().unwrap().inode
+    );
+    assert_eq!(root.unlink("dir").err(), Some(FsError::DirNotEmpty));
+    assert_eq!(root.link("link", &dir).err(), Some(FsError::NotSupported));
+
+    // 目录项多于一个簇时目录会增长
+    for i in 0..40 {
+        sub.create(&format!("file number {}", i), FileType::File, 0o644)
+            .unwrap();
+    }
+    assert_eq!(sub.list().unwrap().len(), 43);
+    assert!(sub.metadata().unwrap().size > 512);
+    fs.sync().unwrap();
+    let reopened = Fat32FS::open(device).unwrap().root_inode();
+    assert!(reopened.lookup("dir/sub directory/file number 39").is_ok());
+
+    // 删除所有内容后，空间全部归还
+    for name in sub.list().unwrap().iter().skip(2) {
+        sub.unlink(name).unwrap();
+    }
+    assert_eq!(sub.list().unwrap(), vec![".", ".."]);
+    dir.unlink("Sub Directory").unwrap();
+    assert_eq!(
+        sub.create("file", FileType::File, 0o644).err(),
+        Some(FsError::DirRemoved)
+    );
+    root.unlink("dir").unwrap();
+    assert_eq!(root.list().unwrap(), vec![".", ".."]);
+    assert_eq!(fs.info().bfree, free);
+}
+
+#[test]
+fn truncate() {
+    let (_, fs) = open();
+    let free = fs.info().bfree;
+    let root = fs.root_inode();
+    let file = root.create("file", FileType::File, 0o644).unwrap();
+    file.write_at(0, &[1u8; 2000]).unwrap();
+    assert_eq!(fs.info().bfree, free - 4);
+    file.resize(10).unwrap();
+    assert_eq!(fs.info().bfree, free - 1);
+    // 再次增长时，原来写过的数据不会重新出现
+    file.resize(600).unwrap();
+    let content = readall(&file);
+    assert_eq!(&content[..10], &[1u8; 10]);
+    assert!(content[10..].iter().all(|&b| b == 0));
+    file.resize(0).unwrap();
+    assert_eq!(fs.info().bfree, free);
+    assert_eq!(root.resize(0).err(), Some(FsError::IsDir));
+
+    // 空间用完
+    let big = root.create("big", FileType::File, 0o644).unwrap();
+    assert_eq!(
+        big.resize((free + 1) * 512).err(),
+        Some(FsError::NoDeviceSpace)
+    );
+    assert_eq!(fs.info().bfree, 0);
+    root.unlink("big").unwrap();
+    assert_eq!(fs.info().bfree, free);
+}
+
+#[test]
+fn rename() {
+    let (device, fs) = open();
+    let root = fs.root_inode();
+    let a = root.create("a", FileType::Dir, 0o755).unwrap();
+    let b = root.create("b", FileType::Dir, 0o755).unwrap();
+    let file = a.create("file", FileType::File, 0o644).unwrap();
+    file.write_at(0, b"data").unwrap();
+    a.move_("file", &b, "a longer name").unwrap();
+    assert_eq!(a.find("file").err(), Some(FsError::EntryNotFound));
+    assert_eq!(readall(&root.lookup("b/a longer name").unwrap()), b"data");
+    // 移动后仍然可以通过原来的 INode 写入
+    file.write_at(4, b"!").unwrap();
+    // 同一目录中改名，并替换已有的文件
+    let other = b.create("other", FileType::File, 0o644).unwrap();
+    other.write_at(0, b"old").unwrap();
+    b.move_("a longer name", &b, "other").unwrap();
+    assert_eq!(b.list().unwrap(), vec![".", "..", "other"]);
+    // 目录不能移动到自己的子树中
+    assert_eq!(root.move_("b", &b, "b").err(), Some(FsError::InvalidParam));
+    root.move_("b", &a, "b").unwrap();
+
+    fs.sync().unwrap();
+    let root = Fat32FS::open(device).unwrap().root_inode();
+    assert_eq!(readall(&root.lookup("a/b/other").unwrap()), b"data!");
+    assert_eq!(root.list().unwrap(), vec![".", "..", "a"]);
+}
+
+#[test]
+fn fat_write_back() {
+    let (device, fat_size) = format(8192);
+    let fs = Fat32FS::open(device.clone()).unwrap();
+    let file = fs
+        .root_inode()
+        .create("file", FileType::File, 0o644)
+        .unwrap();
+    file.write_at(0, &[0u8; 4096]).unwrap();
+    let fat = |index: usize| {
+        let offset = (RESERVED + index * fat_size) * SECTOR;
+        device.data()[offset..offset + SECTOR].to_vec()
+    };
+    // 修改只在缓存中，还没有写回
+    assert_eq!(&fat(0)[8..12], &0x0fff_ffffu32.to_le_bytes());
+    assert_eq!(&fat(0)[12..16], &[0; 4]);
+    fs.sync().unwrap();
+    assert_ne!(&fat(0)[12..16], &[0; 4]);
+    assert_eq!(fat(0), fat(1));
+    // FSInfo 记录了空闲簇数
+    let free = {
+        let data = device.data();
+        u32::from_le_bytes([data[1000], data[1001], data[1002], data[1003]])
+    };
+    assert_eq!(free as usize, fs.info().bfree);
+}
+
+#[test]
+fn contiguous_allocation() {
+    let (device, _) = format(8192);
+    let fs = Fat32FS::open(device.clone()).unwrap();
+    let file = fs
+        .root_inode()
+        .create("file", FileType::File, 0o644)
+        .unwrap();
+    // 根目录占用第 2 簇，文件依次占用第 3 到 10 簇
+    file.write_at(0, &[1u8; 8 * SECTOR]).unwrap();
+    fs.sync().unwrap();
+    let read_u32 = |offset: usize| {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&device.data()[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    };
+    let entry = |cluster: usize| read_u32(RESERVED * SECTOR + cluster * 4);
+    for cluster in 3..10 {
+        assert_eq!(entry(cluster), cluster as u32 + 1);
+    }
+    assert!(entry(10) >= 0x0fff_fff8);
+    // FSInfo 中记录的下一个空闲簇紧跟在最后分配的簇之后
+    assert_eq!(read_u32(SECTOR + 492), 11);
+}
@@ -0,0 +1,227 @@
+//! 在内存中构造 MBR 和 GPT 分区表，测试 [`read_partitions`]
+
+mod common;
+
+use common::MemDevice;
+use filesystem::{read_partitions, Partition};
+
+/// 扇区大小
+const SECTOR: usize = 512;
+/// 测试磁盘的扇区数
+const SECTORS: usize = 4096;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// 写入 MBR 的第 `index` 个分区项
+fn mbr_entry(image: &mut [u8], index: usize, type_: u8, start: u32, sectors: u32) {
+    let entry = &mut image[446 + 16 * index..446 + 16 * (index + 1)];
+    entry[4] = type_;
+    entry[8..12].copy_from_slice(&start.to_le_bytes());
+    entry[12..16].copy_from_slice(&sectors.to_le_bytes());
+}
+
+/// 只有签名的空 MBR
+fn empty_mbr() -> Vec<u8> {
+    let mut image = vec![0u8; SECTORS * SECTOR];
+    image[510] = 0x55;
+    image[511] = 0xaa;
+    image
+}
+
+/// GUID 的磁盘格式，前三段为小端序
+fn guid(text: &str) -> [u8; 16] {
+    let hex: Vec<u8> = text
+        .split('-')
+        .collect::<String>()
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+        .collect();
+    let mut data = [0u8; 16];
+    data.copy_from_slice(&hex);
+    data[0..4].reverse();
+    data[4..6].reverse();
+    data[6..8].reverse();
+    data
+}
+
+/// 在第 `lba` 个扇区写入 GPT 头，分区项数组从第 2 个扇区开始，共 128 项
+fn gpt_header(image: &mut [u8], lba: usize, entries_crc: u32) {
+    let header = &mut image[lba * SECTOR..(lba + 1) * SECTOR];
+    header[..8].copy_from_slice(b"EFI PART");
+    header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+    header[12..16].copy_from_slice(&92u32.to_le_bytes());
+    header[24..32].copy_from_slice(&(lba as u64).to_le_bytes());
+    header[72..80].copy_from_slice(&2u64.to_le_bytes());
+    header[80..84].copy_from_slice(&128u32.to_le_bytes());
+    header[84..88].copy_from_slice(&128u32.to_le_bytes());
+    header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+    let crc = crc32(&header[..92]);
+    header[16..20].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// 修改第 `lba` 个扇区上 GPT 头中的字段，并重新计算校验和
+fn patch_gpt_header(image: &mut [u8], lba: usize, offset: usize, value: &[u8]) {
+    let header = &mut image[lba * SECTOR..(lba + 1) * SECTOR];
+    header[offset..offset + value.len()].copy_from_slice(value);
+    header[16..20].copy_from_slice(&[0; 4]);
+    let crc = crc32(&header[..92]);
+    header[16..20].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// 带有保护 MBR 和两个分区的 GPT 磁盘，`backup` 时同时写入备份 GPT 头
+fn gpt_image(backup: bool) -> Vec<u8> {
+    let mut image = empty_mbr();
+    mbr_entry(&mut image, 0, 0xee, 1, SECTORS as u32 - 1);
+    let partitions = [
+        (
+            "01234567-89ab-cdef-0123-456789abcdef",
+            34u64,
+            1057u64,
+            "boot",
+        ),
+        ("fedcba98-7654-3210-fedc-ba9876543210", 1058, 4062, "root"),
+    ];
+    for (index, (uuid, first, last, name)) in partitions.iter().enumerate() {
+        let entry = &mut image[2 * SECTOR + 128 * index..2 * SECTOR + 128 * (index + 1)];
+        // Linux 文件系统数据的类型 GUID
+        entry[..16].copy_from_slice(&guid("0fc63daf-8483-4772-8e79-3d69d8477de4"));
+        entry[16..32].copy_from_slice(&guid(uuid));
+        entry[32..40].copy_from_slice(&first.to_le_bytes());
+        entry[40..48].copy_from_slice(&last.to_le_bytes());
+        for (offset, unit) in name.encode_utf16().enumerate() {
+            entry[56 + 2 * offset..58 + 2 * offset].copy_from_slice(&unit.to_le_bytes());
+        }
+    }
+    let entries_crc = crc32(&image[2 * SECTOR..2 * SECTOR + 128 * 128]);
+    gpt_header(&mut image, 1, entries_crc);
+    if backup {
+        gpt_header(&mut image, SECTORS - 1, entries_crc);
+    }
+    image
+}
+
+fn expected_gpt() -> Vec<Partition> {
+    vec![
+        Partition {
+            number: 1,
+            start: 34,
+            sectors: 1024,
+            uuid: String::from("01234567-89ab-cdef-0123-456789abcdef"),
+            label: Some(String::from("boot")),
+        },
+        Partition {
+            number: 2,
+            start: 1058,
+            sectors: 3005,
+            uuid: String::from("fedcba98-7654-3210-fedc-ba9876543210"),
+            label: Some(String::from("root")),
+        },
+    ]
+}
+
+#[test]
+fn mbr_partitions() {
+    let mut image = empty_mbr();
+    image[440..444].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+    mbr_entry(&mut image, 0, 0x0c, 63, 1000);
+    // 扩展分区和超出磁盘的分区都被忽略
+    mbr_entry(&mut image, 1, 0x05, 1063, 985);
+    mbr_entry(&mut image, 2, 0x83, 2048, 2048);
+    mbr_entry(&mut image, 3, 0x82, 4000, 1000);
+    let partitions = read_partitions(&MemDevice::new(image), SECTORS).unwrap();
+    assert_eq!(
+        partitions,
+        vec![
+            Partition {
+                number: 1,
+                start: 63,
+                sectors: 1000,
+                uuid: String::from("12345678-01"),
+                label: None,
+            },
+            Partition {
+                number: 3,
+                start: 2048,
+                sectors: 2048,
+                uuid: String::from("12345678-03"),
+                label: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn no_partition_table() {
+    // 没有签名
+    let image = vec![0u8; SECTORS * SECTOR];
+    assert!(read_partitions(&MemDevice::new(image), SECTORS)
+        .unwrap()
+        .is_empty());
+    // 有签名，但是分区项的位置是引导代码
+    let mut image = empty_mbr();
+    image[446..510].copy_from_slice(&[0x4e; 64]);
+    assert!(read_partitions(&MemDevice::new(image), SECTORS)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn gpt_partitions() {
+    let partitions = read_partitions(&MemDevice::new(gpt_image(false)), SECTORS).unwrap();
+    assert_eq!(partitions, expected_gpt());
+}
+
+#[test]
+fn gpt_backup_header() {
+    let mut image = gpt_image(true);
+    // 损坏主 GPT 头
+    image[SECTOR + 40] ^= 0xff;
+    let partitions = read_partitions(&MemDevice::new(image), SECTORS).unwrap();
+    assert_eq!(partitions, expected_gpt());
+}
+
+#[test]
+fn gpt_corrupted() {
+    let mut image = gpt_image(false);
+    // 分区项的校验和不对，又没有备份
+    image[2 * SECTOR + 32] ^= 0xff;
+    assert!(read_partitions(&MemDevice::new(image), SECTORS)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn gpt_entries_out_of_range() {
+    // 分区项数组超出磁盘，乘以扇区大小时会溢出
+    let mut image = gpt_image(false);
+    patch_gpt_header(&mut image, 1, 72, &(u64::MAX / 256).to_le_bytes());
+    assert!(read_partitions(&MemDevice::new(image), SECTORS)
+        .unwrap()
+        .is_empty());
+    // 分区项数组从磁盘的最后一个扇区开始
+    let mut image = gpt_image(false);
+    patch_gpt_header(&mut image, 1, 72, &(SECTORS as u64 - 1).to_le_bytes());
+    assert!(read_partitions(&MemDevice::new(image), SECTORS)
+        .unwrap()
+        .is_empty());
+    // 分区项过长
+    let mut image = gpt_image(false);
+    patch_gpt_header(&mut image, 1, 84, &0x10_0000u32.to_le_bytes());
+    assert!(read_partitions(&MemDevice::new(image), SECTORS)
+        .unwrap()
+        .is_empty());
+}
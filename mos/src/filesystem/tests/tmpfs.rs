@@ -0,0 +1,179 @@
+//! 通过 [`INode`] trait 测试 [`TmpFS`]
+
+mod common;
+
+use common::readall;
+use filesystem::TmpFS;
+use rcore_fs::vfs::*;
+use std::sync::Arc;
+
+/// 创建一个容量为 `capacity` 的文件系统并返回根目录
+fn root(capacity: usize) -> Arc<dyn INode> {
+    TmpFS::new(capacity).root_inode()
+}
+
+#[test]
+fn file_read_write() {
+    let root = root(0x1000);
+    let file = root.create("hello", FileType::File, 0o644).unwrap();
+    assert_eq!(file.write_at(0, b"hello world").unwrap(), 11);
+    assert_eq!(readall(&file), b"hello world");
+    // 在文件末尾之后写入，中间的空洞填 0
+    file.write_at(13, b"!").unwrap();
+    assert_eq!(readall(&file), b"hello world\0\0!");
+    // 读取超出文件末尾的部分
+    let mut buffer = [0u8; 4];
+    assert_eq!(file.read_at(12, &mut buffer).unwrap(), 2);
+    assert_eq!(file.read_at(100, &mut buffer).unwrap(), 0);
+}
+
+#[test]
+fn directory_entries() {
+    let root = root(0x1000);
+    let dir = root.create("dir", FileType::Dir, 0o755).unwrap();
+    dir.create("b", FileType::File, 0o644).unwrap();
+    dir.create("a", FileType::File, 0o644).unwrap();
+    assert_eq!(dir.list().unwrap(), vec![".", "..", "a", "b"]);
+    assert_eq!(
+        root.create("dir", FileType::File, 0o644).err(),
+        Some(FsError::EntryExist)
+    );
+    // 路径查找以及 "." 和 ".."
+    let a = root.lookup("dir/a").unwrap();
+    let parent = root.lookup("dir/..").unwrap();
+    assert_eq!(
+        a.metadata().unwrap().inode,
+        dir.find("a").unwrap().metadata().unwrap().inode
+    );
+    assert_eq!(
+        parent.metadata().unwrap().inode,
+        root.metadata().unwrap().inode
+    );
+    assert_eq!(root.metadata().unwrap().nlinks, 3);
+    assert_eq!(root.find("missing").err(), Some(FsError::EntryNotFound));
+}
+
+#[test]
+fn symlink() {
+    let root = root(0x1000);
+    let dir = root.create("dir", FileType::Dir, 0o755).unwrap();
+    let file = dir.create("file", FileType::File, 0o644).unwrap();
+    file.write_at(0, b"content").unwrap();
+    let link = root.create("link", FileType::SymLink, 0o777).unwrap();
+    link.write_at(0, b"dir/file").unwrap();
+    assert_eq!(link.metadata().unwrap().type_, FileType::SymLink);
+    // 不跟随时得到符号链接本身
+    assert_eq!(readall(&root.lookup("link").unwrap()), b"dir/file");
+    assert_eq!(readall(&root.lookup_follow("link", 1).unwrap()), b"content");
+}
+
+#[test]
+fn truncate_and_metadata() {
+    let root = root(0x1000);
+    let file = root.create("file", FileType::File, 0o600).unwrap();
+    file.write_at(0, &[1u8; 1000]).unwrap();
+    let metadata = file.metadata().unwrap();
+    assert_eq!(metadata.size, 1000);
+    assert_eq!(metadata.blocks, 2);
+    assert_eq!(metadata.mode, 0o600);
+    file.resize(10).unwrap();
+    assert_eq!(readall(&file), vec![1u8; 10]);
+    file.resize(12).unwrap();
+    assert_eq!(&readall(&file)[10..], &[0, 0]);
+    assert_eq!(root.resize(0).err(), Some(FsError::IsDir));
+
+    let mut metadata = file.metadata().unwrap();
+    metadata.mode = 0o644;
+    metadata.mtime = Timespec { sec: 42, nsec: 0 };
+    file.set_metadata(&metadata).unwrap();
+    let metadata = file.metadata().unwrap();
+    assert_eq!(metadata.mode, 0o644);
+    assert_eq!(metadata.mtime.sec, 42);
+}
+
+#[test]
+fn size_limit() {
+    let fs = TmpFS::new(1024);
+    let root = fs.root_inode();
+    let a = root.create("a", FileType::File, 0o644).unwrap();
+    let b = root.create("b", FileType::File, 0o644).unwrap();
+    a.write_at(0, &[0u8; 1000]).unwrap();
+    assert_eq!(b.write_at(0, &[0u8; 100]).err(), Some(FsError::NoDeviceSpace));
+    assert_eq!(fs.info().bfree, 0);
+    // 截断和删除都会归还空间
+    a.resize(500).unwrap();
+    b.write_at(0, &[0u8; 100]).unwrap();
+    drop(a);
+    root.unlink("a").unwrap();
+    b.write_at(0, &[0u8; 900]).unwrap();
+}
+
+#[test]
+fn huge_offset() {
+    let fs = TmpFS::new(1024);
+    let root = fs.root_inode();
+    let a = root.create("a", FileType::File, 0o644).unwrap();
+    let b = root.create("b", FileType::File, 0o644).unwrap();
+    b.write_at(0, b"data").unwrap();
+    // 偏移量加上长度溢出
+    assert_eq!(a.write_at(usize::MAX, b"x").err(), Some(FsError::InvalidParam));
+    assert_eq!(
+        a.read_at(usize::MAX, &mut [0u8; 4]).err(),
+        Some(FsError::InvalidParam)
+    );
+    // 已用空间加上申请的大小溢出
+    assert_eq!(a.resize(usize::MAX).err(), Some(FsError::NoDeviceSpace));
+    assert_eq!(
+        a.write_at(usize::MAX - 1, b"x").err(),
+        Some(FsError::NoDeviceSpace)
+    );
+    assert_eq!(a.metadata().unwrap().size, 0);
+    assert_eq!(readall(&b), b"data");
+}
+
+#[test]
+fn unlink_and_link() {
+    let root = root(0x1000);
+    let dir = root.create("dir", FileType::Dir, 0o755).unwrap();
+    let file = dir.create("file", FileType::File, 0o644).unwrap();
+    root.link("hard", &file).unwrap();
+    assert_eq!(file.metadata().unwrap().nlinks, 2);
+    assert_eq!(root.link("dir2", &dir).err(), Some(FsError::IsDir));
+    assert_eq!(root.unlink("dir").err(), Some(FsError::DirNotEmpty));
+    dir.unlink("file").unwrap();
+    assert_eq!(file.metadata().unwrap().nlinks, 1);
+    root.unlink("dir").unwrap();
+    // 已删除的目录不能再创建文件
+    assert_eq!(
+        dir.create("file", FileType::File, 0o644).err(),
+        Some(FsError::DirRemoved)
+    );
+    assert_eq!(root.list().unwrap(), vec![".", "..", "hard"]);
+}
+
+#[test]
+fn rename() {
+    let root = root(0x1000);
+    let a = root.create("a", FileType::Dir, 0o755).unwrap();
+    let b = root.create("b", FileType::Dir, 0o755).unwrap();
+    let file = a.create("file", FileType::File, 0o644).unwrap();
+    file.write_at(0, b"data").unwrap();
+    a.move_("file", &b, "moved").unwrap();
+    assert_eq!(a.find("file").err(), Some(FsError::EntryNotFound));
+    assert_eq!(readall(&root.lookup("b/moved").unwrap()), b"data");
+    // 同一目录中改名，并替换已有的文件
+    let other = b.create("other", FileType::File, 0o644).unwrap();
+    other.write_at(0, b"old").unwrap();
+    b.move_("moved", &b, "other").unwrap();
+    assert_eq!(readall(&root.lookup("b/other").unwrap()), b"data");
+    // 目录不能移动到自己的子树中
+    assert_eq!(
+        root.move_("b", &b, "b").err(),
+        Some(FsError::InvalidParam)
+    );
+    root.move_("b", &a, "b").unwrap();
+    assert_eq!(
+        root.lookup("a/b/..").unwrap().metadata().unwrap().inode,
+        a.metadata().unwrap().inode
+    );
+}
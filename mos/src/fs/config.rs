@@ -2,3 +2,9 @@
 
 /// 块设备的 Cache 块个数
 pub const BLOCK_CACHE_CAPACITY: usize = 0x10;
+
+/// 内存文件系统 [`TmpFS`](filesystem::TmpFS) 的容量（4M）
+pub const TMPFS_CAPACITY: usize = 0x40_0000;
+
+/// 管道缓冲区的大小（4K）
+pub const PIPE_CAPACITY: usize = 0x1000;
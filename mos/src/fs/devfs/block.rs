@@ -0,0 +1,124 @@
+//! 块设备节点 `/dev/vda`、`/dev/vdb`……以及分区节点 `/dev/vda1`……
+//!
+//! 直接读写设备，不经过文件系统使用的 [`BlockCache`]
+
+use super::*;
+use crate::drivers::block::VIRTIO_BLK_MAJOR;
+use rcore_fs::dev::Device;
+
+/// 获取设备字节数（`BLKGETSIZE64`），参数为 `*mut u64`
+const BLKGETSIZE64: u32 = 0x8008_1272;
+/// 获取 512B 扇区数（`BLKGETSIZE`），参数为 `*mut usize`
+const BLKGETSIZE: u32 = 0x1260;
+/// 获取逻辑块大小（`BLKSSZGET`），参数为 `*mut u32`
+const BLKSSZGET: u32 = 0x1268;
+/// 获取磁盘几何信息（`HDIO_GETGEO`），参数为 `*mut HdGeometry`
+const HDIO_GETGEO: u32 = 0x0301;
+
+/// 块大小，和 [`BlockDevice`] 保持一致
+const BLOCK_SIZE: usize = 512;
+
+/// `HDIO_GETGEO` 返回的磁盘几何信息，布局和 Linux 中的 `struct hd_geometry` 相同
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct HdGeometry {
+    heads: u8,
+    sectors: u8,
+    cylinders: u16,
+    start: usize,
+}
+
+/// 块设备节点
+pub struct BlockINode {
+    /// 磁盘或分区
+    device: BlockDevice,
+    /// 次设备号
+    minor: usize,
+}
+
+impl BlockINode {
+    pub fn new(device: BlockDevice, minor: usize) -> Self {
+        Self { device, minor }
+    }
+
+    /// 设备大小（字节）
+    fn size(&self) -> usize {
+        self.device.blocks * BLOCK_SIZE
+    }
+}
+
+impl INode for BlockINode {
+    /// 读取设备内容，超出设备末尾的部分不会读取
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let size = self.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = buf.len().min(size - offset);
+        Ok(Device::read_at(&self.device, offset, &mut buf[..len])?)
+    }
+
+    /// 写入设备，不允许超出设备末尾
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        let size = self.size();
+        if offset >= size {
+            return Err(FsError::NoDeviceSpace);
+        }
+        let len = buf.len().min(size - offset);
+        Ok(Device::write_at(&self.device, offset, &buf[..len])?)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(device_metadata(
+            FileType::BlockDevice,
+            VIRTIO_BLK_MAJOR,
+            self.minor,
+            self.size(),
+        ))
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        Ok(Device::sync(&self.device)?)
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.sync_all()
+    }
+
+    /// 查询设备的几何信息，结果写入 `data` 指向的用户内存
+    fn io_control(&self, cmd: u32, data: usize) -> Result<usize> {
+        let blocks = self.device.blocks;
+        match cmd {
+            BLKGETSIZE64 => ioctl_write(data, self.size() as u64)?,
+            BLKGETSIZE => ioctl_write(data, blocks)?,
+            BLKSSZGET => ioctl_write(data, BLOCK_SIZE as u32)?,
+            HDIO_GETGEO => {
+                // 和 Linux 的 virtio_blk 一样，虚构 255 磁头、每道 63 扇区
+                let heads = 255;
+                let sectors = 63;
+                let geometry = HdGeometry {
+                    heads,
+                    sectors,
+                    cylinders: (blocks / (heads as usize * sectors as usize))
+                        .min(u16::max_value() as usize) as u16,
+                    start: self.device.offset,
+                };
+                ioctl_write(data, geometry)?
+            }
+            _ => return Err(FsError::NotSupported),
+        }
+        Ok(0)
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,99 @@
+//! 设备文件系统，挂载在 `/dev`
+//!
+//! 把控制台、块设备等驱动以设备文件的形式提供给用户程序：
+//! - `/dev/console`、`/dev/tty`：控制台终端，见 [`TtyINode`]
+//! - `/dev/null`、`/dev/zero`、`/dev/random`、`/dev/urandom`
+//! - `/dev/vda`、`/dev/vdb`……：按照 [`DRIVERS`] 中的顺序，每个块设备一个节点，
+//!   磁盘上的分区为 `/dev/vda1`……
+//! - `/dev/vport0p1`……：virtio 控制台的数据端口，用作日志通道的 `log` 端口除外，见 [`PortINode`]
+
+mod block;
+mod null;
+mod port;
+mod random;
+mod tty;
+
+use super::*;
+use crate::drivers::block::block_devices;
+use crate::drivers::serial::virtio_console::{console_ports, GDB_PORT, LOG_PORT};
+use crate::kernel::{read_user, write_user};
+use rcore_fs_devfs::DevFS;
+
+pub use block::BlockINode;
+pub use null::{NullINode, ZeroINode};
+pub use port::PortINode;
+pub use random::RandomINode;
+pub use tty::{TtyINode, TTY};
+
+/// 构造设备文件的元数据
+///
+/// 设备号按照 Linux 中的约定：`rdev = (major << 8) | minor`
+fn device_metadata(type_: FileType, major: usize, minor: usize, size: usize) -> Metadata {
+    let zero = Timespec { sec: 0, nsec: 0 };
+    let rdev = (major << 8) | minor;
+    Metadata {
+        dev: 0,
+        inode: rdev,
+        size,
+        blk_size: 512,
+        blocks: (size + 511) / 512,
+        atime: zero,
+        mtime: zero,
+        ctime: zero,
+        type_,
+        mode: 0o666,
+        nlinks: 1,
+        uid: 0,
+        gid: 0,
+        rdev,
+    }
+}
+
+/// 读取 `ioctl` 参数指向的用户内存，无法访问时返回 [`FsError::IOCTLError`]，系统调用转换为 `EFAULT`
+fn ioctl_read<T: Copy>(data: usize) -> Result<T> {
+    read_user(data).map_err(|_| FsError::IOCTLError)
+}
+
+/// 写入 `ioctl` 参数指向的用户内存，无法访问时返回 [`FsError::IOCTLError`]
+fn ioctl_write<T: Copy>(data: usize, value: T) -> Result<()> {
+    write_user(data, value).map_err(|_| FsError::IOCTLError)
+}
+
+/// 创建设备文件系统并挂载到 `/dev`
+pub fn init() {
+    let devfs = DevFS::new();
+    devfs
+        .add("console", TTY.clone())
+        .expect("failed to add /dev/console");
+    devfs.add("tty", TTY.clone()).expect("failed to add /dev/tty");
+    devfs
+        .add("null", Arc::new(NullINode))
+        .expect("failed to add /dev/null");
+    devfs
+        .add("zero", Arc::new(ZeroINode))
+        .expect("failed to add /dev/zero");
+    devfs
+        .add("random", Arc::new(RandomINode::random()))
+        .expect("failed to add /dev/random");
+    devfs
+        .add("urandom", Arc::new(RandomINode::urandom()))
+        .expect("failed to add /dev/urandom");
+    for info in block_devices() {
+        let minor = info.minor();
+        devfs
+            .add(&info.name, Arc::new(BlockINode::new(info.device, minor)))
+            .expect("failed to add block device");
+    }
+    let ports = console_ports()
+        .into_iter()
+        .filter(|port| !matches!(port.name().as_deref(), Some(LOG_PORT) | Some(GDB_PORT)));
+    for (index, port) in ports.enumerate() {
+        devfs
+            .add(&port.device_name(), Arc::new(PortINode::new(port, index)))
+            .expect("failed to add console port");
+    }
+    // 只读的根文件系统中没有 /dev 时无法挂载
+    if let Err(error) = mount("/dev", devfs) {
+        warn!("failed to mount devfs on /dev: {:?}", error);
+    }
+}
@@ -0,0 +1,64 @@
+//! `/dev/null` 和 `/dev/zero`
+
+use super::*;
+
+/// `/dev/null`：读取总是得到文件结尾，写入的数据全部丢弃
+pub struct NullINode;
+
+/// `/dev/zero`：读取总是得到 0，写入的数据全部丢弃
+pub struct ZeroINode;
+
+impl INode for NullINode {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(device_metadata(FileType::CharDevice, 1, 3, 0))
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl INode for ZeroINode {
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        for byte in buf.iter_mut() {
+            *byte = 0;
+        }
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(device_metadata(FileType::CharDevice, 1, 5, 0))
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,67 @@
+//! virtio 控制台的数据端口 `/dev/vport*p*`
+
+use super::*;
+use crate::drivers::serial::virtio_console::ConsolePort;
+use crate::process::condvar::Condvar;
+
+/// 端口的主设备号，取自 Linux 留给本地使用的范围
+const PORT_MAJOR: usize = 240;
+
+/// virtio 控制台的一个端口
+///
+/// 没有收到数据时读取返回 [`FsError::Again`]，由调用者在 [`condvar`](Self::condvar) 上等待；
+/// 写入是同步的，主机一端没有连接时数据被丢弃
+pub struct PortINode {
+    port: Arc<ConsolePort>,
+    /// 次设备号，按照设备文件创建的顺序
+    minor: usize,
+}
+
+impl PortINode {
+    pub fn new(port: Arc<ConsolePort>, minor: usize) -> Self {
+        Self { port, minor }
+    }
+
+    /// 收到数据时唤醒的条件变量
+    pub fn condvar(&self) -> &Condvar {
+        self.port.condvar()
+    }
+}
+
+impl INode for PortINode {
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.port.read(buf) {
+            0 => Err(FsError::Again),
+            len => Ok(len),
+        }
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        self.port.write(buf);
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: self.port.readable(),
+            write: self.port.host_connected(),
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(device_metadata(
+            FileType::CharDevice,
+            PORT_MAJOR,
+            self.minor,
+            0,
+        ))
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
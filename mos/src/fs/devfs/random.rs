@@ -0,0 +1,52 @@
+//! 随机数设备 `/dev/random` 和 `/dev/urandom`
+
+use super::*;
+use crate::random;
+
+/// 随机数设备，两者都从内核熵池读取，区别只在设备号
+///
+/// 写入的数据会被混入熵池
+pub struct RandomINode {
+    /// 次设备号，`/dev/random` 为 8，`/dev/urandom` 为 9
+    minor: usize,
+}
+
+impl RandomINode {
+    /// `/dev/random`
+    pub fn random() -> Self {
+        Self { minor: 8 }
+    }
+
+    /// `/dev/urandom`
+    pub fn urandom() -> Self {
+        Self { minor: 9 }
+    }
+}
+
+impl INode for RandomINode {
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        random::fill(buf);
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        random::add_entropy(buf);
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(device_metadata(FileType::CharDevice, 1, self.minor, 0))
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,396 @@
+//! 控制台终端 `/dev/console`（同时也是 `/dev/tty`）和它的行规程
+//!
+//! 输入的字符经过行规程处理后才能被读取：
+//! - 规范模式下按行编辑，支持回显、退格、删除单词和整行，行首的 `Ctrl-D` 表示文件结尾
+//! - 非规范（raw）模式下字符直接可读
+//! - `Ctrl-C`、`Ctrl-\`、`Ctrl-Z` 向前台进程组发送信号
+//!
+//! 模式通过 `ioctl` 读写 `struct termios` 来切换。
+//! 输入来自 [`TtyINode::receive`]：串口中断经 PLIC 分发后由 `supervisor_external` 调用
+//! [`TtyINode::poll_input`] 取得输入，只有控制台不支持中断时才在每次时钟中断时轮询
+
+use super::*;
+use crate::console::{getchar, write_bytes};
+use crate::process::condvar::Condvar;
+use crate::process::process::ProcessID;
+use crate::process::signal::{SIGINT, SIGQUIT, SIGTSTP};
+use crate::PROCESSOR;
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use core::mem::take;
+
+const TCGETS: u32 = 0x5401;
+const TCSETS: u32 = 0x5402;
+const TCSETSW: u32 = 0x5403;
+const TCSETSF: u32 = 0x5404;
+const TIOCGPGRP: u32 = 0x540f;
+const TIOCSPGRP: u32 = 0x5410;
+const TIOCGWINSZ: u32 = 0x5413;
+
+// c_iflag
+const ICRNL: u32 = 0o400;
+// c_oflag
+const OPOST: u32 = 0o1;
+const ONLCR: u32 = 0o4;
+// c_cflag
+const B38400: u32 = 0o17;
+const CS8: u32 = 0o60;
+const CREAD: u32 = 0o200;
+// c_lflag
+const ISIG: u32 = 0o1;
+const ICANON: u32 = 0o2;
+const ECHO: u32 = 0o10;
+const ECHOE: u32 = 0o20;
+const ECHOK: u32 = 0o40;
+const ECHONL: u32 = 0o100;
+const ECHOCTL: u32 = 0o1000;
+const IEXTEN: u32 = 0o100000;
+
+// c_cc 的下标
+const VINTR: usize = 0;
+const VQUIT: usize = 1;
+const VERASE: usize = 2;
+const VKILL: usize = 3;
+const VEOF: usize = 4;
+const VMIN: usize = 6;
+const VSUSP: usize = 10;
+const VWERASE: usize = 14;
+const NCCS: usize = 19;
+
+/// `struct termios`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Termios {
+    iflag: u32,
+    oflag: u32,
+    cflag: u32,
+    lflag: u32,
+    line: u8,
+    cc: [u8; NCCS],
+}
+
+impl Default for Termios {
+    /// 和 Linux 终端的初始设置相同
+    fn default() -> Self {
+        let mut cc = [0; NCCS];
+        cc[VINTR] = 0x03;
+        cc[VQUIT] = 0x1c;
+        cc[VERASE] = 0x7f;
+        cc[VKILL] = 0x15;
+        cc[VEOF] = 0x04;
+        cc[VMIN] = 1;
+        cc[VSUSP] = 0x1a;
+        cc[VWERASE] = 0x17;
+        Self {
+            iflag: ICRNL,
+            oflag: OPOST | ONLCR,
+            cflag: B38400 | CS8 | CREAD,
+            lflag: ISIG | ICANON | ECHO | ECHOE | ECHOK | ECHOCTL | IEXTEN,
+            line: 0,
+            cc,
+        }
+    }
+}
+
+/// `struct winsize`，窗口大小是固定的
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct WinSize {
+    rows: u16,
+    cols: u16,
+    x_pixels: u16,
+    y_pixels: u16,
+}
+
+/// 行规程
+#[derive(Default)]
+struct LineDiscipline {
+    termios: Termios,
+    /// 可以被读取的输入，规范模式下每一项是完整的一行，空行表示文件结尾
+    ready: VecDeque<Vec<u8>>,
+    /// 规范模式下正在编辑的行
+    line: Vec<u8>,
+    /// 前台进程组，0 表示尚未设置
+    foreground: ProcessID,
+}
+
+impl LineDiscipline {
+    fn canonical(&self) -> bool {
+        self.termios.lflag & ICANON != 0
+    }
+
+    /// 经过输出处理后写到控制台
+    fn output(&self, bytes: &[u8]) {
+        if self.termios.oflag & (OPOST | ONLCR) != OPOST | ONLCR {
+            write_bytes(bytes);
+            return;
+        }
+        for &byte in bytes {
+            if byte == b'\n' {
+                write_bytes(b"\r\n");
+            } else {
+                write_bytes(&[byte]);
+            }
+        }
+    }
+
+    /// 回显输入的字符，控制字符显示为 `^C` 的形式
+    fn echo(&self, byte: u8) {
+        let lflag = self.termios.lflag;
+        if lflag & ECHO == 0 {
+            if byte == b'\n' && lflag & ECHONL != 0 {
+                self.output(b"\n");
+            }
+            return;
+        }
+        if byte < 0x20 && byte != b'\n' && byte != b'\t' && lflag & ECHOCTL != 0 {
+            self.output(&[b'^', byte + 0x40]);
+        } else {
+            self.output(&[byte]);
+        }
+    }
+
+    /// 删除正在编辑的行末尾的 `count` 个字符
+    fn erase(&mut self, count: usize) {
+        let count = count.min(self.line.len());
+        let remain = self.line.len() - count;
+        self.line.truncate(remain);
+        if self.termios.lflag & (ECHO | ECHOE) == ECHO | ECHOE {
+            for _ in 0..count {
+                self.output(b"\x08 \x08");
+            }
+        }
+    }
+
+    /// 处理一个输入的字符，需要向前台进程组发送信号时返回信号
+    fn receive(&mut self, mut byte: u8) -> Option<usize> {
+        let termios = self.termios;
+        if byte == b'\r' && termios.iflag & ICRNL != 0 {
+            byte = b'\n';
+        }
+        if termios.lflag & ISIG != 0 {
+            let signal = match byte {
+                b if b == termios.cc[VINTR] => Some(SIGINT),
+                b if b == termios.cc[VQUIT] => Some(SIGQUIT),
+                b if b == termios.cc[VSUSP] => Some(SIGTSTP),
+                _ => None,
+            };
+            if signal.is_some() {
+                // 丢弃还没有被读取的输入
+                self.echo(byte);
+                self.line.clear();
+                self.ready.clear();
+                return signal;
+            }
+        }
+        if !self.canonical() {
+            match self.ready.back_mut() {
+                Some(last) => last.push(byte),
+                None => self.ready.push_back(vec![byte]),
+            }
+            self.echo(byte);
+            return None;
+        }
+        match byte {
+            b if b == termios.cc[VERASE] || b == 0x08 => self.erase(1),
+            b if b == termios.cc[VWERASE] => {
+                let spaces = self.line.iter().rev().take_while(|c| **c == b' ').count();
+                let word = self
+                    .line
+                    .iter()
+                    .rev()
+                    .skip(spaces)
+                    .take_while(|c| **c != b' ')
+                    .count();
+                self.erase(spaces + word);
+            }
+            b if b == termios.cc[VKILL] => self.erase(self.line.len()),
+            b if b == termios.cc[VEOF] => {
+                let line = take(&mut self.line);
+                self.ready.push_back(line);
+            }
+            b'\n' => {
+                self.line.push(byte);
+                self.echo(byte);
+                let line = take(&mut self.line);
+                self.ready.push_back(line);
+            }
+            _ => {
+                self.line.push(byte);
+                self.echo(byte);
+            }
+        }
+        None
+    }
+
+    /// 读取输入，规范模式下最多读取一行
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        while count < buf.len() {
+            let mut entry = match self.ready.pop_front() {
+                Some(entry) => entry,
+                None => break,
+            };
+            let len = entry.len().min(buf.len() - count);
+            buf[count..count + len].copy_from_slice(&entry[..len]);
+            count += len;
+            if len < entry.len() {
+                self.ready.push_front(entry.split_off(len));
+            }
+            if self.canonical() {
+                break;
+            }
+        }
+        count
+    }
+
+    /// 修改设置，离开规范模式时正在编辑的行立即可读
+    fn set_termios(&mut self, termios: Termios) {
+        if self.canonical() && termios.lflag & ICANON == 0 && !self.line.is_empty() {
+            let line = take(&mut self.line);
+            self.ready.push_back(line);
+        }
+        self.termios = termios;
+    }
+}
+
+lazy_static! {
+    /// 控制台终端
+    pub static ref TTY: Arc<TtyINode> = Arc::new(TtyINode::default());
+}
+
+/// 终端设备
+#[derive(Default)]
+pub struct TtyINode {
+    inner: Mutex<LineDiscipline>,
+    /// 有输入可读时唤醒读取的线程
+    condvar: Condvar,
+}
+
+impl TtyINode {
+    /// 有输入可读时被唤醒的条件变量
+    pub fn condvar(&self) -> &Condvar {
+        &self.condvar
+    }
+
+    /// 收到输入的字符
+    ///
+    /// 唤醒线程和发送信号时不持有锁
+    pub fn receive(&self, bytes: &[u8]) {
+        let mut signals = Vec::new();
+        let (readable, foreground) = {
+            let mut inner = self.inner.lock();
+            for &byte in bytes {
+                signals.extend(inner.receive(byte));
+            }
+            (!inner.ready.is_empty(), inner.foreground)
+        };
+        if readable {
+            self.condvar.notify_all();
+        }
+        if foreground != 0 {
+            for signal in signals {
+                crate::kernel::signal_group(foreground, signal);
+            }
+        }
+    }
+
+    /// 轮询控制台，处理所有已经输入的字符
+    pub fn poll_input(&self) {
+        let mut bytes = Vec::new();
+        while let Some(byte) = getchar() {
+            bytes.push(byte);
+        }
+        if !bytes.is_empty() {
+            self.receive(&bytes);
+        }
+    }
+}
+
+impl INode for TtyINode {
+    /// 读取经过行规程处理的输入，没有输入时返回 [`FsError::Again`]
+    ///
+    /// 前台进程组尚未设置时，第一个读取的进程所在的进程组成为前台进程组，
+    /// 类似于 Linux 中会话首进程获得控制终端
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let pgid = PROCESSOR.lock().current_thread().process.inner().pgid;
+        let mut inner = self.inner.lock();
+        if inner.foreground == 0 {
+            inner.foreground = pgid;
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if inner.ready.is_empty() {
+            return Err(FsError::Again);
+        }
+        Ok(inner.read(buf))
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        self.inner.lock().output(buf);
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: !self.inner.lock().ready.is_empty(),
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(device_metadata(FileType::CharDevice, 5, 1, 0))
+    }
+
+    /// 读写 `termios`、前台进程组和窗口大小，`data` 指向用户内存
+    ///
+    /// 访问用户内存时不持有终端的锁
+    fn io_control(&self, cmd: u32, data: usize) -> Result<usize> {
+        match cmd {
+            TCGETS => {
+                let termios = self.inner.lock().termios;
+                ioctl_write(data, termios)?;
+            }
+            TCSETS | TCSETSW | TCSETSF => {
+                let termios = ioctl_read::<Termios>(data)?;
+                let readable = {
+                    let mut inner = self.inner.lock();
+                    if cmd == TCSETSF {
+                        inner.ready.clear();
+                        inner.line.clear();
+                    }
+                    inner.set_termios(termios);
+                    !inner.ready.is_empty()
+                };
+                // 切换到非规范模式后可能有输入可读
+                if readable {
+                    self.condvar.notify_all();
+                }
+            }
+            TIOCGPGRP => {
+                let foreground = self.inner.lock().foreground;
+                ioctl_write(data, foreground as i32)?;
+            }
+            TIOCSPGRP => {
+                let pgid = ioctl_read::<i32>(data)?;
+                self.inner.lock().foreground = pgid as ProcessID;
+            }
+            TIOCGWINSZ => ioctl_write(
+                data,
+                WinSize {
+                    rows: 24,
+                    cols: 80,
+                    x_pixels: 0,
+                    y_pixels: 0,
+                },
+            )?,
+            _ => return Err(FsError::NotSupported),
+        }
+        Ok(0)
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,234 @@
+//! epoll 实例 [`Epoll`]
+//!
+//! 每个被监听的文件都订阅了自己的条件变量，状态变化时把对应的文件描述符放入就绪集合，
+//! 等待时只需检查就绪集合中的文件，而不必扫描全部。
+//! 停止监听或关闭文件描述符时取消订阅
+
+use super::*;
+use crate::process::condvar::{Condvar, Observer};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::sync::Weak;
+
+pub const EPOLLIN: u32 = 0x001;
+pub const EPOLLOUT: u32 = 0x004;
+pub const EPOLLERR: u32 = 0x008;
+pub const EPOLLHUP: u32 = 0x010;
+/// 报告一次之后停止监听，直到被 `EPOLL_CTL_MOD` 重新设置
+pub const EPOLLONESHOT: u32 = 1 << 30;
+/// 边沿触发：报告一次之后，直到文件状态再次变化才会重新报告
+pub const EPOLLET: u32 = 1 << 31;
+
+/// 被监听的文件
+struct Interest {
+    /// 不持有文件，文件的所有描述符关闭后自动停止监听
+    handle: Weak<FileHandle>,
+    events: u32,
+    data: u64,
+    /// 在文件的条件变量上的订阅，键为文件描述符，随监听一起释放
+    subscription: Arc<dyn Observer>,
+}
+
+impl Interest {
+    /// 从文件的条件变量上取消订阅，文件已经释放时条件变量中的弱引用也随之失效
+    fn unsubscribe(&self) {
+        if let Some(handle) = self.handle.upgrade() {
+            if let Some(condvar) = handle.condvar() {
+                condvar.unsubscribe(&self.subscription);
+            }
+        }
+    }
+}
+
+/// 可能就绪的文件描述符
+struct Ready {
+    fds: Mutex<BTreeSet<usize>>,
+    /// 有文件可能就绪时唤醒 `epoll_wait` 的线程
+    condvar: Condvar,
+}
+
+/// 一个被监听的文件上的订阅者，接收文件的通知
+struct Subscription {
+    ready: Weak<Ready>,
+}
+
+impl Observer for Subscription {
+    fn notify(&self, fd: usize) {
+        if let Some(ready) = self.ready.upgrade() {
+            ready.fds.lock().insert(fd);
+            ready.condvar.notify_all();
+        }
+    }
+}
+
+/// epoll 实例
+pub struct Epoll {
+    interests: Mutex<BTreeMap<usize, Interest>>,
+    ready: Arc<Ready>,
+}
+
+/// 文件状态对应的事件
+fn poll_events(handle: &FileHandle) -> u32 {
+    let events = match handle.inode.poll() {
+        Ok(status) => {
+            let mut events = 0;
+            if status.read {
+                events |= EPOLLIN;
+            }
+            if status.write {
+                events |= EPOLLOUT;
+            }
+            if status.error {
+                events |= EPOLLERR;
+            }
+            events
+        }
+        Err(_) => EPOLLERR,
+    };
+    if handle.is_hung_up() {
+        events | EPOLLHUP
+    } else {
+        events
+    }
+}
+
+impl Epoll {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            interests: Mutex::new(BTreeMap::new()),
+            ready: Arc::new(Ready {
+                fds: Mutex::new(BTreeSet::new()),
+                condvar: Condvar::default(),
+            }),
+        })
+    }
+
+    /// 有文件可能就绪时被唤醒的条件变量
+    pub fn condvar(&self) -> &Condvar {
+        &self.ready.condvar
+    }
+
+    /// 开始监听文件描述符 `fd`
+    ///
+    /// 文件需要支持等待（见 [`FileHandle::condvar`]），不支持嵌套的 epoll 实例
+    pub fn add(&self, fd: usize, handle: &Arc<FileHandle>, events: u32, data: u64) -> Result<()> {
+        if handle.inode.downcast_ref::<Epoll>().is_some() {
+            return Err(FsError::InvalidParam);
+        }
+        let condvar = handle.condvar().ok_or(FsError::NotSupported)?;
+        let mut interests = self.interests.lock();
+        if interests.contains_key(&fd) {
+            return Err(FsError::EntryExist);
+        }
+        let subscription: Arc<dyn Observer> = Arc::new(Subscription {
+            ready: Arc::downgrade(&self.ready),
+        });
+        condvar.subscribe(Arc::downgrade(&subscription), fd);
+        interests.insert(
+            fd,
+            Interest {
+                handle: Arc::downgrade(handle),
+                events,
+                data,
+                subscription,
+            },
+        );
+        // 加入时可能已经就绪
+        self.ready.fds.lock().insert(fd);
+        Ok(())
+    }
+
+    /// 修改监听的事件
+    pub fn modify(&self, fd: usize, events: u32, data: u64) -> Result<()> {
+        let mut interests = self.interests.lock();
+        let interest = interests.get_mut(&fd).ok_or(FsError::EntryNotFound)?;
+        interest.events = events;
+        interest.data = data;
+        self.ready.fds.lock().insert(fd);
+        Ok(())
+    }
+
+    /// 停止监听，并从文件的条件变量上取消订阅
+    pub fn remove(&self, fd: usize) -> Result<()> {
+        let interest = self
+            .interests
+            .lock()
+            .remove(&fd)
+            .ok_or(FsError::EntryNotFound)?;
+        interest.unsubscribe();
+        self.ready.fds.lock().remove(&fd);
+        Ok(())
+    }
+
+    /// 文件描述符 `fd` 被关闭，如果监听的正是 `handle` 则停止监听
+    pub fn closed(&self, fd: usize, handle: &Arc<FileHandle>) {
+        let mut interests = self.interests.lock();
+        let watched = match interests.get(&fd) {
+            Some(interest) => interest.handle.upgrade(),
+            None => return,
+        };
+        // 监听的文件已经释放时同样移除
+        if watched.map_or(true, |watched| Arc::ptr_eq(&watched, handle)) {
+            if let Some(interest) = interests.remove(&fd) {
+                interest.unsubscribe();
+            }
+            self.ready.fds.lock().remove(&fd);
+        }
+    }
+
+    /// 检查就绪集合中的文件，返回最多 `max` 个就绪的事件和对应的 `data`
+    ///
+    /// 水平触发的文件在报告后仍留在就绪集合中，下次会被重新检查
+    pub fn collect(&self, max: usize) -> Vec<(u32, u64)> {
+        let mut interests = self.interests.lock();
+        let candidates: Vec<usize> = self.ready.fds.lock().iter().cloned().collect();
+        let mut result = Vec::new();
+        for fd in candidates {
+            if result.len() >= max {
+                break;
+            }
+            let mut keep = false;
+            if let Some(interest) = interests.get_mut(&fd) {
+                if let Some(handle) = interest.handle.upgrade() {
+                    // 错误和挂起总是会被报告
+                    let events = poll_events(&handle) & (interest.events | EPOLLERR | EPOLLHUP);
+                    if events != 0 {
+                        result.push((events, interest.data));
+                        keep = interest.events & (EPOLLET | EPOLLONESHOT) == 0;
+                        if interest.events & EPOLLONESHOT != 0 {
+                            interest.events = 0;
+                        }
+                    }
+                } else {
+                    interests.remove(&fd);
+                }
+            }
+            if !keep {
+                self.ready.fds.lock().remove(&fd);
+            }
+        }
+        result
+    }
+}
+
+impl INode for Epoll {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize> {
+        Err(FsError::InvalidParam)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        Err(FsError::InvalidParam)
+    }
+
+    /// 就绪集合不为空时可读，其中的文件不一定真的就绪
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: !self.ready.fds.lock().is_empty(),
+            write: false,
+            error: false,
+        })
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,161 @@
+//! 打开的文件 [`FileHandle`]
+
+use super::*;
+use crate::clock;
+use crate::net::Socket;
+use crate::process::condvar::Condvar;
+use alloc::string::String;
+
+/// 访问时间距今超过这么多秒时，即使晚于修改时间也会更新
+const ATIME_INTERVAL: i64 = 24 * 60 * 60;
+
+/// 打开文件时的选项
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenOptions {
+    pub read: bool,
+    pub write: bool,
+    /// 每次写入前移动到文件末尾
+    pub append: bool,
+    /// 读写不会阻塞，没有数据时返回 [`FsError::Again`]
+    pub nonblock: bool,
+}
+
+/// 进程中打开的一个文件
+///
+/// 文件描述符表中保存的是它的 `Arc`，`fork` 和 `dup` 得到的描述符共享读写位置
+pub struct FileHandle {
+    /// 文件
+    pub inode: Arc<dyn INode>,
+    /// 打开时的选项
+    pub options: OpenOptions,
+    /// 打开时使用的路径，只用于显示
+    pub path: String,
+    /// 读写位置，对于目录则是下一个要读取的目录项的序号
+    offset: Mutex<usize>,
+}
+
+impl FileHandle {
+    pub fn new(inode: Arc<dyn INode>, options: OpenOptions, path: String) -> Arc<Self> {
+        Arc::new(Self {
+            inode,
+            options,
+            path,
+            offset: Mutex::new(0),
+        })
+    }
+
+    /// 从当前位置读取
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        if !self.options.read {
+            return Err(FsError::InvalidParam);
+        }
+        let mut offset = self.offset.lock();
+        let len = self.inode.read_at(*offset, buf)?;
+        *offset += len;
+        if len > 0 {
+            self.accessed();
+        }
+        Ok(len)
+    }
+
+    /// 写入到当前位置
+    pub fn write(&self, buf: &[u8]) -> Result<usize> {
+        if !self.options.write {
+            return Err(FsError::InvalidParam);
+        }
+        let mut offset = self.offset.lock();
+        if self.options.append {
+            *offset = self.inode.metadata()?.size;
+        }
+        let len = self.inode.write_at(*offset, buf)?;
+        *offset += len;
+        if len > 0 {
+            self.modified();
+        }
+        Ok(len)
+    }
+
+    /// 读取普通文件之后更新访问时间
+    ///
+    /// 和 Linux 默认的 `relatime` 一样，只在访问时间不晚于修改时间或者已经过去一天时更新，
+    /// 以免每次读取都要写回磁盘
+    fn accessed(&self) {
+        let mut metadata = match self.inode.metadata() {
+            Ok(metadata) if metadata.type_ == FileType::File => metadata,
+            _ => return,
+        };
+        let now = clock::realtime();
+        let atime = (metadata.atime.sec, metadata.atime.nsec);
+        let mtime = (metadata.mtime.sec, metadata.mtime.nsec);
+        if atime <= mtime || now.sec - metadata.atime.sec >= ATIME_INTERVAL {
+            metadata.atime = now;
+            self.inode.set_metadata(&metadata).ok();
+        }
+    }
+
+    /// 写入普通文件之后更新修改时间和状态改变时间
+    fn modified(&self) {
+        let mut metadata = match self.inode.metadata() {
+            Ok(metadata) if metadata.type_ == FileType::File => metadata,
+            _ => return,
+        };
+        let now = clock::realtime();
+        metadata.mtime = now;
+        metadata.ctime = now;
+        self.inode.set_metadata(&metadata).ok();
+    }
+
+    /// 从当前位置依次读取目录项，将序号、名字和元数据交给 `accept` 处理
+    ///
+    /// `accept` 返回 `false` 时（例如用户的缓冲区已满）停止，这一项下次会被重新读取
+    pub fn read_entries(
+        &self,
+        mut accept: impl FnMut(usize, &str, &Metadata) -> bool,
+    ) -> Result<()> {
+        let mut offset = self.offset.lock();
+        loop {
+            let name = match self.inode.get_entry(*offset) {
+                Ok(name) => name,
+                Err(FsError::EntryNotFound) => return Ok(()),
+                Err(error) => return Err(error),
+            };
+            let metadata = self.inode.find(&name)?.metadata()?;
+            if !accept(*offset, &name, &metadata) {
+                return Ok(());
+            }
+            *offset += 1;
+        }
+    }
+
+    /// 连接的另一端已经关闭，对应 `POLLHUP`
+    ///
+    /// [`PollStatus`] 来自 `rcore_fs`，没有表示挂起的字段，所以单独检查
+    pub fn is_hung_up(&self) -> bool {
+        if let Some(pipe) = self.inode.downcast_ref::<Pipe>() {
+            pipe.is_hung_up()
+        } else if let Some(socket) = self.inode.downcast_ref::<Socket>() {
+            socket.is_hung_up()
+        } else {
+            false
+        }
+    }
+
+    /// 文件状态变化时会被唤醒的条件变量，不支持等待的文件返回 `None`
+    ///
+    /// 普通文件和设备总是就绪的，不需要等待
+    pub fn condvar(&self) -> Option<&Condvar> {
+        if let Some(pipe) = self.inode.downcast_ref::<Pipe>() {
+            Some(pipe.condvar())
+        } else if let Some(epoll) = self.inode.downcast_ref::<Epoll>() {
+            Some(epoll.condvar())
+        } else if let Some(tty) = self.inode.downcast_ref::<TtyINode>() {
+            Some(tty.condvar())
+        } else if let Some(socket) = self.inode.downcast_ref::<Socket>() {
+            Some(socket.condvar())
+        } else if let Some(port) = self.inode.downcast_ref::<PortINode>() {
+            Some(port.condvar())
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! initramfs
+//!
+//! cpio newc 格式的归档，可以由 bootloader 通过设备树传入，
+//! 也可以在编译时启用 `embedded-initramfs` 特性，将环境变量 `MOS_INITRAMFS` 指向的文件嵌入内核
+
+use crate::drivers::device_tree::INITRD;
+use crate::memory::VirtualAddress;
+use core::slice;
+
+/// 取得 initramfs 归档，优先使用 bootloader 传入的 initrd
+pub fn archive() -> Option<&'static [u8]> {
+    if let Some((start, end)) = *INITRD.read() {
+        let va = VirtualAddress::from(start);
+        return Some(unsafe { slice::from_raw_parts(va.0 as *const u8, end.0 - start.0) });
+    }
+    embedded()
+}
+
+/// 编译时嵌入内核的归档
+#[cfg(feature = "embedded-initramfs")]
+fn embedded() -> Option<&'static [u8]> {
+    Some(include_bytes!(env!("MOS_INITRAMFS")))
+}
+
+/// 编译时嵌入内核的归档
+#[cfg(not(feature = "embedded-initramfs"))]
+fn embedded() -> Option<&'static [u8]> {
+    None
+}
@@ -1,6 +1,7 @@
 //! 为 [`INode`] 实现 trait [`INodeExt`] 以扩展功能
 
 use super::*;
+use crate::clock;
 
 /// 为 [`INode`] 类型添加的扩展功能
 pub trait INodeExt {
@@ -9,6 +10,9 @@ pub trait INodeExt {
 
     /// 读取文件内容
     fn readall(&self) -> Result<Vec<u8>>;
+
+    /// 把访问、修改和状态改变时间都设为当前时间，文件系统不支持修改时间时忽略
+    fn touch(&self);
 }
 
 impl INodeExt for dyn INode {
@@ -29,4 +33,14 @@ impl INodeExt for dyn INode {
         self.read_at(0, buffer.as_mut_slice())?;
         Ok(buffer)
     }
+
+    fn touch(&self) {
+        if let Ok(mut metadata) = self.metadata() {
+            let now = clock::realtime();
+            metadata.atime = now;
+            metadata.mtime = now;
+            metadata.ctime = now;
+            self.set_metadata(&metadata).ok();
+        }
+    }
 }
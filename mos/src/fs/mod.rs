@@ -1,45 +1,185 @@
 //! 文件系统
 //!
-//! 将读取第一个块设备作为根文件系统
+//! 根文件系统（FAT32、SFS 或只读的 ext2）所在的块设备由命令行中的 `root=` 参数指定，
+//! 例如 `root=vda2`、`root=/dev/vda2`、`root=PARTUUID=<uuid>` 或 `root=PARTLABEL=<label>`；
+//! 没有这个参数时使用第一个磁盘，磁盘上有分区表时使用它的第一个分区。
+//! 没有块设备时使用内存文件系统，并将 initramfs（如果有）解压到其中。
+//! 根文件系统外面包了一层 [`MountFS`]，其他文件系统（例如 `/dev`）可以挂载在它的目录上，
+//! 其余块设备上的 ext2 镜像会只读挂载到 `/mnt` 下。
+//! 只读的根文件系统上无法创建目录，`/dev` 和 `/proc` 只能挂载在已有的目录上，
+//! `/mnt` 上则会先挂载一个内存文件系统来放置挂载点
 
 use crate::drivers::{
-    block::BlockDevice,
-    driver::{DeviceType, DRIVERS},
+    block::{block_devices, BlockDevice, BlockDeviceInfo},
+    device_tree::bootarg,
 };
 
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{format, sync::Arc, vec::Vec};
 use core::any::Any;
+use filesystem::{cpio, Ext2FS, Fat32FS, TmpFS};
 use lazy_static::lazy_static;
+use rcore_fs::dev::Device;
+use rcore_fs_mountfs::{MNode, MountFS};
 use rcore_fs_sfs::SimpleFileSystem;
 use spin::Mutex;
 
 mod config;
+mod devfs;
+mod epoll;
+mod file;
+mod initramfs;
 mod inode_ext;
+mod pipe;
+mod procfs;
 
 pub use config::*;
+pub use devfs::{PortINode, TtyINode, TTY};
+pub use epoll::*;
+pub use file::{FileHandle, OpenOptions};
 pub use inode_ext::INodeExt;
+pub use pipe::Pipe;
 pub use rcore_fs::{dev::block_cache::BlockCache, vfs::*};
 
 lazy_static! {
     /// 根文件系统的根目录的 INode
-    pub static ref ROOT_INODE: Arc<dyn INode> = {
-        // 选择第一个块设备
-        for driver in DRIVERS.read().iter() {
-            if driver.device_type() == DeviceType::Block {
-                let device = BlockDevice(driver.clone());
-                // 动态分配一段内存空间作为设备 Cache
-                let device_with_cache = Arc::new(BlockCache::new(device, BLOCK_CACHE_CAPACITY));
-                return SimpleFileSystem::open(device_with_cache)
-                    .expect("failed to open SFS")
-                    .root_inode();
+    pub static ref ROOT_INODE: Arc<dyn INode> = MountFS::new(root_fs()).root_inode();
+    /// 所有块设备的缓存
+    static ref BLOCK_CACHES: Mutex<Vec<Arc<BlockCache<BlockDevice>>>> = Mutex::new(Vec::new());
+}
+
+/// 为块设备加上缓存，登记在 [`static@BLOCK_CACHES`] 中以便关机前写回
+fn cached(device: BlockDevice) -> Arc<BlockCache<BlockDevice>> {
+    let cache = Arc::new(BlockCache::new(device, BLOCK_CACHE_CAPACITY));
+    BLOCK_CACHES.lock().push(cache.clone());
+    cache
+}
+
+/// 写回所有文件系统和块设备缓存中的数据，关机或重启之前调用
+///
+/// 文件系统或某个缓存写回失败时仍然写回其余的缓存，返回遇到的第一个错误
+pub fn sync() -> Result<()> {
+    let mut result = ROOT_INODE.fs().sync();
+    for cache in BLOCK_CACHES.lock().iter() {
+        let synced = cache.sync();
+        if result.is_ok() {
+            result = synced;
+        }
+    }
+    result
+}
+
+/// 根文件系统所在的块设备，找不到 `root=` 指定的设备时返回 `None`
+fn root_device() -> Option<BlockDeviceInfo> {
+    let devices = block_devices();
+    match bootarg("root") {
+        Some(spec) => devices.into_iter().find(|info| info.matches(&spec)),
+        None => {
+            // 第一个磁盘之后紧跟着它的分区
+            let mut disk = devices.into_iter().take_while(|info| info.disk == 0);
+            let whole = disk.next()?;
+            Some(disk.next().unwrap_or(whole))
+        }
+    }
+}
+
+/// 选择根文件系统
+fn root_fs() -> Arc<dyn FileSystem> {
+    if let Some(info) = root_device() {
+        info!("using {} as root", info.name);
+        // 动态分配一段内存空间作为设备 Cache
+        let device_with_cache = cached(info.device);
+        // 根据引导扇区和超级块判断文件系统类型，都不是时当作 SFS
+        if Fat32FS::probe(&*device_with_cache) {
+            return Fat32FS::open(device_with_cache).expect("failed to open FAT32");
+        }
+        if Ext2FS::probe(&*device_with_cache) {
+            return Ext2FS::open(device_with_cache).expect("failed to open ext2");
+        }
+        return SimpleFileSystem::open(device_with_cache).expect("failed to open SFS");
+    }
+    if let Some(spec) = bootarg("root") {
+        warn!("root device {} not found", spec);
+    }
+    // 没有块设备，退而使用内存文件系统，有 initramfs 时将其解压到其中
+    match initramfs::archive() {
+        Some(archive) => {
+            info!("no root device found, unpacking initramfs ({} bytes) as root", archive.len());
+            let tmpfs = TmpFS::new(TMPFS_CAPACITY + archive.len());
+            cpio::unpack(archive, &tmpfs.root_inode()).expect("failed to unpack initramfs");
+            tmpfs
+        }
+        None => {
+            info!("no root device found, using tmpfs as root");
+            TmpFS::new(TMPFS_CAPACITY)
+        }
+    }
+}
+
+/// 将文件系统挂载到 `path` 目录上，路径中不存在的目录会先创建
+///
+/// 目录所在的文件系统只读时返回 [`FsError::NotSupported`]
+pub fn mount(path: &str, fs: Arc<dyn FileSystem>) -> Result<()> {
+    let mut dir = ROOT_INODE.clone();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        dir = match dir.find(name) {
+            Err(FsError::EntryNotFound) => dir.create(name, FileType::Dir, 0o755)?,
+            result => result?,
+        };
+    }
+    dir.downcast_ref::<MNode>()
+        .ok_or(FsError::NotSupported)?
+        .mount(fs)?;
+    Ok(())
+}
+
+/// 将文件系统挂载到 `/mnt/<name>`
+///
+/// 根文件系统只读而无法创建挂载点时，先在 `/mnt` 上挂载内存文件系统
+fn mount_mnt(name: &str, fs: Arc<dyn FileSystem>) -> Result<()> {
+    let path = format!("/mnt/{}", name);
+    match mount(&path, fs.clone()) {
+        Err(FsError::NotSupported) => {
+            mount("/mnt", TmpFS::new(TMPFS_CAPACITY))?;
+            mount(&path, fs)
+        }
+        result => result,
+    }
+}
+
+/// 两个块设备是否重叠：同一个磁盘上的同一个分区，或者其中之一是整个磁盘
+fn overlaps(a: &BlockDeviceInfo, b: &BlockDeviceInfo) -> bool {
+    a.disk == b.disk && (a.partition == b.partition || a.partition == 0 || b.partition == 0)
+}
+
+/// 将根文件系统之外的 ext2 文件系统只读挂载到 `/mnt/<设备名>`
+///
+/// 和根文件系统重叠的设备不会被挂载，例如根文件系统所在磁盘的其他分区（根为整个磁盘时）
+fn mount_block_devices() {
+    let root = root_device();
+    for info in block_devices() {
+        if root.as_ref().map_or(false, |root| overlaps(root, &info)) {
+            continue;
+        }
+        // 直接在设备上检查，只为挂载成功的设备登记缓存
+        if !Ext2FS::probe(&info.device) {
+            continue;
+        }
+        let device = Arc::new(BlockCache::new(info.device, BLOCK_CACHE_CAPACITY));
+        match Ext2FS::open(device.clone()).and_then(|fs| mount_mnt(&info.name, fs)) {
+            Ok(()) => {
+                BLOCK_CACHES.lock().push(device);
+                info!("mounted ext2 on /mnt/{}", info.name);
             }
+            Err(error) => warn!("failed to mount /mnt/{}: {:?}", info.name, error),
         }
-        panic!("failed to load fs")
-    };
+    }
 }
 
-/// 触发 [`static@ROOT_INODE`] 的初始化并打印根目录内容
+/// 触发 [`static@ROOT_INODE`] 的初始化，挂载 `/dev`、`/proc` 和其他块设备并打印根目录内容
 pub fn init() {
+    devfs::init();
+    procfs::init();
+    mount_block_devices();
     ROOT_INODE.ls();
-    println!("mod fs initialized");
+    info!("mod fs initialized");
 }
@@ -0,0 +1,200 @@
+//! 匿名管道 [`Pipe`]
+//!
+//! 管道的两端各是一个 [`INode`]，共享一个有界的环形缓冲区。
+//! 读写不会在这里阻塞：缓冲区为空或已满时返回 [`FsError::Again`]，
+//! 由系统调用在 [`Pipe::condvar`] 上等待，被唤醒后重新读写
+
+use super::*;
+use crate::process::condvar::Condvar;
+use alloc::vec;
+use core::cmp::min;
+
+/// 环形缓冲区及两端的状态
+struct RingBuffer {
+    data: Vec<u8>,
+    /// 第一个未读字节的位置
+    head: usize,
+    /// 未读的字节数
+    len: usize,
+    /// 读端已关闭
+    read_closed: bool,
+    /// 写端已关闭
+    write_closed: bool,
+}
+
+/// 两端共享的部分
+struct Shared {
+    buffer: Mutex<RingBuffer>,
+    /// 等待数据或写端关闭的读者
+    readable: Condvar,
+    /// 等待空间或读端关闭的写者
+    writable: Condvar,
+}
+
+/// 管道的一端
+pub struct Pipe {
+    shared: Arc<Shared>,
+    /// 是否为写端
+    write: bool,
+}
+
+impl Pipe {
+    /// 创建一个管道，返回读端和写端
+    pub fn new() -> (Arc<dyn INode>, Arc<dyn INode>) {
+        let shared = Arc::new(Shared {
+            buffer: Mutex::new(RingBuffer {
+                data: vec![0; PIPE_CAPACITY],
+                head: 0,
+                len: 0,
+                read_closed: false,
+                write_closed: false,
+            }),
+            readable: Condvar::default(),
+            writable: Condvar::default(),
+        });
+        let read = Arc::new(Self {
+            shared: shared.clone(),
+            write: false,
+        });
+        let write = Arc::new(Self {
+            shared,
+            write: true,
+        });
+        (read, write)
+    }
+
+    /// 读写返回 [`FsError::Again`] 时等待的条件变量
+    pub fn condvar(&self) -> &Condvar {
+        if self.write {
+            &self.shared.writable
+        } else {
+            &self.shared.readable
+        }
+    }
+
+    /// 另一端已经全部关闭
+    pub fn is_broken(&self) -> bool {
+        let buffer = self.shared.buffer.lock();
+        if self.write {
+            buffer.read_closed
+        } else {
+            buffer.write_closed
+        }
+    }
+
+    /// 读端的写端已经全部关闭，写端在读端关闭时只报告错误
+    pub fn is_hung_up(&self) -> bool {
+        !self.write && self.is_broken()
+    }
+}
+
+impl INode for Pipe {
+    /// 读取缓冲区中的数据，写端关闭且没有数据时返回 0
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if self.write {
+            return Err(FsError::InvalidParam);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut buffer = self.shared.buffer.lock();
+        if buffer.len == 0 {
+            return if buffer.write_closed {
+                Ok(0)
+            } else {
+                Err(FsError::Again)
+            };
+        }
+        let len = min(buf.len(), buffer.len);
+        for byte in buf[..len].iter_mut() {
+            *byte = buffer.data[buffer.head];
+            buffer.head = (buffer.head + 1) % PIPE_CAPACITY;
+        }
+        buffer.len -= len;
+        drop(buffer);
+        self.shared.writable.notify_all();
+        Ok(len)
+    }
+
+    /// 写入缓冲区，读端已关闭时返回 [`FsError::NoDevice`]
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        if !self.write {
+            return Err(FsError::InvalidParam);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut buffer = self.shared.buffer.lock();
+        if buffer.read_closed {
+            return Err(FsError::NoDevice);
+        }
+        let len = min(buf.len(), PIPE_CAPACITY - buffer.len);
+        if len == 0 {
+            return Err(FsError::Again);
+        }
+        for &byte in buf[..len].iter() {
+            let tail = (buffer.head + buffer.len) % PIPE_CAPACITY;
+            buffer.data[tail] = byte;
+            buffer.len += 1;
+        }
+        drop(buffer);
+        self.shared.readable.notify_all();
+        Ok(len)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        let buffer = self.shared.buffer.lock();
+        Ok(if self.write {
+            PollStatus {
+                read: false,
+                write: buffer.len < PIPE_CAPACITY || buffer.read_closed,
+                error: buffer.read_closed,
+            }
+        } else {
+            PollStatus {
+                read: buffer.len > 0 || buffer.write_closed,
+                write: false,
+                error: false,
+            }
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        let zero = Timespec { sec: 0, nsec: 0 };
+        Ok(Metadata {
+            dev: 0,
+            inode: Arc::as_ptr(&self.shared) as *const u8 as usize,
+            size: self.shared.buffer.lock().len,
+            blk_size: PIPE_CAPACITY,
+            blocks: 0,
+            atime: zero,
+            mtime: zero,
+            ctime: zero,
+            type_: FileType::NamedPipe,
+            mode: 0o600,
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+        })
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// 一端的所有文件描述符都关闭后，唤醒另一端等待的线程
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut buffer = self.shared.buffer.lock();
+        if self.write {
+            buffer.write_closed = true;
+        } else {
+            buffer.read_closed = true;
+        }
+        drop(buffer);
+        self.shared.readable.notify_all();
+        self.shared.writable.notify_all();
+    }
+}
@@ -0,0 +1,196 @@
+//! 生成 procfs 中各个文件的内容
+
+use super::*;
+use crate::drivers::block::{block_devices, VIRTIO_BLK_MAJOR};
+use crate::interrupt::ticks;
+use crate::logging;
+use crate::memory::{frame::FRAME_ALLOCATOR, heap, Flags, PAGE_SIZE};
+use crate::process::thread::Thread;
+use alloc::format;
+use core::fmt::Write;
+
+/// `/proc/meminfo`：物理帧和内核堆的使用情况
+pub fn meminfo() -> String {
+    let (total, allocated) = {
+        let allocator = FRAME_ALLOCATOR.lock();
+        (allocator.total(), allocator.allocated())
+    };
+    let (heap_total, heap_actual, heap_user) = heap::stats();
+    let mut content = String::new();
+    writeln!(content, "FrameTotal:\t{} kB", total * PAGE_SIZE / 1024).unwrap();
+    writeln!(content, "FrameUsed:\t{} kB", allocated * PAGE_SIZE / 1024).unwrap();
+    writeln!(content, "FrameFree:\t{} kB", (total - allocated) * PAGE_SIZE / 1024).unwrap();
+    writeln!(content, "HeapTotal:\t{} B", heap_total).unwrap();
+    writeln!(content, "HeapAllocated:\t{} B", heap_actual).unwrap();
+    writeln!(content, "HeapRequested:\t{} B", heap_user).unwrap();
+    content
+}
+
+/// `/proc/uptime`：时钟中断次数
+pub fn uptime() -> String {
+    format!("{} ticks\n", ticks())
+}
+
+/// `/proc/diskstats`：每个磁盘的读写统计，单位为块
+pub fn diskstats() -> String {
+    let mut content = String::from("name\treads\twrites\terrors\n");
+    for info in block_devices().iter().filter(|info| info.partition == 0) {
+        let statistics = info.device.driver.statistics();
+        writeln!(
+            content,
+            "{}\t{}\t{}\t{}",
+            info.name, statistics.reads, statistics.writes, statistics.errors
+        )
+        .unwrap();
+    }
+    content
+}
+
+/// `/proc/partitions`：所有磁盘和分区，大小的单位为 KiB
+pub fn partitions() -> String {
+    let mut content = String::from("major\tminor\t#blocks\tname\n");
+    for info in block_devices() {
+        writeln!(
+            content,
+            "{}\t{}\t{}\t{}",
+            VIRTIO_BLK_MAJOR,
+            info.minor(),
+            info.device.blocks / 2,
+            info.name
+        )
+        .unwrap();
+    }
+    content
+}
+
+/// `/proc/sched`：调度器中的线程队列
+pub fn sched() -> String {
+    let processor = PROCESSOR.lock();
+    let mut content = String::from("tid\tpid\tstate\n");
+    for thread in processor.scheduled_threads() {
+        writeln!(
+            content,
+            "{}\t{}\t{}",
+            thread.id,
+            thread.process.id,
+            thread_state(&thread, processor.is_current_thread(&thread))
+        )
+        .unwrap();
+    }
+    content
+}
+
+/// `/proc/kmsg`：内核日志的环形缓冲区
+pub fn kmsg() -> String {
+    String::from_utf8_lossy(&logging::read_all()).into_owned()
+}
+
+/// 线程的状态
+fn thread_state(thread: &Thread, is_current: bool) -> &'static str {
+    let inner = thread.inner();
+    if inner.dead {
+        "dead"
+    } else if inner.sleeping {
+        "sleeping"
+    } else if is_current {
+        "running"
+    } else {
+        "ready"
+    }
+}
+
+/// 属于某个进程的所有线程，以及它们是否为当前线程
+fn process_threads(process: &Process) -> Vec<(Arc<Thread>, bool)> {
+    let processor = PROCESSOR.lock();
+    processor
+        .threads()
+        .into_iter()
+        .filter(|thread| thread.process.id == process.id)
+        .map(|thread| {
+            let is_current = processor.is_current_thread(&thread);
+            (thread, is_current)
+        })
+        .collect()
+}
+
+/// `/proc/<pid>/status`
+pub fn status(process: &Process) -> String {
+    let threads = process_threads(process).len();
+    let inner = process.inner();
+    let mut content = String::new();
+    writeln!(content, "Pid:\t{}", process.id).unwrap();
+    writeln!(
+        content,
+        "Type:\t{}",
+        if process.is_user { "user" } else { "kernel" }
+    )
+    .unwrap();
+    writeln!(content, "Pgid:\t{}", inner.pgid).unwrap();
+    writeln!(content, "Threads:\t{}", threads).unwrap();
+    writeln!(content, "Segments:\t{}", inner.memory_set.segments.len()).unwrap();
+    writeln!(
+        content,
+        "Descriptors:\t{}",
+        inner.descriptors.iter().flatten().count()
+    )
+    .unwrap();
+    writeln!(content, "Cwd:\t{}", inner.cwd).unwrap();
+    content
+}
+
+/// `/proc/<pid>/maps`：由 [`MemorySet`](crate::memory::MemorySet) 中的每个段生成
+pub fn maps(process: &Process) -> String {
+    let mut content = String::new();
+    for segment in process.inner().memory_set.segments.iter() {
+        let flag = |bit: Flags, c: char| if segment.flags.contains(bit) { c } else { '-' };
+        writeln!(
+            content,
+            "{:016x}-{:016x} {}{}{}{} {:?}",
+            segment.range.start.0,
+            segment.range.end.0,
+            flag(Flags::READABLE, 'r'),
+            flag(Flags::WRITABLE, 'w'),
+            flag(Flags::EXECUTABLE, 'x'),
+            flag(Flags::USER, 'u'),
+            segment.map_type,
+        )
+        .unwrap();
+    }
+    content
+}
+
+/// `/proc/<pid>/fd`：打开的文件描述符
+pub fn fd(process: &Process) -> String {
+    let mut content = String::from("fd\ttype\tsize\tpath\n");
+    for (fd, handle) in process.inner().descriptors.iter().enumerate() {
+        let handle = match handle {
+            Some(handle) => handle,
+            None => continue,
+        };
+        match handle.inode.metadata() {
+            Ok(metadata) => writeln!(
+                content,
+                "{}\t{:?}\t{}\t{}",
+                fd, metadata.type_, metadata.size, handle.path
+            )
+            .unwrap(),
+            Err(_) => writeln!(content, "{}\t?\t?\t{}", fd, handle.path).unwrap(),
+        }
+    }
+    content
+}
+
+/// `/proc/<pid>/threads`：线程列表以及 [`ThreadInner`](crate::process::thread::ThreadInner) 中的状态
+pub fn threads(process: &Process) -> String {
+    let mut content = String::from("tid\tstate\tsepc\n");
+    for (thread, is_current) in process_threads(process) {
+        let state = thread_state(&thread, is_current);
+        // 只有暂停执行的线程保存了 Context
+        let sepc = thread
+            .inner()
+            .context
+            .map_or(String::from("-"), |context| format!("{:#x}", context.sepc));
+        writeln!(content, "{}\t{}\t{}", thread.id, state, sepc).unwrap();
+    }
+    content
+}
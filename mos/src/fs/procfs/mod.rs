@@ -0,0 +1,290 @@
+//! 进程信息文件系统，挂载在 `/proc`
+//!
+//! 所有文件的内容都在读取时生成：
+//! - `/proc/meminfo`：物理帧和内核堆的使用情况
+//! - `/proc/uptime`：时钟中断次数
+//! - `/proc/diskstats`：每个磁盘的读写统计
+//! - `/proc/partitions`：所有磁盘和分区的设备号和大小
+//! - `/proc/sched`：调度器中的线程队列
+//! - `/proc/kmsg`：内核日志，和 `dmesg` 一样读取时不会清空
+//! - `/proc/<pid>/status`、`maps`、`fd`、`threads`：每个进程的信息
+
+mod content;
+
+use super::*;
+use crate::process::{process::Process, processor::PROCESSOR};
+use alloc::{
+    string::{String, ToString},
+    sync::Weak,
+};
+
+/// 全局文件的名称以及生成内容的函数
+const GLOBAL_FILES: [(&str, fn() -> String); 6] = [
+    ("meminfo", content::meminfo),
+    ("uptime", content::uptime),
+    ("diskstats", content::diskstats),
+    ("partitions", content::partitions),
+    ("sched", content::sched),
+    ("kmsg", content::kmsg),
+];
+
+/// 每个进程目录中的文件的名称以及生成内容的函数
+const PROCESS_FILES: [(&str, fn(&Process) -> String); 4] = [
+    ("status", content::status),
+    ("maps", content::maps),
+    ("fd", content::fd),
+    ("threads", content::threads),
+];
+
+/// procfs 中的节点
+#[derive(Clone)]
+enum Entry {
+    /// 根目录
+    Root,
+    /// 全局文件，参数为在 [`GLOBAL_FILES`] 中的下标
+    Global(usize),
+    /// 进程目录
+    ProcessDir(Weak<Process>),
+    /// 进程目录中的文件，参数为在 [`PROCESS_FILES`] 中的下标
+    ProcessFile(Weak<Process>, usize),
+}
+
+/// 进程信息文件系统
+pub struct ProcFS {
+    /// 指向自身的弱引用，INode 通过它来返回所属的文件系统
+    self_ref: Weak<ProcFS>,
+}
+
+/// procfs 中的 INode
+pub struct ProcINode {
+    /// 节点类型
+    entry: Entry,
+    /// 所属的文件系统
+    fs: Arc<ProcFS>,
+}
+
+impl ProcFS {
+    pub fn new() -> Arc<Self> {
+        let fs = Arc::new(Self {
+            self_ref: Weak::new(),
+        });
+        let weak = Arc::downgrade(&fs);
+        let ptr = Arc::into_raw(fs) as *mut Self;
+        unsafe {
+            (*ptr).self_ref = weak;
+            Arc::from_raw(ptr)
+        }
+    }
+
+    /// 创建一个节点
+    fn inode(&self, entry: Entry) -> Arc<dyn INode> {
+        Arc::new(ProcINode {
+            entry,
+            fs: self.self_ref.upgrade().unwrap(),
+        })
+    }
+}
+
+impl FileSystem for ProcFS {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        self.inode(Entry::Root)
+    }
+
+    fn info(&self) -> FsInfo {
+        FsInfo {
+            bsize: 0,
+            frsize: 0,
+            blocks: 0,
+            bfree: 0,
+            bavail: 0,
+            files: 0,
+            ffree: 0,
+            namemax: 255,
+        }
+    }
+}
+
+/// 列出所有进程，按照进程 ID 排序
+fn processes() -> Vec<Arc<Process>> {
+    let mut processes: Vec<Arc<Process>> = Vec::new();
+    for thread in PROCESSOR.lock().threads() {
+        if processes.iter().all(|process| process.id != thread.process.id) {
+            processes.push(thread.process.clone());
+        }
+    }
+    processes.sort_by_key(|process| process.id);
+    processes
+}
+
+impl ProcINode {
+    /// 是否为目录
+    fn is_dir(&self) -> bool {
+        match self.entry {
+            Entry::Root | Entry::ProcessDir(_) => true,
+            _ => false,
+        }
+    }
+
+    /// inode 编号：根目录为 1，全局文件从 2 开始，每个进程占用 16 个编号
+    fn id(&self) -> usize {
+        match &self.entry {
+            Entry::Root => 1,
+            Entry::Global(index) => 2 + index,
+            Entry::ProcessDir(process) => Self::process_id(process) * 16,
+            Entry::ProcessFile(process, index) => Self::process_id(process) * 16 + 1 + index,
+        }
+    }
+
+    /// 进程 ID，进程已经结束时为 0
+    fn process_id(process: &Weak<Process>) -> usize {
+        process.upgrade().map_or(0, |process| process.id as usize)
+    }
+
+    /// 目录中的所有目录项（不含 "." 和 ".."）
+    fn entries(&self) -> Result<Vec<(String, Entry)>> {
+        match &self.entry {
+            Entry::Root => {
+                let mut entries: Vec<(String, Entry)> = GLOBAL_FILES
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (name, _))| (name.to_string(), Entry::Global(index)))
+                    .collect();
+                for process in processes() {
+                    entries.push((
+                        process.id.to_string(),
+                        Entry::ProcessDir(Arc::downgrade(&process)),
+                    ));
+                }
+                Ok(entries)
+            }
+            Entry::ProcessDir(process) => {
+                if process.upgrade().is_none() {
+                    return Err(FsError::DirRemoved);
+                }
+                Ok(PROCESS_FILES
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (name, _))| {
+                        (name.to_string(), Entry::ProcessFile(process.clone(), index))
+                    })
+                    .collect())
+            }
+            _ => Err(FsError::NotDir),
+        }
+    }
+
+    /// 生成文件内容
+    fn content(&self) -> Result<String> {
+        match &self.entry {
+            Entry::Global(index) => Ok((GLOBAL_FILES[*index].1)()),
+            Entry::ProcessFile(process, index) => {
+                let process = process.upgrade().ok_or(FsError::EntryNotFound)?;
+                Ok((PROCESS_FILES[*index].1)(&process))
+            }
+            _ => Err(FsError::IsDir),
+        }
+    }
+}
+
+impl INode for ProcINode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let content = self.content()?;
+        let content = content.as_bytes();
+        if offset >= content.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(content.len() - offset);
+        buf[..len].copy_from_slice(&content[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        Err(FsError::NotSupported)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: false,
+            error: false,
+        })
+    }
+
+    /// 文件大小为当前生成的内容长度，这样 [`INodeExt::readall`] 可以读到完整内容
+    fn metadata(&self) -> Result<Metadata> {
+        let zero = Timespec { sec: 0, nsec: 0 };
+        let (type_, mode, size) = if self.is_dir() {
+            (FileType::Dir, 0o555, 0)
+        } else {
+            (FileType::File, 0o444, self.content()?.len())
+        };
+        Ok(Metadata {
+            dev: 0,
+            inode: self.id(),
+            size,
+            blk_size: 0,
+            blocks: 0,
+            atime: zero,
+            mtime: zero,
+            ctime: zero,
+            type_,
+            mode,
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+        })
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        if !self.is_dir() {
+            return Err(FsError::NotDir);
+        }
+        match name {
+            "." => Ok(self.fs.inode(self.entry.clone())),
+            ".." => Ok(self.fs.inode(Entry::Root)),
+            name => self
+                .entries()?
+                .into_iter()
+                .find(|(entry_name, _)| entry_name == name)
+                .map(|(_, entry)| self.fs.inode(entry))
+                .ok_or(FsError::EntryNotFound),
+        }
+    }
+
+    fn get_entry(&self, id: usize) -> Result<String> {
+        if !self.is_dir() {
+            return Err(FsError::NotDir);
+        }
+        match id {
+            0 => Ok(String::from(".")),
+            1 => Ok(String::from("..")),
+            id => self
+                .entries()?
+                .into_iter()
+                .nth(id - 2)
+                .map(|(name, _)| name)
+                .ok_or(FsError::EntryNotFound),
+        }
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.clone()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// 创建进程信息文件系统并挂载到 `/proc`
+pub fn init() {
+    // 只读的根文件系统中没有 /proc 时无法挂载
+    if let Err(error) = mount("/proc", ProcFS::new()) {
+        warn!("failed to mount procfs on /proc: {:?}", error);
+    }
+}
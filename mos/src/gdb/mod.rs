@@ -0,0 +1,570 @@
+//! GDB 远程调试
+//!
+//! 在名为 `gdb` 的 virtio 控制台端口上实现 GDB 远程串行协议（RSP）的服务端。和 QEMU 自带的 gdbstub 不同，
+//! 只有触发调试的线程停在中断处理中，其他线程的 `Context` 保存在 [`Thread`] 中，可以通过 `Hg` 切换查看。
+//!
+//! 以下情况进入调试，直到 GDB 继续执行：
+//! - 任意线程执行 `ebreak`，包括 GDB 插入的断点和单步使用的临时断点
+//! - 调试端口收到数据，例如 GDB 连接时的第一个数据包或 Ctrl-C
+//!
+//! 断点通过改写指令实现，只能用于可写的页面。内核代码段是只读的，无法插入断点，
+//! 需要在代码中直接写 `ebreak`；用户程序的页面都可以写入。
+//!
+//! 寄存器编号与 GDB 的 riscv:rv64 相同：0 到 31 号为通用寄存器，32 号为 `pc`
+
+mod packet;
+mod step;
+
+use crate::drivers::serial::virtio_console::gdb_port;
+use crate::interrupt::Context;
+use crate::memory::{
+    layout, mapping::Mapping, PhysicalAddress, VirtualAddress, KERNEL_END_ADDRESS,
+};
+use crate::process::processor::PROCESSOR;
+use crate::process::thread::{Thread, ThreadID};
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc, vec::Vec};
+use core::str;
+use lazy_static::lazy_static;
+use packet::*;
+use spin::Mutex;
+
+/// 报告给 GDB 的停止原因
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+/// `c.ebreak` 和 `ebreak` 的编码
+const C_EBREAK: [u8; 2] = 0x9002u16.to_le_bytes();
+const EBREAK: [u8; 4] = 0x0010_0073u32.to_le_bytes();
+
+/// 寄存器的数量，包括 `pc`
+const REGISTER_COUNT: usize = 33;
+
+/// 插入的断点
+struct Breakpoint {
+    /// 被替换的指令
+    original: Vec<u8>,
+    /// 单步使用的临时断点，停下时全部移除
+    temporary: bool,
+}
+
+/// 调试的状态
+struct Stub {
+    port: Port,
+    /// 断点的地址，位于插入时的地址空间中
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    /// `Hg` 选择的线程，读写寄存器时使用
+    selected: ThreadID,
+    /// GDB 是否在等待停止的报告，即上一个命令是 `c` 或 `s`
+    running: bool,
+    /// 最近一次停止的原因
+    signal: u8,
+}
+
+/// 处理一个数据包的结果
+enum Action {
+    /// 回复数据包，继续等待命令
+    Reply(String),
+    /// 恢复执行
+    Resume,
+}
+
+lazy_static! {
+    /// 没有调试端口时为 `None`
+    static ref STUB: Mutex<Option<Stub>> = Mutex::new(None);
+}
+
+extern "C" {
+    /// 由 `linker.ld` 指定的数据段起始位置，此后直到内存结束都是可写的
+    fn data_start();
+}
+
+/// 虚拟地址在内核线性映射中对应的地址，未映射时返回 `None`
+fn linear(address: usize) -> Option<usize> {
+    Mapping::lookup(VirtualAddress(address)).map(|pa| VirtualAddress::from(pa).0)
+}
+
+/// 读取当前地址空间中的内存，遇到未映射的页面时返回 `None`
+fn read_memory(address: usize, len: usize) -> Option<Vec<u8>> {
+    (address..address.checked_add(len)?)
+        .map(|address| linear(address).map(|linear| unsafe { *(linear as *const u8) }))
+        .collect()
+}
+
+/// 写入当前地址空间中的内存，任何一个字节不可写或者地址溢出时都不会写入
+fn write_memory(address: usize, data: &[u8]) -> bool {
+    let writable = |linear: usize| {
+        (linear >= data_start as usize && linear < KERNEL_END_ADDRESS.0)
+            || layout::is_memory(PhysicalAddress::from(VirtualAddress(linear)))
+    };
+    let targets: Option<Vec<usize>> = (0..data.len())
+        .map(|offset| {
+            address
+                .checked_add(offset)
+                .and_then(linear)
+                .filter(|&linear| writable(linear))
+        })
+        .collect();
+    match targets {
+        Some(targets) => {
+            for (&target, &byte) in targets.iter().zip(data) {
+                unsafe { *(target as *mut u8) = byte };
+            }
+            // 可能改写了指令
+            unsafe { llvm_asm!("fence.i" :::: "volatile") };
+            true
+        }
+        None => false,
+    }
+}
+
+/// 按照 ID 找到线程
+fn find_thread(id: ThreadID) -> Option<Arc<Thread>> {
+    let threads = PROCESSOR.lock().threads();
+    threads.into_iter().find(|thread| thread.id == id)
+}
+
+/// 开启调试端口，在驱动初始化之后调用
+pub fn init() {
+    if let Some(driver) = gdb_port() {
+        let name = driver.device_name();
+        *STUB.lock() = Some(Stub {
+            port: Port::new(driver),
+            breakpoints: BTreeMap::new(),
+            selected: 0,
+            running: false,
+            signal: SIGTRAP,
+        });
+        info!("gdb stub listening on virtio console port {}", name);
+    }
+}
+
+/// 是否开启了调试端口，此时 `ebreak` 交给 GDB 处理
+pub fn enabled() -> bool {
+    STUB.lock().is_some()
+}
+
+/// 调试端口收到数据时进入调试，在外部中断和时钟中断中调用
+pub fn poll(context: &mut Context) {
+    let received = match STUB.lock().as_mut() {
+        Some(stub) => match stub.port.try_getc() {
+            Some(byte) => {
+                stub.port.unget(byte);
+                true
+            }
+            None => false,
+        },
+        None => false,
+    };
+    if received {
+        enter(context, SIGINT);
+    }
+}
+
+/// 处理 `ebreak`，由 [`enabled`] 时的断点异常调用
+pub fn handle_breakpoint(context: &mut Context) {
+    enter(context, SIGTRAP);
+}
+
+/// 停下当前线程，处理 GDB 的命令直到继续执行
+fn enter(context: &mut Context, signal: u8) {
+    let mut guard = STUB.lock();
+    let stub = guard.as_mut().unwrap();
+    let current = PROCESSOR.lock().current_thread();
+    // 停在不是由 GDB 插入的 `ebreak` 上时，继续执行需要跳过这条指令
+    let skip = match signal {
+        SIGTRAP if !stub.breakpoints.contains_key(&context.sepc) => {
+            step::instruction_len(context.sepc).unwrap_or(0)
+        }
+        _ => 0,
+    };
+    let pc = context.sepc;
+    stub.remove_temporary();
+    stub.selected = current.id;
+    stub.signal = signal;
+    if stub.running {
+        stub.running = false;
+        let reply = stub.stop_reply(current.id);
+        stub.port.send(reply.as_bytes());
+    } else {
+        info!("waiting for gdb at {:#x}", pc);
+    }
+    loop {
+        let packet = stub.port.receive();
+        match stub.handle(&packet, context, &current) {
+            Action::Reply(reply) => stub.port.send(reply.as_bytes()),
+            Action::Resume => break,
+        }
+    }
+    if context.sepc == pc {
+        context.sepc += skip;
+    }
+}
+
+impl Stub {
+    /// 停止时回复的数据包
+    fn stop_reply(&self, thread: ThreadID) -> String {
+        format!("T{:02x}thread:{:x};", self.signal, thread)
+    }
+
+    /// 处理一个数据包
+    fn handle(&mut self, packet: &[u8], context: &mut Context, current: &Arc<Thread>) -> Action {
+        let (command, arguments) = match packet.split_first() {
+            Some((&command, arguments)) => (command, arguments),
+            None => return Action::Reply(String::new()),
+        };
+        let reply = match command {
+            b'?' => self.stop_reply(current.id),
+            b'g' => self.read_registers(context, current),
+            b'G' => self.write_registers(arguments, context, current),
+            b'p' => self.read_register(arguments, context, current),
+            b'P' => self.write_register(arguments, context, current),
+            b'm' => Self::read_memory(arguments),
+            b'M' => Self::write_memory(arguments),
+            b'Z' => self.insert_breakpoint(arguments),
+            b'z' => self.remove_breakpoint(arguments),
+            b'H' => self.select_thread(arguments, current),
+            b'T' => match parse_thread(arguments).and_then(find_thread) {
+                Some(_) => ok(),
+                None => error(),
+            },
+            b'q' => self.query(arguments, current),
+            b'c' => {
+                self.running = true;
+                return Action::Resume;
+            }
+            b's' => return self.step(context),
+            b'D' => {
+                self.detach();
+                self.port.send(b"OK");
+                return Action::Resume;
+            }
+            b'k' => {
+                self.detach();
+                return Action::Resume;
+            }
+            // 不支持的命令回复空数据包
+            _ => String::new(),
+        };
+        Action::Reply(reply)
+    }
+
+    /// 对选中线程的 `Context` 执行操作，当前线程使用中断时保存的 `Context`
+    fn with_context<T>(
+        &self,
+        context: &mut Context,
+        current: &Arc<Thread>,
+        f: impl FnOnce(&mut Context) -> T,
+    ) -> Option<T> {
+        if self.selected == current.id {
+            return Some(f(context));
+        }
+        let thread = find_thread(self.selected)?;
+        let result = thread.inner().context.as_mut().map(f);
+        result
+    }
+
+    fn read_registers(&self, context: &mut Context, current: &Arc<Thread>) -> String {
+        self.with_context(context, current, |context| {
+            (0..REGISTER_COUNT)
+                .map(|index| encode_register(get_register(context, index)))
+                .collect()
+        })
+        .unwrap_or_else(error)
+    }
+
+    fn write_registers(
+        &self,
+        arguments: &[u8],
+        context: &mut Context,
+        current: &Arc<Thread>,
+    ) -> String {
+        let values: Option<Vec<usize>> = arguments.chunks(16).map(decode_register).collect();
+        match values {
+            Some(values) if values.len() >= REGISTER_COUNT => self
+                .with_context(context, current, |context| {
+                    for (index, &value) in values.iter().take(REGISTER_COUNT).enumerate() {
+                        set_register(context, index, value);
+                    }
+                })
+                .map_or_else(error, |_| ok()),
+            _ => error(),
+        }
+    }
+
+    fn read_register(
+        &self,
+        arguments: &[u8],
+        context: &mut Context,
+        current: &Arc<Thread>,
+    ) -> String {
+        match parse_hex(arguments) {
+            Some(index) if index < REGISTER_COUNT => self
+                .with_context(context, current, |context| {
+                    encode_register(get_register(context, index))
+                })
+                .unwrap_or_else(error),
+            _ => error(),
+        }
+    }
+
+    fn write_register(
+        &self,
+        arguments: &[u8],
+        context: &mut Context,
+        current: &Arc<Thread>,
+    ) -> String {
+        let mut parts = arguments.splitn(2, |&byte| byte == b'=');
+        let index = parts.next().and_then(parse_hex);
+        let value = parts.next().and_then(decode_register);
+        match (index, value) {
+            (Some(index), Some(value)) if index < REGISTER_COUNT => self
+                .with_context(context, current, |context| {
+                    set_register(context, index, value)
+                })
+                .map_or_else(error, |_| ok()),
+            _ => error(),
+        }
+    }
+
+    /// `m addr,len`
+    fn read_memory(arguments: &[u8]) -> String {
+        match parse_pair(arguments).and_then(|(address, len)| read_memory(address, len)) {
+            Some(data) => encode_hex(&data),
+            None => error(),
+        }
+    }
+
+    /// `M addr,len:data`
+    fn write_memory(arguments: &[u8]) -> String {
+        let mut parts = arguments.splitn(2, |&byte| byte == b':');
+        let target = parts.next().and_then(parse_pair);
+        let data = parts.next().and_then(decode_hex);
+        match (target, data) {
+            (Some((address, len)), Some(data)) if data.len() == len => {
+                if write_memory(address, &data) {
+                    ok()
+                } else {
+                    error()
+                }
+            }
+            _ => error(),
+        }
+    }
+
+    /// 在 `address` 插入断点，`len` 为被替换指令的长度
+    fn insert(&mut self, address: usize, len: usize, temporary: bool) -> bool {
+        if self.breakpoints.contains_key(&address) {
+            return true;
+        }
+        let instruction: &[u8] = match len {
+            2 => &C_EBREAK,
+            4 => &EBREAK,
+            _ => return false,
+        };
+        let original = match read_memory(address, len) {
+            Some(original) => original,
+            None => return false,
+        };
+        if !write_memory(address, instruction) {
+            return false;
+        }
+        self.breakpoints.insert(
+            address,
+            Breakpoint {
+                original,
+                temporary,
+            },
+        );
+        true
+    }
+
+    /// 移除断点并恢复原来的指令
+    fn remove(&mut self, address: usize) -> bool {
+        match self.breakpoints.remove(&address) {
+            Some(breakpoint) => write_memory(address, &breakpoint.original),
+            None => false,
+        }
+    }
+
+    /// 移除单步使用的临时断点
+    fn remove_temporary(&mut self) {
+        let temporary: Vec<usize> = self
+            .breakpoints
+            .iter()
+            .filter(|(_, breakpoint)| breakpoint.temporary)
+            .map(|(&address, _)| address)
+            .collect();
+        for address in temporary {
+            self.remove(address);
+        }
+    }
+
+    /// `Z0,addr,kind`，只支持软件断点
+    fn insert_breakpoint(&mut self, arguments: &[u8]) -> String {
+        match parse_breakpoint(arguments) {
+            Some((address, kind)) => {
+                // GDB 插入的断点替换此前的临时断点
+                if let Some(breakpoint) = self.breakpoints.get_mut(&address) {
+                    breakpoint.temporary = false;
+                }
+                if self.insert(address, kind, false) {
+                    ok()
+                } else {
+                    error()
+                }
+            }
+            None => String::new(),
+        }
+    }
+
+    /// `z0,addr,kind`
+    fn remove_breakpoint(&mut self, arguments: &[u8]) -> String {
+        match parse_breakpoint(arguments) {
+            Some((address, _)) => {
+                if self.remove(address) {
+                    ok()
+                } else {
+                    error()
+                }
+            }
+            None => String::new(),
+        }
+    }
+
+    /// 在下一条指令可能的位置放置临时断点后继续执行
+    fn step(&mut self, context: &Context) -> Action {
+        let targets = match step::successors(context) {
+            Some(targets) => targets,
+            None => return Action::Reply(error()),
+        };
+        for target in targets {
+            let inserted = match step::instruction_len(target) {
+                Some(len) => self.insert(target, len, true),
+                None => false,
+            };
+            if !inserted {
+                self.remove_temporary();
+                return Action::Reply(error());
+            }
+        }
+        self.running = true;
+        Action::Resume
+    }
+
+    /// `Hg tid` 选择读写寄存器的线程，`Hc` 只能继续所有线程
+    fn select_thread(&mut self, arguments: &[u8], current: &Arc<Thread>) -> String {
+        match arguments.split_first() {
+            Some((b'g', thread)) => match parse_thread(thread) {
+                Some(id) if find_thread(id).is_some() => {
+                    self.selected = id;
+                    ok()
+                }
+                // 0 和 -1 表示任意线程
+                _ if thread == b"0" || thread == b"-1" => {
+                    self.selected = current.id;
+                    ok()
+                }
+                _ => error(),
+            },
+            Some((b'c', _)) => ok(),
+            _ => error(),
+        }
+    }
+
+    /// 处理 `q` 开头的查询
+    fn query(&self, arguments: &[u8], current: &Arc<Thread>) -> String {
+        let text = str::from_utf8(arguments).unwrap_or("");
+        if text.starts_with("Supported") {
+            String::from("PacketSize=1000")
+        } else if text == "Attached" {
+            String::from("1")
+        } else if text == "C" {
+            format!("QC{:x}", current.id)
+        } else if text == "fThreadInfo" {
+            let mut ids: Vec<ThreadID> = {
+                let threads = PROCESSOR.lock().threads();
+                threads.iter().map(|thread| thread.id).collect()
+            };
+            ids.sort();
+            let ids: Vec<String> = ids.iter().map(|id| format!("{:x}", id)).collect();
+            format!("m{}", ids.join(","))
+        } else if text == "sThreadInfo" {
+            String::from("l")
+        } else if text.starts_with("ThreadExtraInfo,") {
+            match parse_thread(&arguments[16..]).and_then(find_thread) {
+                Some(thread) => {
+                    let state = if thread.id == current.id {
+                        "stopped"
+                    } else if thread.inner().sleeping {
+                        "sleeping"
+                    } else {
+                        "ready"
+                    };
+                    let kind = if thread.process.is_user {
+                        "user"
+                    } else {
+                        "kernel"
+                    };
+                    let info = format!("process {} {} {}", thread.process.id, kind, state);
+                    encode_hex(info.as_bytes())
+                }
+                None => error(),
+            }
+        } else {
+            String::new()
+        }
+    }
+
+    /// 移除所有断点，GDB 断开连接
+    fn detach(&mut self) {
+        let addresses: Vec<usize> = self.breakpoints.keys().cloned().collect();
+        for address in addresses {
+            self.remove(address);
+        }
+        self.running = false;
+    }
+}
+
+/// 按照 GDB 的编号读取寄存器
+fn get_register(context: &Context, index: usize) -> usize {
+    match index {
+        32 => context.sepc,
+        _ => context.x[index],
+    }
+}
+
+/// 按照 GDB 的编号写入寄存器，`zero` 不会被写入
+fn set_register(context: &mut Context, index: usize, value: usize) {
+    match index {
+        0 => {}
+        32 => context.sepc = value,
+        _ => context.x[index] = value,
+    }
+}
+
+/// 解析 `a,b`
+fn parse_pair(arguments: &[u8]) -> Option<(usize, usize)> {
+    let mut parts = arguments.splitn(2, |&byte| byte == b',');
+    Some((parse_hex(parts.next()?)?, parse_hex(parts.next()?)?))
+}
+
+/// 解析 `0,addr,kind`，只接受软件断点
+fn parse_breakpoint(arguments: &[u8]) -> Option<(usize, usize)> {
+    if !arguments.starts_with(b"0,") {
+        return None;
+    }
+    parse_pair(&arguments[2..])
+}
+
+/// 解析线程编号
+fn parse_thread(arguments: &[u8]) -> Option<ThreadID> {
+    parse_hex(arguments).map(|id| id as ThreadID)
+}
+
+fn ok() -> String {
+    String::from("OK")
+}
+
+fn error() -> String {
+    String::from("E01")
+}
@@ -0,0 +1,139 @@
+//! GDB 远程串行协议的数据包
+//!
+//! 数据包的格式为 `$<数据>#<两位十六进制校验和>`，校验和是数据各字节之和的低 8 位。
+//! 收到正确的数据包后回复 `+`，否则回复 `-` 要求重发
+
+use crate::drivers::driver::Driver;
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use core::fmt::Write;
+
+/// 调试端口，在调试期间轮询读写串口
+pub struct Port {
+    driver: Arc<dyn Driver>,
+    /// 已经读出但尚未处理的字节
+    lookahead: Option<u8>,
+}
+
+impl Port {
+    pub fn new(driver: Arc<dyn Driver>) -> Self {
+        Self {
+            driver,
+            lookahead: None,
+        }
+    }
+
+    /// 读取一个字节，没有数据时返回 `None`
+    pub fn try_getc(&mut self) -> Option<u8> {
+        self.lookahead.take().or_else(|| self.driver.read_char())
+    }
+
+    /// 放回一个读出的字节
+    pub fn unget(&mut self, byte: u8) {
+        self.lookahead = Some(byte);
+    }
+
+    /// 等待并读取一个字节
+    fn getc(&mut self) -> u8 {
+        loop {
+            if let Some(byte) = self.try_getc() {
+                return byte;
+            }
+        }
+    }
+
+    /// 同步写出，调试时中断是关闭的，不能依靠发送中断
+    fn write(&self, bytes: &[u8]) {
+        self.driver.write_chars(bytes);
+        self.driver.flush();
+    }
+
+    /// 接收一个数据包，跳过其前的确认字符和 Ctrl-C
+    pub fn receive(&mut self) -> Vec<u8> {
+        loop {
+            while self.getc() != b'$' {}
+            let mut data = Vec::new();
+            let mut checksum = 0u8;
+            loop {
+                match self.getc() {
+                    b'#' => break,
+                    byte => {
+                        checksum = checksum.wrapping_add(byte);
+                        data.push(byte);
+                    }
+                }
+            }
+            let expected = [self.getc(), self.getc()];
+            if parse_hex(&expected) == Some(checksum as usize) {
+                self.write(b"+");
+                return data;
+            }
+            self.write(b"-");
+        }
+    }
+
+    /// 发送一个数据包，直到 GDB 确认收到
+    pub fn send(&mut self, data: &[u8]) {
+        let checksum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(data);
+        packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+        loop {
+            self.write(&packet);
+            match self.getc() {
+                b'-' => continue,
+                // GDB 已经开始发送下一个数据包
+                b'$' => self.unget(b'$'),
+                _ => {}
+            }
+            return;
+        }
+    }
+}
+
+/// 解析十六进制数
+pub fn parse_hex(text: &[u8]) -> Option<usize> {
+    if text.is_empty() || text.len() > 16 {
+        return None;
+    }
+    text.iter().try_fold(0usize, |value, &byte| {
+        (byte as char)
+            .to_digit(16)
+            .map(|digit| value << 4 | digit as usize)
+    })
+}
+
+/// 把字节编码为十六进制文本
+pub fn encode_hex(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(text, "{:02x}", byte).unwrap();
+    }
+    text
+}
+
+/// 把十六进制文本解码为字节
+pub fn decode_hex(text: &[u8]) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    text.chunks(2)
+        .map(|pair| parse_hex(pair).map(|byte| byte as u8))
+        .collect()
+}
+
+/// 寄存器的值，按照目标的小端序编码
+pub fn encode_register(value: usize) -> String {
+    encode_hex(&value.to_le_bytes())
+}
+
+/// 解码 [`encode_register`] 编码的寄存器
+pub fn decode_register(text: &[u8]) -> Option<usize> {
+    let bytes = decode_hex(text)?;
+    if bytes.len() != 8 {
+        return None;
+    }
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes);
+    Some(usize::from_le_bytes(value))
+}
@@ -0,0 +1,100 @@
+//! 单步的模拟
+//!
+//! RISC-V 没有硬件单步，解析当前指令得到所有可能的下一条指令的地址，在那里放置临时断点。
+//! 条件分支的两个方向都会放置断点
+
+use super::read_memory;
+use crate::interrupt::Context;
+use alloc::{vec, vec::Vec};
+
+/// 取出 `value` 的 `[low, low + len)` 位
+fn bits(value: u32, low: u32, len: u32) -> usize {
+    ((value >> low) & ((1 << len) - 1)) as usize
+}
+
+/// 把 `width` 位的有符号数扩展为 `usize`
+fn sign_extend(value: usize, width: u32) -> usize {
+    let shift = 64 - width;
+    (((value << shift) as isize) >> shift) as usize
+}
+
+/// 读取 `pc` 处的指令，压缩指令只有低 16 位有效
+fn fetch(pc: usize) -> Option<(u32, usize)> {
+    let low = read_memory(pc, 2)?;
+    let low = u16::from_le_bytes([low[0], low[1]]) as u32;
+    if low & 0b11 != 0b11 {
+        return Some((low, 2));
+    }
+    let high = read_memory(pc + 2, 2)?;
+    let high = u16::from_le_bytes([high[0], high[1]]) as u32;
+    Some((high << 16 | low, 4))
+}
+
+/// `pc` 处的指令的长度
+pub fn instruction_len(pc: usize) -> Option<usize> {
+    fetch(pc).map(|(_, len)| len)
+}
+
+/// 执行 `context.sepc` 处的指令之后可能到达的地址
+pub fn successors(context: &Context) -> Option<Vec<usize>> {
+    let pc = context.sepc;
+    let (inst, len) = fetch(pc)?;
+    let next = pc + len;
+    let register = |index: usize| context.x[index];
+    let targets = if len == 2 {
+        match (bits(inst, 0, 2), bits(inst, 13, 3)) {
+            // c.j
+            (0b01, 0b101) => {
+                let offset = bits(inst, 12, 1) << 11
+                    | bits(inst, 11, 1) << 4
+                    | bits(inst, 9, 2) << 8
+                    | bits(inst, 8, 1) << 10
+                    | bits(inst, 7, 1) << 6
+                    | bits(inst, 6, 1) << 7
+                    | bits(inst, 3, 3) << 1
+                    | bits(inst, 2, 1) << 5;
+                vec![pc.wrapping_add(sign_extend(offset, 12))]
+            }
+            // c.beqz 和 c.bnez
+            (0b01, 0b110) | (0b01, 0b111) => {
+                let offset = bits(inst, 12, 1) << 8
+                    | bits(inst, 10, 2) << 3
+                    | bits(inst, 5, 2) << 6
+                    | bits(inst, 3, 2) << 1
+                    | bits(inst, 2, 1) << 5;
+                vec![pc.wrapping_add(sign_extend(offset, 9)), next]
+            }
+            // c.jr 和 c.jalr，rs1 为 0 时是 c.ebreak 等其他指令
+            (0b10, 0b100) if bits(inst, 2, 5) == 0 && bits(inst, 7, 5) != 0 => {
+                vec![register(bits(inst, 7, 5))]
+            }
+            _ => vec![next],
+        }
+    } else {
+        match bits(inst, 0, 7) {
+            // jal
+            0b110_1111 => {
+                let offset = bits(inst, 31, 1) << 20
+                    | bits(inst, 21, 10) << 1
+                    | bits(inst, 20, 1) << 11
+                    | bits(inst, 12, 8) << 12;
+                vec![pc.wrapping_add(sign_extend(offset, 21))]
+            }
+            // jalr
+            0b110_0111 => {
+                let offset = sign_extend(bits(inst, 20, 12), 12);
+                vec![register(bits(inst, 15, 5)).wrapping_add(offset) & !1]
+            }
+            // 条件分支
+            0b110_0011 => {
+                let offset = bits(inst, 31, 1) << 12
+                    | bits(inst, 25, 6) << 5
+                    | bits(inst, 8, 4) << 1
+                    | bits(inst, 7, 1) << 11;
+                vec![pc.wrapping_add(sign_extend(offset, 13)), next]
+            }
+            _ => vec![next],
+        }
+    };
+    Some(targets)
+}
@@ -1,8 +1,20 @@
+use crate::backtrace::{print_context, print_trap_backtrace};
+use crate::console;
+use crate::drivers::plic;
+use crate::fs::TTY;
+use crate::gdb;
+use crate::net;
 use crate::PROCESSOR;
 use super::context::Context;
 use super::timer;
+use crate::kernel::{
+    force_signal, handle_signals, signal_return, syscall_handler, SIGNAL_TRAMPOLINE,
+};
+use crate::process::processor::release_thread;
+use crate::process::signal::{SIGBUS, SIGILL, SIGSEGV, SIGTRAP, SIG_DFL};
 use riscv::register::scause::{Exception, Interrupt, Scause, Trap};
-use riscv::register::stvec;
+use riscv::register::sstatus::SPP;
+use riscv::register::{sstatus, stvec};
 
 global_asm!(include_str!("./interrupt.asm"));
 
@@ -17,6 +29,8 @@ pub fn init() {
         }
         // 使用 Direct 模式，将中断入口设置为 `__interrupt`
         stvec::write(__interrupt as usize, stvec::TrapMode::Direct);
+        // 允许内核访问用户页面，系统调用需要读写用户传入的缓冲区
+        sstatus::set_sum();
     }
 }
 
@@ -26,57 +40,123 @@ pub fn init() {
 /// 具体的中断类型需要根据 scause 来推断，然后分别处理
 #[no_mangle]
 pub fn handle_interrupt(context: &mut Context, scause: Scause, stval: usize) -> *mut Context {
-    println!("{:x?}", scause.cause());
-    println!("handle_interrupt is called");
     // 首先检查线程是否已经结束（内核线程会自己设置标记来结束自己）
     {
-        let mut processor = PROCESSOR.lock();
-        let current_thread = processor.current_thread();
+        let current_thread = PROCESSOR.lock().current_thread();
         if current_thread.as_ref().inner().dead {
-            println!("thread {} exit", current_thread.id);
-            processor.kill_current_thread();
-            return processor.prepare_next_thread();
+            debug!("thread {} exit", current_thread.id);
+            PROCESSOR.lock().kill_current_thread();
+            release_thread(current_thread);
+            return handle_signals(PROCESSOR.lock().prepare_next_thread());
         }
     }
     // 根据中断类型来处理，返回的 Context 必须位于放在内核栈顶
-    match scause.cause() {
+    let next = match scause.cause() {
+        // 系统调用
+        Trap::Exception(Exception::UserEnvCall) => syscall_handler(context),
+        // 开启调试端口时，内核和用户程序的断点都交给 GDB
+        Trap::Exception(Exception::Breakpoint) if gdb::enabled() => {
+            gdb::handle_breakpoint(context);
+            context
+        }
+        // 用户程序的其他异常转换为信号
+        Trap::Exception(exception) if context.sstatus.spp() == SPP::User => {
+            user_exception(context, exception, stval)
+        }
         // 断点中断（ebreak）
         Trap::Exception(Exception::Breakpoint) => breakpoint(context),
         // 时钟中断
         Trap::Interrupt(Interrupt::SupervisorTimer) => supervisor_timer(context),
+        // 外部中断
+        Trap::Interrupt(Interrupt::SupervisorExternal) => supervisor_external(context),
         // 其他情况，无法处理
-        _ => fault("unimplemented interrupt type", scause, stval),
-    }
+        _ => fault(context, "unimplemented interrupt type", scause, stval),
+    };
+    // 返回用户态之前递送信号
+    handle_signals(next)
 }
 
 /// 处理 ebreak 断点
 ///
 /// 继续执行，其中 `sepc` 增加 2 字节，以跳过当前这条 `ebreak` 指令
 fn breakpoint(context: &mut Context)  -> *mut Context {
-    println!("Breakpoint at 0x{:x}", context.sepc);
+    debug!("breakpoint at {:#x}", context.sepc);
     context.sepc += 2;
     context
 }
 
+/// 用户程序的异常，转换为发送给当前线程的信号
+///
+/// 信号处理函数返回到 [`SIGNAL_TRAMPOLINE`] 时产生的缺页异常用来结束信号处理
+fn user_exception(context: &mut Context, exception: Exception, stval: usize) -> *mut Context {
+    let signal = match exception {
+        Exception::InstructionPageFault if stval == SIGNAL_TRAMPOLINE => {
+            signal_return(context);
+            return context;
+        }
+        Exception::IllegalInstruction => SIGILL,
+        Exception::Breakpoint => SIGTRAP,
+        Exception::InstructionMisaligned | Exception::StoreMisaligned => SIGBUS,
+        // 访问错误和缺页
+        _ => SIGSEGV,
+    };
+    force_signal(signal);
+    // 没有处理函数时进程将被终止，打印现场以便调试
+    let thread = PROCESSOR.lock().current_thread();
+    if thread.process.inner().signal_actions[signal - 1].handler == SIG_DFL {
+        warn!(
+            "process {} {:?} at {:#x}, stval: {:#x}",
+            thread.process.id, exception, context.sepc, stval
+        );
+        print_context(context);
+        print_trap_backtrace(context);
+    }
+    context
+}
+
 /// 处理时钟中断
 ///
-/// 目前只会在 [`timer`] 模块中进行计数
-fn supervisor_timer(context: &Context)  -> *mut Context {
+/// 在 [`timer`] 模块中计数并唤醒到时的线程，同时轮询控制台输入和调试端口
+fn supervisor_timer(context: &mut Context)  -> *mut Context {
     timer::tick();
+    // 没有串口中断时，在时钟中断中轮询控制台输入和调试端口
+    if !console::interrupt_driven() {
+        TTY.poll_input();
+        gdb::poll(context);
+    }
+    // 推进协议栈，处理 TCP 重传等超时
+    net::poll();
     PROCESSOR.lock().park_current_thread(context);
     PROCESSOR.lock().prepare_next_thread()
 }
 
+/// 处理外部中断
+///
+/// 由 PLIC 分发给设备的驱动，串口收到的输入随后交给终端，调试端口收到数据时进入调试，
+/// 网卡收到的数据交给协议栈
+fn supervisor_external(context: &mut Context) -> *mut Context {
+    plic::handle_interrupt();
+    TTY.poll_input();
+    gdb::poll(context);
+    net::poll();
+    context
+}
+
 /// 出现未能解决的异常，终止当前线程
-fn fault(msg: &str, scause: Scause, stval: usize) -> *mut Context {
-    println!(
+///
+/// 用户程序的异常会转换为信号，只有内核线程会到达这里
+fn fault(context: &Context, msg: &str, scause: Scause, stval: usize) -> *mut Context {
+    error!(
         "{:#x?} terminated: {}",
         PROCESSOR.lock().current_thread(),
         msg
     );
-    println!("cause: {:?}, stval: {:x}", scause.cause(), stval);
+    error!("cause: {:?}, stval: {:x}", scause.cause(), stval);
+    print_context(context);
+    print_trap_backtrace(context);
 
-    PROCESSOR.lock().kill_current_thread();
+    let thread = PROCESSOR.lock().kill_current_thread();
+    release_thread(thread);
     // 跳转到 PROCESSOR 调度的下一个线程
     PROCESSOR.lock().prepare_next_thread()
 }
\ No newline at end of file
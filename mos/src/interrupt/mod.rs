@@ -12,6 +12,7 @@ mod handler;
 mod timer;
 
 pub use context::Context;
+pub use timer::{cancel_sleep, sleep_until, ticks, CLOCK_FREQ, TICKS_PER_SECOND};
 
 /// 初始化中断相关的子模块
 ///
@@ -20,5 +21,5 @@ pub use context::Context;
 pub fn init() {
     handler::init();
     timer::init();
-    println!("mod interrupt initialized");
+    info!("mod interrupt initialized");
 }
@@ -1,22 +1,82 @@
 //! 预约和处理时钟中断
 
+use crate::process::thread::{Thread, ThreadID};
 use crate::sbi::set_timer;
+use crate::PROCESSOR;
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
 use riscv::register::{sie, sstatus, time};
+use spin::Mutex;
 
+/// QEMU virt 的时钟频率
+pub const CLOCK_FREQ: usize = 10_000_000;
 /// 时钟中断的间隔，单位是 CPU 指令
-static INTERVAL: usize = 100000;
+const INTERVAL: usize = 100000;
+/// 每秒的时钟中断次数
+pub const TICKS_PER_SECOND: usize = CLOCK_FREQ / INTERVAL;
 /// 触发时钟中断计数
 pub static mut TICKS: usize = 0;
 
+lazy_static! {
+    /// 等待超时的线程，按照唤醒时刻排序
+    static ref SLEEPERS: Mutex<BTreeMap<(usize, ThreadID), Arc<Thread>>> =
+        Mutex::new(BTreeMap::new());
+}
+
 /// 每一次时钟中断时调用
 ///
-/// 设置下一次时钟中断，同时计数 +1
+/// 设置下一次时钟中断，同时计数 +1，并唤醒到时的线程
 pub fn tick() {
     set_next_timeout();
     unsafe {
         TICKS += 1;
-        if TICKS % 100 == 0 {
-            println!("{} tick", TICKS);
+    }
+    wake_sleepers();
+}
+
+/// 获取触发时钟中断的次数
+pub fn ticks() -> usize {
+    unsafe { TICKS }
+}
+
+/// 令当前线程休眠，直到第 `deadline` 次时钟中断
+///
+/// 和 [`Condvar::wait`](crate::process::condvar::Condvar::wait) 一样，
+/// 调用者随后需要保存 `Context` 并切换到下一个线程
+pub fn sleep_until(deadline: usize) {
+    let mut processor = PROCESSOR.lock();
+    let thread = processor.current_thread();
+    SLEEPERS
+        .lock()
+        .insert((deadline, thread.id), thread.clone());
+    if !thread.inner().sleeping {
+        processor.sleep_current_thread();
+    }
+}
+
+/// 取消线程在第 `deadline` 次时钟中断的唤醒，用于终止线程
+pub fn cancel_sleep(deadline: usize, thread: ThreadID) {
+    SLEEPERS.lock().remove(&(deadline, thread));
+}
+
+/// 唤醒已经到时的线程
+fn wake_sleepers() {
+    let now = ticks();
+    let mut expired = Vec::new();
+    {
+        let mut sleepers = SLEEPERS.lock();
+        while let Some(&key) = sleepers.keys().next() {
+            if key.0 > now {
+                break;
+            }
+            expired.push(sleepers.remove(&key).unwrap());
+        }
+    }
+    let mut processor = PROCESSOR.lock();
+    for thread in expired {
+        // 线程可能已经被条件变量唤醒
+        if thread.inner().sleeping {
+            processor.wake_thread(thread);
         }
     }
 }
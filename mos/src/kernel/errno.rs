@@ -0,0 +1,96 @@
+//! 系统调用的错误码 [`Errno`]
+
+use super::*;
+use crate::net::NetError;
+
+/// 错误码，系统调用失败时返回它的相反数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Errno(pub isize);
+
+impl Errno {
+    pub const EPERM: Self = Self(1);
+    pub const ENOENT: Self = Self(2);
+    pub const ESRCH: Self = Self(3);
+    pub const EINTR: Self = Self(4);
+    pub const EIO: Self = Self(5);
+    pub const EBADF: Self = Self(9);
+    pub const EAGAIN: Self = Self(11);
+    pub const ENOMEM: Self = Self(12);
+    pub const EFAULT: Self = Self(14);
+    pub const EBUSY: Self = Self(16);
+    pub const EEXIST: Self = Self(17);
+    pub const EXDEV: Self = Self(18);
+    pub const ENOTDIR: Self = Self(20);
+    pub const EISDIR: Self = Self(21);
+    pub const EINVAL: Self = Self(22);
+    pub const EMFILE: Self = Self(24);
+    pub const ENOTTY: Self = Self(25);
+    pub const ENOSPC: Self = Self(28);
+    pub const EPIPE: Self = Self(32);
+    pub const ERANGE: Self = Self(34);
+    pub const ENAMETOOLONG: Self = Self(36);
+    pub const ENOSYS: Self = Self(38);
+    pub const ENOTEMPTY: Self = Self(39);
+    pub const ELOOP: Self = Self(40);
+    pub const ENOTSOCK: Self = Self(88);
+    pub const EDESTADDRREQ: Self = Self(89);
+    pub const EPROTONOSUPPORT: Self = Self(93);
+    pub const EOPNOTSUPP: Self = Self(95);
+    pub const EAFNOSUPPORT: Self = Self(97);
+    pub const EADDRINUSE: Self = Self(98);
+    pub const EADDRNOTAVAIL: Self = Self(99);
+    pub const ENETDOWN: Self = Self(100);
+    pub const EISCONN: Self = Self(106);
+    pub const ENOTCONN: Self = Self(107);
+    pub const ECONNREFUSED: Self = Self(111);
+    pub const EALREADY: Self = Self(114);
+    pub const EINPROGRESS: Self = Self(115);
+    /// 只在内核中使用：当前线程已在等待，被唤醒后重新执行系统调用
+    pub const ERESTARTSYS: Self = Self(512);
+}
+
+impl From<FsError> for Errno {
+    fn from(error: FsError) -> Self {
+        match error {
+            FsError::NotSupported => Self::ENOSYS,
+            FsError::NotFile | FsError::IsDir => Self::EISDIR,
+            FsError::NotDir => Self::ENOTDIR,
+            FsError::EntryNotFound | FsError::DirRemoved => Self::ENOENT,
+            FsError::EntryExist => Self::EEXIST,
+            FsError::NotSameFs => Self::EXDEV,
+            FsError::InvalidParam | FsError::WrongFs | FsError::IOCTLError | FsError::NoDevice => {
+                Self::EINVAL
+            }
+            FsError::NoDeviceSpace => Self::ENOSPC,
+            FsError::DirNotEmpty => Self::ENOTEMPTY,
+            FsError::DeviceError => Self::EIO,
+            FsError::Again => Self::EAGAIN,
+            FsError::SymLoop => Self::ELOOP,
+            FsError::Busy => Self::EBUSY,
+            FsError::Interrupted => Self::EINTR,
+        }
+    }
+}
+
+impl From<NetError> for Errno {
+    fn from(error: NetError) -> Self {
+        match error {
+            NetError::NoDevice => Self::ENETDOWN,
+            NetError::Again => Self::EAGAIN,
+            NetError::InProgress => Self::EINPROGRESS,
+            NetError::Already => Self::EALREADY,
+            NetError::IsConnected => Self::EISCONN,
+            NetError::NotConnected => Self::ENOTCONN,
+            NetError::AddrInUse => Self::EADDRINUSE,
+            NetError::AddrNotAvailable => Self::EADDRNOTAVAIL,
+            NetError::ConnectionRefused => Self::ECONNREFUSED,
+            NetError::BrokenPipe => Self::EPIPE,
+            NetError::DestinationRequired => Self::EDESTADDRREQ,
+            NetError::InvalidParam => Self::EINVAL,
+            NetError::NotSupported => Self::EOPNOTSUPP,
+        }
+    }
+}
+
+/// 系统调用的结果，成功时为返回值
+pub type SysResult<T = usize> = core::result::Result<T, Errno>;
@@ -0,0 +1,501 @@
+//! 文件相关的系统调用
+//!
+//! 路径可以是绝对路径，或相对于 `dirfd` 指向的目录（`AT_FDCWD` 表示当前工作目录）
+
+use super::stat::*;
+use super::*;
+use crate::net::Socket;
+use crate::process::config::MAX_DESCRIPTORS;
+use crate::process::signal::SIGPIPE;
+use alloc::vec::Vec;
+
+/// 使用当前工作目录作为相对路径的起点
+const AT_FDCWD: isize = -100;
+/// 不跟随最后一项的符号链接
+const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+/// `unlinkat` 删除目录
+const AT_REMOVEDIR: usize = 0x200;
+/// `linkat` 跟随最后一项的符号链接
+const AT_SYMLINK_FOLLOW: usize = 0x400;
+/// 路径为空时使用 `dirfd` 本身
+const AT_EMPTY_PATH: usize = 0x1000;
+
+const O_ACCMODE: usize = 0o3;
+const O_RDONLY: usize = 0o0;
+const O_WRONLY: usize = 0o1;
+const O_CREAT: usize = 0o100;
+const O_EXCL: usize = 0o200;
+const O_TRUNC: usize = 0o1000;
+const O_APPEND: usize = 0o2000;
+const O_NONBLOCK: usize = 0o4000;
+const O_DIRECTORY: usize = 0o200_000;
+const O_NOFOLLOW: usize = 0o400_000;
+const O_CLOEXEC: usize = 0o2_000_000;
+
+/// `renameat2` 不覆盖已有的文件
+const RENAME_NOREPLACE: usize = 1;
+
+/// 解析路径时最多跟随的符号链接数
+const FOLLOW_MAX_DEPTH: usize = 40;
+
+/// 取得文件描述符对应的文件
+pub(super) fn descriptor(fd: usize) -> SysResult<Arc<FileHandle>> {
+    current_process()
+        .inner()
+        .get_descriptor(fd)
+        .ok_or(Errno::EBADF)
+}
+
+/// 将文件加入当前进程的文件描述符表，打开的文件过多时返回 `EMFILE`
+pub(super) fn add_descriptor(handle: Arc<FileHandle>) -> SysResult {
+    current_process()
+        .inner()
+        .add_descriptor(handle)
+        .ok_or(Errno::EMFILE)
+}
+
+/// 相对路径的起点
+fn start_dir(dirfd: isize, path: &str) -> SysResult<Arc<dyn INode>> {
+    if path.starts_with('/') {
+        Ok(ROOT_INODE.clone())
+    } else if dirfd == AT_FDCWD {
+        // 先复制一份路径，查找时不能持有进程的锁（例如 /proc 中的文件会用到它）
+        let cwd = current_process().inner().cwd.clone();
+        Ok(ROOT_INODE.lookup_follow(&cwd, FOLLOW_MAX_DEPTH)?)
+    } else {
+        Ok(descriptor(dirfd as usize)?.inode.clone())
+    }
+}
+
+/// 查找路径对应的文件，`follow` 表示是否跟随最后一项的符号链接
+fn lookup(dirfd: isize, path: &str, follow: bool) -> SysResult<Arc<dyn INode>> {
+    if path.is_empty() {
+        return Err(Errno::ENOENT);
+    }
+    let inode = if follow || path.ends_with('/') {
+        start_dir(dirfd, path)?.lookup_follow(path, FOLLOW_MAX_DEPTH)?
+    } else {
+        let (parent, name) = lookup_parent(dirfd, path)?;
+        parent.find(&name)?
+    };
+    if follow && inode.metadata()?.type_ == FileType::SymLink {
+        return Err(Errno::ELOOP);
+    }
+    Ok(inode)
+}
+
+/// 查找路径的父目录，返回父目录和最后一项的名字
+///
+/// 根目录的名字为 `.`
+fn lookup_parent(dirfd: isize, path: &str) -> SysResult<(Arc<dyn INode>, String)> {
+    if path.is_empty() {
+        return Err(Errno::ENOENT);
+    }
+    let trimmed = path.trim_end_matches('/');
+    let (parent, name) = match trimmed.rfind('/') {
+        Some(index) => (&trimmed[..=index], &trimmed[index + 1..]),
+        None if trimmed.is_empty() => ("/", "."),
+        None => ("", trimmed),
+    };
+    let parent = start_dir(dirfd, path)?.lookup_follow(parent, FOLLOW_MAX_DEPTH)?;
+    if parent.metadata()?.type_ != FileType::Dir {
+        return Err(Errno::ENOTDIR);
+    }
+    Ok((parent, String::from(name)))
+}
+
+/// 删除、链接和重命名不能作用于 `.` 和 `..`
+fn check_name(name: &str) -> SysResult<()> {
+    if name == "." || name == ".." {
+        Err(Errno::EINVAL)
+    } else {
+        Ok(())
+    }
+}
+
+/// 将路径按字面规范化为绝对路径，处理其中的 `.` 和 `..`
+fn normalize(cwd: &str, path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    let base = if path.starts_with('/') { "" } else { cwd };
+    for name in base.split('/').chain(path.split('/')) {
+        match name {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            name => components.push(name),
+        }
+    }
+    let mut result = String::new();
+    for name in components {
+        result.push('/');
+        result.push_str(name);
+    }
+    if result.is_empty() {
+        result.push('/');
+    }
+    result
+}
+
+/// 打开文件，返回文件描述符
+pub(super) fn sys_openat(dirfd: isize, path: usize, flags: usize, mode: usize) -> SysResult {
+    let path = user_path(path)?;
+    let options = OpenOptions {
+        read: flags & O_ACCMODE != O_WRONLY,
+        write: flags & O_ACCMODE != O_RDONLY,
+        append: flags & O_APPEND != 0,
+        nonblock: flags & O_NONBLOCK != 0,
+    };
+    let inode = match lookup(dirfd, &path, flags & O_NOFOLLOW == 0) {
+        Ok(_) if flags & O_CREAT != 0 && flags & O_EXCL != 0 => return Err(Errno::EEXIST),
+        Ok(inode) => inode,
+        Err(Errno::ENOENT) if flags & O_CREAT != 0 => {
+            let (parent, name) = lookup_parent(dirfd, &path)?;
+            let inode = parent.create(&name, FileType::File, (mode & 0o7777) as u32)?;
+            inode.touch();
+            parent.touch();
+            inode
+        }
+        Err(errno) => return Err(errno),
+    };
+    match inode.metadata()?.type_ {
+        FileType::SymLink => return Err(Errno::ELOOP),
+        FileType::Dir if options.write => return Err(Errno::EISDIR),
+        FileType::Dir => {}
+        _ if flags & O_DIRECTORY != 0 => return Err(Errno::ENOTDIR),
+        FileType::File if flags & O_TRUNC != 0 && options.write => {
+            inode.resize(0)?;
+            inode.touch();
+        }
+        _ => {}
+    }
+    let handle = FileHandle::new(inode, options, path);
+    add_descriptor(handle)
+}
+
+/// 关闭文件描述符
+pub(super) fn sys_close(fd: usize) -> SysResult {
+    let handle = current_process()
+        .inner()
+        .remove_descriptor(fd)
+        .ok_or(Errno::EBADF)?;
+    unwatch(fd, &handle);
+    Ok(0)
+}
+
+/// 文件描述符被关闭之后，当前进程中的 epoll 实例停止监听它
+fn unwatch(fd: usize, handle: &Arc<FileHandle>) {
+    // 不持有进程的锁
+    let others: Vec<_> = current_process()
+        .inner()
+        .descriptors
+        .iter()
+        .flatten()
+        .cloned()
+        .collect();
+    for other in others {
+        if let Some(epoll) = other.inode.downcast_ref::<Epoll>() {
+            epoll.closed(fd, handle);
+        }
+    }
+}
+
+/// 读写需要阻塞时，在文件对应的条件变量上等待，被唤醒后重新执行系统调用
+///
+/// 以非阻塞方式打开，或文件不支持等待时返回 `EAGAIN`
+pub fn block_on(handle: &FileHandle) -> SysResult {
+    if handle.options.nonblock {
+        return Err(Errno::EAGAIN);
+    }
+    match handle.condvar() {
+        Some(condvar) => condvar.wait(),
+        None => return Err(Errno::EAGAIN),
+    }
+    Err(Errno::ERESTARTSYS)
+}
+
+/// 从文件中读取
+pub(super) fn sys_read(fd: usize, buffer: usize, size: usize) -> SysResult {
+    let handle = descriptor(fd)?;
+    if !handle.options.read {
+        return Err(Errno::EBADF);
+    }
+    match handle.read(user_slice_mut(buffer, size)?) {
+        Ok(len) => Ok(len),
+        Err(FsError::Again) => block_on(&handle),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// 写入文件
+pub(super) fn sys_write(fd: usize, buffer: usize, size: usize) -> SysResult {
+    let handle = descriptor(fd)?;
+    if !handle.options.write {
+        return Err(Errno::EBADF);
+    }
+    match handle.write(user_slice(buffer, size)?) {
+        Ok(len) => Ok(len),
+        Err(FsError::Again) => block_on(&handle),
+        Err(FsError::NoDevice)
+            if handle.inode.downcast_ref::<Pipe>().is_some()
+                || handle.inode.downcast_ref::<Socket>().is_some() =>
+        {
+            // 和 Linux 一样，同时发送 SIGPIPE
+            let thread = PROCESSOR.lock().current_thread();
+            send_signal(&thread, SIGPIPE);
+            Err(Errno::EPIPE)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// 设备相关的控制操作，由文件的 INode 处理
+pub(super) fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> SysResult {
+    let handle = descriptor(fd)?;
+    match handle.inode.io_control(cmd as u32, arg) {
+        Ok(value) => Ok(value),
+        // 不是设备，或者设备不支持这个操作
+        Err(FsError::NotSupported) => Err(Errno::ENOTTY),
+        // 参数指向的用户内存无法访问
+        Err(FsError::IOCTLError) => Err(Errno::EFAULT),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// 复制文件描述符，使用最小的空闲编号
+pub(super) fn sys_dup(fd: usize) -> SysResult {
+    let handle = descriptor(fd)?;
+    add_descriptor(handle)
+}
+
+/// 复制文件描述符到 `new_fd`，原来打开的文件会被关闭
+pub(super) fn sys_dup3(old_fd: usize, new_fd: usize, flags: usize) -> SysResult {
+    if old_fd == new_fd || flags & !O_CLOEXEC != 0 {
+        return Err(Errno::EINVAL);
+    }
+    if new_fd >= MAX_DESCRIPTORS {
+        return Err(Errno::EBADF);
+    }
+    let handle = descriptor(old_fd)?;
+    let old = current_process().inner().set_descriptor(new_fd, handle);
+    if let Some(old) = old {
+        unwatch(new_fd, &old);
+    }
+    Ok(new_fd)
+}
+
+/// 创建管道，将读端和写端的文件描述符写入 `fds`
+pub(super) fn sys_pipe2(fds: usize, flags: usize) -> SysResult {
+    if flags & !(O_NONBLOCK | O_CLOEXEC) != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let nonblock = flags & O_NONBLOCK != 0;
+    let (read, write) = Pipe::new();
+    let read = FileHandle::new(
+        read,
+        OpenOptions {
+            read: true,
+            nonblock,
+            ..OpenOptions::default()
+        },
+        String::from("pipe"),
+    );
+    let write = FileHandle::new(
+        write,
+        OpenOptions {
+            write: true,
+            nonblock,
+            ..OpenOptions::default()
+        },
+        String::from("pipe"),
+    );
+    let process = current_process();
+    let (read_fd, write_fd) = {
+        let mut inner = process.inner();
+        let read_fd = inner.add_descriptor(read).ok_or(Errno::EMFILE)?;
+        match inner.add_descriptor(write) {
+            Some(write_fd) => (read_fd, write_fd),
+            None => {
+                // 关闭读端时不能持有进程的锁
+                let read = inner.remove_descriptor(read_fd);
+                drop(inner);
+                drop(read);
+                return Err(Errno::EMFILE);
+            }
+        }
+    };
+    // 检查用户内存时需要锁住进程
+    if let Err(errno) = write_user(fds, [read_fd as i32, write_fd as i32]) {
+        let mut inner = process.inner();
+        inner.remove_descriptor(read_fd);
+        inner.remove_descriptor(write_fd);
+        return Err(errno);
+    }
+    Ok(0)
+}
+
+/// 创建目录
+pub(super) fn sys_mkdirat(dirfd: isize, path: usize, mode: usize) -> SysResult {
+    let (parent, name) = lookup_parent(dirfd, &user_path(path)?)?;
+    parent
+        .create(&name, FileType::Dir, (mode & 0o7777) as u32)?
+        .touch();
+    parent.touch();
+    Ok(0)
+}
+
+/// 删除文件，带有 `AT_REMOVEDIR` 时删除空目录
+pub(super) fn sys_unlinkat(dirfd: isize, path: usize, flags: usize) -> SysResult {
+    let (parent, name) = lookup_parent(dirfd, &user_path(path)?)?;
+    check_name(&name)?;
+    let is_dir = parent.find(&name)?.metadata()?.type_ == FileType::Dir;
+    match (is_dir, flags & AT_REMOVEDIR != 0) {
+        (false, true) => return Err(Errno::ENOTDIR),
+        (true, false) => return Err(Errno::EISDIR),
+        _ => {}
+    }
+    parent.unlink(&name)?;
+    Ok(0)
+}
+
+/// 创建硬链接
+pub(super) fn sys_linkat(
+    old_dirfd: isize,
+    old_path: usize,
+    new_dirfd: isize,
+    new_path: usize,
+    flags: usize,
+) -> SysResult {
+    let old = lookup(
+        old_dirfd,
+        &user_path(old_path)?,
+        flags & AT_SYMLINK_FOLLOW != 0,
+    )?;
+    if old.metadata()?.type_ == FileType::Dir {
+        return Err(Errno::EPERM);
+    }
+    let (parent, name) = lookup_parent(new_dirfd, &user_path(new_path)?)?;
+    check_name(&name)?;
+    parent.link(&name, &old)?;
+    Ok(0)
+}
+
+/// 重命名文件，目标已存在时将其替换
+///
+/// 替换由文件系统的 `move_` 完成，失败时目标保持不变；不支持替换的文件系统返回 `EEXIST`
+pub(super) fn sys_renameat2(
+    old_dirfd: isize,
+    old_path: usize,
+    new_dirfd: isize,
+    new_path: usize,
+    flags: usize,
+) -> SysResult {
+    if flags & !RENAME_NOREPLACE != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let (old_parent, old_name) = lookup_parent(old_dirfd, &user_path(old_path)?)?;
+    let (new_parent, new_name) = lookup_parent(new_dirfd, &user_path(new_path)?)?;
+    check_name(&old_name)?;
+    check_name(&new_name)?;
+    old_parent.find(&old_name)?;
+    // 每个挂载点有各自的文件系统对象
+    if !Arc::ptr_eq(&old_parent.fs(), &new_parent.fs()) {
+        return Err(Errno::EXDEV);
+    }
+    if flags & RENAME_NOREPLACE != 0 {
+        match new_parent.find(&new_name) {
+            Ok(_) => return Err(Errno::EEXIST),
+            Err(FsError::EntryNotFound) => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    old_parent.move_(&old_name, &new_parent, &new_name)?;
+    Ok(0)
+}
+
+/// 取得打开的文件的元数据
+pub(super) fn sys_fstat(fd: usize, stat: usize) -> SysResult {
+    let metadata = descriptor(fd)?.inode.metadata()?;
+    write_user(stat, Stat::from(metadata))?;
+    Ok(0)
+}
+
+/// 写回所有文件系统和块设备缓存
+pub(super) fn sys_sync() -> SysResult {
+    crate::fs::sync()?;
+    Ok(0)
+}
+
+/// 取得路径对应的文件的元数据
+pub(super) fn sys_fstatat(dirfd: isize, path: usize, stat: usize, flags: usize) -> SysResult {
+    let path = user_path(path)?;
+    let inode = if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        start_dir(dirfd, &path)?
+    } else {
+        lookup(dirfd, &path, flags & AT_SYMLINK_NOFOLLOW == 0)?
+    };
+    write_user(stat, Stat::from(inode.metadata()?))?;
+    Ok(0)
+}
+
+/// 读取目录项，以 `linux_dirent64` 的格式写入用户的缓冲区，返回写入的长度
+pub(super) fn sys_getdents64(fd: usize, buffer: usize, size: usize) -> SysResult {
+    let handle = descriptor(fd)?;
+    if handle.inode.metadata()?.type_ != FileType::Dir {
+        return Err(Errno::ENOTDIR);
+    }
+    let buffer = user_slice_mut(buffer, size)?;
+    let mut written = 0;
+    let mut too_small = false;
+    handle.read_entries(|index, name, metadata| {
+        // 每一项按 8 字节对齐，名字以 0 结尾
+        let length = (DIRENT_HEADER_SIZE + name.len() + 1 + 7) & !7;
+        if written + length > buffer.len() {
+            too_small = written == 0;
+            return false;
+        }
+        let entry = &mut buffer[written..written + length];
+        entry[0..8].copy_from_slice(&(metadata.inode as u64).to_le_bytes());
+        entry[8..16].copy_from_slice(&(index as i64 + 1).to_le_bytes());
+        entry[16..18].copy_from_slice(&(length as u16).to_le_bytes());
+        entry[18] = dirent_type(metadata.type_);
+        entry[DIRENT_HEADER_SIZE..DIRENT_HEADER_SIZE + name.len()].copy_from_slice(name.as_bytes());
+        for byte in entry[DIRENT_HEADER_SIZE + name.len()..].iter_mut() {
+            *byte = 0;
+        }
+        written += length;
+        true
+    })?;
+    if too_small {
+        return Err(Errno::EINVAL);
+    }
+    Ok(written)
+}
+
+/// 取得当前工作目录，返回包括结尾的 0 在内的长度
+pub(super) fn sys_getcwd(buffer: usize, size: usize) -> SysResult {
+    let cwd = current_process().inner().cwd.clone();
+    if cwd.len() + 1 > size {
+        return Err(Errno::ERANGE);
+    }
+    let buffer = user_slice_mut(buffer, cwd.len() + 1)?;
+    buffer[..cwd.len()].copy_from_slice(cwd.as_bytes());
+    buffer[cwd.len()] = 0;
+    Ok(cwd.len() + 1)
+}
+
+/// 切换当前工作目录
+pub(super) fn sys_chdir(path: usize) -> SysResult {
+    let path = user_path(path)?;
+    if path.is_empty() {
+        return Err(Errno::ENOENT);
+    }
+    let process = current_process();
+    let cwd = normalize(&process.inner().cwd, &path);
+    let inode = ROOT_INODE.lookup_follow(&cwd, FOLLOW_MAX_DEPTH)?;
+    if inode.metadata()?.type_ != FileType::Dir {
+        return Err(Errno::ENOTDIR);
+    }
+    process.inner().cwd = cwd;
+    Ok(0)
+}
@@ -0,0 +1,42 @@
+//! 为用户程序提供的系统调用
+//!
+//! 系统调用号和参数约定与 Linux riscv64 相同，失败时返回负的错误码
+
+mod errno;
+mod fs;
+mod poll;
+mod process;
+mod signal;
+mod socket;
+mod stat;
+mod syscall;
+mod system;
+mod user;
+
+use crate::fs::*;
+use crate::interrupt::Context;
+use crate::process::process::Process;
+use crate::PROCESSOR;
+use alloc::{string::String, sync::Arc};
+use errno::*;
+use fs::*;
+use poll::*;
+use process::*;
+use signal::*;
+use socket::*;
+use syscall::*;
+use system::*;
+use user::*;
+
+pub use fs::block_on;
+pub use signal::{
+    force_signal, handle_signals, send_signal, signal_group, signal_return, SIGNAL_TRAMPOLINE,
+};
+pub use syscall::syscall_handler;
+pub use system::poweroff;
+pub use user::{read_user, write_user};
+
+/// 当前线程所属的进程
+fn current_process() -> Arc<Process> {
+    PROCESSOR.lock().current_thread().process.clone()
+}
@@ -0,0 +1,253 @@
+//! 同时等待多个文件的 `ppoll` 和 epoll
+//!
+//! riscv64 没有 `poll` 和 `epoll_wait` 系统调用，libc 分别通过 `ppoll` 和 `epoll_pwait` 实现。
+//! 没有文件就绪时，线程在所有文件的条件变量上休眠，有超时时同时预约时钟唤醒，
+//! 被唤醒后重新执行系统调用，超时时刻保存在线程中沿用
+
+use super::*;
+use crate::interrupt::{sleep_until, ticks, TICKS_PER_SECOND};
+use crate::process::condvar::Condvar;
+use crate::process::config::MAX_DESCRIPTORS;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+const POLLIN: i16 = 0x001;
+const POLLOUT: i16 = 0x004;
+const POLLERR: i16 = 0x008;
+const POLLHUP: i16 = 0x010;
+const POLLNVAL: i16 = 0x020;
+
+const EPOLL_CTL_ADD: usize = 1;
+const EPOLL_CTL_DEL: usize = 2;
+const EPOLL_CTL_MOD: usize = 3;
+
+/// `struct pollfd`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PollFd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+/// `struct epoll_event`，riscv64 上没有 packed
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+/// `struct timespec`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct TimeSpec {
+    pub(super) sec: i64,
+    pub(super) nsec: i64,
+}
+
+impl TimeSpec {
+    /// 向上取整到时钟中断的次数，溢出时返回 `None`，视为一直等待
+    fn ticks(&self) -> SysResult<Option<usize>> {
+        if self.sec < 0 || self.nsec < 0 || self.nsec >= 1_000_000_000 {
+            return Err(Errno::EINVAL);
+        }
+        let nanos_per_tick = 1_000_000_000 / TICKS_PER_SECOND;
+        Ok((self.sec as usize)
+            .checked_mul(TICKS_PER_SECOND)
+            .and_then(|ticks| {
+                ticks.checked_add((self.nsec as usize + nanos_per_tick - 1) / nanos_per_tick)
+            }))
+    }
+}
+
+/// 没有就绪的文件时等待，`timeout` 为 `None` 表示一直等待
+///
+/// 已经超时返回 0，否则令当前线程休眠并返回 `ERESTARTSYS`
+fn wait(condvars: &[&Condvar], timeout: Option<usize>) -> SysResult {
+    let thread = PROCESSOR.lock().current_thread();
+    // 超时时刻溢出时同样一直等待
+    let deadline = match thread.inner().timeout {
+        Some(deadline) => Some(deadline),
+        None => timeout.and_then(|timeout| ticks().checked_add(timeout)),
+    };
+    if let Some(deadline) = deadline {
+        thread.inner().timeout = Some(deadline);
+        if ticks() >= deadline {
+            thread.inner().timeout = None;
+            return Ok(0);
+        }
+        sleep_until(deadline);
+    }
+    for condvar in condvars {
+        condvar.wait();
+    }
+    Err(Errno::ERESTARTSYS)
+}
+
+/// 系统调用结束（包括出错）时不再沿用之前的超时时刻，只有需要重新执行时保留
+fn finish(result: SysResult) -> SysResult {
+    if result != Err(Errno::ERESTARTSYS) {
+        PROCESSOR.lock().current_thread().inner().timeout = None;
+    }
+    result
+}
+
+/// 等待多个文件描述符就绪，返回就绪的个数
+///
+/// 暂不支持信号屏蔽字
+pub(super) fn sys_ppoll(fds: usize, nfds: usize, timeout: usize) -> SysResult {
+    finish(ppoll(fds, nfds, timeout))
+}
+
+fn ppoll(fds: usize, nfds: usize, timeout: usize) -> SysResult {
+    if nfds > MAX_DESCRIPTORS {
+        return Err(Errno::EINVAL);
+    }
+    let timeout = match timeout {
+        0 => None,
+        address => read_user::<TimeSpec>(address)?.ticks()?,
+    };
+    let mut poll_fds: Vec<PollFd> = (0..nfds)
+        .map(|i| read_user(fds + i * size_of::<PollFd>()))
+        .collect::<SysResult<_>>()?;
+
+    let mut handles = Vec::new();
+    let mut count = 0;
+    for poll_fd in poll_fds.iter_mut() {
+        poll_fd.revents = 0;
+        if poll_fd.fd < 0 {
+            continue;
+        }
+        let handle = match descriptor(poll_fd.fd as usize) {
+            Ok(handle) => handle,
+            Err(_) => {
+                poll_fd.revents = POLLNVAL;
+                count += 1;
+                continue;
+            }
+        };
+        // 普通文件不支持 poll 时视为总是就绪
+        let status = handle.inode.poll().unwrap_or(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        });
+        if status.read {
+            poll_fd.revents |= poll_fd.events & POLLIN;
+        }
+        if status.write {
+            poll_fd.revents |= poll_fd.events & POLLOUT;
+        }
+        if status.error {
+            poll_fd.revents |= POLLERR;
+        }
+        if handle.is_hung_up() {
+            poll_fd.revents |= POLLHUP;
+        }
+        if poll_fd.revents != 0 {
+            count += 1;
+        }
+        handles.push(handle);
+    }
+
+    if count == 0 {
+        let condvars: Vec<&Condvar> = handles.iter().filter_map(|h| h.condvar()).collect();
+        let result = wait(&condvars, timeout);
+        if result != Ok(0) {
+            return result;
+        }
+    }
+    for (i, poll_fd) in poll_fds.iter().enumerate() {
+        write_user(fds + i * size_of::<PollFd>(), *poll_fd)?;
+    }
+    Ok(count)
+}
+
+/// 创建 epoll 实例
+pub(super) fn sys_epoll_create1(flags: usize) -> SysResult {
+    // 只接受 EPOLL_CLOEXEC
+    if flags & !0o2_000_000 != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let handle = FileHandle::new(
+        Epoll::new(),
+        OpenOptions {
+            read: true,
+            ..OpenOptions::default()
+        },
+        String::from("epoll"),
+    );
+    add_descriptor(handle)
+}
+
+/// 取得 epoll 实例
+fn epoll(handle: &FileHandle) -> SysResult<&Epoll> {
+    handle.inode.downcast_ref::<Epoll>().ok_or(Errno::EINVAL)
+}
+
+/// 添加、修改或删除 epoll 实例中监听的文件描述符
+pub(super) fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: usize) -> SysResult {
+    let handle = descriptor(epfd)?;
+    let epoll = epoll(&handle)?;
+    let target = descriptor(fd)?;
+    let result = match op {
+        EPOLL_CTL_ADD => {
+            let event = read_user::<EpollEvent>(event)?;
+            epoll.add(fd, &target, event.events, event.data)
+        }
+        EPOLL_CTL_MOD => {
+            let event = read_user::<EpollEvent>(event)?;
+            epoll.modify(fd, event.events, event.data)
+        }
+        EPOLL_CTL_DEL => epoll.remove(fd),
+        _ => return Err(Errno::EINVAL),
+    };
+    match result {
+        Ok(()) => Ok(0),
+        // 普通文件不能被监听
+        Err(FsError::NotSupported) => Err(Errno::EPERM),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// 等待 epoll 实例中的文件就绪，`timeout` 以毫秒为单位，负数表示一直等待
+///
+/// 暂不支持信号屏蔽字
+pub(super) fn sys_epoll_pwait(
+    epfd: usize,
+    buffer: usize,
+    max_events: usize,
+    timeout: isize,
+) -> SysResult {
+    finish(epoll_pwait(epfd, buffer, max_events, timeout))
+}
+
+fn epoll_pwait(epfd: usize, buffer: usize, max_events: usize, timeout: isize) -> SysResult {
+    let max_events = max_events as i32;
+    if max_events <= 0 {
+        return Err(Errno::EINVAL);
+    }
+    let handle = descriptor(epfd)?;
+    let epoll = epoll(&handle)?;
+    // 先检查用户的缓冲区，避免取出的事件丢失
+    user_slice_mut(buffer, max_events as usize * size_of::<EpollEvent>())?;
+    let ready = epoll.collect(max_events as usize);
+    if ready.is_empty() {
+        let timeout = match timeout as i32 {
+            timeout if timeout < 0 => None,
+            timeout => {
+                let millis_per_tick = 1000 / TICKS_PER_SECOND;
+                Some((timeout as usize + millis_per_tick - 1) / millis_per_tick)
+            }
+        };
+        return wait(&[epoll.condvar()], timeout);
+    }
+    for (i, &(events, data)) in ready.iter().enumerate() {
+        write_user(
+            buffer + i * size_of::<EpollEvent>(),
+            EpollEvent { events, data },
+        )?;
+    }
+    Ok(ready.len())
+}
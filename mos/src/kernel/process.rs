@@ -0,0 +1,81 @@
+//! 进程相关的系统调用
+
+use super::*;
+
+/// `clone` 的参数中表示子进程退出时发送的信号的位
+const CSIGNAL: usize = 0xff;
+
+/// 结束当前线程
+pub(super) fn sys_exit(code: usize) -> SyscallResult {
+    debug!(
+        "thread {} exit with code {}",
+        PROCESSOR.lock().current_thread().id,
+        code as i32
+    );
+    SyscallResult::Kill
+}
+
+/// 结束当前进程的所有线程
+pub(super) fn sys_exit_group(code: usize) -> SyscallResult {
+    debug!(
+        "process {} exit with code {}",
+        current_process().id,
+        code as i32
+    );
+    SyscallResult::KillProcess
+}
+
+/// 当前进程的 ID
+pub(super) fn sys_getpid() -> SysResult {
+    Ok(current_process().id as usize)
+}
+
+/// 当前线程的 ID
+pub(super) fn sys_gettid() -> SysResult {
+    Ok(PROCESSOR.lock().current_thread().id as usize)
+}
+
+/// 按照 ID 找到用户进程，0 表示当前进程
+fn find_process(pid: isize) -> SysResult<Arc<Process>> {
+    if pid == 0 {
+        return Ok(current_process());
+    }
+    process_threads(pid)
+        .first()
+        .map(|thread| thread.process.clone())
+        .filter(|process| process.is_user)
+        .ok_or(Errno::ESRCH)
+}
+
+/// 设置进程所在的进程组，`pgid` 为 0 时使用进程自身的 ID
+pub(super) fn sys_setpgid(pid: isize, pgid: isize) -> SysResult {
+    if pgid < 0 {
+        return Err(Errno::EINVAL);
+    }
+    let process = find_process(pid)?;
+    process.inner().pgid = if pgid == 0 { process.id } else { pgid };
+    Ok(0)
+}
+
+/// 取得进程所在的进程组
+pub(super) fn sys_getpgid(pid: isize) -> SysResult {
+    Ok(find_process(pid)?.inner().pgid as usize)
+}
+
+/// 复制当前进程，只支持 `fork` 的语义，返回子进程的 ID
+///
+/// 子进程从同一个位置继续执行，其中 `clone` 的返回值为 0
+pub(super) fn sys_clone(context: &Context, flags: usize) -> SysResult {
+    // 不支持共享内存空间、文件描述符表等选项
+    if flags & !CSIGNAL != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let thread = PROCESSOR.lock().current_thread();
+    let process = thread.process.fork().map_err(|_| Errno::ENOMEM)?;
+    let mut child_context = *context;
+    child_context.x[10] = 0;
+    PROCESSOR
+        .lock()
+        .add_thread(thread.fork(process.clone(), child_context));
+    Ok(process.id as usize)
+}
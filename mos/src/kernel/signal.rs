@@ -0,0 +1,405 @@
+//! 信号的发送、屏蔽和处理函数，以及返回用户态之前的递送
+//!
+//! 递送给处理函数时，被打断的 `Context` 保存在用户栈上的信号栈帧中，随后改写 `Context` 跳转到处理函数。
+//! Linux 在 riscv64 上通过 vDSO 提供处理函数的返回地址，这里没有 vDSO，
+//! 而是令处理函数返回到不会被映射的 [`SIGNAL_TRAMPOLINE`]，由此产生的缺页异常和 `rt_sigreturn` 一样恢复栈帧。
+//!
+//! 休眠中的线程收到信号时会被唤醒，被打断的系统调用在处理函数返回后重新执行，相当于总是设置了 `SA_RESTART`
+
+use super::*;
+use crate::process::process::ProcessID;
+use crate::process::processor::release_thread;
+use crate::process::signal::*;
+use crate::process::thread::{Thread, ThreadID};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::mem::size_of;
+use riscv::register::sstatus::SPP;
+
+/// 信号处理函数的返回地址，位于用户地址空间的最高一页，不会被映射
+pub const SIGNAL_TRAMPOLINE: usize = 0x3f_ffff_f000;
+
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+/// 信号栈帧，递送时压入用户栈
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    /// `siginfo_t`，只填写了 `si_signo`
+    info: [i32; 32],
+    /// 被打断时的 `Context`，和 Linux 的 `ucontext_t` 布局不同
+    context: Context,
+    /// 被打断时的信号屏蔽字
+    blocked: u64,
+}
+
+/// 检查信号编号，0 只用于检查目标是否存在
+fn check_signal(signal: usize) -> SysResult<()> {
+    if signal > SIGNAL_COUNT {
+        Err(Errno::EINVAL)
+    } else {
+        Ok(())
+    }
+}
+
+/// 检查用户传入的信号集合的大小
+fn check_sigset_size(size: usize) -> SysResult<()> {
+    if size != size_of::<u64>() {
+        Err(Errno::EINVAL)
+    } else {
+        Ok(())
+    }
+}
+
+/// 进程中的所有线程
+pub fn process_threads(pid: ProcessID) -> Vec<Arc<Thread>> {
+    PROCESSOR
+        .lock()
+        .threads()
+        .into_iter()
+        .filter(|thread| thread.process.id == pid)
+        .collect()
+}
+
+/// 向线程发送信号
+///
+/// `SIGCONT` 和 `SIGKILL` 在发送时就恢复被暂停的线程；信号没有被屏蔽时，唤醒休眠的线程来处理它
+pub fn send_signal(thread: &Arc<Thread>, signal: usize) {
+    let wake = {
+        let mut inner = thread.inner();
+        let signals = &mut inner.signals;
+        match signal {
+            SIGKILL | SIGCONT => {
+                signals.stopped = false;
+                signals.pending &= !STOP_SIGNALS;
+            }
+            _ if bit(signal) & STOP_SIGNALS != 0 => signals.pending &= !bit(SIGCONT),
+            _ => {}
+        }
+        signals.pending |= bit(signal);
+        let wake = bit(signal) & !signals.blocked != 0 || signal == SIGCONT;
+        wake && inner.sleeping
+    };
+    if wake {
+        PROCESSOR.lock().wake_thread(thread.clone());
+    }
+}
+
+/// 向当前线程发送由异常产生的信号
+///
+/// 这类信号被屏蔽或忽略时，线程会反复触发同一个异常，因此恢复为默认行为
+pub fn force_signal(signal: usize) {
+    let thread = PROCESSOR.lock().current_thread();
+    {
+        let mut process = thread.process.inner();
+        let mut inner = thread.inner();
+        let action = &mut process.signal_actions[signal - 1];
+        if inner.signals.blocked & bit(signal) != 0 || action.handler == SIG_IGN {
+            *action = SignalAction::default();
+            inner.signals.blocked &= !bit(signal);
+        }
+    }
+    send_signal(&thread, signal);
+}
+
+/// 返回用户态之前，为即将运行的线程递送信号
+///
+/// `context` 是即将恢复的 `Context`，位于内核栈顶，调用处理函数时就地改写它。
+/// 线程被终止或暂停时切换到下一个线程，并继续为其递送信号
+pub fn handle_signals(mut context: *mut Context) -> *mut Context {
+    loop {
+        match deliver(unsafe { &mut *context }) {
+            Some(next) => context = next,
+            None => return context,
+        }
+    }
+}
+
+/// 递送当前线程的信号，需要切换线程时返回下一个线程的 `Context`
+fn deliver(context: &mut Context) -> Option<*mut Context> {
+    let thread = PROCESSOR.lock().current_thread();
+    // 内核线程不处理信号
+    if !thread.process.is_user || context.sstatus.spp() != SPP::User {
+        return None;
+    }
+    loop {
+        let mut inner = thread.inner();
+        if inner.signals.stopped {
+            drop(inner);
+            return Some(stop_current_thread(context));
+        }
+        let deliverable = inner.signals.pending & !inner.signals.blocked;
+        if deliverable == 0 {
+            return None;
+        }
+        let signal = deliverable.trailing_zeros() as usize + 1;
+        inner.signals.pending &= !bit(signal);
+        let blocked = inner.signals.blocked;
+        drop(inner);
+
+        let action = thread.process.inner().signal_actions[signal - 1];
+        if action.is_ignored(signal) {
+            continue;
+        }
+        if action.handler == SIG_DFL {
+            match default_action(signal) {
+                DefaultAction::Terminate => return Some(terminate(&thread, signal)),
+                DefaultAction::Stop => {
+                    thread.inner().signals.stopped = true;
+                    return Some(stop_current_thread(context));
+                }
+                _ => continue,
+            }
+        }
+        return match push_frame(&thread, context, signal, &action, blocked) {
+            Ok(()) => None,
+            // 用户栈无法写入
+            Err(_) => Some(terminate(&thread, SIGSEGV)),
+        };
+    }
+}
+
+/// 暂停当前线程，直到收到 `SIGCONT` 或 `SIGKILL`
+fn stop_current_thread(context: &Context) -> *mut Context {
+    let mut processor = PROCESSOR.lock();
+    processor.park_current_thread(context);
+    processor.sleep_current_thread();
+    processor.prepare_next_thread()
+}
+
+/// 以信号的默认行为终止当前线程所在的进程
+fn terminate(thread: &Arc<Thread>, signal: usize) -> *mut Context {
+    info!("process {} killed by signal {}", thread.process.id, signal);
+    kill_process(thread)
+}
+
+/// 终止当前线程所在进程的所有线程，返回下一个要执行的线程的 `Context`
+pub(super) fn kill_process(thread: &Arc<Thread>) -> *mut Context {
+    let others: Vec<_> = process_threads(thread.process.id)
+        .into_iter()
+        .filter(|other| other.id != thread.id)
+        .collect();
+    let current = {
+        let mut processor = PROCESSOR.lock();
+        for other in &others {
+            processor.kill_thread(other);
+        }
+        processor.kill_current_thread()
+    };
+    // 释放锁之后再丢弃线程
+    for other in others {
+        release_thread(other);
+    }
+    release_thread(current);
+    PROCESSOR.lock().prepare_next_thread()
+}
+
+/// 在用户栈上压入信号栈帧，改写 `Context` 以调用处理函数
+///
+/// 处理函数的参数依次为信号编号、`siginfo_t` 和被打断时的 `Context`
+fn push_frame(
+    thread: &Thread,
+    context: &mut Context,
+    signal: usize,
+    action: &SignalAction,
+    blocked: u64,
+) -> SysResult<()> {
+    let address = context
+        .sp()
+        .checked_sub(size_of::<SignalFrame>())
+        .ok_or(Errno::EFAULT)?
+        & !0xf;
+    let mut info = [0; 32];
+    info[0] = signal as i32;
+    write_user(
+        address,
+        SignalFrame {
+            info,
+            context: *context,
+            blocked,
+        },
+    )?;
+
+    let mut blocked = blocked | action.mask;
+    if action.flags & SA_NODEFER == 0 {
+        blocked |= bit(signal);
+    }
+    thread.inner().signals.blocked = blocked & !UNBLOCKABLE;
+    if action.flags & SA_RESETHAND != 0 {
+        thread.process.inner().signal_actions[signal - 1] = SignalAction::default();
+    }
+
+    context.sepc = action.handler;
+    context
+        .set_ra(SIGNAL_TRAMPOLINE)
+        .set_sp(address)
+        .set_arguments(&[signal, address, address + size_of::<[i32; 32]>()]);
+    Ok(())
+}
+
+/// 从信号处理函数返回，恢复信号栈帧中的 `Context` 和信号屏蔽字，返回恢复后的 `a0`
+///
+/// 栈帧无法读取时向线程发送 `SIGSEGV`
+pub fn signal_return(context: &mut Context) -> usize {
+    match read_user::<SignalFrame>(context.sp()) {
+        Ok(frame) => {
+            // 不恢复 sstatus，避免用户程序借此进入内核态
+            context.x = frame.context.x;
+            context.sepc = frame.context.sepc;
+            PROCESSOR.lock().current_thread().inner().signals.blocked =
+                frame.blocked & !UNBLOCKABLE;
+        }
+        Err(_) => force_signal(SIGSEGV),
+    }
+    context.x[10]
+}
+
+/// 向一个进程发送信号，`threads` 是进程中的所有线程
+///
+/// 暂停、继续和 `SIGKILL` 作用于进程的所有线程，其他信号递送给任意一个没有屏蔽它的线程
+fn signal_process(threads: &[Arc<Thread>], signal: usize) {
+    if signal == 0 || threads.is_empty() {
+        return;
+    }
+    if bit(signal) & (STOP_SIGNALS | bit(SIGCONT) | bit(SIGKILL)) != 0 {
+        for thread in threads {
+            send_signal(thread, signal);
+        }
+    } else {
+        let target = threads
+            .iter()
+            .find(|thread| thread.inner().signals.blocked & bit(signal) == 0)
+            .unwrap_or(&threads[0]);
+        send_signal(target, signal);
+    }
+}
+
+/// 向满足条件的所有用户进程发送信号，返回进程的个数
+fn signal_processes(filter: impl Fn(&Process) -> bool, signal: usize) -> usize {
+    let mut processes: BTreeMap<ProcessID, Vec<Arc<Thread>>> = BTreeMap::new();
+    for thread in PROCESSOR.lock().threads() {
+        if thread.process.is_user && filter(&thread.process) {
+            processes.entry(thread.process.id).or_default().push(thread);
+        }
+    }
+    for threads in processes.values() {
+        signal_process(threads, signal);
+    }
+    processes.len()
+}
+
+/// 向进程组中的所有进程发送信号，返回进程的个数
+pub fn signal_group(pgid: ProcessID, signal: usize) -> usize {
+    signal_processes(|process| process.inner().pgid == pgid, signal)
+}
+
+/// 向进程发送信号
+///
+/// `pid` 为 0 时发送给当前进程组，为 -1 时发送给除自己以外的所有进程，小于 -1 时发送给进程组 `-pid`
+pub(super) fn sys_kill(pid: isize, signal: usize) -> SysResult {
+    check_signal(signal)?;
+    let count = match pid {
+        0 => {
+            // signal_group 需要锁住每个进程，包括当前进程
+            let pgid = current_process().inner().pgid;
+            signal_group(pgid, signal)
+        }
+        -1 => {
+            let current = current_process().id;
+            signal_processes(|process| process.id != current, signal)
+        }
+        pid if pid < 0 => signal_group(-pid, signal),
+        pid => signal_processes(|process| process.id == pid, signal),
+    };
+    if count == 0 {
+        return Err(Errno::ESRCH);
+    }
+    Ok(0)
+}
+
+/// 向线程发送信号，`tgid` 不为 `None` 时线程必须属于这个进程
+pub(super) fn sys_tgkill(tgid: Option<ProcessID>, tid: ThreadID, signal: usize) -> SysResult {
+    check_signal(signal)?;
+    let thread = PROCESSOR
+        .lock()
+        .threads()
+        .into_iter()
+        .find(|thread| thread.id == tid && tgid.map_or(true, |tgid| thread.process.id == tgid))
+        .ok_or(Errno::ESRCH)?;
+    if !thread.process.is_user {
+        return Err(Errno::EPERM);
+    }
+    if signal != 0 {
+        send_signal(&thread, signal);
+    }
+    Ok(0)
+}
+
+/// 设置信号的处理方式，并取得之前的处理方式
+pub(super) fn sys_rt_sigaction(
+    signal: usize,
+    action: usize,
+    old_action: usize,
+    sigset_size: usize,
+) -> SysResult {
+    check_sigset_size(sigset_size)?;
+    if signal == 0 || signal > SIGNAL_COUNT {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let old = process.inner().signal_actions[signal - 1];
+    if action != 0 {
+        if bit(signal) & UNBLOCKABLE != 0 {
+            return Err(Errno::EINVAL);
+        }
+        let mut action = read_user::<SignalAction>(action)?;
+        action.mask &= !UNBLOCKABLE;
+        process.inner().signal_actions[signal - 1] = action;
+        // 改为忽略时，丢弃已经在等待递送的信号
+        if action.is_ignored(signal) {
+            for thread in process_threads(process.id) {
+                thread.inner().signals.pending &= !bit(signal);
+            }
+        }
+    }
+    if old_action != 0 {
+        write_user(old_action, old)?;
+    }
+    Ok(0)
+}
+
+/// 修改当前线程的信号屏蔽字，并取得之前的屏蔽字
+pub(super) fn sys_rt_sigprocmask(
+    how: usize,
+    set: usize,
+    old_set: usize,
+    sigset_size: usize,
+) -> SysResult {
+    check_sigset_size(sigset_size)?;
+    let thread = PROCESSOR.lock().current_thread();
+    let old = thread.inner().signals.blocked;
+    if set != 0 {
+        let set = read_user::<u64>(set)?;
+        let blocked = match how {
+            SIG_BLOCK => old | set,
+            SIG_UNBLOCK => old & !set,
+            SIG_SETMASK => set,
+            _ => return Err(Errno::EINVAL),
+        };
+        thread.inner().signals.blocked = blocked & !UNBLOCKABLE;
+    }
+    if old_set != 0 {
+        write_user(old_set, old)?;
+    }
+    Ok(0)
+}
+
+/// 取得当前线程被屏蔽而在等待递送的信号
+pub(super) fn sys_rt_sigpending(set: usize, sigset_size: usize) -> SysResult {
+    check_sigset_size(sigset_size)?;
+    let thread = PROCESSOR.lock().current_thread();
+    let pending = thread.inner().signals.pending;
+    write_user(set, pending)?;
+    Ok(0)
+}
@@ -0,0 +1,250 @@
+//! 套接字相关的系统调用
+//!
+//! 只支持 IPv4 的 TCP 和 UDP。套接字放在文件描述符表中，也可以用 `read`、`write` 和 `close` 操作；
+//! 需要等待时和管道一样在条件变量上休眠，以 `SOCK_NONBLOCK` 创建或者带有 `MSG_DONTWAIT` 时返回 `EAGAIN`
+
+use super::*;
+use crate::net::{NetError, Socket, SocketKind};
+use crate::process::signal::SIGPIPE;
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+
+const AF_INET: u16 = 2;
+const SOCK_STREAM: usize = 1;
+const SOCK_DGRAM: usize = 2;
+const SOCK_TYPE_MASK: usize = 0xf;
+const SOCK_NONBLOCK: usize = 0o4000;
+const SOCK_CLOEXEC: usize = 0o2_000_000;
+const IPPROTO_TCP: usize = 6;
+const IPPROTO_UDP: usize = 17;
+
+const SHUT_RD: usize = 0;
+const SHUT_WR: usize = 1;
+const SHUT_RDWR: usize = 2;
+
+/// `send` 和 `recv` 不阻塞
+const MSG_DONTWAIT: usize = 0x40;
+
+/// `struct sockaddr_in`，端口和地址都是网络字节序
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SockAddrIn {
+    family: u16,
+    port: [u8; 2],
+    addr: [u8; 4],
+    zero: [u8; 8],
+}
+
+/// 读取用户传入的地址
+fn read_address(address: usize, len: usize) -> SysResult<IpEndpoint> {
+    if len < core::mem::size_of::<SockAddrIn>() {
+        return Err(Errno::EINVAL);
+    }
+    let sockaddr: SockAddrIn = read_user(address)?;
+    if sockaddr.family != AF_INET {
+        return Err(Errno::EAFNOSUPPORT);
+    }
+    Ok(IpEndpoint::new(
+        IpAddress::Ipv4(Ipv4Address(sockaddr.addr)),
+        u16::from_be_bytes(sockaddr.port),
+    ))
+}
+
+/// 将地址写回用户，`len` 指向缓冲区的长度，之后写入地址的实际长度
+fn write_address(address: usize, len: usize, endpoint: IpEndpoint) -> SysResult<()> {
+    if address == 0 {
+        return Ok(());
+    }
+    let addr = match endpoint.addr {
+        IpAddress::Ipv4(addr) => addr.0,
+        _ => [0; 4],
+    };
+    let sockaddr = SockAddrIn {
+        family: AF_INET,
+        port: endpoint.port.to_be_bytes(),
+        addr,
+        zero: [0; 8],
+    };
+    let size = core::mem::size_of::<SockAddrIn>();
+    let capacity = read_user::<u32>(len)? as usize;
+    let bytes = unsafe { core::slice::from_raw_parts(&sockaddr as *const _ as *const u8, size) };
+    let count = capacity.min(size);
+    user_slice_mut(address, count)?.copy_from_slice(&bytes[..count]);
+    write_user(len, size as u32)
+}
+
+/// 对文件描述符对应的套接字执行操作，不是套接字时返回 `ENOTSOCK`
+fn with_socket(fd: usize, f: impl FnOnce(&FileHandle, &Socket) -> SysResult) -> SysResult {
+    let handle = descriptor(fd)?;
+    let socket = handle
+        .inode
+        .downcast_ref::<Socket>()
+        .ok_or(Errno::ENOTSOCK)?;
+    f(&handle, socket)
+}
+
+/// 操作需要等待时阻塞，`dontwait` 表示这次调用不阻塞
+fn wait(handle: &FileHandle, error: NetError, dontwait: bool) -> SysResult {
+    match error {
+        NetError::Again if !dontwait => block_on(handle),
+        error => Err(error.into()),
+    }
+}
+
+/// 为套接字分配文件描述符
+fn add_socket(socket: Arc<Socket>, nonblock: bool) -> SysResult {
+    let handle = FileHandle::new(
+        socket,
+        OpenOptions {
+            read: true,
+            write: true,
+            nonblock,
+            ..OpenOptions::default()
+        },
+        String::from("socket"),
+    );
+    add_descriptor(handle)
+}
+
+/// 创建套接字
+pub(super) fn sys_socket(domain: usize, type_: usize, protocol: usize) -> SysResult {
+    if domain != AF_INET as usize {
+        return Err(Errno::EAFNOSUPPORT);
+    }
+    if type_ & !(SOCK_TYPE_MASK | SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let kind = match (type_ & SOCK_TYPE_MASK, protocol) {
+        (SOCK_STREAM, 0) | (SOCK_STREAM, IPPROTO_TCP) => SocketKind::Tcp,
+        (SOCK_DGRAM, 0) | (SOCK_DGRAM, IPPROTO_UDP) => SocketKind::Udp,
+        (SOCK_STREAM, _) | (SOCK_DGRAM, _) => return Err(Errno::EPROTONOSUPPORT),
+        _ => return Err(Errno::EINVAL),
+    };
+    add_socket(Socket::new(kind)?, type_ & SOCK_NONBLOCK != 0)
+}
+
+/// 绑定本地地址
+pub(super) fn sys_bind(fd: usize, address: usize, len: usize) -> SysResult {
+    let endpoint = read_address(address, len)?;
+    with_socket(fd, |_, socket| {
+        socket.bind(endpoint)?;
+        Ok(0)
+    })
+}
+
+/// 开始监听连接
+pub(super) fn sys_listen(fd: usize, backlog: usize) -> SysResult {
+    with_socket(fd, |_, socket| {
+        socket.listen(backlog)?;
+        Ok(0)
+    })
+}
+
+/// 接受一个连接，返回新的文件描述符，`address` 中写入对端地址
+pub(super) fn sys_accept4(fd: usize, address: usize, len: usize, flags: usize) -> SysResult {
+    if flags & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+        return Err(Errno::EINVAL);
+    }
+    with_socket(fd, |handle, socket| {
+        let connection = match socket.accept() {
+            Ok(connection) => connection,
+            Err(error) => return wait(handle, error, false),
+        };
+        write_address(address, len, connection.peer_endpoint()?)?;
+        add_socket(connection, flags & SOCK_NONBLOCK != 0)
+    })
+}
+
+/// 连接对端，阻塞的 TCP 套接字等待连接建立
+pub(super) fn sys_connect(fd: usize, address: usize, len: usize) -> SysResult {
+    let endpoint = read_address(address, len)?;
+    with_socket(fd, |handle, socket| match socket.connect(endpoint) {
+        Ok(()) => Ok(0),
+        // 被唤醒后重新执行，直到连接建立或被拒绝
+        Err(NetError::InProgress) | Err(NetError::Already) if !handle.options.nonblock => {
+            block_on(handle)
+        }
+        Err(error) => Err(error.into()),
+    })
+}
+
+/// 本地地址
+pub(super) fn sys_getsockname(fd: usize, address: usize, len: usize) -> SysResult {
+    with_socket(fd, |_, socket| {
+        write_address(address, len, socket.local_endpoint())?;
+        Ok(0)
+    })
+}
+
+/// 对端地址
+pub(super) fn sys_getpeername(fd: usize, address: usize, len: usize) -> SysResult {
+    with_socket(fd, |_, socket| {
+        write_address(address, len, socket.peer_endpoint()?)?;
+        Ok(0)
+    })
+}
+
+/// 发送数据，`address` 不为 0 时作为 UDP 的目的地址
+pub(super) fn sys_sendto(
+    fd: usize,
+    buffer: usize,
+    size: usize,
+    flags: usize,
+    address: usize,
+    len: usize,
+) -> SysResult {
+    let destination = match address {
+        0 => None,
+        address => Some(read_address(address, len)?),
+    };
+    let data = user_slice(buffer, size)?;
+    with_socket(fd, |handle, socket| match socket.send(data, destination) {
+        Ok(len) => Ok(len),
+        Err(NetError::BrokenPipe) => {
+            // 和 write 一样，同时发送 SIGPIPE
+            let thread = PROCESSOR.lock().current_thread();
+            send_signal(&thread, SIGPIPE);
+            Err(Errno::EPIPE)
+        }
+        Err(error) => wait(handle, error, flags & MSG_DONTWAIT != 0),
+    })
+}
+
+/// 接收数据，`address` 不为 0 时写入对端地址
+pub(super) fn sys_recvfrom(
+    fd: usize,
+    buffer: usize,
+    size: usize,
+    flags: usize,
+    address: usize,
+    len: usize,
+) -> SysResult {
+    let data = user_slice_mut(buffer, size)?;
+    with_socket(fd, |handle, socket| match socket.recv(data) {
+        Ok((size, peer)) => {
+            if let Some(peer) = peer {
+                write_address(address, len, peer)?;
+            }
+            Ok(size)
+        }
+        Err(error) => wait(handle, error, flags & MSG_DONTWAIT != 0),
+    })
+}
+
+/// 设置套接字选项，目前全部忽略
+pub(super) fn sys_setsockopt(fd: usize) -> SysResult {
+    with_socket(fd, |_, _| Ok(0))
+}
+
+/// 关闭连接的一个或两个方向
+pub(super) fn sys_shutdown(fd: usize, how: usize) -> SysResult {
+    let (read, write) = match how {
+        SHUT_RD => (true, false),
+        SHUT_WR => (false, true),
+        SHUT_RDWR => (true, true),
+        _ => return Err(Errno::EINVAL),
+    };
+    with_socket(fd, |_, socket| {
+        socket.shutdown(read, write)?;
+        Ok(0)
+    })
+}
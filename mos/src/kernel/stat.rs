@@ -0,0 +1,82 @@
+//! 用户程序看到的文件元数据 [`Stat`] 和目录项
+
+use super::*;
+
+/// 文件类型在 `st_mode` 中的位
+fn mode_type(type_: FileType) -> u32 {
+    match type_ {
+        FileType::File => 0o100_000,
+        FileType::Dir => 0o040_000,
+        FileType::SymLink => 0o120_000,
+        FileType::CharDevice => 0o020_000,
+        FileType::BlockDevice => 0o060_000,
+        FileType::NamedPipe => 0o010_000,
+        FileType::Socket => 0o140_000,
+    }
+}
+
+/// 目录项中的文件类型 `d_type`
+pub fn dirent_type(type_: FileType) -> u8 {
+    match type_ {
+        FileType::NamedPipe => 1,
+        FileType::CharDevice => 2,
+        FileType::Dir => 4,
+        FileType::BlockDevice => 6,
+        FileType::File => 8,
+        FileType::SymLink => 10,
+        FileType::Socket => 12,
+    }
+}
+
+/// `linux_dirent64` 中名字之前的部分的长度
+pub const DIRENT_HEADER_SIZE: usize = 19;
+
+/// Linux riscv64 的 `struct stat`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stat {
+    dev: u64,
+    ino: u64,
+    mode: u32,
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    rdev: u64,
+    __pad1: u64,
+    size: i64,
+    blksize: i32,
+    __pad2: i32,
+    blocks: i64,
+    atime_sec: i64,
+    atime_nsec: i64,
+    mtime_sec: i64,
+    mtime_nsec: i64,
+    ctime_sec: i64,
+    ctime_nsec: i64,
+    __unused: [u32; 2],
+}
+
+impl From<Metadata> for Stat {
+    fn from(metadata: Metadata) -> Self {
+        Self {
+            dev: metadata.dev as u64,
+            ino: metadata.inode as u64,
+            mode: mode_type(metadata.type_) | metadata.mode as u32,
+            nlink: metadata.nlinks as u32,
+            uid: metadata.uid as u32,
+            gid: metadata.gid as u32,
+            rdev: metadata.rdev as u64,
+            size: metadata.size as i64,
+            blksize: metadata.blk_size as i32,
+            // 各文件系统的 `blocks` 和 st_blocks 一样以 512 字节为单位，与 `blk_size` 无关
+            blocks: metadata.blocks as i64,
+            atime_sec: metadata.atime.sec,
+            atime_nsec: metadata.atime.nsec as i64,
+            mtime_sec: metadata.mtime.sec,
+            mtime_nsec: metadata.mtime.nsec as i64,
+            ctime_sec: metadata.ctime.sec,
+            ctime_nsec: metadata.ctime.nsec as i64,
+            ..Self::default()
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! 系统调用的分发
+
+use super::*;
+use crate::process::processor::release_thread;
+
+pub const SYS_GETCWD: usize = 17;
+pub const SYS_EPOLL_CREATE1: usize = 20;
+pub const SYS_EPOLL_CTL: usize = 21;
+pub const SYS_EPOLL_PWAIT: usize = 22;
+pub const SYS_DUP: usize = 23;
+pub const SYS_DUP3: usize = 24;
+pub const SYS_IOCTL: usize = 29;
+pub const SYS_MKDIRAT: usize = 34;
+pub const SYS_UNLINKAT: usize = 35;
+pub const SYS_LINKAT: usize = 37;
+pub const SYS_CHDIR: usize = 49;
+pub const SYS_OPENAT: usize = 56;
+pub const SYS_CLOSE: usize = 57;
+pub const SYS_PIPE2: usize = 59;
+pub const SYS_GETDENTS64: usize = 61;
+pub const SYS_READ: usize = 63;
+pub const SYS_WRITE: usize = 64;
+pub const SYS_PPOLL: usize = 73;
+pub const SYS_FSTATAT: usize = 79;
+pub const SYS_FSTAT: usize = 80;
+pub const SYS_SYNC: usize = 81;
+pub const SYS_EXIT: usize = 93;
+pub const SYS_EXIT_GROUP: usize = 94;
+pub const SYS_CLOCK_GETTIME: usize = 113;
+pub const SYS_SYSLOG: usize = 116;
+pub const SYS_KILL: usize = 129;
+pub const SYS_TKILL: usize = 130;
+pub const SYS_TGKILL: usize = 131;
+pub const SYS_RT_SIGACTION: usize = 134;
+pub const SYS_RT_SIGPROCMASK: usize = 135;
+pub const SYS_RT_SIGPENDING: usize = 136;
+pub const SYS_RT_SIGRETURN: usize = 139;
+pub const SYS_REBOOT: usize = 142;
+pub const SYS_SETPGID: usize = 154;
+pub const SYS_GETPGID: usize = 155;
+pub const SYS_GETTIMEOFDAY: usize = 169;
+pub const SYS_GETPID: usize = 172;
+pub const SYS_GETTID: usize = 178;
+pub const SYS_SOCKET: usize = 198;
+pub const SYS_BIND: usize = 200;
+pub const SYS_LISTEN: usize = 201;
+pub const SYS_ACCEPT: usize = 202;
+pub const SYS_CONNECT: usize = 203;
+pub const SYS_GETSOCKNAME: usize = 204;
+pub const SYS_GETPEERNAME: usize = 205;
+pub const SYS_SENDTO: usize = 206;
+pub const SYS_RECVFROM: usize = 207;
+pub const SYS_SETSOCKOPT: usize = 208;
+pub const SYS_SHUTDOWN: usize = 210;
+pub const SYS_CLONE: usize = 220;
+pub const SYS_ACCEPT4: usize = 242;
+pub const SYS_RENAMEAT2: usize = 276;
+pub const SYS_GETRANDOM: usize = 278;
+
+/// 系统调用在内核之内的返回值
+pub(super) enum SyscallResult {
+    /// 继续执行，带有返回值
+    Proceed(isize),
+    /// 记录返回值，但暂存当前线程
+    Park(isize),
+    /// 丢弃当前 context，调度下一个线程继续执行
+    Kill,
+    /// 终止当前进程的所有线程，调度下一个线程继续执行
+    KillProcess,
+    /// 当前线程已进入休眠，被唤醒后重新执行这个系统调用
+    Restart,
+}
+
+impl From<SysResult> for SyscallResult {
+    fn from(result: SysResult) -> Self {
+        match result {
+            Ok(value) => SyscallResult::Proceed(value as isize),
+            Err(Errno::ERESTARTSYS) => SyscallResult::Restart,
+            Err(errno) => SyscallResult::Proceed(-errno.0),
+        }
+    }
+}
+
+/// 系统调用的总入口
+///
+/// 调用号在 `a7` 中，参数在 `a0` 到 `a5` 中，返回值写回 `a0`
+pub fn syscall_handler(context: &mut Context) -> *mut Context {
+    // 无论如何处理，一定会跳过当前的 ecall 指令
+    context.sepc += 4;
+
+    let syscall_id = context.x[17];
+    let args = [
+        context.x[10],
+        context.x[11],
+        context.x[12],
+        context.x[13],
+        context.x[14],
+        context.x[15],
+    ];
+
+    let result = match syscall_id {
+        SYS_GETCWD => sys_getcwd(args[0], args[1]).into(),
+        SYS_EPOLL_CREATE1 => sys_epoll_create1(args[0]).into(),
+        SYS_EPOLL_CTL => sys_epoll_ctl(args[0], args[1], args[2], args[3]).into(),
+        SYS_EPOLL_PWAIT => sys_epoll_pwait(args[0], args[1], args[2], args[3] as isize).into(),
+        SYS_DUP => sys_dup(args[0]).into(),
+        SYS_DUP3 => sys_dup3(args[0], args[1], args[2]).into(),
+        SYS_IOCTL => sys_ioctl(args[0], args[1], args[2]).into(),
+        SYS_MKDIRAT => sys_mkdirat(args[0] as isize, args[1], args[2]).into(),
+        SYS_UNLINKAT => sys_unlinkat(args[0] as isize, args[1], args[2]).into(),
+        SYS_LINKAT => sys_linkat(
+            args[0] as isize,
+            args[1],
+            args[2] as isize,
+            args[3],
+            args[4],
+        )
+        .into(),
+        SYS_CHDIR => sys_chdir(args[0]).into(),
+        SYS_OPENAT => sys_openat(args[0] as isize, args[1], args[2], args[3]).into(),
+        SYS_CLOSE => sys_close(args[0]).into(),
+        SYS_PIPE2 => sys_pipe2(args[0], args[1]).into(),
+        SYS_GETDENTS64 => sys_getdents64(args[0], args[1], args[2]).into(),
+        SYS_READ => sys_read(args[0], args[1], args[2]).into(),
+        SYS_WRITE => sys_write(args[0], args[1], args[2]).into(),
+        SYS_PPOLL => sys_ppoll(args[0], args[1], args[2]).into(),
+        SYS_FSTATAT => sys_fstatat(args[0] as isize, args[1], args[2], args[3]).into(),
+        SYS_FSTAT => sys_fstat(args[0], args[1]).into(),
+        SYS_SYNC => sys_sync().into(),
+        SYS_EXIT => sys_exit(args[0]),
+        SYS_EXIT_GROUP => sys_exit_group(args[0]),
+        SYS_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1]).into(),
+        SYS_SYSLOG => sys_syslog(args[0], args[1], args[2]).into(),
+        SYS_KILL => sys_kill(args[0] as isize, args[1]).into(),
+        SYS_TKILL => sys_tgkill(None, args[0] as isize, args[1]).into(),
+        SYS_TGKILL => sys_tgkill(Some(args[0] as isize), args[1] as isize, args[2]).into(),
+        SYS_RT_SIGACTION => sys_rt_sigaction(args[0], args[1], args[2], args[3]).into(),
+        SYS_RT_SIGPROCMASK => sys_rt_sigprocmask(args[0], args[1], args[2], args[3]).into(),
+        SYS_RT_SIGPENDING => sys_rt_sigpending(args[0], args[1]).into(),
+        // 恢复的 a0 作为返回值写回，保持不变
+        SYS_RT_SIGRETURN => SyscallResult::Proceed(signal_return(context) as isize),
+        SYS_REBOOT => sys_reboot(args[0], args[1], args[2], args[3]).into(),
+        SYS_SETPGID => sys_setpgid(args[0] as isize, args[1] as isize).into(),
+        SYS_GETPGID => sys_getpgid(args[0] as isize).into(),
+        SYS_GETTIMEOFDAY => sys_gettimeofday(args[0], args[1]).into(),
+        SYS_GETPID => sys_getpid().into(),
+        SYS_GETTID => sys_gettid().into(),
+        SYS_SOCKET => sys_socket(args[0], args[1], args[2]).into(),
+        SYS_BIND => sys_bind(args[0], args[1], args[2]).into(),
+        SYS_LISTEN => sys_listen(args[0], args[1]).into(),
+        SYS_ACCEPT => sys_accept4(args[0], args[1], args[2], 0).into(),
+        SYS_CONNECT => sys_connect(args[0], args[1], args[2]).into(),
+        SYS_GETSOCKNAME => sys_getsockname(args[0], args[1], args[2]).into(),
+        SYS_GETPEERNAME => sys_getpeername(args[0], args[1], args[2]).into(),
+        SYS_SENDTO => sys_sendto(args[0], args[1], args[2], args[3], args[4], args[5]).into(),
+        SYS_RECVFROM => sys_recvfrom(args[0], args[1], args[2], args[3], args[4], args[5]).into(),
+        SYS_SETSOCKOPT => sys_setsockopt(args[0]).into(),
+        SYS_SHUTDOWN => sys_shutdown(args[0], args[1]).into(),
+        SYS_CLONE => sys_clone(context, args[0]).into(),
+        SYS_ACCEPT4 => sys_accept4(args[0], args[1], args[2], args[3]).into(),
+        SYS_RENAMEAT2 => sys_renameat2(
+            args[0] as isize,
+            args[1],
+            args[2] as isize,
+            args[3],
+            args[4],
+        )
+        .into(),
+        SYS_GETRANDOM => sys_getrandom(args[0], args[1], args[2]).into(),
+        _ => {
+            warn!("unimplemented syscall: {}", syscall_id);
+            SyscallResult::Proceed(-Errno::ENOSYS.0)
+        }
+    };
+
+    match result {
+        SyscallResult::Proceed(ret) => {
+            // 将返回值放入 context 中
+            context.x[10] = ret as usize;
+            context
+        }
+        SyscallResult::Park(ret) => {
+            // 将返回值放入 context 中
+            context.x[10] = ret as usize;
+            // 保存 context，准备下一个线程
+            PROCESSOR.lock().park_current_thread(context);
+            PROCESSOR.lock().prepare_next_thread()
+        }
+        SyscallResult::Restart => {
+            // 回到 ecall 指令，线程被唤醒后会重新执行
+            context.sepc -= 4;
+            PROCESSOR.lock().park_current_thread(context);
+            PROCESSOR.lock().prepare_next_thread()
+        }
+        SyscallResult::Kill => {
+            // 终止，跳转到 PROCESSOR 调度的下一个线程
+            let thread = PROCESSOR.lock().kill_current_thread();
+            release_thread(thread);
+            PROCESSOR.lock().prepare_next_thread()
+        }
+        SyscallResult::KillProcess => {
+            // 终止进程中的其他线程和当前线程
+            let thread = PROCESSOR.lock().current_thread();
+            kill_process(&thread)
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! 系统信息和控制相关的系统调用
+
+use super::*;
+use crate::drivers::power;
+use crate::{clock, console, logging, random};
+
+const SYSLOG_ACTION_CLOSE: usize = 0;
+const SYSLOG_ACTION_OPEN: usize = 1;
+const SYSLOG_ACTION_READ: usize = 2;
+const SYSLOG_ACTION_READ_ALL: usize = 3;
+const SYSLOG_ACTION_READ_CLEAR: usize = 4;
+const SYSLOG_ACTION_CLEAR: usize = 5;
+const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+
+const LINUX_REBOOT_MAGIC1: usize = 0xfee1_dead;
+const LINUX_REBOOT_MAGIC2: usize = 672_274_793;
+const LINUX_REBOOT_MAGIC2A: usize = 85_072_278;
+const LINUX_REBOOT_MAGIC2B: usize = 369_367_448;
+const LINUX_REBOOT_MAGIC2C: usize = 537_993_216;
+
+const LINUX_REBOOT_CMD_CAD_OFF: usize = 0;
+const LINUX_REBOOT_CMD_RESTART: usize = 0x0123_4567;
+const LINUX_REBOOT_CMD_CAD_ON: usize = 0x89ab_cdef;
+const LINUX_REBOOT_CMD_HALT: usize = 0xcdef_0123;
+const LINUX_REBOOT_CMD_POWER_OFF: usize = 0x4321_fedc;
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+const CLOCK_MONOTONIC_RAW: usize = 4;
+const CLOCK_REALTIME_COARSE: usize = 5;
+const CLOCK_MONOTONIC_COARSE: usize = 6;
+const CLOCK_BOOTTIME: usize = 7;
+
+const GRND_NONBLOCK: usize = 1;
+const GRND_RANDOM: usize = 2;
+const GRND_INSECURE: usize = 4;
+
+/// 读取或清空内核日志的环形缓冲区，见 [`logging`]
+///
+/// 读取未读日志时不会等待，没有新日志时返回 0
+pub(super) fn sys_syslog(action: usize, buffer: usize, len: usize) -> SysResult {
+    let len = len as i32;
+    match action {
+        SYSLOG_ACTION_CLOSE | SYSLOG_ACTION_OPEN => Ok(0),
+        SYSLOG_ACTION_READ | SYSLOG_ACTION_READ_ALL | SYSLOG_ACTION_READ_CLEAR => {
+            if len < 0 {
+                return Err(Errno::EINVAL);
+            }
+            let len = len as usize;
+            let buffer = user_slice_mut(buffer, len)?;
+            let data = if action == SYSLOG_ACTION_READ {
+                logging::read_unread(len)
+            } else {
+                // 读取最后 `len` 字节
+                let all = logging::read_all();
+                all[all.len().saturating_sub(len)..].to_vec()
+            };
+            buffer[..data.len()].copy_from_slice(&data);
+            if action == SYSLOG_ACTION_READ_CLEAR {
+                logging::clear();
+            }
+            Ok(data.len())
+        }
+        SYSLOG_ACTION_CLEAR => {
+            logging::clear();
+            Ok(0)
+        }
+        SYSLOG_ACTION_SIZE_UNREAD => Ok(logging::unread()),
+        SYSLOG_ACTION_SIZE_BUFFER => Ok(logging::LOG_BUFFER_SIZE),
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+/// 写回文件系统和块设备缓存，等待控制台输出完毕，准备关机或重启
+fn prepare_power_down() {
+    if let Err(error) = crate::fs::sync() {
+        error!("failed to sync filesystems: {:?}", error);
+    }
+    console::flush();
+}
+
+/// 写回文件系统之后关机，`code` 为报告给 QEMU 的退出码
+pub fn poweroff(code: u16) -> ! {
+    prepare_power_down();
+    power::poweroff(code)
+}
+
+/// 关机或重启，见 [`power`]
+///
+/// 关机前总是写回文件系统。`LINUX_REBOOT_CMD_HALT` 和 `LINUX_REBOOT_CMD_POWER_OFF` 都会关机，
+/// 此时 `arg` 的低 16 位作为 QEMU 的退出码（Linux 忽略这个参数，传入 0 即正常关机）；
+/// 不支持 Ctrl-Alt-Del，相应的命令直接返回
+pub(super) fn sys_reboot(magic1: usize, magic2: usize, cmd: usize, arg: usize) -> SysResult {
+    let magic2 = magic2 as u32 as usize;
+    if magic1 as u32 as usize != LINUX_REBOOT_MAGIC1
+        || ![
+            LINUX_REBOOT_MAGIC2,
+            LINUX_REBOOT_MAGIC2A,
+            LINUX_REBOOT_MAGIC2B,
+            LINUX_REBOOT_MAGIC2C,
+        ]
+        .contains(&magic2)
+    {
+        return Err(Errno::EINVAL);
+    }
+    match cmd as u32 as usize {
+        LINUX_REBOOT_CMD_CAD_ON | LINUX_REBOOT_CMD_CAD_OFF => Ok(0),
+        LINUX_REBOOT_CMD_RESTART => {
+            info!("restarting system");
+            prepare_power_down();
+            power::reboot()
+        }
+        LINUX_REBOOT_CMD_HALT | LINUX_REBOOT_CMD_POWER_OFF => {
+            info!("power down, exit status {}", arg as u16);
+            poweroff(arg as u16)
+        }
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+/// `struct timeval`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TimeVal {
+    sec: i64,
+    usec: i64,
+}
+
+/// 读取时钟，见 [`clock`]
+///
+/// 内核不会挂起，`CLOCK_BOOTTIME` 和 `CLOCK_MONOTONIC` 相同；不支持进程和线程的 CPU 时间
+pub(super) fn sys_clock_gettime(clock_id: usize, tp: usize) -> SysResult {
+    let time = match clock_id {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => clock::realtime(),
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => {
+            clock::monotonic()
+        }
+        _ => return Err(Errno::EINVAL),
+    };
+    write_user(
+        tp,
+        TimeSpec {
+            sec: time.sec,
+            nsec: time.nsec as i64,
+        },
+    )?;
+    Ok(0)
+}
+
+/// 读取墙上时间，时区总是 UTC
+pub(super) fn sys_gettimeofday(tv: usize, tz: usize) -> SysResult {
+    if tv != 0 {
+        let time = clock::realtime();
+        write_user(
+            tv,
+            TimeVal {
+                sec: time.sec,
+                usec: time.nsec as i64 / 1000,
+            },
+        )?;
+    }
+    if tz != 0 {
+        // `struct timezone` 的 tz_minuteswest 和 tz_dsttime
+        write_user(tz, [0i32; 2])?;
+    }
+    Ok(0)
+}
+
+/// 从内核熵池读取随机数，见 [`random`]
+///
+/// 熵池在启动时就已经初始化，不会等待，`GRND_NONBLOCK` 和 `GRND_RANDOM` 不影响结果
+pub(super) fn sys_getrandom(buffer: usize, len: usize, flags: usize) -> SysResult {
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM | GRND_INSECURE) != 0
+        || flags & (GRND_RANDOM | GRND_INSECURE) == GRND_RANDOM | GRND_INSECURE
+    {
+        return Err(Errno::EINVAL);
+    }
+    let buffer = user_slice_mut(buffer, len)?;
+    random::fill(buffer);
+    Ok(len)
+}
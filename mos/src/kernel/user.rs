@@ -0,0 +1,91 @@
+//! 访问用户传入的指针
+//!
+//! 中断处理时设置了 `sstatus.SUM`，内核可以直接读写当前进程的用户页面。
+//! 访问之前在当前进程的 `memory_set` 中检查页面已经以用户权限映射，
+//! 否则访问错误的指针会在内核中产生缺页异常，而不是返回 `EFAULT`
+
+use super::*;
+use crate::memory::{Flags, VirtualAddress, KERNEL_MAP_OFFSET, PAGE_SIZE};
+use alloc::vec::Vec;
+use core::{mem::size_of, slice, str};
+
+/// 路径的最大长度（包括结尾的 0）
+pub const PATH_MAX: usize = 4096;
+
+/// 检查 `[address, address + len)` 位于用户地址空间内，并且以用户权限映射，`write` 时还需要可写
+///
+/// 需要锁住当前进程，调用时不能持有进程的锁
+fn check(address: usize, len: usize, write: bool) -> SysResult<()> {
+    let end = match address.checked_add(len) {
+        Some(end) if address != 0 && end <= KERNEL_MAP_OFFSET => end,
+        _ => return Err(Errno::EFAULT),
+    };
+    let mut flags = Flags::USER | Flags::READABLE;
+    if write {
+        flags |= Flags::WRITABLE;
+    }
+    let mapped = current_process().inner().memory_set.contains(
+        VirtualAddress(address),
+        VirtualAddress(end),
+        flags,
+    );
+    if mapped {
+        Ok(())
+    } else {
+        Err(Errno::EFAULT)
+    }
+}
+
+/// 用户内存中的一段只读缓冲区
+pub fn user_slice<'a>(address: usize, len: usize) -> SysResult<&'a [u8]> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    check(address, len, false)?;
+    Ok(unsafe { slice::from_raw_parts(address as *const u8, len) })
+}
+
+/// 用户内存中的一段可写缓冲区
+pub fn user_slice_mut<'a>(address: usize, len: usize) -> SysResult<&'a mut [u8]> {
+    if len == 0 {
+        return Ok(&mut []);
+    }
+    check(address, len, true)?;
+    Ok(unsafe { slice::from_raw_parts_mut(address as *mut u8, len) })
+}
+
+/// 读取用户传入的以 0 结尾的路径
+pub fn user_path(address: usize) -> SysResult<String> {
+    let mut bytes = Vec::new();
+    loop {
+        let current = address + bytes.len();
+        // 每进入一个新的页面检查一次
+        if bytes.is_empty() || current % PAGE_SIZE == 0 {
+            check(current, 1, false)?;
+        }
+        let byte = unsafe { *(current as *const u8) };
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+        if bytes.len() >= PATH_MAX {
+            return Err(Errno::ENAMETOOLONG);
+        }
+    }
+    str::from_utf8(&bytes)
+        .map(String::from)
+        .map_err(|_| Errno::EINVAL)
+}
+
+/// 从用户内存中读取一个结构
+pub fn read_user<T: Copy>(address: usize) -> SysResult<T> {
+    check(address, size_of::<T>(), false)?;
+    Ok(unsafe { (address as *const T).read_unaligned() })
+}
+
+/// 将一个结构写入用户内存
+pub fn write_user<T: Copy>(address: usize, value: T) -> SysResult<()> {
+    check(address, size_of::<T>(), true)?;
+    unsafe { (address as *mut T).write_unaligned(value) };
+    Ok(())
+}
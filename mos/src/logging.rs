@@ -0,0 +1,218 @@
+//! 内核日志，实现 [`log`] crate 的 [`Log`] 接口
+//!
+//! 每条日志带有时间戳、hart 编号、级别和所在模块，按级别着色输出到控制台，
+//! 同时不带颜色地写入环形缓冲区，可以通过 `syslog` 系统调用或 `/proc/kmsg` 读取。
+//! 设置了日志通道（见 [`use_channel`]）之后，日志不带颜色地写入通道，不再输出到控制台。
+//!
+//! 过滤规则来自设备树 `/chosen/bootargs` 中的 `log=` 参数，形如 `log=info,fs=debug,process::processor=trace`：
+//! 不带模块的一项是默认级别，其余各项按照模块路径（不含 crate 名）的最长前缀匹配
+
+use crate::drivers::driver::Driver;
+use crate::interrupt::CLOCK_FREQ;
+use crate::process::lock::Lock;
+use alloc::{collections::VecDeque, string::String, sync::Arc, vec::Vec};
+use core::cmp::max;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use riscv::register::time;
+use spin::RwLock;
+
+/// 默认的日志级别
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+/// 环形缓冲区的大小，写满后丢弃最早的日志
+pub const LOG_BUFFER_SIZE: usize = 0x10000;
+
+/// 过滤规则
+struct Filters {
+    default: LevelFilter,
+    /// 模块路径前缀和对应的级别
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filters {
+    /// 模块对应的级别，`target` 为完整的模块路径
+    fn level(&self, target: &str) -> LevelFilter {
+        let path = match target.find("::") {
+            Some(index) => &target[index + 2..],
+            None => "",
+        };
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                path.starts_with(prefix.as_str())
+                    && (path.len() == prefix.len() || path[prefix.len()..].starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// 所有规则中最详细的级别
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, max)
+    }
+}
+
+/// 日志的环形缓冲区
+struct LogBuffer {
+    data: VecDeque<u8>,
+    /// 末尾还没有被 `syslog` 读取的字节数
+    unread: usize,
+}
+
+impl LogBuffer {
+    fn push(&mut self, line: &[u8]) {
+        self.data.extend(line);
+        self.unread += line.len();
+        if self.data.len() > LOG_BUFFER_SIZE {
+            // 丢弃最早的若干行
+            let mut excess = self.data.len() - LOG_BUFFER_SIZE;
+            while let Some(byte) = self.data.pop_front() {
+                excess = excess.saturating_sub(1);
+                if excess == 0 && byte == b'\n' {
+                    break;
+                }
+            }
+            self.unread = self.unread.min(self.data.len());
+        }
+    }
+}
+
+lazy_static! {
+    static ref FILTERS: RwLock<Filters> = RwLock::new(Filters {
+        default: DEFAULT_LEVEL,
+        targets: Vec::new(),
+    });
+    /// 中断处理中也会写日志，因此使用关闭中断的锁
+    static ref BUFFER: Lock<LogBuffer> = Lock::new(LogBuffer {
+        data: VecDeque::new(),
+        unread: 0,
+    });
+    /// 日志通道，例如 virtio 控制台的一个端口
+    static ref CHANNEL: RwLock<Option<Arc<dyn Driver>>> = RwLock::new(None);
+}
+
+/// 运行内核的 hart 的编号，目前只有启动的 hart 运行内核
+static HART_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// 当前 hart 的编号
+pub fn hart_id() -> usize {
+    HART_ID.load(Ordering::Relaxed)
+}
+
+/// 日志级别对应的 ANSI 颜色
+fn color(level: Level) -> u8 {
+    match level {
+        Level::Error => 31,
+        Level::Warn => 93,
+        Level::Info => 34,
+        Level::Debug => 32,
+        Level::Trace => 90,
+    }
+}
+
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= FILTERS.read().level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let micros = time::read() / (CLOCK_FREQ / 1_000_000);
+        let mut line = String::new();
+        writeln!(
+            line,
+            "[{:>5}.{:06}] [{}] {:<5} {}: {}",
+            micros / 1_000_000,
+            micros % 1_000_000,
+            hart_id(),
+            record.level(),
+            record.target(),
+            record.args()
+        )
+        .unwrap();
+        match CHANNEL.read().as_ref() {
+            Some(channel) => channel.write_chars(line.as_bytes()),
+            None => print!("\x1b[{}m{}\x1b[0m", color(record.level()), line),
+        }
+        BUFFER.lock().push(line.as_bytes());
+    }
+
+    fn flush(&self) {}
+}
+
+/// 安装日志，此后即可使用 [`log`] 的宏
+pub fn init(hart_id: usize) {
+    HART_ID.store(hart_id, Ordering::Relaxed);
+    log::set_logger(&Logger).unwrap();
+    log::set_max_level(DEFAULT_LEVEL);
+}
+
+/// 此后日志写入 `driver`，不再输出到控制台
+pub fn use_channel(driver: Arc<dyn Driver>) {
+    *CHANNEL.write() = Some(driver);
+}
+
+/// 按照内核命令行中的 `log=` 参数设置过滤规则，无法识别的项会被忽略
+pub fn configure(bootargs: &str) {
+    let spec = match bootargs
+        .split_whitespace()
+        .find(|arg| arg.starts_with("log="))
+    {
+        Some(arg) => &arg[4..],
+        None => return,
+    };
+    let mut filters = Filters {
+        default: DEFAULT_LEVEL,
+        targets: Vec::new(),
+    };
+    for item in spec.split(',') {
+        let mut parts = item.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(level), None) => match level.parse::<LevelFilter>() {
+                Ok(level) => filters.default = level,
+                Err(_) => warn!("unknown log level: {}", level),
+            },
+            (Some(target), Some(level)) => match level.parse::<LevelFilter>() {
+                Ok(level) => filters.targets.push((String::from(target), level)),
+                Err(_) => warn!("unknown log level: {}", level),
+            },
+            _ => {}
+        }
+    }
+    log::set_max_level(filters.max_level());
+    *FILTERS.write() = filters;
+}
+
+/// 缓冲区中的全部日志
+pub fn read_all() -> Vec<u8> {
+    BUFFER.lock().data.iter().cloned().collect()
+}
+
+/// 读取最多 `len` 字节还没有读取过的日志
+pub fn read_unread(len: usize) -> Vec<u8> {
+    let mut buffer = BUFFER.lock();
+    let len = len.min(buffer.unread);
+    let start = buffer.data.len() - buffer.unread;
+    buffer.unread -= len;
+    buffer.data.iter().skip(start).take(len).cloned().collect()
+}
+
+/// 还没有读取过的日志的字节数
+pub fn unread() -> usize {
+    BUFFER.lock().unread
+}
+
+/// 清空缓冲区
+pub fn clear() {
+    let mut buffer = BUFFER.lock();
+    buffer.data.clear();
+    buffer.unread = 0;
+}
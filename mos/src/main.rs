@@ -26,6 +26,12 @@
 #![feature(alloc_error_handler)]
 
 #![feature(drain_filter)]
+//!
+//! - `#![feature(custom_test_frameworks)]`
+//!   `cargo test` 时在内核中运行 `#[test_case]`，见 [`tests`]
+#![feature(custom_test_frameworks)]
+#![test_runner(crate::tests::runner)]
+#![reexport_test_harness_main = "test_main"]
 
 use crate::memory::PhysicalAddress;
 use alloc::sync::Arc;
@@ -35,6 +41,7 @@ use process::processor::PROCESSOR;
 
 #[macro_use]
 mod console;
+mod backtrace;
 mod panic;
 mod sbi;
 
@@ -47,12 +54,23 @@ extern crate lazy_static;
 #[macro_use]
 extern crate bitflags;
 
+#[macro_use]
+extern crate log;
+
 mod interrupt;
+mod logging;
 mod memory;
 
 mod process;
+mod clock;
 mod drivers;
 mod fs;
+mod gdb;
+mod kernel;
+mod net;
+mod random;
+#[cfg(test)]
+mod tests;
 
 // 汇编编写的程序入口，具体见该文件
 global_asm!(include_str!("entry.asm"));
@@ -61,12 +79,24 @@ global_asm!(include_str!("entry.asm"));
 ///
 /// 在 `_start` 为我们进行了一系列准备之后，这是第一个被调用的 Rust 函数
 #[no_mangle]
-pub extern "C" fn rust_main(_hart_id: usize, dtb_pa: PhysicalAddress) -> ! {
-    memory::init();
+pub extern "C" fn rust_main(hart_id: usize, dtb_pa: PhysicalAddress) -> ! {
+    logging::init(hart_id);
+    memory::init(dtb_pa);
+    // 日志需要在堆上格式化，因此在内存初始化之后才打印固件信息
+    sbi::init();
     interrupt::init();
     drivers::init(dtb_pa);
+    clock::init();
+    random::init();
+    gdb::init();
+    net::init();
     fs::init();
 
+    // 测试在一个内核线程中运行，见 [`tests`]
+    #[cfg(test)]
+    tests::spawn(Process::new_kernel().unwrap(), test_main as usize, None);
+
+    #[cfg(not(test))]
     {
         let mut processor = PROCESSOR.lock();
         // 创建一个内核进程
@@ -91,6 +121,7 @@ pub extern "C" fn rust_main(_hart_id: usize, dtb_pa: PhysicalAddress) -> ! {
     unreachable!()
 }
 
+#[cfg(not(test))]
 fn sample_process(message: usize) {
     println!("hello from kernel thread {}", message);
 }
@@ -118,7 +149,7 @@ pub fn create_kernel_thread(
 
 /// 内核线程需要调用这个函数来退出
 fn kernel_thread_exit() {
-    println!("kernel thread exit is called");
+    debug!("kernel thread {} exit", PROCESSOR.lock().current_thread().id);
     // 当前线程标记为结束
     PROCESSOR.lock().current_thread().as_ref().inner().dead = true;
     // 制造一个中断来交给操作系统处理
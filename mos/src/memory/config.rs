@@ -6,21 +6,10 @@ pub const KERNEL_HEAP_SIZE: usize = 0x80_0000;
 /// 页 / 帧大小，必须是 2^n
 pub const PAGE_SIZE: usize = 4096;
 
-/// MMIO 设备段内存区域起始地址
-pub const DEVICE_START_ADDRESS: PhysicalAddress = PhysicalAddress(0x1000_0000);
-/// MMIO 设备段内存区域结束地址
-pub const DEVICE_END_ADDRESS: PhysicalAddress = PhysicalAddress(0x1001_0000);
-
-// 下列两个地址表示，0x8000_0000 是 qemu 起始地址
-
-/**
- * 这个变更中，物理地址相关的内容是不需要被改变的。
- */
-
-/// 可以访问的内存区域起始地址
-pub const MEMORY_START_ADDRESS: PhysicalAddress = PhysicalAddress(0x8_000_0000);
-/// 可以访问的内存区域结束地址
-pub const MEMORY_END_ADDRESS: PhysicalAddress = PhysicalAddress(0x8_800_0000);
+/// 线性映射能够覆盖的物理地址上限（4G），更高的内存不会被使用
+///
+/// 内存和设备的实际位置从设备树中读取，见 [`super::layout`]
+pub const LINEAR_MAP_END_ADDRESS: PhysicalAddress = PhysicalAddress(0x1_0000_0000);
 
 lazy_static! {
     /// 内核代码结束的地址，即可以用来分配的内存起始地址
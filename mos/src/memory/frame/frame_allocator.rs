@@ -1,53 +1,84 @@
-use super::super::address::{PhysicalAddress, PhysicalPageNumber};
+use super::super::address::PhysicalPageNumber;
 use super::super::range::Range;
 use super::frame_tracker::FrameTracker;
 use crate::memory::*;
 
 use algorithm::*;
+use alloc::vec::Vec;
 use lazy_static::*;
 use spin::Mutex;
 
 lazy_static! {
-    /// 帧分配器
-    pub static ref FRAME_ALLOCATOR: Mutex<FrameAllocator<AllocatorImpl>> = Mutex::new(FrameAllocator::new(Range::from(
-            PhysicalPageNumber::ceil(PhysicalAddress::from(*KERNEL_END_ADDRESS))..PhysicalPageNumber::floor(MEMORY_END_ADDRESS),
-        )
-    ));
+    /// 帧分配器，可用的内存区域在 [`crate::memory::init`] 时从设备树中读出
+    pub static ref FRAME_ALLOCATOR: Mutex<FrameAllocator<AllocatorImpl>> = Mutex::new(FrameAllocator::new());
 }
 
 /// 基于线段树的帧分配 / 回收
 pub struct FrameAllocator<T: Allocator> {
-    /// 可用区间的起始
-    start_ppn: PhysicalPageNumber,
-    /// 分配器
-    allocator: T,
+    /// 每个可用区间和对应的分配器
+    regions: Vec<(Range<PhysicalPageNumber>, T)>,
+    /// 可用的帧总数
+    total: usize,
+    /// 已经分配的帧数
+    allocated: usize,
+}
+
+impl<T: Allocator> Default for FrameAllocator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: Allocator> FrameAllocator<T> {
-    /// 创建对象
-    pub fn new(range: impl Into<Range<PhysicalPageNumber>> + Copy) -> Self {
+    /// 创建对象，此时没有可用的帧
+    pub fn new() -> Self {
         FrameAllocator {
-            start_ppn: range.into().start,
-            allocator: T::new(range.into().len()),
+            regions: Vec::new(),
+            total: 0,
+            allocated: 0,
         }
     }
 
+    /// 添加一段可用的物理页
+    pub fn add_region(&mut self, range: impl Into<Range<PhysicalPageNumber>>) {
+        let range = range.into();
+        self.total += range.len();
+        self.regions.push((range, T::new(range.len())));
+    }
+
     /// 分配帧，如果没有剩余则返回 `Err`
     pub fn alloc(&mut self) -> MemoryResult<FrameTracker> {
-        let resp = self.allocator
-            .alloc()
-            .ok_or("no available frame to allocate")
-            .map(|offset| FrameTracker(self.start_ppn + offset));
-        if resp.is_err() {
-            println!("no space, maybe");
+        for (range, allocator) in self.regions.iter_mut() {
+            if let Some(offset) = allocator.alloc() {
+                self.allocated += 1;
+                return Ok(FrameTracker(range.start + offset));
+            }
         }
-        resp
+        warn!("no space, maybe");
+        Err("no available frame to allocate")
     }
 
     /// 将被释放的帧添加到空闲列表的尾部
     ///
     /// 这个函数会在 [`FrameTracker`] 被 drop 时自动调用，不应在其他地方调用
     pub(super) fn dealloc(&mut self, frame: &FrameTracker) {
-        self.allocator.dealloc(frame.page_number() - self.start_ppn);
+        let page_number = frame.page_number();
+        let (range, allocator) = self
+            .regions
+            .iter_mut()
+            .find(|(range, _)| range.contains(page_number))
+            .expect("frame not from any region");
+        allocator.dealloc(page_number - range.start);
+        self.allocated -= 1;
+    }
+
+    /// 可用的帧总数
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// 已经分配的帧数
+    pub fn allocated(&self) -> usize {
+        self.allocated
     }
 }
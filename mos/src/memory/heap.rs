@@ -31,6 +31,18 @@ pub fn init() {
     }
 }
 
+/// 堆的使用情况，依次为堆的总大小、实际分配的字节数和用户请求的字节数
+///
+/// 实际分配的字节数中包含了 buddy system 向上取整带来的浪费
+pub fn stats() -> (usize, usize, usize) {
+    let heap = HEAP.lock();
+    (
+        heap.stats_total_bytes(),
+        heap.stats_alloc_actual(),
+        heap.stats_alloc_user(),
+    )
+}
+
 /// 空间分配错误的回调，直接 panic 退出
 #[alloc_error_handler]
 fn alloc_error_handler(_: alloc::alloc::Layout) -> ! {
@@ -0,0 +1,124 @@
+//! 物理内存布局
+//!
+//! 启动时从设备树中读出内存和保留区域，可分配的帧为内存减去固件、内核镜像、保留区域、设备树和 initrd。
+//! 设备的 MMIO 区域在驱动探测时登记，此后创建的内核映射 [`MemorySet::new_kernel`] 都会包含这些区域
+//!
+//! [`MemorySet::new_kernel`]: super::MemorySet::new_kernel
+
+use super::{
+    frame::FRAME_ALLOCATOR, range::Range, PhysicalAddress, PhysicalPageNumber, VirtualAddress,
+    KERNEL_END_ADDRESS, LINEAR_MAP_END_ADDRESS,
+};
+use alloc::{vec, vec::Vec};
+use lazy_static::lazy_static;
+use spin::RwLock;
+
+/// 设备树中描述的物理内存
+#[derive(Default)]
+pub struct MemoryLayout {
+    /// `/memory` 节点中的内存区域
+    pub memory: Vec<Range<PhysicalAddress>>,
+    /// 不能分配的区域：`/reserved-memory`、内存保留块、设备树自身和 initrd
+    pub reserved: Vec<Range<PhysicalAddress>>,
+    /// 带有 `no-map` 属性的保留区域，不能建立映射
+    pub no_map: Vec<Range<PhysicalAddress>>,
+}
+
+lazy_static! {
+    /// 内核需要线性映射的内存区域，不包括内核镜像本身
+    static ref MEMORY: RwLock<Vec<Range<PhysicalAddress>>> = RwLock::new(Vec::new());
+    /// 已经登记的 MMIO 区域，按页对齐且互不重叠
+    static ref MMIO: RwLock<Vec<Range<PhysicalAddress>>> = RwLock::new(Vec::new());
+}
+
+/// 从 `ranges` 中去掉 `hole`
+fn subtract(
+    ranges: Vec<Range<PhysicalAddress>>,
+    hole: Range<PhysicalAddress>,
+) -> Vec<Range<PhysicalAddress>> {
+    let mut result = Vec::new();
+    for range in ranges {
+        if !range.overlap_with(&hole) {
+            result.push(range);
+            continue;
+        }
+        if range.start < hole.start {
+            result.push(Range::from(range.start..hole.start));
+        }
+        if hole.end < range.end {
+            result.push(Range::from(hole.end..range.end));
+        }
+    }
+    result
+}
+
+/// 按照内存布局初始化帧分配器，并记录需要线性映射的内存
+pub fn init(layout: MemoryLayout) {
+    // 固件和内核镜像都位于内核结束地址之下；线性映射只能覆盖低 4G 的物理地址
+    let kernel = Range::from(PhysicalAddress(0)..PhysicalAddress::from(*KERNEL_END_ADDRESS));
+    let unreachable = Range::from(LINEAR_MAP_END_ADDRESS..PhysicalAddress(usize::MAX));
+    let mut mapped = layout.memory;
+    for hole in [kernel, unreachable].iter().chain(layout.no_map.iter()) {
+        mapped = subtract(mapped, *hole);
+    }
+    // 只映射完整的页，避免和相邻的区域重复映射
+    mapped = mapped
+        .into_iter()
+        .map(|range| {
+            Range::from(
+                PhysicalAddress::from(PhysicalPageNumber::ceil(range.start))
+                    ..PhysicalAddress::from(PhysicalPageNumber::floor(range.end)),
+            )
+        })
+        .filter(|range: &Range<PhysicalAddress>| range.start < range.end)
+        .collect();
+    let mut usable = mapped.clone();
+    for hole in layout.reserved.iter() {
+        usable = subtract(usable, *hole);
+    }
+
+    let mut allocator = FRAME_ALLOCATOR.lock();
+    for range in usable.iter() {
+        let start = PhysicalPageNumber::ceil(range.start);
+        let end = PhysicalPageNumber::floor(range.end);
+        if start < end {
+            info!("usable memory: [{:#x}, {:#x})", range.start.0, range.end.0);
+            allocator.add_region(start..end);
+        }
+    }
+    info!(
+        "{} MiB available for frame allocation",
+        allocator.total() * super::PAGE_SIZE >> 20
+    );
+    *MEMORY.write() = mapped;
+}
+
+/// 内核需要线性映射的内存区域
+pub fn memory_regions() -> Vec<Range<PhysicalAddress>> {
+    MEMORY.read().clone()
+}
+
+/// 物理地址是否位于内核线性映射的内存中（不包括内核镜像）
+pub fn is_memory(address: PhysicalAddress) -> bool {
+    MEMORY.read().iter().any(|range| range.contains(address))
+}
+
+/// 登记设备的 MMIO 区域 `[address, address + size)`，返回其虚拟地址
+///
+/// 启动页表线性映射了低 4G 的物理地址，登记之后的区域在切换页表之后也能访问
+pub fn map_mmio(address: PhysicalAddress, size: usize) -> VirtualAddress {
+    let start = PhysicalAddress::from(PhysicalPageNumber::floor(address));
+    let end = PhysicalAddress::from(PhysicalPageNumber::ceil(address + size));
+    let mut mmio = MMIO.write();
+    let mut missing = vec![Range::from(start..end)];
+    for range in mmio.iter() {
+        missing = subtract(missing, *range);
+    }
+    mmio.extend(missing);
+    VirtualAddress::from(address)
+}
+
+/// 已经登记的 MMIO 区域
+pub fn mmio_regions() -> Vec<Range<PhysicalAddress>> {
+    MMIO.read().clone()
+}
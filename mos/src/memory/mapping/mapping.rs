@@ -112,7 +112,6 @@ impl Mapping {
         match segment.map_type {
             // 线性映射，直接对虚拟地址进行转换
             MapType::Linear => {
-                println!("map linear is called");
                 for vpn in segment.page_range().iter() {
                     // vpn, 线性映射的 ppn, 对应的 flag
                     self.map_one(vpn, Some(vpn.into()), segment.flags)?;
@@ -194,6 +193,9 @@ impl Mapping {
                 break;
             }
         }
+        if entry.is_empty() {
+            return None;
+        }
         let base = PhysicalAddress::from(entry.page_number()).0;
         let offset = va.0 & ((1 << length) - 1);
         Some(PhysicalAddress(base + offset))
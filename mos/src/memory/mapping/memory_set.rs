@@ -1,8 +1,6 @@
 //! 一个线程中关于内存空间的所有信息 [`MemorySet`]
 //!
 
-use crate::memory::DEVICE_END_ADDRESS;
-use crate::memory::DEVICE_START_ADDRESS;
 use super::page_table_entry::Flags;
 use super::MapType;
 use crate::memory::address::VirtualAddress;
@@ -11,9 +9,10 @@ use crate::memory::mapping::mapping::Mapping;
 use crate::memory::mapping::segment::Segment;
 use crate::memory::range::Range;
 use crate::memory::MemoryResult;
+use crate::memory::layout;
 use crate::memory::KERNEL_END_ADDRESS;
-use crate::memory::MEMORY_END_ADDRESS;
 use alloc::{vec, vec::Vec};
+use core::slice;
 
 /// 一个进程所有关于内存空间管理的信息
 pub struct MemorySet {
@@ -36,13 +35,7 @@ impl MemorySet {
         }
 
         // 建立字段
-        let segments = vec![
-            // DEVICE 段，rw-
-            Segment {
-                map_type: MapType::Linear,
-                range: Range::from(DEVICE_START_ADDRESS..DEVICE_END_ADDRESS),
-                flags: Flags::READABLE | Flags::WRITABLE,
-            },
+        let mut segments = vec![
             // .text 段，r-x
             Segment {
                 map_type: MapType::Linear,
@@ -67,14 +60,18 @@ impl MemorySet {
                 range: Range::from(VirtualAddress::from(bss_start as usize)..*KERNEL_END_ADDRESS),
                 flags: Flags::READABLE | Flags::WRITABLE,
             },
-            // 剩余内存空间，rw-
-            // 卧槽，这个地方用户也全部 Linear 映射了啊
-            Segment {
+        ];
+        // 设备树中的剩余内存空间和已经登记的设备 MMIO 区域，rw-
+        for range in layout::memory_regions()
+            .into_iter()
+            .chain(layout::mmio_regions())
+        {
+            segments.push(Segment {
                 map_type: MapType::Linear,
-                range: Range::from(*KERNEL_END_ADDRESS..VirtualAddress::from(MEMORY_END_ADDRESS)),
+                range: Range::from(VirtualAddress::from(range.start)..VirtualAddress::from(range.end)),
                 flags: Flags::READABLE | Flags::WRITABLE,
-            },
-        ];
+            });
+        }
         let mut mapping = Mapping::new()?;
 
         // 每个字段在页表中进行映射
@@ -84,6 +81,29 @@ impl MemorySet {
         Ok(MemorySet { mapping, segments })
     }
 
+    /// 复制一份内存空间，`Framed` 映射的页面会复制其内容
+    ///
+    /// 直接通过虚拟地址读取页面，因此必须在自身的页表被激活时调用
+    pub fn fork(&self) -> MemoryResult<MemorySet> {
+        let mut memory_set = MemorySet {
+            mapping: Mapping::new()?,
+            segments: Vec::new(),
+        };
+        for segment in self.segments.iter() {
+            let data = match segment.map_type {
+                MapType::Linear => None,
+                MapType::Framed => Some(unsafe {
+                    slice::from_raw_parts(
+                        segment.range.start.0 as *const u8,
+                        segment.range.end - segment.range.start,
+                    )
+                }),
+            };
+            memory_set.add_segment(*segment, data)?;
+        }
+        Ok(memory_set)
+    }
+
     /// 替换 `satp` 以激活页表
     ///
     /// 如果当前页表就是自身，则不会替换，但仍然会刷新 TLB。
@@ -117,6 +137,25 @@ impl MemorySet {
         Ok(())
     }
 
+    /// 虚拟地址区间 `[start, end)` 是否全部位于带有 `flags` 中所有权限的 [`Segment`] 中
+    ///
+    /// 区间可以跨越多个相邻的 [`Segment`]
+    pub fn contains(&self, start: VirtualAddress, end: VirtualAddress, flags: Flags) -> bool {
+        let end = VirtualPageNumber::ceil(end);
+        let mut page = VirtualPageNumber::floor(start);
+        while page < end {
+            match self
+                .segments
+                .iter()
+                .find(|seg| seg.flags.contains(flags) && seg.page_range().contains(page))
+            {
+                Some(seg) => page = seg.page_range().end,
+                None => return false,
+            }
+        }
+        true
+    }
+
     /// 检测一段内存区域和已有的是否存在重叠区域
     pub fn overlap_with(&self, range: Range<VirtualPageNumber>) -> bool {
         for seg in self.segments.iter() {
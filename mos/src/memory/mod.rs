@@ -2,6 +2,7 @@ mod address;
 pub mod config;
 pub mod frame;
 pub mod heap;
+pub mod layout;
 pub mod mapping;
 #[allow(dead_code)]
 pub mod range;
@@ -11,6 +12,7 @@ pub use address::*;
 pub use mapping::MemorySet;
 pub use mapping::Flags;
 pub use mapping::MapType;
+pub use layout::map_mmio;
 
 /// 一个缩写，模块中一些函数会使用
 pub type MemoryResult<T> = Result<T, &'static str>;
@@ -18,10 +20,12 @@ pub type MemoryResult<T> = Result<T, &'static str>;
 /// 初始化内存相关的子模块
 ///
 /// - [`heap::init`]
-pub fn init() {
+/// - 从设备树中读取内存布局，初始化 [`frame::FRAME_ALLOCATOR`]，见 [`layout::init`]
+pub fn init(dtb_pa: PhysicalAddress) {
     heap::init();
     // 允许内核读写用户态内存
     unsafe { riscv::register::sstatus::set_sum() };
+    layout::init(crate::drivers::device_tree::memory_layout(dtb_pa));
 
-    println!("mod memory initialized");
+    info!("mod memory initialized");
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+//! 把网络设备的 [`Driver`] 接口适配为 [`smoltcp`] 的 [`Device`]
+
+use crate::drivers::driver::Driver;
+use alloc::{sync::Arc, vec, vec::Vec};
+use smoltcp::{
+    phy::{self, Device, DeviceCapabilities},
+    time::Instant,
+};
+
+/// 以太网帧的最大长度（不含 FCS）
+const MAX_FRAME_SIZE: usize = 1514;
+
+/// 协议栈使用的网络设备
+pub struct NetDevice(pub Arc<dyn Driver>);
+
+/// 一个收到的帧
+pub struct RxToken(Vec<u8>);
+
+/// 发送一个帧的许可，网卡总是可以发送
+pub struct TxToken(Arc<dyn Driver>);
+
+impl<'a> Device<'a> for NetDevice {
+    type RxToken = RxToken;
+    type TxToken = TxToken;
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        self.0
+            .receive_frame()
+            .map(|frame| (RxToken(frame), TxToken(self.0.clone())))
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        Some(TxToken(self.0.clone()))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut capabilities = DeviceCapabilities::default();
+        capabilities.max_transmission_unit = MAX_FRAME_SIZE;
+        capabilities.max_burst_size = Some(1);
+        capabilities
+    }
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        f(&mut self.0)
+    }
+}
+
+impl phy::TxToken for TxToken {
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        let mut frame = vec![0u8; len];
+        let result = f(&mut frame)?;
+        if self.0.send_frame(&frame) {
+            Ok(result)
+        } else {
+            Err(smoltcp::Error::Exhausted)
+        }
+    }
+}
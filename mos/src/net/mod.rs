@@ -0,0 +1,283 @@
+//! 基于 [`smoltcp`] 的 TCP/IP 协议栈
+//!
+//! 总是有一个 127.0.0.1 的回环接口 `lo`；存在网络设备时在第一个设备上建立接口，地址由命令行的 `ip=` 参数指定：
+//! `ip=dhcp` 通过 DHCP 获取，`ip=10.0.2.15/24,10.0.2.2` 为静态地址和网关，没有时使用 QEMU 用户态网络的默认地址。
+//!
+//! 每个接口有自己的套接字集合，套接字在绑定地址或者发起连接时确定所在的接口。
+//! 协议栈在时钟中断、网卡中断和套接字操作之后推进，之后唤醒所有等待套接字的线程
+
+use crate::drivers::{
+    device_tree::bootarg,
+    driver::Driver,
+    net::{loopback::LoopbackDriver, net_devices},
+};
+use crate::interrupt::CLOCK_FREQ;
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use riscv::register::time;
+use smoltcp::{
+    dhcp::Dhcpv4Client,
+    iface::{EthernetInterface, EthernetInterfaceBuilder, NeighborCache, Routes},
+    socket::{RawPacketMetadata, RawSocketBuffer, SocketHandle, SocketSet, TcpSocket, TcpState},
+    time::Instant,
+    wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr},
+};
+use spin::Mutex;
+
+mod device;
+mod socket;
+
+pub use socket::{NetError, NetResult, Socket, SocketKind};
+
+use device::NetDevice;
+
+/// 回环接口的地址
+const LOOPBACK_ADDRESS: ([u8; 4], u8) = ([127, 0, 0, 1], 8);
+/// 没有 `ip=` 参数时使用的地址和网关，即 QEMU 用户态网络分配给虚拟机的地址
+const DEFAULT_ADDRESS: ([u8; 4], u8) = ([10, 0, 2, 15], 24);
+const DEFAULT_GATEWAY: [u8; 4] = [10, 0, 2, 2];
+/// 每次推进时最多容忍的错误数，例如网卡持续发送失败时不会一直重试
+const MAX_POLL_ERRORS: usize = 16;
+/// 回环接口在 [`Network::interfaces`] 中的下标
+const LOOPBACK: usize = 0;
+
+/// 一个网络接口及其上的套接字
+struct Interface {
+    name: String,
+    iface: EthernetInterface<'static, 'static, 'static, NetDevice>,
+    sockets: SocketSet<'static, 'static, 'static>,
+    /// 使用 DHCP 时的客户端
+    dhcp: Option<Dhcpv4Client>,
+    /// 已经关闭、等待 TCP 挥手完成的套接字
+    closing: Vec<SocketHandle>,
+}
+
+/// 协议栈中的所有接口，第一个为回环接口
+struct Network {
+    interfaces: Vec<Interface>,
+}
+
+lazy_static! {
+    /// 协议栈，初始化之前为 `None`
+    static ref NETWORK: Mutex<Option<Network>> = Mutex::new(None);
+}
+
+/// 当前时间，单位为毫秒
+fn now() -> Instant {
+    Instant::from_millis((time::read() / (CLOCK_FREQ / 1000)) as i64)
+}
+
+/// 解析 `ip=` 参数中的静态配置：`地址/前缀长度[,网关]`
+fn parse_static(spec: &str) -> Option<(Ipv4Cidr, Option<Ipv4Address>)> {
+    let mut parts = spec.splitn(2, ',');
+    let mut cidr = parts.next()?.splitn(2, '/');
+    let address = parse_ipv4(cidr.next()?)?;
+    let prefix = cidr.next().map_or(Some(24), |prefix| prefix.parse().ok())?;
+    let gateway = match parts.next() {
+        Some(gateway) => Some(parse_ipv4(gateway)?),
+        None => None,
+    };
+    Some((Ipv4Cidr::new(address, prefix), gateway))
+}
+
+/// 解析点分十进制的 IPv4 地址
+fn parse_ipv4(text: &str) -> Option<Ipv4Address> {
+    let mut bytes = [0u8; 4];
+    let mut parts = text.split('.');
+    for byte in bytes.iter_mut() {
+        *byte = parts.next()?.parse().ok()?;
+    }
+    match parts.next() {
+        Some(_) => None,
+        None => Some(Ipv4Address(bytes)),
+    }
+}
+
+/// 在设备上建立接口
+fn interface(
+    name: String,
+    driver: Arc<dyn Driver>,
+    cidr: Ipv4Cidr,
+    gateway: Option<Ipv4Address>,
+) -> Interface {
+    let mut routes = Routes::new(BTreeMap::new());
+    if let Some(gateway) = gateway {
+        routes.add_default_ipv4_route(gateway).unwrap();
+    }
+    let iface = EthernetInterfaceBuilder::new(NetDevice(driver.clone()))
+        .ethernet_addr(EthernetAddress(driver.mac_address()))
+        .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+        .ip_addrs(vec![IpCidr::Ipv4(cidr)])
+        .routes(routes)
+        .finalize();
+    Interface {
+        name,
+        iface,
+        sockets: SocketSet::new(vec![]),
+        dhcp: None,
+        closing: Vec::new(),
+    }
+}
+
+/// 建立回环接口，并在第一个网络设备上建立接口
+pub fn init() {
+    let loopback = interface(
+        "lo".to_string(),
+        Arc::new(LoopbackDriver::default()),
+        Ipv4Cidr::new(Ipv4Address(LOOPBACK_ADDRESS.0), LOOPBACK_ADDRESS.1),
+        None,
+    );
+    let mut interfaces = vec![loopback];
+
+    if let Some((name, driver)) = net_devices().into_iter().next() {
+        let spec = bootarg("ip");
+        let (cidr, gateway) = match spec.as_deref() {
+            Some("dhcp") => (Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0), None),
+            Some(spec) => parse_static(spec).unwrap_or_else(|| {
+                warn!("invalid ip= argument: {}", spec);
+                (Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0), None)
+            }),
+            None => (
+                Ipv4Cidr::new(Ipv4Address(DEFAULT_ADDRESS.0), DEFAULT_ADDRESS.1),
+                Some(Ipv4Address(DEFAULT_GATEWAY)),
+            ),
+        };
+        let mut interface = interface(name, driver, cidr, gateway);
+        if spec.as_deref() == Some("dhcp") {
+            let rx = RawSocketBuffer::new(vec![RawPacketMetadata::EMPTY; 1], vec![0; 900]);
+            let tx = RawSocketBuffer::new(vec![RawPacketMetadata::EMPTY; 1], vec![0; 600]);
+            interface.dhcp = Some(Dhcpv4Client::new(&mut interface.sockets, rx, tx, now()));
+            info!("{}: waiting for dhcp", interface.name);
+        } else {
+            info!(
+                "{}: address {}, gateway {:?}",
+                interface.name, cidr, gateway
+            );
+        }
+        interfaces.push(interface);
+    }
+    *NETWORK.lock() = Some(Network { interfaces });
+}
+
+impl Interface {
+    /// 推进协议栈：收发数据、处理 DHCP，并释放挥手完成的套接字
+    fn poll(&mut self) {
+        let timestamp = now();
+        for _ in 0..MAX_POLL_ERRORS {
+            match self.iface.poll(&mut self.sockets, timestamp) {
+                Ok(_) => break,
+                // 错误只影响出错的数据包，继续处理其余的
+                Err(error) => debug!("{}: poll: {}", self.name, error),
+            }
+        }
+        if let Some(dhcp) = self.dhcp.as_mut() {
+            match dhcp.poll(&mut self.iface, &mut self.sockets, timestamp) {
+                Ok(Some(config)) => {
+                    if let Some(cidr) = config.address {
+                        info!("{}: dhcp address {}", self.name, cidr);
+                        self.iface
+                            .update_ip_addrs(|addrs| addrs[0] = IpCidr::Ipv4(cidr));
+                    }
+                    if let Some(router) = config.router {
+                        info!("{}: dhcp gateway {}", self.name, router);
+                        self.iface
+                            .routes_mut()
+                            .add_default_ipv4_route(router)
+                            .unwrap();
+                    }
+                }
+                Ok(None) => {}
+                Err(error) => debug!("{}: dhcp: {}", self.name, error),
+            }
+        }
+        let sockets = &mut self.sockets;
+        self.closing.retain(|&handle| {
+            let closed = matches!(
+                sockets.get::<TcpSocket>(handle).state(),
+                TcpState::Closed | TcpState::TimeWait
+            );
+            if closed {
+                sockets.remove(handle);
+            }
+            !closed
+        });
+    }
+
+    /// 接口的 IPv4 地址，DHCP 尚未完成时为 `None`
+    fn address(&self) -> Option<IpAddress> {
+        self.iface
+            .ip_addrs()
+            .iter()
+            .map(|cidr| cidr.address())
+            .find(|address| !address.is_unspecified())
+    }
+}
+
+impl Network {
+    /// 推进所有接口
+    fn poll(&mut self) {
+        for interface in self.interfaces.iter_mut() {
+            interface.poll();
+        }
+    }
+
+    /// 新的套接字所在的接口：有网络设备时为设备上的接口，否则为回环接口
+    fn default_interface(&self) -> usize {
+        self.interfaces.len() - 1
+    }
+
+    /// 发往 `address` 的数据所用的接口
+    fn route(&self, address: IpAddress) -> usize {
+        self.interface_of(address)
+            .unwrap_or_else(|| self.default_interface())
+    }
+
+    /// 拥有地址 `address` 的接口，`127.0.0.0/8` 都属于回环接口
+    fn interface_of(&self, address: IpAddress) -> Option<usize> {
+        if let IpAddress::Ipv4(address) = address {
+            if address.is_loopback() {
+                return Some(LOOPBACK);
+            }
+        }
+        self.interfaces
+            .iter()
+            .position(|interface| interface.iface.has_ip_addr(address))
+    }
+
+    /// 接口上的套接字集合
+    fn sockets(&mut self, interface: usize) -> &mut SocketSet<'static, 'static, 'static> {
+        &mut self.interfaces[interface].sockets
+    }
+}
+
+/// 推进协议栈并唤醒等待套接字的线程，在时钟中断和外部中断中调用
+///
+/// 协议栈正在被使用时直接返回，使用者完成操作后会自己推进
+pub fn poll() {
+    match NETWORK.try_lock() {
+        Some(mut network) => match network.as_mut() {
+            Some(network) => network.poll(),
+            None => return,
+        },
+        None => return,
+    }
+    socket::notify_all();
+}
+
+/// 在协议栈上执行操作，之后推进协议栈，初始化之前返回 [`NetError::NoDevice`]
+fn with_network<T>(f: impl FnOnce(&mut Network) -> NetResult<T>) -> NetResult<T> {
+    let result = {
+        let mut network = NETWORK.lock();
+        let network = network.as_mut().ok_or(NetError::NoDevice)?;
+        let result = f(network);
+        network.poll();
+        result
+    };
+    socket::notify_all();
+    result
+}
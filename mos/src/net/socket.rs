@@ -0,0 +1,691 @@
+//! 套接字 [`Socket`]
+//!
+//! 每个套接字对应协议栈中的一个（TCP 监听时为多个）[`smoltcp`] 套接字，通过所在的接口和 [`SocketHandle`] 引用。
+//! 套接字创建在默认接口上，绑定到具体地址或者发起连接时移动到对应的接口；
+//! 绑定到任意地址的 TCP 监听套接字在每个接口上都接受连接。
+//! 操作不会在这里阻塞：需要等待时返回 [`NetError::Again`]，由系统调用在 [`Socket::condvar`] 上等待。
+//! 作为 [`INode`] 放入文件描述符表，`read` 和 `write` 即为 `recv` 和 `send`
+
+use super::{with_network, Network};
+use crate::fs::{FileType, FsError, INode, Metadata, PollStatus, Result, Timespec};
+use crate::process::condvar::Condvar;
+use alloc::{
+    collections::BTreeSet,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::any::Any;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use smoltcp::{
+    socket::{
+        SocketHandle, TcpSocket, TcpSocketBuffer, TcpState, UdpPacketMetadata, UdpSocket,
+        UdpSocketBuffer,
+    },
+    wire::{IpAddress, IpEndpoint},
+};
+use spin::Mutex;
+
+/// TCP 套接字每个方向的缓冲区大小
+const TCP_BUFFER_SIZE: usize = 16 * 1024;
+/// UDP 套接字每个方向最多缓存的数据包个数和总大小
+const UDP_PACKETS: usize = 16;
+const UDP_BUFFER_SIZE: usize = 16 * 1024;
+/// 监听队列的最大长度
+const MAX_BACKLOG: usize = 16;
+/// 自动分配的端口范围
+const EPHEMERAL_PORT_START: usize = 49152;
+const EPHEMERAL_PORT_COUNT: usize = 16384;
+
+/// 套接字操作的错误，系统调用将其转换为对应的错误码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetError {
+    /// 没有网络设备
+    NoDevice,
+    /// 需要等待
+    Again,
+    /// 已经发起连接，尚未完成
+    InProgress,
+    /// 之前发起的连接尚未完成
+    Already,
+    IsConnected,
+    NotConnected,
+    AddrInUse,
+    /// 绑定的地址不属于任何接口
+    AddrNotAvailable,
+    ConnectionRefused,
+    /// 连接的写端已经关闭
+    BrokenPipe,
+    /// UDP 发送时没有目的地址
+    DestinationRequired,
+    InvalidParam,
+    /// 套接字类型不支持这个操作
+    NotSupported,
+}
+
+/// 套接字操作的结果
+pub type NetResult<T = ()> = core::result::Result<T, NetError>;
+
+/// 套接字类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SocketKind {
+    Tcp,
+    Udp,
+}
+
+/// 可变的状态
+struct Inner {
+    /// 所在的接口
+    interface: usize,
+    /// 收发数据所用的套接字，TCP 监听时为 `None`
+    handle: Option<SocketHandle>,
+    /// TCP 监听时等待连接的套接字及其所在的接口，每个至多接受一个连接
+    backlog: Vec<(usize, SocketHandle)>,
+    /// 本地地址，绑定或者自动分配端口之后才有
+    local: Option<IpEndpoint>,
+    /// 本地端口由这个套接字占用，关闭时释放
+    owns_port: bool,
+    /// UDP 通过 `connect` 指定的对端
+    peer: Option<IpEndpoint>,
+    /// TCP 已经发起连接，等待完成
+    connecting: bool,
+    /// 读端已经关闭
+    read_shutdown: bool,
+}
+
+/// 套接字
+pub struct Socket {
+    pub kind: SocketKind,
+    inner: Mutex<Inner>,
+    /// 状态可能变化时唤醒，协议栈每次推进后都会通知
+    condvar: Condvar,
+}
+
+lazy_static! {
+    /// 所有套接字，协议栈推进后依次唤醒
+    static ref SOCKETS: Mutex<Vec<Weak<Socket>>> = Mutex::new(Vec::new());
+    /// 已经占用的端口
+    static ref PORTS: Mutex<BTreeSet<(SocketKind, u16)>> = Mutex::new(BTreeSet::new());
+}
+
+/// 下一个尝试分配的端口
+static NEXT_PORT: AtomicUsize = AtomicUsize::new(0);
+
+/// 唤醒所有等待套接字的线程
+///
+/// 在中断中调用时套接字列表可能正被使用，此时直接返回，使用者稍后会推进协议栈并再次唤醒
+pub(super) fn notify_all() {
+    let sockets: Vec<Arc<Socket>> = match SOCKETS.try_lock() {
+        Some(mut sockets) => {
+            sockets.retain(|socket| socket.strong_count() > 0);
+            sockets.iter().filter_map(Weak::upgrade).collect()
+        }
+        None => return,
+    };
+    for socket in sockets {
+        socket.condvar.notify_all();
+    }
+}
+
+/// 占用一个端口，`port` 为 0 时自动分配
+fn reserve_port(kind: SocketKind, port: u16) -> NetResult<u16> {
+    let mut ports = PORTS.lock();
+    if port != 0 {
+        return if ports.insert((kind, port)) {
+            Ok(port)
+        } else {
+            Err(NetError::AddrInUse)
+        };
+    }
+    for _ in 0..EPHEMERAL_PORT_COUNT {
+        let index = NEXT_PORT.fetch_add(1, Ordering::Relaxed) % EPHEMERAL_PORT_COUNT;
+        let port = (EPHEMERAL_PORT_START + index) as u16;
+        if ports.insert((kind, port)) {
+            return Ok(port);
+        }
+    }
+    Err(NetError::AddrInUse)
+}
+
+fn tcp_socket() -> TcpSocket<'static> {
+    TcpSocket::new(
+        TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+        TcpSocketBuffer::new(vec![0; TCP_BUFFER_SIZE]),
+    )
+}
+
+fn udp_socket() -> UdpSocket<'static, 'static> {
+    UdpSocket::new(
+        UdpSocketBuffer::new(
+            vec![UdpPacketMetadata::EMPTY; UDP_PACKETS],
+            vec![0; UDP_BUFFER_SIZE],
+        ),
+        UdpSocketBuffer::new(
+            vec![UdpPacketMetadata::EMPTY; UDP_PACKETS],
+            vec![0; UDP_BUFFER_SIZE],
+        ),
+    )
+}
+
+/// 在接口上新建一个协议栈中的套接字
+fn add_socket(network: &mut Network, interface: usize, kind: SocketKind) -> SocketHandle {
+    let sockets = network.sockets(interface);
+    match kind {
+        SocketKind::Tcp => sockets.add(tcp_socket()),
+        SocketKind::Udp => sockets.add(udp_socket()),
+    }
+}
+
+/// 监听中的套接字已经建立了连接
+fn established(socket: &TcpSocket) -> bool {
+    !matches!(
+        socket.state(),
+        TcpState::Listen | TcpState::SynReceived | TcpState::Closed
+    )
+}
+
+impl Socket {
+    /// 创建一个未绑定、未连接的套接字
+    pub fn new(kind: SocketKind) -> NetResult<Arc<Self>> {
+        let (interface, handle) = with_network(|network| {
+            let interface = network.default_interface();
+            Ok((interface, add_socket(network, interface, kind)))
+        })?;
+        Ok(Self::with_inner(
+            kind,
+            Inner {
+                interface,
+                handle: Some(handle),
+                backlog: Vec::new(),
+                local: None,
+                owns_port: false,
+                peer: None,
+                connecting: false,
+                read_shutdown: false,
+            },
+        ))
+    }
+
+    fn with_inner(kind: SocketKind, inner: Inner) -> Arc<Self> {
+        let socket = Arc::new(Self {
+            kind,
+            inner: Mutex::new(inner),
+            condvar: Condvar::default(),
+        });
+        SOCKETS.lock().push(Arc::downgrade(&socket));
+        socket
+    }
+
+    /// 需要等待时所用的条件变量
+    pub fn condvar(&self) -> &Condvar {
+        &self.condvar
+    }
+
+    /// 把套接字移动到另一个接口，已经建立的 TCP 连接不会移动
+    fn move_to(&self, inner: &mut Inner, network: &mut Network, interface: usize) -> NetResult {
+        if inner.interface == interface {
+            return Ok(());
+        }
+        let handle = inner.handle.ok_or(NetError::InvalidParam)?;
+        if self.kind == SocketKind::Tcp
+            && network
+                .sockets(inner.interface)
+                .get::<TcpSocket>(handle)
+                .is_open()
+        {
+            return Ok(());
+        }
+        network.sockets(inner.interface).remove(handle);
+        let handle = add_socket(network, interface, self.kind);
+        if let (SocketKind::Udp, Some(local)) = (self.kind, inner.local) {
+            let mut socket = network.sockets(interface).get::<UdpSocket>(handle);
+            socket.bind(local).map_err(|_| NetError::InvalidParam)?;
+        }
+        inner.interface = interface;
+        inner.handle = Some(handle);
+        Ok(())
+    }
+
+    /// 没有绑定或者绑定到任意地址时，移动到发往 `address` 所用的接口
+    fn follow_route(
+        &self,
+        inner: &mut Inner,
+        network: &mut Network,
+        address: IpAddress,
+    ) -> NetResult {
+        if inner
+            .local
+            .map_or(true, |local| local.addr.is_unspecified())
+        {
+            let interface = network.route(address);
+            self.move_to(inner, network, interface)?;
+        }
+        Ok(())
+    }
+
+    /// 没有绑定时自动分配端口
+    fn ensure_bound(&self, inner: &mut Inner, network: &mut Network) -> NetResult<IpEndpoint> {
+        if inner.local.is_none() {
+            self.bind_locked(inner, network, IpEndpoint::default())?;
+        }
+        Ok(inner.local.unwrap())
+    }
+
+    fn bind_locked(
+        &self,
+        inner: &mut Inner,
+        network: &mut Network,
+        mut endpoint: IpEndpoint,
+    ) -> NetResult {
+        if inner.local.is_some() {
+            return Err(NetError::InvalidParam);
+        }
+        if !endpoint.addr.is_unspecified() {
+            let interface = network
+                .interface_of(endpoint.addr)
+                .ok_or(NetError::AddrNotAvailable)?;
+            self.move_to(inner, network, interface)?;
+        }
+        endpoint.port = reserve_port(self.kind, endpoint.port)?;
+        if let (SocketKind::Udp, Some(handle)) = (self.kind, inner.handle) {
+            let mut socket = network.sockets(inner.interface).get::<UdpSocket>(handle);
+            if socket.bind(endpoint).is_err() {
+                PORTS.lock().remove(&(self.kind, endpoint.port));
+                return Err(NetError::InvalidParam);
+            }
+        }
+        inner.local = Some(endpoint);
+        inner.owns_port = true;
+        Ok(())
+    }
+
+    /// 绑定本地地址，端口为 0 时自动分配
+    pub fn bind(&self, endpoint: IpEndpoint) -> NetResult {
+        let mut inner = self.inner.lock();
+        with_network(|network| self.bind_locked(&mut inner, network, endpoint))
+    }
+
+    /// 开始监听 TCP 连接，绑定到任意地址时在每个接口上监听
+    pub fn listen(&self, backlog: usize) -> NetResult {
+        if self.kind != SocketKind::Tcp {
+            return Err(NetError::NotSupported);
+        }
+        let mut inner = self.inner.lock();
+        if !inner.backlog.is_empty() {
+            return Ok(());
+        }
+        with_network(|network| {
+            let handle = inner.handle.ok_or(NetError::InvalidParam)?;
+            if network
+                .sockets(inner.interface)
+                .get::<TcpSocket>(handle)
+                .is_open()
+            {
+                return Err(NetError::IsConnected);
+            }
+            let local = self.ensure_bound(&mut inner, network)?;
+            network.sockets(inner.interface).remove(handle);
+            inner.handle = None;
+            let interfaces = if local.addr.is_unspecified() {
+                0..network.interfaces.len()
+            } else {
+                inner.interface..inner.interface + 1
+            };
+            for interface in interfaces {
+                for _ in 0..backlog.max(1).min(MAX_BACKLOG) {
+                    let mut socket = tcp_socket();
+                    socket.listen(local).map_err(|_| NetError::InvalidParam)?;
+                    let handle = network.sockets(interface).add(socket);
+                    inner.backlog.push((interface, handle));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// 取出一个已经建立的 TCP 连接
+    pub fn accept(&self) -> NetResult<Arc<Socket>> {
+        let mut inner = self.inner.lock();
+        if inner.backlog.is_empty() {
+            return Err(NetError::InvalidParam);
+        }
+        let local = inner.local.unwrap();
+        let (interface, handle) = with_network(|network| {
+            for (interface, slot) in inner.backlog.iter_mut() {
+                let interface = *interface;
+                let sockets = network.sockets(interface);
+                let mut socket = sockets.get::<TcpSocket>(*slot);
+                // 尚未被取走就被对方重置的连接重新监听
+                if socket.state() == TcpState::Closed {
+                    socket.listen(local).map_err(|_| NetError::InvalidParam)?;
+                    continue;
+                }
+                if established(&socket) {
+                    drop(socket);
+                    let mut listener = tcp_socket();
+                    listener.listen(local).map_err(|_| NetError::InvalidParam)?;
+                    let handle = core::mem::replace(slot, sockets.add(listener));
+                    return Ok((interface, handle));
+                }
+            }
+            Err(NetError::Again)
+        })?;
+        Ok(Self::with_inner(
+            SocketKind::Tcp,
+            Inner {
+                interface,
+                handle: Some(handle),
+                backlog: Vec::new(),
+                local: Some(local),
+                owns_port: false,
+                peer: None,
+                connecting: false,
+                read_shutdown: false,
+            },
+        ))
+    }
+
+    /// 连接对端
+    ///
+    /// TCP 发起连接后返回 [`NetError::InProgress`]，之后再次调用返回 [`NetError::Already`]，
+    /// 直到连接建立或失败；UDP 只是记录默认的对端
+    pub fn connect(&self, endpoint: IpEndpoint) -> NetResult {
+        let mut inner = self.inner.lock();
+        with_network(|network| {
+            self.follow_route(&mut inner, network, endpoint.addr)?;
+            let local = self.ensure_bound(&mut inner, network)?;
+            let handle = inner.handle.ok_or(NetError::InvalidParam)?;
+            if self.kind == SocketKind::Udp {
+                inner.peer = Some(endpoint);
+                return Ok(());
+            }
+            let mut socket = network.sockets(inner.interface).get::<TcpSocket>(handle);
+            if inner.connecting {
+                return match socket.state() {
+                    TcpState::SynSent | TcpState::SynReceived => Err(NetError::Already),
+                    TcpState::Closed => {
+                        inner.connecting = false;
+                        Err(NetError::ConnectionRefused)
+                    }
+                    _ => {
+                        inner.connecting = false;
+                        Ok(())
+                    }
+                };
+            }
+            if socket.is_open() {
+                return Err(NetError::IsConnected);
+            }
+            socket
+                .connect(endpoint, local)
+                .map_err(|_| NetError::InvalidParam)?;
+            inner.connecting = true;
+            Err(NetError::InProgress)
+        })
+    }
+
+    /// 发送数据，UDP 没有连接时需要指定目的地址
+    pub fn send(&self, data: &[u8], destination: Option<IpEndpoint>) -> NetResult<usize> {
+        let mut inner = self.inner.lock();
+        with_network(|network| {
+            if self.kind == SocketKind::Udp {
+                let destination = destination
+                    .or(inner.peer)
+                    .ok_or(NetError::DestinationRequired)?;
+                // 已经绑定的套接字可能还有待取的数据包，不再移动
+                if inner.local.is_none() {
+                    self.follow_route(&mut inner, network, destination.addr)?;
+                }
+                self.ensure_bound(&mut inner, network)?;
+                let handle = inner.handle.ok_or(NetError::InvalidParam)?;
+                let mut socket = network.sockets(inner.interface).get::<UdpSocket>(handle);
+                return match socket.send_slice(data, destination) {
+                    Ok(()) => Ok(data.len()),
+                    Err(smoltcp::Error::Exhausted) => Err(NetError::Again),
+                    Err(_) => Err(NetError::InvalidParam),
+                };
+            }
+            let handle = inner.handle.ok_or(NetError::NotConnected)?;
+            let mut socket = network.sockets(inner.interface).get::<TcpSocket>(handle);
+            if !socket.may_send() {
+                return Err(match socket.state() {
+                    TcpState::Closed | TcpState::Listen => NetError::NotConnected,
+                    TcpState::SynSent | TcpState::SynReceived => NetError::Again,
+                    _ => NetError::BrokenPipe,
+                });
+            }
+            match socket.send_slice(data) {
+                Ok(0) if !data.is_empty() => Err(NetError::Again),
+                Ok(len) => Ok(len),
+                Err(_) => Err(NetError::BrokenPipe),
+            }
+        })
+    }
+
+    /// 接收数据，返回长度和对端地址，TCP 连接关闭后返回 0
+    pub fn recv(&self, buffer: &mut [u8]) -> NetResult<(usize, Option<IpEndpoint>)> {
+        let inner = self.inner.lock();
+        if inner.read_shutdown {
+            return Ok((0, None));
+        }
+        with_network(|network| {
+            let handle = inner.handle.ok_or(NetError::NotConnected)?;
+            if self.kind == SocketKind::Udp {
+                let mut socket = network.sockets(inner.interface).get::<UdpSocket>(handle);
+                return match socket.recv_slice(buffer) {
+                    Ok((len, endpoint)) => Ok((len, Some(endpoint))),
+                    Err(_) => Err(NetError::Again),
+                };
+            }
+            let mut socket = network.sockets(inner.interface).get::<TcpSocket>(handle);
+            let peer = Some(socket.remote_endpoint());
+            if socket.can_recv() {
+                return match socket.recv_slice(buffer) {
+                    Ok(len) => Ok((len, peer)),
+                    Err(_) => Ok((0, peer)),
+                };
+            }
+            if socket.may_recv() {
+                return Err(NetError::Again);
+            }
+            match socket.state() {
+                TcpState::Closed if inner.connecting => Err(NetError::ConnectionRefused),
+                TcpState::Listen => Err(NetError::NotConnected),
+                TcpState::SynSent | TcpState::SynReceived => Err(NetError::Again),
+                _ => Ok((0, peer)),
+            }
+        })
+    }
+
+    /// 关闭连接的读端和（或）写端，TCP 关闭写端时发送 FIN
+    pub fn shutdown(&self, read: bool, write: bool) -> NetResult {
+        let mut inner = self.inner.lock();
+        inner.read_shutdown |= read;
+        if !write || self.kind != SocketKind::Tcp {
+            return Ok(());
+        }
+        with_network(|network| {
+            let handle = inner.handle.ok_or(NetError::NotConnected)?;
+            let mut socket = network.sockets(inner.interface).get::<TcpSocket>(handle);
+            if !socket.is_open() {
+                return Err(NetError::NotConnected);
+            }
+            socket.close();
+            Ok(())
+        })
+    }
+
+    /// 本地地址，没有绑定时为 `0.0.0.0:0`，绑定到任意地址时使用接口的地址
+    pub fn local_endpoint(&self) -> IpEndpoint {
+        let inner = self.inner.lock();
+        let mut endpoint = inner.local.unwrap_or_default();
+        if endpoint.port != 0 && endpoint.addr.is_unspecified() {
+            let interface = inner.interface;
+            if let Ok(Some(address)) =
+                with_network(|network| Ok(network.interfaces[interface].address()))
+            {
+                endpoint.addr = address;
+            }
+        }
+        endpoint
+    }
+
+    /// TCP 连接已被对端关闭或者没有连接，对应 `POLLHUP`
+    pub fn is_hung_up(&self) -> bool {
+        let inner = self.inner.lock();
+        if self.kind != SocketKind::Tcp || !inner.backlog.is_empty() {
+            return false;
+        }
+        with_network(|network| {
+            let handle = inner.handle.ok_or(NetError::NotConnected)?;
+            let socket = network.sockets(inner.interface).get::<TcpSocket>(handle);
+            Ok(matches!(
+                socket.state(),
+                TcpState::Closed
+                    | TcpState::CloseWait
+                    | TcpState::LastAck
+                    | TcpState::Closing
+                    | TcpState::TimeWait
+            ))
+        })
+        .unwrap_or(false)
+    }
+
+    /// 对端地址
+    pub fn peer_endpoint(&self) -> NetResult<IpEndpoint> {
+        let inner = self.inner.lock();
+        if self.kind == SocketKind::Udp {
+            return inner.peer.ok_or(NetError::NotConnected);
+        }
+        with_network(|network| {
+            let handle = inner.handle.ok_or(NetError::NotConnected)?;
+            let socket = network.sockets(inner.interface).get::<TcpSocket>(handle);
+            match socket.state() {
+                TcpState::Closed | TcpState::Listen | TcpState::SynSent => {
+                    Err(NetError::NotConnected)
+                }
+                _ => Ok(socket.remote_endpoint()),
+            }
+        })
+    }
+}
+
+impl From<NetError> for FsError {
+    fn from(error: NetError) -> Self {
+        match error {
+            NetError::Again | NetError::InProgress | NetError::Already => FsError::Again,
+            // 和管道一样，写入已经关闭的连接时返回 NoDevice，系统调用转换为 EPIPE
+            NetError::BrokenPipe => FsError::NoDevice,
+            NetError::NotConnected | NetError::InvalidParam | NetError::DestinationRequired => {
+                FsError::InvalidParam
+            }
+            NetError::NotSupported => FsError::NotSupported,
+            _ => FsError::DeviceError,
+        }
+    }
+}
+
+impl INode for Socket {
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.recv(buf)?.0)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        Ok(self.send(buf, None)?)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        let inner = self.inner.lock();
+        let status = with_network(|network| {
+            if !inner.backlog.is_empty() {
+                let ready = inner.backlog.iter().any(|&(interface, handle)| {
+                    established(&network.sockets(interface).get::<TcpSocket>(handle))
+                });
+                return Ok(PollStatus {
+                    read: ready,
+                    write: false,
+                    error: false,
+                });
+            }
+            let handle = inner.handle.ok_or(NetError::NotConnected)?;
+            Ok(match self.kind {
+                SocketKind::Tcp => {
+                    let socket = network.sockets(inner.interface).get::<TcpSocket>(handle);
+                    let connecting =
+                        matches!(socket.state(), TcpState::SynSent | TcpState::SynReceived);
+                    PollStatus {
+                        read: socket.can_recv() || (!socket.may_recv() && !connecting),
+                        write: socket.can_send() || (!socket.may_send() && !connecting),
+                        error: inner.connecting && socket.state() == TcpState::Closed,
+                    }
+                }
+                SocketKind::Udp => {
+                    let socket = network.sockets(inner.interface).get::<UdpSocket>(handle);
+                    PollStatus {
+                        read: socket.can_recv(),
+                        write: socket.can_send(),
+                        error: false,
+                    }
+                }
+            })
+        });
+        Ok(status.unwrap_or(PollStatus {
+            read: false,
+            write: false,
+            error: true,
+        }))
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        let zero = Timespec { sec: 0, nsec: 0 };
+        Ok(Metadata {
+            dev: 0,
+            inode: self as *const _ as *const u8 as usize,
+            size: 0,
+            blk_size: 0,
+            blocks: 0,
+            atime: zero,
+            mtime: zero,
+            ctime: zero,
+            type_: FileType::Socket,
+            mode: 0o777,
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+        })
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// 所有文件描述符都关闭后释放协议栈中的套接字，TCP 连接会先完成挥手
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+        if let (true, Some(local)) = (inner.owns_port, inner.local) {
+            PORTS.lock().remove(&(self.kind, local.port));
+        }
+        let (interface, handle) = (inner.interface, inner.handle);
+        let backlog = core::mem::take(&mut inner.backlog);
+        let kind = self.kind;
+        let _ = with_network(|network| {
+            for (interface, handle) in backlog {
+                network.sockets(interface).remove(handle);
+            }
+            match (kind, handle) {
+                (SocketKind::Tcp, Some(handle)) => {
+                    network.sockets(interface).get::<TcpSocket>(handle).close();
+                    network.interfaces[interface].closing.push(handle);
+                }
+                (SocketKind::Udp, Some(handle)) => {
+                    network.sockets(interface).remove(handle);
+                }
+                _ => {}
+            }
+            Ok(())
+        });
+    }
+}
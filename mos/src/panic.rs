@@ -1,20 +1,39 @@
 //! 代替 std 库，实现 panic 和 abort 的功能
 
-use crate::sbi::shutdown;
+use crate::backtrace::print_backtrace;
+use crate::drivers::power::poweroff;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-/// 打印 panic 的信息并 [`shutdown`]
+/// 是否已经发生 panic，避免回溯时再次 panic 导致无限递归
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+/// 打印 panic 的信息和调用链，然后以退出码 1 [`poweroff`]
 ///
 /// ### `#[panic_handler]` 属性
 /// 声明此函数是 panic 的回调
 #[panic_handler]
 fn panic_handler(info: &PanicInfo) -> ! {
+    #[cfg(test)]
+    crate::tests::report_failure();
     // `\x1b[??m` 是控制终端字符输出格式的指令，在支持的平台上可以改变文字颜色等等，这里使用红色
     // 参考：https://misc.flogisoft.com/bash/tip_colors_and_formatting
     //
     // 需要全局开启 feature(panic_info_message) 才可以调用 .message() 函数
-    println!("\x1b[1;31mpanic: '{}, {}'\x1b[0m", info.message().unwrap(), info.location().unwrap());
-    shutdown()
+    println!(
+        "\x1b[1;31mpanic: '{}, {}'\x1b[0m",
+        info.message().unwrap(),
+        info.location().unwrap()
+    );
+    if !PANICKED.swap(true, Ordering::Relaxed) {
+        print_backtrace();
+    }
+    // 串口发送缓冲区中可能还有数据
+    crate::console::flush();
+    #[cfg(test)]
+    crate::tests::exit_failure();
+    #[cfg(not(test))]
+    poweroff(1)
 }
 
 /// 终止程序
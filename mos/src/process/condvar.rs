@@ -0,0 +1,90 @@
+//! 条件变量 [`Condvar`]
+
+use super::processor::PROCESSOR;
+use super::thread::Thread;
+use alloc::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use spin::Mutex;
+
+/// 条件变量被唤醒时得到通知的对象，例如 epoll 实例
+pub trait Observer: Send + Sync {
+    /// `key` 是订阅时给出的值
+    fn notify(&self, key: usize);
+}
+
+/// 条件变量，线程在其上休眠，直到被其他线程唤醒
+#[derive(Default)]
+pub struct Condvar {
+    /// 正在等待的线程
+    watchers: Mutex<VecDeque<Arc<Thread>>>,
+    /// 订阅的对象，和线程不同，每次唤醒之后不会被移除
+    observers: Mutex<Vec<(Weak<dyn Observer>, usize)>>,
+}
+
+impl Condvar {
+    /// 令当前线程休眠，等待此条件变量
+    ///
+    /// 只会将线程移出调度器，调用者随后需要保存 `Context` 并切换到下一个线程。
+    /// 同一个线程可以同时等待多个条件变量，任意一个被唤醒即可
+    pub fn wait(&self) {
+        let mut processor = PROCESSOR.lock();
+        let thread = processor.current_thread();
+        let mut watchers = self.watchers.lock();
+        if !watchers.contains(&thread) {
+            watchers.push_back(thread.clone());
+        }
+        if !thread.inner().sleeping {
+            processor.sleep_current_thread();
+        }
+    }
+
+    /// 订阅此条件变量，对象被释放后自动取消
+    pub fn subscribe(&self, observer: Weak<dyn Observer>, key: usize) {
+        let mut observers = self.observers.lock();
+        // 顺便清理已经释放的对象
+        observers.retain(|(observer, _)| observer.strong_count() > 0);
+        observers.push((observer, key));
+    }
+
+    /// 取消对象的所有订阅
+    pub fn unsubscribe(&self, observer: &Arc<dyn Observer>) {
+        let target = Arc::as_ptr(observer) as *const u8;
+        self.observers
+            .lock()
+            .retain(|(other, _)| match other.upgrade() {
+                Some(other) => Arc::as_ptr(&other) as *const u8 != target,
+                None => false,
+            });
+    }
+
+    /// 唤醒所有等待的线程，并通知订阅的对象
+    pub fn notify_all(&self) {
+        let watchers: VecDeque<_> = self.watchers.lock().drain(..).collect();
+        {
+            let mut processor = PROCESSOR.lock();
+            for thread in watchers {
+                // 线程可能已经被其他条件变量唤醒
+                if thread.inner().sleeping {
+                    processor.wake_thread(thread);
+                }
+            }
+        }
+        let mut observers = Vec::new();
+        self.observers
+            .lock()
+            .retain(|(observer, key)| match observer.upgrade() {
+                Some(observer) => {
+                    observers.push((observer, *key));
+                    true
+                }
+                None => false,
+            });
+        // 通知时不持有锁，对象可能会继续唤醒其他条件变量
+        for (observer, key) in observers {
+            observer.notify(key);
+        }
+    }
+}
@@ -5,3 +5,6 @@ pub const STACK_SIZE: usize = 0x8_0000;
 
 /// 共用的内核栈大小 512 KB
 pub const KERNEL_STACK_SIZE: usize = 0x8_0000;
+
+/// 每个进程最多打开的文件描述符个数
+pub const MAX_DESCRIPTORS: usize = 1024;
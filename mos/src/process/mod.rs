@@ -1,10 +1,12 @@
 use super::memory::{VirtualAddress, MemorySet};
 use super::interrupt::Context;
 
+pub mod condvar;
 pub mod config;
-mod lock;
+pub mod lock;
 
 pub mod process;
 pub mod thread;
 pub mod processor;
+pub mod signal;
 mod kernel_stack;
\ No newline at end of file
@@ -6,12 +6,25 @@ use crate::process::VirtualAddress;
 use crate::memory::mapping::Flags;
 use crate::memory::mapping::Segment;
 use crate::memory::mapping::MapType;
+use super::config::MAX_DESCRIPTORS;
+use super::signal::{SignalAction, SIGNAL_COUNT};
+use crate::fs::FileHandle;
+use alloc::string::String;
+use alloc::vec::Vec;
 use spin::Mutex;
 
 use super::MemorySet;
 
+/// 进程 ID
+pub type ProcessID = isize;
+
+/// 进程计数，用于设置进程 ID
+static mut PROCESS_COUNTER: ProcessID = 0;
+
 /// 进程的信息
 pub struct Process {
+    /// 进程 ID
+    pub id: ProcessID,
     /// 是否属于用户态
     pub is_user: bool,
     /// 用 `Mutex` 包装一些可变的变量
@@ -22,18 +35,91 @@ pub struct ProcessInner {
     /// 进程中的线程公用页表 / 内存映射
     /// Note(mwish): 一个进程对应一个映射，这个因为关联到更多内存，是需要可变的。
     pub memory_set: MemorySet,
-//  /// 打开的文件描述符（实验五）
-//  pub descriptors: Vec<Arc<dyn INode>>,
+    /// 打开的文件描述符，已关闭的位置为 `None`
+    pub descriptors: Vec<Option<Arc<FileHandle>>>,
+    /// 当前工作目录的绝对路径
+    pub cwd: String,
+    /// 所在的进程组，终端按照进程组发送信号
+    pub pgid: ProcessID,
+    /// 各个信号的处理方式，下标为信号编号减 1
+    pub signal_actions: [SignalAction; SIGNAL_COUNT],
+}
+
+impl ProcessInner {
+    /// 加入一个文件描述符，使用最小的空闲编号
+    ///
+    /// 已经打开了 [`MAX_DESCRIPTORS`] 个文件时返回 `None`
+    pub fn add_descriptor(&mut self, handle: Arc<FileHandle>) -> Option<usize> {
+        match self.descriptors.iter().position(Option::is_none) {
+            Some(fd) => {
+                self.descriptors[fd] = Some(handle);
+                Some(fd)
+            }
+            None if self.descriptors.len() < MAX_DESCRIPTORS => {
+                self.descriptors.push(Some(handle));
+                Some(self.descriptors.len() - 1)
+            }
+            None => None,
+        }
+    }
+
+    /// 取得文件描述符对应的文件
+    pub fn get_descriptor(&self, fd: usize) -> Option<Arc<FileHandle>> {
+        self.descriptors.get(fd).cloned().flatten()
+    }
+
+    /// 将文件放在指定的文件描述符上，返回原来的文件
+    pub fn set_descriptor(&mut self, fd: usize, handle: Arc<FileHandle>) -> Option<Arc<FileHandle>> {
+        if fd >= self.descriptors.len() {
+            self.descriptors.resize(fd + 1, None);
+        }
+        self.descriptors[fd].replace(handle)
+    }
+
+    /// 关闭文件描述符，返回原来的文件
+    pub fn remove_descriptor(&mut self, fd: usize) -> Option<Arc<FileHandle>> {
+        self.descriptors.get_mut(fd).and_then(Option::take)
+    }
 }
 
 #[allow(unused)]
 impl Process {
     /// 创建一个内核进程
     pub fn new_kernel() -> MemoryResult<Arc<Self>> {
+        let id = unsafe {
+            PROCESS_COUNTER += 1;
+            PROCESS_COUNTER
+        };
         Ok(Arc::new(Self {
+            id,
             is_user: false,
             inner: Mutex::new(ProcessInner {
                 memory_set: MemorySet::new_kernel()?,
+                descriptors: Vec::new(),
+                cwd: String::from("/"),
+                pgid: id,
+                signal_actions: [SignalAction::default(); SIGNAL_COUNT],
+            }),
+        }))
+    }
+
+    /// 复制进程，新进程有相同的内存内容，并共享打开的文件
+    ///
+    /// 必须在自身的页表被激活时调用，见 [`MemorySet::fork`]
+    pub fn fork(&self) -> MemoryResult<Arc<Self>> {
+        let inner = self.inner();
+        Ok(Arc::new(Self {
+            id: unsafe {
+                PROCESS_COUNTER += 1;
+                PROCESS_COUNTER
+            },
+            is_user: self.is_user,
+            inner: Mutex::new(ProcessInner {
+                memory_set: inner.memory_set.fork()?,
+                descriptors: inner.descriptors.clone(),
+                cwd: inner.cwd.clone(),
+                pgid: inner.pgid,
+                signal_actions: inner.signal_actions,
             }),
         }))
     }
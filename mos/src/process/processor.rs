@@ -1,8 +1,9 @@
 use crate::process::Context;
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use super::thread::Thread;
 use super::process::Process;
 use super::lock::Lock;
+use crate::interrupt::cancel_sleep;
 
 use algorithm::{SchedulerImpl, Scheduler};
 
@@ -48,7 +49,6 @@ pub struct Processor {
 impl Processor {
     /// 获取一个当前线程的 `Arc` 引用
     pub fn current_thread(&self) -> Arc<Thread> {
-        println!("current_thread is called");
         self.current_thread.as_ref().unwrap().clone()
     }
 
@@ -63,8 +63,9 @@ impl Processor {
         } else {
             // 没有活跃线程
             if self.sleeping_threads.is_empty() {
-                // 也没有休眠线程，则退出
-                panic!("all threads terminated, shutting down");
+                // 也没有休眠线程，则写回文件系统之后关机
+                info!("all threads terminated, shutting down");
+                crate::kernel::poweroff(0)
             } else {
                 // 有休眠线程，则等待中断
                 self.current_thread = Some(IDLE_THREAD.clone());
@@ -73,11 +74,54 @@ impl Processor {
         }
     }
 
+    /// 列出调度器中的线程，顺序和调度队列相同
+    pub fn scheduled_threads(&self) -> Vec<Arc<Thread>> {
+        self.scheduler.threads()
+    }
+
+    /// 列出所有线程，包括调度器中的线程、休眠线程以及当前线程
+    pub fn threads(&self) -> Vec<Arc<Thread>> {
+        let mut threads = self.scheduler.threads();
+        threads.extend(self.sleeping_threads.iter().cloned());
+        if let Some(current_thread) = &self.current_thread {
+            if !threads.contains(current_thread) {
+                threads.push(current_thread.clone());
+            }
+        }
+        threads
+    }
+
+    /// 判断是否为当前正在执行的线程
+    pub fn is_current_thread(&self, thread: &Thread) -> bool {
+        self.current_thread
+            .as_ref()
+            .map_or(false, |current_thread| current_thread.id == thread.id)
+    }
+
     /// 添加一个待执行的线程
     pub fn add_thread(&mut self, thread: Arc<Thread>) {
         self.scheduler.add_thread(thread);
     }
 
+    /// 终止一个不在运行的线程，它位于调度器中或者正在休眠
+    ///
+    /// 和 [`Processor::kill_current_thread`] 一样，调用者应当在释放锁之后通过 [`release_thread`] 丢弃这个线程
+    pub fn kill_thread(&mut self, thread: &Arc<Thread>) {
+        let mut inner = thread.inner();
+        if inner.sleeping {
+            // 之后条件变量的唤醒会因此被忽略
+            inner.sleeping = false;
+            self.sleeping_threads.remove(thread);
+        } else {
+            self.scheduler.remove_thread(thread);
+        }
+        // 带超时的等待还在时钟的队列中
+        if let Some(deadline) = inner.timeout {
+            cancel_sleep(deadline, thread.id);
+        }
+        inner.dead = true;
+    }
+
     /// 唤醒一个休眠线程
     pub fn wake_thread(&mut self, thread: Arc<Thread>) {
         thread.inner().sleeping = false;
@@ -87,13 +131,11 @@ impl Processor {
 
     /// 保存当前线程的 `Context`
     pub fn park_current_thread(&mut self, context: &Context) {
-        println!("part_current_thread is called");
         self.current_thread().park(*context);
     }
 
     /// 令当前线程进入休眠
     pub fn sleep_current_thread(&mut self) {
-        println!("sleep current thread is called");
         // 从 current_thread 中取出
         let current_thread = self.current_thread();
         // 记为 sleeping
@@ -103,11 +145,32 @@ impl Processor {
         self.sleeping_threads.insert(current_thread);
     }
 
-    /// 终止当前的线程
-    pub fn kill_current_thread(&mut self) {
-        println!("kill current thread is called");
+    /// 终止当前的线程，返回这个线程
+    ///
+    /// 调用者应当在释放 [`PROCESSOR`] 的锁之后通过 [`release_thread`] 丢弃返回的线程
+    pub fn kill_current_thread(&mut self) -> Arc<Thread> {
         // 从调度器中移除
         let thread = self.current_thread.take().unwrap();
         self.scheduler.remove_thread(&thread);
+        thread
+    }
+}
+
+/// 丢弃被终止的线程，进程中没有其他线程时关闭它打开的所有文件
+///
+/// 被终止的线程可能还留在条件变量的等待队列中，使进程不能立即释放，
+/// 这里关闭文件，管道和套接字的另一端才能看到连接关闭。
+/// 不能持有 [`PROCESSOR`] 的锁：关闭管道需要唤醒另一端的线程
+pub fn release_thread(thread: Arc<Thread>) {
+    let process = thread.process.clone();
+    drop(thread);
+    let alive = PROCESSOR
+        .lock()
+        .threads()
+        .iter()
+        .any(|other| Arc::ptr_eq(&other.process, &process));
+    if !alive {
+        let descriptors = core::mem::take(&mut process.inner().descriptors);
+        drop(descriptors);
     }
 }
@@ -0,0 +1,109 @@
+//! 信号的编号、默认行为，以及进程和线程中保存的信号状态
+//!
+//! 信号集合用 `u64` 表示，第 `n - 1` 位对应编号为 `n` 的信号
+
+/// 信号的个数，编号从 1 开始
+pub const SIGNAL_COUNT: usize = 64;
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGABRT: usize = 6;
+pub const SIGBUS: usize = 7;
+pub const SIGFPE: usize = 8;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGPIPE: usize = 13;
+pub const SIGALRM: usize = 14;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGURG: usize = 23;
+pub const SIGWINCH: usize = 28;
+
+/// 使用默认行为
+pub const SIG_DFL: usize = 0;
+/// 忽略信号
+pub const SIG_IGN: usize = 1;
+
+/// 处理函数接收 `siginfo_t` 和上下文两个额外参数
+pub const SA_SIGINFO: usize = 0x4;
+/// 处理函数执行时不屏蔽这个信号本身
+pub const SA_NODEFER: usize = 0x4000_0000;
+/// 递送一次之后恢复为默认行为
+pub const SA_RESETHAND: usize = 0x8000_0000;
+
+/// 信号在集合中对应的位
+pub const fn bit(signal: usize) -> u64 {
+    1 << (signal - 1)
+}
+
+/// 不能被屏蔽、捕获或忽略的信号
+pub const UNBLOCKABLE: u64 = bit(SIGKILL) | bit(SIGSTOP);
+
+/// 默认行为是暂停的信号
+pub const STOP_SIGNALS: u64 = bit(SIGSTOP) | bit(SIGTSTP) | bit(SIGTTIN) | bit(SIGTTOU);
+
+/// 信号的默认行为
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultAction {
+    /// 终止进程
+    Terminate,
+    /// 忽略
+    Ignore,
+    /// 暂停进程，直到收到 `SIGCONT`
+    Stop,
+    /// 继续被暂停的进程，这在发送时就已完成，递送时忽略
+    Continue,
+}
+
+/// 取得信号的默认行为
+pub fn default_action(signal: usize) -> DefaultAction {
+    match signal {
+        SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        SIGCONT => DefaultAction::Continue,
+        _ => DefaultAction::Terminate,
+    }
+}
+
+/// `struct sigaction`，riscv64 上没有 `sa_restorer`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SignalAction {
+    /// 处理函数的地址，或者 [`SIG_DFL`]、[`SIG_IGN`]
+    pub handler: usize,
+    pub flags: usize,
+    /// 处理函数执行时额外屏蔽的信号
+    pub mask: u64,
+}
+
+impl SignalAction {
+    /// 这个信号递送时会被丢弃
+    pub fn is_ignored(&self, signal: usize) -> bool {
+        match self.handler {
+            SIG_IGN => true,
+            SIG_DFL => default_action(signal) == DefaultAction::Ignore,
+            _ => false,
+        }
+    }
+}
+
+/// 线程的信号状态
+#[derive(Debug, Default, Clone)]
+pub struct SignalState {
+    /// 等待递送的信号
+    pub pending: u64,
+    /// 被屏蔽的信号
+    pub blocked: u64,
+    /// 被暂停，直到收到 `SIGCONT` 或 `SIGKILL`
+    pub stopped: bool,
+}
@@ -10,6 +10,7 @@ use super::process::Process;
 use super::config::STACK_SIZE;
 use crate::memory::Flags;
 use super::kernel_stack::KERNEL_STACK;
+use super::signal::SignalState;
 
 use core::hash::{Hash, Hasher};
 
@@ -41,6 +42,10 @@ pub struct ThreadInner {
     pub sleeping: bool,
     /// 是否已经结束
     pub dead: bool,
+    /// 带超时的系统调用等待到的时刻（tick），被重新执行时沿用
+    pub timeout: Option<usize>,
+    /// 信号状态
+    pub signals: SignalState,
 }
 
 impl Thread {
@@ -91,12 +96,40 @@ impl Thread {
                 context: Some(context),
                 sleeping: false,
                 dead: false,
+                timeout: None,
+                signals: SignalState::default(),
             }),
         });
 
         Ok(thread)
     }
 
+    /// 在 `process` 中复制一个线程，使用相同的栈，从 `context` 开始执行
+    ///
+    /// 用于 `fork`，`process` 应当是由当前进程复制而来的
+    pub fn fork(&self, process: Arc<Process>, context: Context) -> Arc<Thread> {
+        // 继承信号屏蔽字，但不继承等待递送的信号
+        let signals = SignalState {
+            blocked: self.inner().signals.blocked,
+            ..SignalState::default()
+        };
+        Arc::new(Thread {
+            id: unsafe {
+                THREAD_COUNTER += 1;
+                THREAD_COUNTER
+            },
+            stack: self.stack,
+            process,
+            inner: Mutex::new(ThreadInner {
+                context: Some(context),
+                sleeping: false,
+                dead: false,
+                timeout: None,
+                signals,
+            }),
+        })
+    }
+
     /// 上锁并获得可变部分的引用
     pub fn inner(&self) -> spin::MutexGuard<ThreadInner> {
         self.inner.lock()
@@ -0,0 +1,162 @@
+//! 内核熵池，为 `getrandom` 系统调用和 `/dev/random`、`/dev/urandom` 提供随机数
+//!
+//! 熵源设备读出的随机数、启动时间以及用户写入 `/dev/random` 的数据都混入熵池的密钥中，
+//! 输出由 ChaCha20 生成。每次输出之后立即用新生成的一块替换密钥，
+//! 即使之后密钥泄露也无法恢复此前的输出。有熵源设备时，每隔 [`RESEED_INTERVAL`] 从设备补充一次
+
+use crate::drivers::rng::rng_devices;
+use crate::interrupt::CLOCK_FREQ;
+use core::sync::atomic::{AtomicBool, Ordering};
+use riscv::register::time;
+use spin::Mutex;
+
+/// 两次从熵源设备补充之间的最短时间（时钟周期数）
+const RESEED_INTERVAL: usize = CLOCK_FREQ;
+/// 每次从每个熵源设备读取的字节数
+const SEED_SIZE: usize = 32;
+/// ChaCha20 的常量 "expand 32-byte k"
+const SIGMA: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+/// 熵池状态
+struct Pool {
+    key: [u32; 8],
+    /// 每生成一块加一，保证同一个密钥下的各块不同
+    counter: u64,
+    /// 上一次从熵源设备补充的时间
+    last_reseed: usize,
+}
+
+lazy_static! {
+    static ref POOL: Mutex<Pool> = Mutex::new(Pool {
+        key: [0; 8],
+        counter: 0,
+        last_reseed: 0,
+    });
+}
+
+/// 是否从熵源设备得到过随机数
+static HARDWARE_SEEDED: AtomicBool = AtomicBool::new(false);
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// ChaCha20 的一块输出，nonce 固定为 0
+fn chacha20_block(key: &[u32; 8], counter: u64) -> [u32; 16] {
+    let mut input = [0u32; 16];
+    input[..4].copy_from_slice(&SIGMA);
+    input[4..12].copy_from_slice(key);
+    input[12] = counter as u32;
+    input[13] = (counter >> 32) as u32;
+    let mut state = input;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    for (word, input) in state.iter_mut().zip(input.iter()) {
+        *word = word.wrapping_add(*input);
+    }
+    state
+}
+
+impl Pool {
+    fn block(&mut self) -> [u32; 16] {
+        let block = chacha20_block(&self.key, self.counter);
+        self.counter = self.counter.wrapping_add(1);
+        block
+    }
+
+    /// 用新生成的一块替换密钥
+    fn rekey(&mut self) {
+        let block = self.block();
+        self.key.copy_from_slice(&block[..8]);
+    }
+
+    /// 把数据混入密钥
+    fn mix(&mut self, data: &[u8]) {
+        for chunk in data.chunks(32) {
+            for (index, word) in chunk.chunks(4).enumerate() {
+                let mut bytes = [0u8; 4];
+                bytes[..word.len()].copy_from_slice(word);
+                self.key[index] ^= u32::from_le_bytes(bytes);
+            }
+            self.rekey();
+        }
+    }
+
+    /// 从所有熵源设备读取随机数，返回读到的字节数
+    fn reseed(&mut self, now: usize) -> usize {
+        let mut total = 0;
+        for device in rng_devices() {
+            let mut seed = [0u8; SEED_SIZE];
+            let len = device.read_random(&mut seed);
+            self.mix(&seed[..len]);
+            total += len;
+        }
+        self.mix(&now.to_le_bytes());
+        self.last_reseed = now;
+        if total > 0 {
+            HARDWARE_SEEDED.store(true, Ordering::Relaxed);
+        }
+        total
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(64) {
+            let block = self.block();
+            for (bytes, word) in chunk.chunks_mut(4).zip(block.iter()) {
+                bytes.copy_from_slice(&word.to_le_bytes()[..bytes.len()]);
+            }
+        }
+        self.rekey();
+    }
+}
+
+/// 把数据混入熵池，例如用户写入 `/dev/random` 的数据
+pub fn add_entropy(data: &[u8]) {
+    POOL.lock().mix(data);
+}
+
+/// 用随机数填满缓冲区
+pub fn fill(buf: &mut [u8]) {
+    let mut pool = POOL.lock();
+    let now = time::read();
+    if HARDWARE_SEEDED.load(Ordering::Relaxed) && now - pool.last_reseed >= RESEED_INTERVAL {
+        pool.reseed(now);
+    } else {
+        // 不补充时也让每次读取的时间参与生成
+        pool.mix(&now.to_le_bytes());
+    }
+    pool.fill(buf);
+}
+
+/// 熵池是否从熵源设备得到过随机数，否则只混入了时间等可预测的数据
+pub fn hardware_seeded() -> bool {
+    HARDWARE_SEEDED.load(Ordering::Relaxed)
+}
+
+/// 驱动初始化之后，从熵源设备和启动时间初始化熵池
+pub fn init() {
+    let total = POOL.lock().reseed(time::read());
+    if total > 0 {
+        info!(
+            "mod random initialized, {} bytes from entropy sources",
+            total
+        );
+    } else {
+        warn!("mod random initialized without an entropy source");
+    }
+}
@@ -0,0 +1,79 @@
+//! Base 扩展：固件的版本和支持的扩展
+
+use super::*;
+
+/// 启动时探测到的固件信息
+pub(super) struct Firmware {
+    /// SBI 规范的版本，legacy 固件为 `None`
+    pub spec_version: Option<(usize, usize)>,
+    pub impl_id: usize,
+    pub impl_version: usize,
+    pub time: bool,
+    pub ipi: bool,
+    pub rfence: bool,
+    pub hsm: bool,
+    pub srst: bool,
+}
+
+lazy_static! {
+    /// 第一次调用 SBI 时探测
+    pub(super) static ref FIRMWARE: Firmware = probe();
+}
+
+/// 探测固件，不支持 Base 扩展时只能使用 legacy 调用
+fn probe() -> Firmware {
+    let version = match sbi_call(EXTENSION_BASE, 0, 0, 0, 0, 0, 0) {
+        Ok(version) => version,
+        Err(_) => {
+            return Firmware {
+                spec_version: None,
+                impl_id: 0,
+                impl_version: 0,
+                time: false,
+                ipi: false,
+                rfence: false,
+                hsm: false,
+                srst: false,
+            }
+        }
+    };
+    let base = |function| sbi_call(EXTENSION_BASE, function, 0, 0, 0, 0, 0).unwrap_or(0);
+    Firmware {
+        spec_version: Some(((version >> 24) & 0x7f, version & 0xff_ffff)),
+        impl_id: base(1),
+        impl_version: base(2),
+        time: probe_extension(EXTENSION_TIME),
+        ipi: probe_extension(EXTENSION_IPI),
+        rfence: probe_extension(EXTENSION_RFENCE),
+        hsm: probe_extension(EXTENSION_HSM),
+        srst: probe_extension(EXTENSION_SRST),
+    }
+}
+
+/// 固件是否支持某个扩展
+pub fn probe_extension(extension: usize) -> bool {
+    matches!(sbi_call(EXTENSION_BASE, 3, extension, 0, 0, 0, 0), Ok(value) if value != 0)
+}
+
+/// SBI 规范的主版本号和次版本号，legacy 固件返回 `None`
+pub fn spec_version() -> Option<(usize, usize)> {
+    FIRMWARE.spec_version
+}
+
+/// 固件实现的名称
+pub fn impl_id() -> &'static str {
+    match FIRMWARE.impl_id {
+        0 => "BBL",
+        1 => "OpenSBI",
+        2 => "Xvisor",
+        3 => "KVM",
+        4 => "RustSBI",
+        5 => "Diosix",
+        _ => "unknown",
+    }
+}
+
+/// 固件实现的版本，格式由各实现自己定义
+pub fn impl_version() -> usize {
+    FIRMWARE.impl_version
+}
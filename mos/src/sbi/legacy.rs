@@ -0,0 +1,72 @@
+//! legacy SBI 调用，即扩展编号 0 到 8，见
+//! https://github.com/riscv/riscv-sbi-doc/blob/master/riscv-sbi.adoc#legacy-sbi-extension-extension-ids-0x00-through-0x0f
+//!
+//! 它们在 SBI v0.2 中已经废弃，只在固件不支持对应的新扩展时使用。控制台的读写没有对应的新扩展
+
+/// SBI 调用，返回值只有 `a0`
+#[inline(always)]
+fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize, arg3: usize) -> usize {
+    let ret;
+    unsafe {
+        llvm_asm!("ecall"
+            : "={x10}" (ret)
+            : "{x10}" (arg0), "{x11}" (arg1), "{x12}" (arg2), "{x13}" (arg3), "{x17}" (which)
+            : "memory"      // 如果汇编可能改变内存，则需要加入 memory 选项
+            : "volatile"); // 防止编译器做激进的优化（如调换指令顺序等破坏 SBI 调用行为的优化）
+    }
+    ret
+}
+
+const SBI_SET_TIMER: usize = 0;
+const SBI_CONSOLE_PUTCHAR: usize = 1;
+const SBI_CONSOLE_GETCHAR: usize = 2;
+const SBI_CLEAR_IPI: usize = 3;
+const SBI_SEND_IPI: usize = 4;
+const SBI_REMOTE_FENCE_I: usize = 5;
+const SBI_REMOTE_SFENCE_VMA: usize = 6;
+const SBI_REMOTE_SFENCE_VMA_ASID: usize = 7;
+const SBI_SHUTDOWN: usize = 8;
+
+/// 向控制台输出一个字符
+///
+/// 需要注意我们不能直接使用 Rust 中的 char 类型
+pub fn console_putchar(c: usize) {
+    sbi_call(SBI_CONSOLE_PUTCHAR, c, 0, 0, 0);
+}
+
+/// 从控制台中读取一个字符
+///
+/// 没有读取到字符则返回 -1
+pub fn console_getchar() -> usize {
+    sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0, 0)
+}
+
+/// 调用 SBI_SHUTDOWN 来关闭操作系统（直接退出 QEMU）
+pub fn shutdown() -> ! {
+    sbi_call(SBI_SHUTDOWN, 0, 0, 0, 0);
+    unreachable!()
+}
+
+/// 设置下一次时钟中断的时间
+pub fn set_timer(time: usize) {
+    sbi_call(SBI_SET_TIMER, time, 0, 0, 0);
+}
+
+/// 向 `hart_mask` 中的 hart 发送软件中断，legacy 调用传入的是位图的地址
+pub fn send_ipi(hart_mask: usize) {
+    sbi_call(SBI_SEND_IPI, &hart_mask as *const _ as usize, 0, 0, 0);
+}
+
+/// 让其他 hart 执行 `fence.i`
+pub fn remote_fence_i(hart_mask: usize) {
+    sbi_call(SBI_REMOTE_FENCE_I, &hart_mask as *const _ as usize, 0, 0, 0);
+}
+
+/// 让其他 hart 刷新 TLB
+pub fn remote_sfence_vma(hart_mask: usize, start: usize, size: usize, asid: Option<usize>) {
+    let mask = &hart_mask as *const _ as usize;
+    match asid {
+        Some(asid) => sbi_call(SBI_REMOTE_SFENCE_VMA_ASID, mask, start, size, asid),
+        None => sbi_call(SBI_REMOTE_SFENCE_VMA, mask, start, size, 0),
+    };
+}
@@ -0,0 +1,251 @@
+//! 调用 Machine 层的操作
+//!
+//! 按照 SBI v0.2 之后的调用规则，`a7` 为扩展编号，`a6` 为功能编号，返回时 `a0` 为错误码、`a1` 为返回值，
+//! 见 https://github.com/riscv/riscv-sbi-doc/blob/master/riscv-sbi.adoc 。
+//!
+//! 启动时通过 Base 扩展探测固件支持的扩展，不支持时退回到已经废弃的 legacy 调用，见 [`legacy`]
+// 目前还不会用到全部的 SBI 调用，暂时允许未使用的变量或函数
+#![allow(unused)]
+
+mod base;
+mod legacy;
+
+pub use base::{impl_id, impl_version, probe_extension, spec_version};
+pub use legacy::{console_getchar, console_putchar};
+
+use base::FIRMWARE;
+
+/// 各个扩展的编号
+const EXTENSION_BASE: usize = 0x10;
+const EXTENSION_TIME: usize = 0x5449_4d45;
+const EXTENSION_IPI: usize = 0x73_5049;
+const EXTENSION_RFENCE: usize = 0x5246_4e43;
+const EXTENSION_HSM: usize = 0x48_534d;
+const EXTENSION_SRST: usize = 0x5352_5354;
+
+/// SBI 调用返回的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbiError {
+    Failed,
+    NotSupported,
+    InvalidParam,
+    Denied,
+    InvalidAddress,
+    AlreadyAvailable,
+    AlreadyStarted,
+    AlreadyStopped,
+    /// 规范中没有定义的错误码
+    Unknown(isize),
+}
+
+impl SbiError {
+    fn from_code(code: isize) -> Self {
+        match code {
+            -1 => SbiError::Failed,
+            -2 => SbiError::NotSupported,
+            -3 => SbiError::InvalidParam,
+            -4 => SbiError::Denied,
+            -5 => SbiError::InvalidAddress,
+            -6 => SbiError::AlreadyAvailable,
+            -7 => SbiError::AlreadyStarted,
+            -8 => SbiError::AlreadyStopped,
+            code => SbiError::Unknown(code),
+        }
+    }
+}
+
+pub type SbiResult<T = usize> = Result<T, SbiError>;
+
+/// SBI 调用
+#[inline(always)]
+fn sbi_call(
+    extension: usize,
+    function: usize,
+    arg0: usize,
+    arg1: usize,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+) -> SbiResult {
+    let (error, value): (isize, usize);
+    unsafe {
+        llvm_asm!("ecall"
+            : "={x10}" (error), "={x11}" (value)
+            : "{x10}" (arg0), "{x11}" (arg1), "{x12}" (arg2), "{x13}" (arg3), "{x14}" (arg4),
+              "{x16}" (function), "{x17}" (extension)
+            : "memory"
+            : "volatile");
+    }
+    match error {
+        0 => Ok(value),
+        code => Err(SbiError::from_code(code)),
+    }
+}
+
+/// 设置下一次时钟中断的时间
+pub fn set_timer(time: usize) {
+    if FIRMWARE.time {
+        sbi_call(EXTENSION_TIME, 0, time, 0, 0, 0, 0).unwrap();
+    } else {
+        legacy::set_timer(time);
+    }
+}
+
+/// 向 `hart_mask` 中的 hart 发送软件中断，`hart_mask` 的第 0 位对应 `hart_mask_base` 号 hart
+pub fn send_ipi(hart_mask: usize, hart_mask_base: usize) -> SbiResult<()> {
+    if FIRMWARE.ipi {
+        sbi_call(EXTENSION_IPI, 0, hart_mask, hart_mask_base, 0, 0, 0).map(|_| ())
+    } else {
+        legacy::send_ipi(hart_mask << hart_mask_base);
+        Ok(())
+    }
+}
+
+/// 让其他 hart 执行 `fence.i`
+pub fn remote_fence_i(hart_mask: usize, hart_mask_base: usize) -> SbiResult<()> {
+    if FIRMWARE.rfence {
+        sbi_call(EXTENSION_RFENCE, 0, hart_mask, hart_mask_base, 0, 0, 0).map(|_| ())
+    } else {
+        legacy::remote_fence_i(hart_mask << hart_mask_base);
+        Ok(())
+    }
+}
+
+/// 让其他 hart 刷新 `[start, start + size)` 的 TLB，`asid` 为 `None` 时刷新所有地址空间
+pub fn remote_sfence_vma(
+    hart_mask: usize,
+    hart_mask_base: usize,
+    start: usize,
+    size: usize,
+    asid: Option<usize>,
+) -> SbiResult<()> {
+    if FIRMWARE.rfence {
+        match asid {
+            Some(asid) => sbi_call(
+                EXTENSION_RFENCE,
+                2,
+                hart_mask,
+                hart_mask_base,
+                start,
+                size,
+                asid,
+            ),
+            None => sbi_call(EXTENSION_RFENCE, 1, hart_mask, hart_mask_base, start, size, 0),
+        }
+        .map(|_| ())
+    } else {
+        legacy::remote_sfence_vma(hart_mask << hart_mask_base, start, size, asid);
+        Ok(())
+    }
+}
+
+/// hart 的状态，由 HSM 扩展报告
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HartState {
+    Started,
+    Stopped,
+    StartPending,
+    StopPending,
+}
+
+/// 在 `start_address` 以 S 态启动一个 hart，其 `a0` 为 hart 编号，`a1` 为 `opaque`
+///
+/// HSM 扩展没有 legacy 的对应
+pub fn hart_start(hart_id: usize, start_address: usize, opaque: usize) -> SbiResult<()> {
+    if !FIRMWARE.hsm {
+        return Err(SbiError::NotSupported);
+    }
+    sbi_call(EXTENSION_HSM, 0, hart_id, start_address, opaque, 0, 0).map(|_| ())
+}
+
+/// 停止当前 hart，成功时不会返回
+pub fn hart_stop() -> SbiResult<()> {
+    if !FIRMWARE.hsm {
+        return Err(SbiError::NotSupported);
+    }
+    sbi_call(EXTENSION_HSM, 1, 0, 0, 0, 0, 0).map(|_| ())
+}
+
+/// 查询 hart 的状态
+pub fn hart_status(hart_id: usize) -> SbiResult<HartState> {
+    if !FIRMWARE.hsm {
+        return Err(SbiError::NotSupported);
+    }
+    match sbi_call(EXTENSION_HSM, 2, hart_id, 0, 0, 0, 0)? {
+        0 => Ok(HartState::Started),
+        1 => Ok(HartState::Stopped),
+        2 => Ok(HartState::StartPending),
+        3 => Ok(HartState::StopPending),
+        _ => Err(SbiError::Failed),
+    }
+}
+
+/// 系统复位的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetType {
+    Shutdown = 0,
+    ColdReboot = 1,
+    WarmReboot = 2,
+}
+
+/// 系统复位的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetReason {
+    NoReason = 0,
+    SystemFailure = 1,
+}
+
+/// 关机或重启，成功时不会返回
+///
+/// 没有 SRST 扩展时，只能通过 legacy 调用关机，无法重启或报告原因
+pub fn system_reset(reset_type: ResetType, reason: ResetReason) -> SbiError {
+    if FIRMWARE.srst {
+        return match sbi_call(
+            EXTENSION_SRST,
+            0,
+            reset_type as usize,
+            reason as usize,
+            0,
+            0,
+            0,
+        ) {
+            Ok(_) => SbiError::Failed,
+            Err(error) => error,
+        };
+    }
+    match reset_type {
+        ResetType::Shutdown => legacy::shutdown(),
+        _ => SbiError::NotSupported,
+    }
+}
+
+/// 关闭操作系统（直接退出 QEMU）
+pub fn shutdown() -> ! {
+    system_reset(ResetType::Shutdown, ResetReason::NoReason);
+    legacy::shutdown()
+}
+
+/// 因为系统错误而关机，在 panic 时调用
+pub fn shutdown_on_failure() -> ! {
+    system_reset(ResetType::Shutdown, ResetReason::SystemFailure);
+    legacy::shutdown()
+}
+
+/// 打印固件的信息，在日志和堆初始化之后调用
+pub fn init() {
+    match spec_version() {
+        Some((major, minor)) => info!(
+            "SBI v{}.{}, implementation {} v{:#x}, extensions:{}{}{}{}{}",
+            major,
+            minor,
+            impl_id(),
+            impl_version(),
+            if FIRMWARE.time { " TIME" } else { "" },
+            if FIRMWARE.ipi { " IPI" } else { "" },
+            if FIRMWARE.rfence { " RFENCE" } else { "" },
+            if FIRMWARE.hsm { " HSM" } else { "" },
+            if FIRMWARE.srst { " SRST" } else { "" },
+        ),
+        None => info!("SBI v0.1, using legacy calls"),
+    }
+}
@@ -0,0 +1,139 @@
+//! 设备树中找到的设备
+
+use crate::drivers::{
+    block::{block_devices, BlockDevice},
+    net::net_devices,
+    plic,
+    rng::rng_devices,
+    rtc::rtc_device,
+    serial::console_serial,
+};
+use crate::interrupt::CLOCK_FREQ;
+use crate::{clock, random};
+use alloc::{vec, vec::Vec};
+use rcore_fs::dev;
+use riscv::register::time;
+
+#[test_case]
+fn console_is_serial() {
+    // QEMU virt 机器总有一个 ns16550a 串口
+    assert!(console_serial().is_some());
+    assert!(plic::enabled());
+}
+
+#[test_case]
+fn block_read_write() {
+    // 没有挂载磁盘镜像时跳过
+    let driver = match block_devices().into_iter().next() {
+        Some(info) => info.device.driver,
+        None => return,
+    };
+    let last = driver.num_blocks() - 1;
+    let mut original = vec![0u8; 512];
+    assert!(driver.read_block(last, &mut original));
+    let pattern: Vec<u8> = (0..512).map(|index| index as u8).collect();
+    assert!(driver.write_block(last, &pattern));
+    let mut buffer = vec![0u8; 512];
+    assert!(driver.read_block(last, &mut buffer));
+    assert_eq!(buffer, pattern);
+    // 恢复原来的内容
+    assert!(driver.write_block(last, &original));
+    let statistics = driver.statistics();
+    assert!(statistics.reads >= 2 && statistics.writes >= 2);
+}
+
+#[test_case]
+fn block_partition_bounds() {
+    let driver = match block_devices().into_iter().next() {
+        Some(info) => info.device.driver,
+        None => return,
+    };
+    // 把磁盘的第 1 块当作只有一块的分区
+    let partition = BlockDevice {
+        driver: driver.clone(),
+        offset: 1,
+        blocks: 1,
+    };
+    let mut expected = vec![0u8; 512];
+    assert!(driver.read_block(1, &mut expected));
+    let mut buffer = vec![0u8; 512];
+    assert!(dev::BlockDevice::read_at(&partition, 0, &mut buffer).is_ok());
+    assert_eq!(buffer, expected);
+    // 不能越过分区末尾
+    assert!(dev::BlockDevice::read_at(&partition, 1, &mut buffer).is_err());
+    assert!(dev::BlockDevice::write_at(&partition, 1, &buffer).is_err());
+}
+
+#[test_case]
+fn net_arp_gateway() {
+    // 没有网卡时跳过，否则需要 QEMU 的用户态网络：本机 10.0.2.15，网关 10.0.2.2
+    let (_, driver) = match net_devices().into_iter().next() {
+        Some(device) => device,
+        None => return,
+    };
+    assert!(driver.link_up());
+    let mac = driver.mac_address();
+    let (local, gateway) = ([10, 0, 2, 15], [10, 0, 2, 2]);
+    // 广播一个 ARP 请求询问网关的 MAC 地址
+    let mut request = Vec::new();
+    request.extend_from_slice(&[0xff; 6]);
+    request.extend_from_slice(&mac);
+    request.extend_from_slice(&[0x08, 0x06, 0, 1, 0x08, 0x00, 6, 4, 0, 1]);
+    request.extend_from_slice(&mac);
+    request.extend_from_slice(&local);
+    request.extend_from_slice(&[0; 6]);
+    request.extend_from_slice(&gateway);
+    request.resize(60, 0);
+    assert!(driver.send_frame(&request));
+
+    let deadline = time::read() + CLOCK_FREQ;
+    while time::read() < deadline {
+        let frame = match driver.receive_frame() {
+            Some(frame) => frame,
+            None => continue,
+        };
+        // 以太网类型为 ARP，操作为应答，发送方为网关
+        if frame.len() >= 42
+            && frame[12..14] == [0x08, 0x06]
+            && frame[20..22] == [0, 2]
+            && frame[28..32] == gateway
+        {
+            assert_eq!(frame[0..6], mac);
+            return;
+        }
+    }
+    panic!("no arp reply from the gateway");
+}
+
+#[test_case]
+fn rng_read() {
+    // 测试的 runner 总是添加 virtio-rng
+    let driver = rng_devices().into_iter().next().expect("no entropy source");
+    let mut first = [0u8; 64];
+    let mut second = [0u8; 64];
+    assert_eq!(driver.read_random(&mut first), first.len());
+    assert_eq!(driver.read_random(&mut second), second.len());
+    assert_ne!(first[..], second[..]);
+    assert!(random::hardware_seeded());
+}
+
+#[test_case]
+fn random_fill() {
+    // 每次输出之后都会更换密钥，连续两次读取不会相同
+    let mut first = [0u8; 100];
+    let mut second = [0u8; 100];
+    random::fill(&mut first);
+    random::fill(&mut second);
+    assert_ne!(first[..], second[..]);
+    assert!(first.iter().any(|byte| *byte != 0));
+}
+
+#[test_case]
+fn rtc_wall_clock() {
+    // QEMU virt 机器总有 goldfish RTC，墙上时间一定晚于 2020-01-01
+    assert!(rtc_device().is_some());
+    let now = clock::realtime();
+    assert!(now.sec > 1_577_836_800);
+    let later = clock::realtime();
+    assert!((later.sec, later.nsec) >= (now.sec, now.nsec));
+}
@@ -0,0 +1,166 @@
+//! 根文件系统、管道和 procfs
+
+use super::{spawn, wait_for_interrupt};
+use crate::clock;
+use crate::fs::{
+    sync, Epoll, FileHandle, INodeExt, OpenOptions, Pipe, EPOLLHUP, EPOLLIN, ROOT_INODE,
+};
+use crate::kernel::block_on;
+use crate::process::{
+    process::Process,
+    processor::{release_thread, PROCESSOR},
+};
+use alloc::{string::String, vec};
+use filesystem::TmpFS;
+use rcore_fs::vfs::{FileSystem, FileType, FsError, INode};
+
+#[test_case]
+fn create_write_read() {
+    let file = ROOT_INODE
+        .create("kernel-test", FileType::File, 0o644)
+        .unwrap();
+    assert_eq!(file.write_at(0, b"hello, world").unwrap(), 12);
+    assert_eq!(file.write_at(7, b"mos").unwrap(), 3);
+    assert_eq!(file.readall().unwrap(), b"hello, mosld");
+    assert_eq!(file.metadata().unwrap().size, 12);
+    ROOT_INODE.unlink("kernel-test").unwrap();
+    assert!(ROOT_INODE.find("kernel-test").is_err());
+}
+
+#[test_case]
+fn sync_all() {
+    // 关机之前写回根文件系统、挂载的文件系统和所有块设备缓存
+    let file = ROOT_INODE
+        .create("kernel-sync", FileType::File, 0o644)
+        .unwrap();
+    file.write_at(0, b"sync").unwrap();
+    sync().unwrap();
+    ROOT_INODE.unlink("kernel-sync").unwrap();
+    sync().unwrap();
+}
+
+#[test_case]
+fn write_read_timestamps() {
+    let fs = TmpFS::new(0x1000);
+    let file = fs
+        .root_inode()
+        .create("file", FileType::File, 0o644)
+        .unwrap();
+    let options = OpenOptions {
+        read: true,
+        write: true,
+        ..OpenOptions::default()
+    };
+    let handle = FileHandle::new(file.clone(), options, String::from("/file"));
+    let before = clock::realtime();
+    assert_eq!(handle.write(b"time").unwrap(), 4);
+    let mtime = file.metadata().unwrap().mtime;
+    assert!((mtime.sec, mtime.nsec) >= (before.sec, before.nsec));
+    // 访问时间不晚于修改时间，读取之后更新
+    let reader = FileHandle::new(file.clone(), options, String::from("/file"));
+    let mut buffer = [0u8; 4];
+    assert_eq!(reader.read(&mut buffer).unwrap(), 4);
+    let atime = file.metadata().unwrap().atime;
+    assert!((atime.sec, atime.nsec) >= (mtime.sec, mtime.nsec));
+}
+
+#[test_case]
+fn pipe_read_write() {
+    let (read, write) = Pipe::new();
+    let mut buffer = [0u8; 16];
+    assert!(matches!(read.read_at(0, &mut buffer), Err(FsError::Again)));
+    assert_eq!(write.write_at(0, b"ping").unwrap(), 4);
+    assert_eq!(read.read_at(0, &mut buffer).unwrap(), 4);
+    assert_eq!(&buffer[..4], b"ping");
+    // 写端关闭之后读到文件结尾
+    drop(write);
+    assert_eq!(read.read_at(0, &mut buffer).unwrap(), 0);
+}
+
+/// 和 `read` 系统调用一样从 0 号文件描述符读取，没有数据时在管道上休眠
+fn pipe_reader() {
+    let mut buffer = [0u8; 8];
+    loop {
+        let handle = PROCESSOR
+            .lock()
+            .current_thread()
+            .process
+            .inner()
+            .get_descriptor(0)
+            .unwrap();
+        match handle.read(&mut buffer) {
+            Err(FsError::Again) => assert!(block_on(&handle).is_err()),
+            _ => return,
+        }
+        // 休眠时不持有文件
+        drop(handle);
+        while PROCESSOR.lock().current_thread().inner().sleeping {
+            wait_for_interrupt();
+        }
+    }
+}
+
+#[test_case]
+fn kill_blocked_pipe_reader() {
+    let (read, write) = Pipe::new();
+    let read = FileHandle::new(
+        read,
+        OpenOptions {
+            read: true,
+            ..OpenOptions::default()
+        },
+        String::from("pipe"),
+    );
+    let write = FileHandle::new(
+        write,
+        OpenOptions {
+            write: true,
+            ..OpenOptions::default()
+        },
+        String::from("pipe"),
+    );
+    // 读者所在的进程同时持有写端
+    let process = Process::new_kernel().unwrap();
+    process.inner().add_descriptor(read.clone()).unwrap();
+    process.inner().add_descriptor(write).unwrap();
+    let reader = spawn(process, pipe_reader as usize, None);
+    while !reader.inner().sleeping {
+        wait_for_interrupt();
+    }
+    // 被终止的读者还留在管道的等待队列中，但进程中的写端已经关闭
+    PROCESSOR.lock().kill_thread(&reader);
+    release_thread(reader);
+    assert_eq!(read.read(&mut [0u8; 8]).unwrap(), 0);
+}
+
+#[test_case]
+fn epoll_remove_and_hangup() {
+    let (read, write) = Pipe::new();
+    let read = FileHandle::new(
+        read,
+        OpenOptions {
+            read: true,
+            ..OpenOptions::default()
+        },
+        String::from("pipe"),
+    );
+    let epoll = Epoll::new();
+    epoll.add(3, &read, EPOLLIN, 7).unwrap();
+    assert!(epoll.collect(8).is_empty());
+    // 停止监听之后，管道的通知不会再让 epoll 就绪
+    epoll.remove(3).unwrap();
+    write.write_at(0, b"x").unwrap();
+    assert!(!epoll.poll().unwrap().read);
+    // 写端关闭之后同时报告挂起
+    epoll.add(3, &read, EPOLLIN, 7).unwrap();
+    drop(write);
+    assert_eq!(epoll.collect(8), vec![(EPOLLIN | EPOLLHUP, 7)]);
+}
+
+#[test_case]
+fn procfs_meminfo() {
+    let meminfo = ROOT_INODE.lookup("/proc/meminfo").unwrap();
+    let mut buffer = [0u8; 256];
+    let len = meminfo.read_at(0, &mut buffer).unwrap();
+    assert!(buffer[..len].starts_with(b"FrameTotal:"));
+}
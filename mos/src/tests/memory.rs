@@ -0,0 +1,82 @@
+//! 物理页分配、堆和页表映射
+
+use crate::memory::{
+    frame::FRAME_ALLOCATOR,
+    layout,
+    mapping::{Mapping, MemorySet, Segment},
+    Flags, MapType, PhysicalAddress, VirtualAddress, VirtualPageNumber, KERNEL_END_ADDRESS,
+    PAGE_SIZE,
+};
+use alloc::{boxed::Box, vec, vec::Vec};
+
+#[test_case]
+fn frame_alloc() {
+    let allocated = FRAME_ALLOCATOR.lock().allocated();
+    let frames: Vec<_> = (0..8)
+        .map(|_| FRAME_ALLOCATOR.lock().alloc().unwrap())
+        .collect();
+    assert_eq!(FRAME_ALLOCATOR.lock().allocated(), allocated + 8);
+    for (index, frame) in frames.iter().enumerate() {
+        assert_eq!(frame.address().page_offset(), 0);
+        for other in &frames[index + 1..] {
+            assert_ne!(frame.page_number(), other.page_number());
+        }
+    }
+    drop(frames);
+    assert_eq!(FRAME_ALLOCATOR.lock().allocated(), allocated);
+}
+
+#[test_case]
+fn memory_from_device_tree() {
+    let kernel_end = PhysicalAddress::from(*KERNEL_END_ADDRESS);
+    let regions = layout::memory_regions();
+    assert!(!regions.is_empty());
+    assert!(regions.iter().all(|range| range.start >= kernel_end));
+    assert!(FRAME_ALLOCATOR.lock().total() > 0);
+    let frame = FRAME_ALLOCATOR.lock().alloc().unwrap();
+    assert!(layout::is_memory(frame.address()));
+    // 设备的 MMIO 区域都已经登记，内核映射中可以找到
+    let mut memory_set = MemorySet::new_kernel().unwrap();
+    for range in layout::mmio_regions() {
+        let vpn = VirtualPageNumber::floor(VirtualAddress::from(range.start));
+        assert!(!memory_set.mapping.find_entry(vpn).unwrap().is_empty());
+    }
+}
+
+#[test_case]
+fn heap_alloc() {
+    let boxed = Box::new(42usize);
+    assert_eq!(*boxed, 42);
+    let vector: Vec<usize> = (0..10000).collect();
+    assert_eq!(vector.iter().sum::<usize>(), 9999 * 10000 / 2);
+}
+
+#[test_case]
+fn kernel_linear_mapping() {
+    let value = Box::new(0x1234usize);
+    let va = VirtualAddress::from(&*value as *const usize);
+    assert_eq!(Mapping::lookup(va), Some(PhysicalAddress::from(va)));
+    assert_eq!(Mapping::lookup(VirtualAddress(0x1000)), None);
+}
+
+#[test_case]
+fn framed_segment() {
+    let mut memory_set = MemorySet::new_kernel().unwrap();
+    let segment = Segment {
+        map_type: MapType::Framed,
+        range: (0x1000_0000usize..0x1000_0000 + 2 * PAGE_SIZE).into(),
+        flags: Flags::READABLE | Flags::WRITABLE | Flags::USER,
+    };
+    let mut data = vec![0x5au8; 2 * PAGE_SIZE];
+    data[PAGE_SIZE..].iter_mut().for_each(|byte| *byte = 0xa5);
+    memory_set.add_segment(segment, Some(&data)).unwrap();
+
+    let vpn = VirtualPageNumber::floor(VirtualAddress(0x1000_0000 + PAGE_SIZE));
+    let entry = *memory_set.mapping.find_entry(vpn).unwrap();
+    assert!(entry.flags().contains(Flags::VALID | Flags::USER));
+    let page = entry.page_number().deref_kernel();
+    assert!(page.iter().all(|&byte| byte == 0xa5));
+
+    memory_set.remove_segment(&segment).unwrap();
+    assert!(memory_set.mapping.find_entry(vpn).unwrap().is_empty());
+}
@@ -0,0 +1,118 @@
+//! 内核测试
+//!
+//! 使用 `custom_test_frameworks`，`cargo test` 编译出的内核在初始化之后，
+//! 在一个内核线程中依次运行所有 `#[test_case]`。
+//! 测试线程和系统调用一样关闭中断执行，需要其他线程时用 [`spawn`] 创建，
+//! 在 [`wait_for_interrupt`] 中让出处理器。
+//! 输出格式与 libtest 相同：
+//!
+//! ```text
+//! running 3 tests
+//! test memory::frame_alloc ... ok
+//! test result: ok. 3 passed; 0 failed
+//! ```
+//!
+//! 测试中的 panic 即为失败，之后的测试不再运行。结束时通过 sifive_test 设备退出 QEMU，
+//! 全部通过时退出码为 0，否则为 1
+
+mod drivers;
+mod fs;
+mod memory;
+mod net;
+mod scheduler;
+
+use crate::create_kernel_thread;
+use crate::drivers::power::poweroff;
+use crate::process::{process::Process, processor::PROCESSOR, thread::Thread};
+use alloc::sync::Arc;
+use core::any::type_name;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::sstatus;
+
+/// 已经通过的测试数
+static PASSED: AtomicUsize = AtomicUsize::new(0);
+
+/// 可以运行的测试，即 `#[test_case]` 标记的函数
+pub trait Testable {
+    fn run(&self);
+}
+
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        // 去掉 crate 名和本模块的路径
+        let name = type_name::<T>();
+        let name = name.splitn(3, "::").nth(2).unwrap_or(name);
+        print!("test {} ... ", name);
+        self();
+        println!("ok");
+    }
+}
+
+/// 测试的入口，由 `test_main` 调用
+pub fn runner(tests: &[&dyn Testable]) {
+    println!("running {} tests", tests.len());
+    for test in tests {
+        test.run();
+        PASSED.fetch_add(1, Ordering::Relaxed);
+    }
+    println!("test result: ok. {} passed; 0 failed", tests.len());
+    exit(true)
+}
+
+/// 创建并调度一个内核线程
+///
+/// 线程关闭中断执行，只在 [`wait_for_interrupt`] 中被时钟中断切换，
+/// 和系统调用一样不会在持有锁时被打断
+pub fn spawn(
+    process: Arc<Process>,
+    entry_point: usize,
+    arguments: Option<&[usize]>,
+) -> Arc<Thread> {
+    let thread = create_kernel_thread(process, entry_point, arguments);
+    thread
+        .inner()
+        .context
+        .as_mut()
+        .unwrap()
+        .sstatus
+        .set_spie(false);
+    PROCESSOR.lock().add_thread(thread.clone());
+    thread
+}
+
+/// 等待下一次中断并处理它，时钟中断时切换到其他线程
+pub fn wait_for_interrupt() {
+    unsafe {
+        llvm_asm!("wfi" :::: "volatile");
+        // 只在这两条指令之间打开中断
+        sstatus::set_sie();
+        sstatus::clear_sie();
+    }
+}
+
+/// 等待线程全部结束
+pub fn join(threads: &[Arc<Thread>]) {
+    while threads.iter().any(|thread| !thread.inner().dead) {
+        wait_for_interrupt();
+    }
+}
+
+/// 测试中发生 panic，在打印 panic 信息之前调用
+pub fn report_failure() {
+    println!("FAILED");
+}
+
+/// 报告失败并退出 QEMU，在 panic 的最后调用
+pub fn exit_failure() -> ! {
+    println!(
+        "test result: FAILED. {} passed; 1 failed",
+        PASSED.load(Ordering::Relaxed)
+    );
+    exit(false)
+}
+
+/// 通过 sifive_test 设备退出 QEMU，见 [`poweroff`]
+fn exit(success: bool) -> ! {
+    crate::console::flush();
+    poweroff(if success { 0 } else { 1 })
+}
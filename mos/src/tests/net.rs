@@ -0,0 +1,219 @@
+//! TCP/IP 协议栈和套接字
+//!
+//! 连接两端的套接字大多由测试线程以非阻塞的方式交替操作；
+//! 阻塞的情形由服务端和客户端两个内核线程完成，它们和系统调用一样通过 [`block_on`] 在套接字上休眠
+
+use super::{join, spawn, wait_for_interrupt};
+use crate::drivers::net::net_devices;
+use crate::fs::{FileHandle, OpenOptions};
+use crate::interrupt::CLOCK_FREQ;
+use crate::kernel::block_on;
+use crate::net::{NetError, NetResult, Socket, SocketKind};
+use crate::process::{process::Process, processor::PROCESSOR};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use riscv::register::time;
+use smoltcp::wire::{IpAddress, IpEndpoint};
+use spin::Mutex;
+
+/// `make test` 通过 QEMU 的 guestfwd 在这个地址上提供回显服务
+fn echo_server() -> IpEndpoint {
+    IpEndpoint::new(IpAddress::v4(10, 0, 2, 100), 7)
+}
+
+/// 重复执行 `f` 直到不再返回需要等待的错误，最多等待 5 秒
+fn retry<T>(mut f: impl FnMut() -> NetResult<T>) -> NetResult<T> {
+    let deadline = time::read() + 5 * CLOCK_FREQ;
+    loop {
+        match f() {
+            Err(NetError::Again) | Err(NetError::InProgress) | Err(NetError::Already)
+                if time::read() < deadline => {}
+            result => return result,
+        }
+    }
+}
+
+/// 回环接口上的地址
+fn loopback(port: u16) -> IpEndpoint {
+    IpEndpoint::new(IpAddress::v4(127, 0, 0, 1), port)
+}
+
+#[test_case]
+fn udp_port_in_use() {
+    let endpoint = IpEndpoint::new(IpAddress::Unspecified, 5353);
+    let first = Socket::new(SocketKind::Udp).unwrap();
+    first.bind(endpoint).unwrap();
+    let second = Socket::new(SocketKind::Udp).unwrap();
+    assert_eq!(second.bind(endpoint), Err(NetError::AddrInUse));
+    // 同一端口的 TCP 不受影响
+    let tcp = Socket::new(SocketKind::Tcp).unwrap();
+    tcp.bind(endpoint).unwrap();
+    // 关闭之后端口可以再次使用
+    drop(first);
+    second.bind(endpoint).unwrap();
+    // 没有目的地址、没有数据时不会阻塞
+    assert_eq!(second.send(b"x", None), Err(NetError::DestinationRequired));
+    assert_eq!(second.recv(&mut [0; 16]), Err(NetError::Again));
+}
+
+#[test_case]
+fn tcp_echo() {
+    if net_devices().is_empty() {
+        return;
+    }
+    let server = echo_server();
+    let socket = Socket::new(SocketKind::Tcp).unwrap();
+    assert_eq!(socket.connect(server), Err(NetError::InProgress));
+    retry(|| socket.connect(server)).unwrap();
+    assert_eq!(socket.peer_endpoint(), Ok(server));
+    assert_ne!(socket.local_endpoint().port, 0);
+
+    let message = b"hello, echo";
+    assert_eq!(retry(|| socket.send(message, None)), Ok(message.len()));
+    let mut buffer = [0u8; 64];
+    let mut received = 0;
+    while received < message.len() {
+        let (len, peer) = retry(|| socket.recv(&mut buffer[received..])).unwrap();
+        assert!(len > 0);
+        assert_eq!(peer, Some(server));
+        received += len;
+    }
+    assert_eq!(&buffer[..received], message);
+
+    // 关闭写端后对方结束回显，读到文件结尾
+    socket.shutdown(false, true).unwrap();
+    assert_eq!(
+        retry(|| socket.recv(&mut buffer)).map(|(len, _)| len),
+        Ok(0)
+    );
+}
+
+#[test_case]
+fn tcp_loopback() {
+    let server = Socket::new(SocketKind::Tcp).unwrap();
+    server.bind(loopback(8080)).unwrap();
+    server.listen(4).unwrap();
+    assert_eq!(server.local_endpoint(), loopback(8080));
+    assert_eq!(server.accept().err(), Some(NetError::Again));
+
+    let client = Socket::new(SocketKind::Tcp).unwrap();
+    assert_eq!(client.connect(loopback(8080)), Err(NetError::InProgress));
+    retry(|| client.connect(loopback(8080))).unwrap();
+    let connection = retry(|| server.accept()).unwrap();
+    assert_eq!(connection.peer_endpoint(), Ok(client.local_endpoint()));
+    assert_eq!(client.peer_endpoint(), Ok(loopback(8080)));
+
+    // 两个方向各发送一次
+    let mut buffer = [0u8; 64];
+    assert_eq!(client.send(b"ping", None), Ok(4));
+    assert_eq!(retry(|| connection.recv(&mut buffer)).unwrap().0, 4);
+    assert_eq!(&buffer[..4], b"ping");
+    assert_eq!(connection.send(b"pong", None), Ok(4));
+    assert_eq!(retry(|| client.recv(&mut buffer)).unwrap().0, 4);
+    assert_eq!(&buffer[..4], b"pong");
+    assert_eq!(client.recv(&mut buffer), Err(NetError::Again));
+
+    // 一端关闭后另一端读到文件结尾
+    drop(client);
+    assert_eq!(
+        retry(|| connection.recv(&mut buffer)).map(|(len, _)| len),
+        Ok(0)
+    );
+}
+
+/// 服务端线程收到、客户端线程又收回的数据
+static ECHOED: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// 把套接字包装成打开的文件，以便在上面等待
+fn socket_file(socket: Arc<Socket>) -> Arc<FileHandle> {
+    FileHandle::new(
+        socket,
+        OpenOptions {
+            read: true,
+            write: true,
+            ..OpenOptions::default()
+        },
+        String::from("socket"),
+    )
+}
+
+/// 和系统调用一样执行套接字上的操作
+///
+/// 需要等待时由 [`block_on`] 令当前线程在套接字上休眠，返回 `ERESTARTSYS` 之后
+/// 等待时钟中断切换到其他线程，被唤醒之后重新执行
+fn blocking<T>(file: &FileHandle, mut f: impl FnMut() -> NetResult<T>) -> NetResult<T> {
+    let thread = PROCESSOR.lock().current_thread();
+    loop {
+        match f() {
+            Err(NetError::Again) | Err(NetError::InProgress) | Err(NetError::Already) => {
+                assert!(block_on(file).is_err());
+                while thread.inner().sleeping {
+                    wait_for_interrupt();
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 服务端线程：接受一个连接，把收到的数据发送回去
+fn echo_thread(listener: usize) {
+    let listener = unsafe { Arc::from_raw(listener as *const Socket) };
+    let file = socket_file(listener.clone());
+    let connection = blocking(&file, || listener.accept()).unwrap();
+    let file = socket_file(connection.clone());
+    let mut buffer = [0u8; 64];
+    let (len, _) = blocking(&file, || connection.recv(&mut buffer)).unwrap();
+    assert_eq!(connection.send(&buffer[..len], None), Ok(len));
+}
+
+/// 客户端线程：连接到服务端，发送数据并把收回的数据记录在 [`ECHOED`] 中
+fn client_thread(port: usize) {
+    let client = Socket::new(SocketKind::Tcp).unwrap();
+    let file = socket_file(client.clone());
+    blocking(&file, || client.connect(loopback(port as u16))).unwrap();
+    assert_eq!(client.send(b"ping", None), Ok(4));
+    let mut buffer = [0u8; 64];
+    let (len, _) = blocking(&file, || client.recv(&mut buffer)).unwrap();
+    ECHOED.lock().extend_from_slice(&buffer[..len]);
+}
+
+#[test_case]
+fn tcp_loopback_blocking() {
+    let listener = Socket::new(SocketKind::Tcp).unwrap();
+    listener.bind(loopback(8082)).unwrap();
+    listener.listen(1).unwrap();
+    // 服务端先运行，在 accept 上休眠，之后两个线程轮流等待对方
+    let process = Process::new_kernel().unwrap();
+    let server = spawn(
+        process.clone(),
+        echo_thread as usize,
+        Some(&[Arc::into_raw(listener) as usize]),
+    );
+    let client = spawn(process, client_thread as usize, Some(&[8082]));
+    join(&[server, client]);
+    assert_eq!(*ECHOED.lock(), b"ping");
+}
+
+#[test_case]
+fn tcp_loopback_refused() {
+    // 没有监听的端口，连接被重置
+    let client = Socket::new(SocketKind::Tcp).unwrap();
+    assert_eq!(client.connect(loopback(8081)), Err(NetError::InProgress));
+    assert_eq!(
+        retry(|| client.connect(loopback(8081))),
+        Err(NetError::ConnectionRefused)
+    );
+}
+
+#[test_case]
+fn udp_loopback() {
+    let receiver = Socket::new(SocketKind::Udp).unwrap();
+    receiver.bind(loopback(9000)).unwrap();
+    let sender = Socket::new(SocketKind::Udp).unwrap();
+    assert_eq!(sender.send(b"datagram", Some(loopback(9000))), Ok(8));
+
+    let mut buffer = [0u8; 64];
+    let (len, peer) = retry(|| receiver.recv(&mut buffer)).unwrap();
+    assert_eq!(&buffer[..len], b"datagram");
+    assert_eq!(peer, Some(sender.local_endpoint()));
+}
@@ -0,0 +1,54 @@
+//! 调度算法和线程的调度
+
+use crate::process::{process::Process, processor::PROCESSOR};
+use crate::{create_kernel_thread, kernel_thread_exit};
+use algorithm::{FifoScheduler, HrrnScheduler, Scheduler};
+use alloc::vec::Vec;
+
+#[test_case]
+fn fifo_order() {
+    let mut scheduler = FifoScheduler::default();
+    for thread in 0..4usize {
+        scheduler.add_thread(thread);
+    }
+    scheduler.remove_thread(&2);
+    assert_eq!(scheduler.threads(), [0, 1, 3]);
+    assert_eq!(scheduler.get_next(), Some(0));
+    // 取出的线程放回队尾
+    assert_eq!(scheduler.threads(), [1, 3, 0]);
+}
+
+#[test_case]
+fn hrrn_round_robin() {
+    let mut scheduler = HrrnScheduler::default();
+    for thread in 0..3usize {
+        scheduler.add_thread(thread);
+    }
+    let mut picked: Vec<_> = (0..3).map(|_| scheduler.get_next().unwrap()).collect();
+    picked.sort();
+    assert_eq!(picked, [0, 1, 2]);
+}
+
+#[test_case]
+fn add_and_kill_thread() {
+    let process = Process::new_kernel().unwrap();
+    let threads: Vec<_> = (0..2)
+        .map(|_| create_kernel_thread(process.clone(), kernel_thread_exit as usize, None))
+        .collect();
+    let scheduled = |thread_id| {
+        PROCESSOR
+            .lock()
+            .scheduled_threads()
+            .iter()
+            .any(|thread| thread.id == thread_id)
+    };
+    for thread in &threads {
+        PROCESSOR.lock().add_thread(thread.clone());
+        assert!(scheduled(thread.id));
+    }
+    for thread in &threads {
+        PROCESSOR.lock().kill_thread(thread);
+        assert!(!scheduled(thread.id));
+        assert!(thread.inner().dead);
+    }
+}